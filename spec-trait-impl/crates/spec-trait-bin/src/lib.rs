@@ -0,0 +1,58 @@
+//! `no_std` library built together with the example, to check that the code generated by `when` and `spec!`
+//! only needs `core`: the macros run at compile time, so `spec-trait-utils` never reaches the generated code.
+//!
+//! `std` is only needed by the crates that run on the host: `spec-trait-macro` and `spec-trait-utils`
+//! in the compiler, and `spec-trait-order` in the build script, which read and write the cache file.
+//! The CI builds this library for a target without `std` (e.g. `--target thumbv6m-none-eabi`),
+//! while its tests use `std` for the test harness.
+
+#![cfg_attr(not(test), no_std)]
+
+use spec_trait_macro::{spec, when};
+
+pub struct NoStd;
+
+pub trait Describe<T> {
+    fn describe(&self, x: T) -> &'static str;
+}
+
+impl<T> Describe<T> for NoStd {
+    fn describe(&self, _x: T) -> &'static str {
+        "Default Describe for NoStd"
+    }
+}
+
+#[when(T = u8)]
+impl<T> Describe<T> for NoStd {
+    fn describe(&self, _x: T) -> &'static str {
+        "Describe for NoStd where T is u8"
+    }
+}
+
+#[when(T = &[_])]
+impl<T> Describe<T> for NoStd {
+    fn describe(&self, _x: T) -> &'static str {
+        "Describe for NoStd where T is &[_]"
+    }
+}
+
+pub fn describe_u8(x: u8) -> &'static str {
+    let no_std = NoStd;
+    spec! { no_std.describe(x); NoStd; [u8] }
+}
+
+pub fn describe_slice(x: &[u16]) -> &'static str {
+    let no_std = NoStd;
+    spec! { no_std.describe(x); NoStd; [&[u16]] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_without_std() {
+        assert_eq!(describe_u8(1), "Describe for NoStd where T is u8");
+        assert_eq!(describe_slice(&[1]), "Describe for NoStd where T is &[_]");
+    }
+}
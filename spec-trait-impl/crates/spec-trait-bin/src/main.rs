@@ -1,9 +1,22 @@
-use spec_trait_macro::{spec, when};
+#![deny(warnings)]
+
+use spec_trait_macro::{
+    spec, spec_all, spec_chain, spec_fn, spec_for, spec_trait, spec_trait_names, spec_type,
+    spec_type_traits, when,
+};
 use std::fmt::Debug;
 
 #[allow(clippy::upper_case_acronyms)]
 struct ZST;
 struct ZST2;
+struct Wrapper<'a>(&'a str);
+struct Holder<T>(T);
+struct Pair<T, U>(T, U);
+struct Stage(u8);
+#[allow(dead_code)]
+struct Config {
+    size: u32,
+}
 
 trait Foo<T> {
     fn foo(&self, x: T);
@@ -18,8 +31,95 @@ trait Foo3<T> {
     fn foo(&self, x: T, y: String);
 }
 
+trait Foo4<T> {
+    fn foo(&self) -> T;
+}
+
+trait Ping {
+    fn ping(&self);
+}
+
+#[allow(dead_code)]
+trait Foo5<T> {
+    type Out;
+    fn convert(&self, x: T) -> Self::Out;
+}
+
+trait Scale<T = u8> {
+    fn scale(&self, x: T) -> u32;
+}
+
+#[allow(dead_code)]
+trait Kind {
+    fn kind(&self) -> &'static str;
+}
+
+trait Pick<'a, T> {
+    fn pick<'b>(&'b self, x: &'b T, fallback: &'a T) -> &'b T;
+}
+
+trait Summable {
+    fn sum(&self) -> u32;
+}
+
+trait Divide<T> {
+    fn divide(&self, x: T) -> u32;
+}
+
+trait ScaleTwice {
+    fn scale_twice(&self, x: u16) -> u32;
+}
+
+trait Show {
+    fn show(&self);
+}
+
+trait Collect<A> {
+    fn collect_into(&self, items: A);
+}
+
+trait Explain<T> {
+    fn explain(&self, x: T);
+}
+
+trait Feed<A, B> {
+    fn feed(&self, item: A, items: B);
+}
+
+trait Begin<T> {
+    fn begin(&self, x: T) -> Stage;
+}
+
+trait Finish<T> {
+    fn finish(&self, x: T);
+}
+
+trait Tally<T> {
+    fn tally(&self, x: T) -> &'static str;
+}
+
+trait Label<T> {
+    fn label(&self, x: T);
+}
+
+trait Produce<T> {
+    type Out;
+    fn produce(&self) -> Self::Out;
+}
+
+trait First<C: IntoIterator = Vec<u8>> {
+    fn first(&self) -> Option<<C as IntoIterator>::Item>;
+}
+
+spec_trait! {
+    trait Greet<T> {
+        fn greet(&self, x: T);
+    }
+}
+
 type MyType = u8;
 type MyVecAlias = Vec<i32>;
+type MyResult<E> = Result<String, E>;
 
 trait Bar {}
 trait FooBar {}
@@ -27,9 +127,16 @@ trait FooBar {}
 impl Bar for i32 {}
 impl Bar for i64 {}
 impl FooBar for i64 {}
+impl Bar for i16 {}
+impl Bar for Config {}
+
+spec_type_traits! {
+    i16: Bar;
+}
 
 // ZST - Foo
 
+#[when()]
 impl<T> Foo<T> for ZST {
     fn foo(&self, _x: T) {
         println!("Default Foo for ZST");
@@ -92,6 +199,13 @@ impl<T> Foo<T> for ZST {
     }
 }
 
+#[when(all(T = &[i32], len = 3))]
+impl<T> Foo<T> for ZST {
+    fn foo(&self, _x: T) {
+        println!("Foo impl ZST where T is &[i32] with 3 elements");
+    }
+}
+
 #[when(all(T = &_, T: 'a))]
 impl<'a, T> Foo<T> for ZST {
     fn foo(&self, _x: T) {
@@ -151,6 +265,30 @@ impl<T, U> Foo3<T> for ZST {
     }
 }
 
+#[when(T = MyResult<_>)]
+impl<T> Foo3<T> for ZST {
+    fn foo(&self, _x: T, _y: String) {
+        println!("Foo3 impl ZST where T is MyResult<_>");
+    }
+}
+
+// ZST - Foo4
+
+impl<T: Default> Foo4<T> for ZST {
+    fn foo(&self) -> T {
+        println!("Default Foo4 for ZST");
+        Default::default()
+    }
+}
+
+#[when(T = u8)]
+impl<T: Default> Foo4<T> for ZST {
+    fn foo(&self) -> T {
+        println!("Foo4 for ZST where T is u8");
+        Default::default()
+    }
+}
+
 // ZST2 - Foo
 
 impl<T> Foo<T> for ZST2 {
@@ -202,6 +340,414 @@ impl<T, U> Foo2<T, U> for ZST2 {
     }
 }
 
+// ZST2 - Foo5
+
+#[when(T = u8)]
+impl<T> Foo5<T> for ZST2 {
+    type Out = u16;
+    fn convert(&self, x: T) -> u16 {
+        x.into()
+    }
+}
+
+#[when(T = &str)]
+impl<T> Foo5<T> for ZST2 {
+    type Out = String;
+    fn convert(&self, x: T) -> String {
+        x.to_uppercase()
+    }
+}
+
+// ZST2 - Produce, First
+
+#[when()]
+impl<T: Default> Produce<T> for ZST2 {
+    type Out = T;
+    fn produce(&self) -> T {
+        T::default()
+    }
+}
+
+#[when()]
+impl<C: IntoIterator + Default> First<C> for ZST2 {
+    fn first(&self) -> Option<C::Item> {
+        C::default().into_iter().next()
+    }
+}
+
+// ZST2 - Scale
+
+#[when()]
+impl Scale for ZST2 {
+    fn scale(&self, x: u8) -> u32 {
+        x as u32
+    }
+}
+
+#[when(T = u16)]
+impl<T: Into<u32>> Scale<T> for ZST2 {
+    fn scale(&self, x: T) -> u32 {
+        let x: u32 = x.into();
+        x * 1000
+    }
+}
+
+// ZST2 - ScaleTwice
+
+impl ScaleTwice for ZST2 {
+    fn scale_twice(&self, x: u16) -> u32 {
+        // `self` is already a reference
+        let scaled = spec! { self.scale(x); ZST2; [u16]; no_borrow };
+        scaled * 2
+    }
+}
+
+// T - Scale
+
+#[when(T: Bar)]
+impl<T: Copy + Into<i64>> Scale for T {
+    fn scale(&self, x: u8) -> u32 {
+        let value = <T as Into<i64>>::into(*self);
+        value as u32 + x as u32
+    }
+}
+
+// `T: Bar` and `T: FooBar` are equally specific, the priority selects this one when both are satisfied
+#[when(T: FooBar, priority = 1)]
+impl<T: Copy + Into<i64>> Scale for T {
+    fn scale(&self, x: u8) -> u32 {
+        let value = <T as Into<i64>>::into(*self);
+        value as u32 * x as u32
+    }
+}
+
+// T - Kind
+
+#[when(T = ZST)]
+impl<T> Kind for T {
+    fn kind(&self) -> &'static str {
+        "Kind for owned ZST"
+    }
+}
+
+#[when(T = &ZST)]
+impl<T> Kind for T {
+    fn kind(&self) -> &'static str {
+        "Kind for borrowed ZST"
+    }
+}
+
+// ZST2 - Pick
+
+#[when()]
+impl<'a, T> Pick<'a, T> for ZST2 {
+    fn pick<'b>(&'b self, x: &'b T, _fallback: &'a T) -> &'b T {
+        println!("Default Pick for ZST2");
+        x
+    }
+}
+
+#[when(T = u8)]
+impl<'a, T> Pick<'a, T> for ZST2 {
+    fn pick<'b>(&'b self, x: &'b T, _fallback: &'a T) -> &'b T {
+        println!("Pick for ZST2 where T is u8");
+        x
+    }
+}
+
+// Wrapper - Ping
+
+#[when()]
+impl<'a> Ping for Wrapper<'a> {
+    fn ping(&self) {
+        println!("Default Ping for Wrapper<'a>: {}", self.0);
+    }
+}
+
+#[when('a = 'static)]
+impl<'a> Ping for Wrapper<'a> {
+    fn ping(&self) {
+        println!("Ping for Wrapper<'static>: {}", self.0);
+    }
+}
+
+// Holder - Ping
+
+#[when()]
+impl<T> Ping for Holder<T> {
+    fn ping(&self) {
+        println!("Default Ping for Holder<T>");
+    }
+}
+
+#[when(Self = Holder<i32>)]
+impl<T: Debug> Ping for Holder<T> {
+    fn ping(&self) {
+        println!("Ping for Holder<i32>: {:?}", self.0);
+    }
+}
+
+// Holder - Show
+
+#[when()]
+impl<T> Show for Holder<T> {
+    fn show(&self) {
+        println!("Default Show for Holder<T>");
+    }
+}
+
+// `T: Clone` is needed by the body, but the `spec!` calls do not have to annotate it
+#[when(T: Debug, requires(T: Clone))]
+impl<T: Debug> Show for Holder<T> {
+    fn show(&self) {
+        let inner = self.0.clone();
+        println!("Show for Holder<T> where T implements Debug: {:?}", inner);
+    }
+}
+
+// Holder - Collect
+
+#[when()]
+impl<T> Collect<Vec<T>> for Holder<T> {
+    fn collect_into(&self, items: Vec<T>) {
+        println!("Default Collect for Holder<T>: {} items", items.len());
+    }
+}
+
+#[when(T = u8)]
+impl<T> Collect<Vec<T>> for Holder<T> {
+    fn collect_into(&self, items: Vec<T>) {
+        let sum = items.iter().map(|x| u32::from(*x)).sum::<u32>() + u32::from(self.0);
+        println!("Collect for Holder<u8>: {}", sum);
+    }
+}
+
+// ZST - Collect
+
+#[when()]
+impl<T> Collect<Vec<T>> for ZST {
+    fn collect_into(&self, items: Vec<T>) {
+        println!("Default Collect for ZST: {} items", items.len());
+    }
+}
+
+// `T` is only in the trait argument, so it is taken from the argument of the call
+#[when(T = u8)]
+impl<T> Collect<Vec<T>> for ZST {
+    fn collect_into(&self, items: Vec<T>) {
+        let sum = items.iter().map(|x| u32::from(*x)).sum::<u32>();
+        println!("Collect for ZST where T is u8: {}", sum);
+    }
+}
+
+// ZST - Explain
+
+// without a default impl, the other calls select the one generated by `fallback`
+#[when(T = u8, fallback = explain_default)]
+impl<T> Explain<T> for ZST {
+    fn explain(&self, _x: T) {
+        println!("Explain for ZST where T is u8");
+    }
+}
+
+fn explain_default<T>(_zst: &ZST, _x: T) {
+    println!("Fallback Explain for ZST");
+}
+
+// ZST - Feed
+
+#[when()]
+impl<T, U> Feed<T, U> for ZST {
+    fn feed(&self, _item: T, _items: U) {
+        println!("Default Feed for ZST");
+    }
+}
+
+#[when(all(U: Iterator, T = U::Item))]
+impl<T, U> Feed<T, U> for ZST {
+    fn feed(&self, item: T, items: U) {
+        let count = std::iter::once(item).chain(items).count();
+        println!("Feed for ZST where T is the item of U: {} items", count);
+    }
+}
+
+// ZST - Begin, Stage - Finish
+
+#[when()]
+impl<T> Begin<T> for ZST {
+    fn begin(&self, _x: T) -> Stage {
+        Stage(0)
+    }
+}
+
+#[when(T = u8)]
+impl<T> Begin<T> for ZST {
+    fn begin(&self, x: T) -> Stage {
+        Stage(x)
+    }
+}
+
+#[when()]
+impl<T> Finish<T> for Stage {
+    fn finish(&self, _x: T) {
+        println!("Default Finish for Stage {}", self.0);
+    }
+}
+
+#[when(T = i32)]
+impl<T> Finish<T> for Stage {
+    fn finish(&self, x: T) {
+        println!("Finish for Stage {} where T is i32: {}", self.0, x);
+    }
+}
+
+// ZST - Tally
+
+#[when()]
+impl<T> Tally<T> for ZST {
+    fn tally(&self, _x: T) -> &'static str {
+        "default"
+    }
+}
+
+#[when(T: Copy)]
+impl<T> Tally<T> for ZST {
+    fn tally(&self, _x: T) -> &'static str {
+        "Copy"
+    }
+}
+
+#[when(T: Clone)]
+impl<T> Tally<T> for ZST {
+    fn tally(&self, _x: T) -> &'static str {
+        "Clone"
+    }
+}
+
+// ZST - Greet
+
+#[when()]
+impl<T> Greet<T> for ZST {
+    fn greet(&self, _x: T) {
+        println!("Default Greet for ZST");
+    }
+}
+
+#[when(T = u8)]
+impl<T> Greet<T> for ZST {
+    fn greet(&self, _x: T) {
+        println!("Greet for ZST where T is u8");
+    }
+}
+
+#[when(T: _)]
+impl<T> Greet<T> for ZST {
+    fn greet(&self, _x: T) {
+        println!("Greet for ZST where T implements an annotated trait");
+    }
+}
+
+// Pair<T, U> - Ping
+
+#[when()]
+impl<T, U> Ping for Pair<T, U> {
+    fn ping(&self) {
+        println!("Default Ping for Pair<T, U>");
+    }
+}
+
+#[when(T = U)]
+impl<T, U> Ping for Pair<T, U> {
+    fn ping(&self) {
+        println!("Ping for Pair<T, T>");
+    }
+}
+
+// &Holder<T> - Ping
+
+#[when()]
+impl<T> Ping for &Holder<T> {
+    fn ping(&self) {
+        println!("Default Ping for &Holder<T>");
+    }
+}
+
+#[when(T = i32)]
+impl<T: Debug> Ping for &Holder<T> {
+    fn ping(&self) {
+        println!("Ping for &Holder<i32>: {:?}", self.0);
+    }
+}
+
+// [u8; N] - Summable
+
+#[when()]
+impl<const N: usize> Summable for [u8; N] {
+    fn sum(&self) -> u32 {
+        println!("Default Summable for [u8; N]");
+        self.iter().map(|&x| x as u32).sum()
+    }
+}
+
+#[when(N = 0)]
+impl<const N: usize> Summable for [u8; N] {
+    fn sum(&self) -> u32 {
+        println!("Summable for [u8; 0]");
+        0
+    }
+}
+
+// [T] - Summable
+
+#[when()]
+impl<T> Summable for [T] {
+    fn sum(&self) -> u32 {
+        println!("Default Summable for [T]");
+        self.len() as u32
+    }
+}
+
+#[when(T = u8)]
+impl<T: Copy + Into<u32>> Summable for [T] {
+    fn sum(&self) -> u32 {
+        println!("Summable for [T] where T is u8");
+        self.iter().map(|&x| Into::<u32>::into(x)).sum()
+    }
+}
+
+// ZST - Divide
+
+impl<T: Into<u32>> Divide<T> for ZST {
+    fn divide(&self, x: T) -> u32 {
+        println!("Default Divide for ZST");
+        100 / x.into()
+    }
+}
+
+#[when(arg0 = 0)]
+impl<T: Into<u32>> Divide<T> for ZST {
+    fn divide(&self, _x: T) -> u32 {
+        println!("Divide for ZST where x is 0");
+        0
+    }
+}
+
+// ZST, ZST2 - Label
+
+#[when(for = {ZST, ZST2})]
+impl<T> Label<T> for _ {
+    fn label(&self, _x: T) {
+        println!("Default Label for {}", std::any::type_name::<Self>());
+    }
+}
+
+#[when(T = u8, for = {ZST, ZST2})]
+impl<T> Label<T> for _ {
+    fn label(&self, _x: T) {
+        println!("Label for {} where T is u8", std::any::type_name::<Self>());
+    }
+}
+
 // T - Foo
 
 #[when(all(U = MyType, T = i32))]
@@ -232,6 +778,14 @@ impl<T, U> Foo<U> for T {
     }
 }
 
+#[cfg(feature = "extra")]
+#[when(T = u8)]
+impl<T, U> Foo<U> for T {
+    fn foo(&self, _x: U) {
+        println!("Foo impl T where T is u8");
+    }
+}
+
 #[when(all(not(T = i32), not(T = ZST)))]
 impl<T, U> Foo<U> for T {
     fn foo(&self, _x: U) {
@@ -239,6 +793,16 @@ impl<T, U> Foo<U> for T {
     }
 }
 
+// the generics of a fn are not resolved to the types they are instantiated with,
+// so only the impls without conditions on them, or with conditions on their annotated bounds, are selected
+fn foo_generic<X>(zst: &ZST, x: X) {
+    spec! { zst.foo(x); ZST; [X] }
+}
+
+fn foo_generic_bar<X: Bar>(zst: &ZST, x: X) {
+    spec! { zst.foo(x); ZST; [X]; X: Bar }
+}
+
 fn main() {
     let zst = ZST;
     let zst2 = ZST2;
@@ -251,6 +815,7 @@ fn main() {
     spec! { zst.foo(vec![1i32]); ZST; [Vec<i32>] } // -> "Foo impl ZST where T is Vec<_>"
     spec! { zst.foo((1, 2)); ZST; [(i32, i32)] } // -> "Foo impl ZST where T is (i32, _)"
     spec! { zst.foo(&[1i32]); ZST; [&[i32]] } // -> "Foo impl ZST where T is &[i32]"
+    spec! { zst.foo(&[1i32, 2, 3]); ZST; [&[i32]]; len = 3 } // -> "Foo impl ZST where T is &[i32] with 3 elements"
     spec! { zst.foo(&1i32); ZST; [&'static i32] } // -> "Foo impl ZST where T is &'static _"
     spec! { zst.foo(&1i32); ZST; [&i32]; &i32: 'static } // -> "Foo impl ZST where T is &'static _"
     spec! { zst.foo(&1i32); ZST; [&'a i32] } // -> "Foo impl ZST where T is &'a _"
@@ -258,7 +823,16 @@ fn main() {
     spec! { zst.foo(&1i32); ZST; [&i32] } // -> "Foo impl ZST where T is &'a _"
     spec! { zst.foo(1i32); ZST; [i32]; i32: Bar  } // -> "Foo impl ZST where T implements Bar"
     spec! { zst.foo(1i64); ZST; [i64]; i64: Bar + FooBar } // -> "Foo impl ZST where T implements Bar and FooBar"
+    spec! { zst.foo(1i16); ZST; [i16] } // -> "Foo impl ZST where T implements Bar"
     spec! { zst.foo(1i8); ZST; [i8] } // -> "Default Foo for ZST"
+    spec! { zst.foo(Config { size: 3 }); ZST; [Config]; Config: Bar } // -> "Foo impl ZST where T implements Bar"
+    let foo_my_type = spec_fn! { ZST::foo; ZST; [u8]; u8 = MyType };
+    let foo_default: fn(&ZST, i8) = spec_fn! { ZST::foo; ZST; [i8] };
+    foo_my_type(&zst, 2u8); // -> "Foo impl ZST where T is MyType"
+    foo_default(&zst, 2i8); // -> "Default Foo for ZST"
+    spec_for! { z in [ZST, ZST].iter(); z.foo(3u8); ZST; [u8]; u8 = MyType; no_borrow } // -> "Foo impl ZST where T is MyType" (x2)
+    foo_generic(&zst, 1u8); // -> "Default Foo for ZST"
+    foo_generic_bar(&zst, 1i32); // -> "Foo impl ZST where T implements Bar"
     println!();
 
     // ZST - Foo2
@@ -270,6 +844,13 @@ fn main() {
     spec! { zst.foo("a".to_string(), "b".to_string()); ZST; [String, String] } // -> "Foo3 impl ZST where T is String"
     spec! { zst.foo(vec!["a".to_string()], "b".to_string()); ZST; [Vec<String>, String] } // -> "Foo3 impl ZST where T is Vec<U>"
     spec! { zst.foo(vec!["a".to_string()], "b".to_string()); ZST; [Vec<String>, String]; String: Debug } // -> "Foo3 impl ZST where T is Vec<U> and U implements Debug"
+    spec! { zst.foo(vec!["a".to_string()], "b".to_string()); ZST; [Vec<String>, String]; String: std::fmt::Debug } // -> "Foo3 impl ZST where T is Vec<U> and U implements Debug"
+    spec! { zst.foo(MyResult::<i32>::Ok("a".to_string()), "b".to_string()); ZST; [MyResult<i32>, String] } // -> "Foo3 impl ZST where T is MyResult<_>"
+    println!();
+
+    // ZST - Foo4
+    let _: u8 = spec! { zst.foo() -> u8; ZST; [] }; // -> "Foo4 for ZST where T is u8"
+    let _: i8 = spec! { zst.foo() -> i8; ZST; [] }; // -> "Default Foo4 for ZST"
     println!();
 
     // ZST2 - Foo
@@ -284,11 +865,165 @@ fn main() {
     spec! { zst2.foo(1i8, 1i8); ZST2; [i8, i8] } // -> "Foo2 for ZST2 where T is not MyType"
     println!();
 
+    // ZST2 - Foo5
+    let y: spec_type! { Out; zst2.convert(1u8); ZST2; [u8] } =
+        spec! { zst2.convert(1u8); ZST2; [u8] };
+    println!("{}: {}", std::any::type_name_of_val(&y), y); // -> "u16: 1"
+    let y: spec_type! { Out; zst2.convert("str"); ZST2; [&str] } =
+        spec! { zst2.convert("str"); ZST2; [&str] };
+    println!("{}: {}", std::any::type_name_of_val(&y), y); // -> "alloc::string::String: STR"
+    println!();
+
+    // ZST2 - Produce, First
+    let y: u8 = spec! { zst2.produce() -> u8; ZST2; [] };
+    println!("{}", y); // -> "0"
+    let y: Option<u8> = spec! { zst2.first() -> Option<u8>; ZST2; [] };
+    println!("{:?}", y); // -> "None"
+    println!();
+
+    // ZST2 - Scale
+    println!("{}", spec! { zst2.scale(1u8); ZST2; [u8] }); // -> "1"
+    println!("{}", spec! { zst2.scale(1u16); ZST2; [u16] }); // -> "1000"
+    println!("{}", zst2.scale_twice(1u16)); // -> "2000"
+    println!("{}", spec! { 1i32.scale(2u8); i32; [u8]; i32: Bar }); // -> "3"
+    println!(
+        "{}",
+        spec! { 3i64.scale(2u8); i64; [u8]; i64: Bar + FooBar }
+    ); // -> "6"
+    println!();
+
+    // T - Kind
+    println!("{}", spec! { zst.kind(); ZST; [] }); // -> "Kind for owned ZST"
+    let zst_ref = &zst;
+    println!("{}", spec! { zst_ref.kind(); &ZST; [] }); // -> "Kind for borrowed ZST"
+    println!();
+
+    // ZST2 - Pick
+    let picked = spec! { zst2.pick(&1u8, &2u8); ZST2; [&u8, &u8] }; // -> "Pick for ZST2 where T is u8"
+    println!("{}", picked); // -> "1"
+    spec! { zst2.pick(&1i32, &2i32); ZST2; [&i32, &i32] }; // -> "Default Pick for ZST2"
+    println!();
+
+    // Wrapper - Ping
+    let w = Wrapper("static");
+    spec! { w.ping(); Wrapper<'static>; [] } // -> "Ping for Wrapper<'static>: static"
+    let s = String::from("borrowed");
+    let w = Wrapper(&s);
+    spec! { w.ping(); Wrapper<'_>; [] } // -> "Default Ping for Wrapper<'a>: borrowed"
+    println!();
+
+    // Holder - Ping
+    let h = Holder(1i32);
+    spec! { h.ping(); Holder<i32>; [] } // -> "Ping for Holder<i32>: 1"
+    let h = Holder(1u8);
+    spec! { h.ping(); Holder<u8>; [] } // -> "Default Ping for Holder<T>"
+    let h = Box::new(Holder(3i32));
+    spec! { h.ping(); Box<Holder<i32>>; []; deref } // -> "Ping for Holder<i32>: 3"
+    println!();
+
+    // Holder - Collect
+    let h = Holder(1u8);
+    spec! { h.collect_into(vec![2u8, 3]); Holder<u8>; [Vec<u8>] } // -> "Collect for Holder<u8>: 6"
+    let h = Holder(1i32);
+    spec! { h.collect_into(vec![2i32, 3]); Holder<i32>; [Vec<i32>] } // -> "Default Collect for Holder<T>: 2 items"
+    spec! { zst.collect_into(vec![2u8, 3]); ZST; [Vec<u8>] } // -> "Collect for ZST where T is u8: 5"
+    spec! { zst.collect_into(vec![2i32]); ZST; [Vec<i32>] } // -> "Default Collect for ZST: 1 items"
+    println!();
+
+    // Holder - Show
+    let h = Holder(4i32);
+    spec! { h.show(); Holder<i32>; []; i32: Debug } // -> "Show for Holder<T> where T implements Debug: 4"
+    spec! { h.show(); Holder<i32>; [] } // -> "Default Show for Holder<T>"
+    println!();
+
+    // ZST - Explain
+    spec! { zst.explain(1u8); ZST; [u8] } // -> "Explain for ZST where T is u8"
+    spec! { zst.explain(1i32); ZST; [i32] } // -> "Fallback Explain for ZST"
+    println!();
+
+    // ZST - Feed
+    spec! { zst.feed(1u8, vec![2u8, 3].into_iter()); ZST; [u8, std::vec::IntoIter<u8>]; std::vec::IntoIter<u8>: Iterator; std::vec::IntoIter<u8>::Item = u8 } // -> "Feed for ZST where T is the item of U: 3 items"
+    spec! { zst.feed(1i32, vec![2u8, 3].into_iter()); ZST; [i32, std::vec::IntoIter<u8>]; std::vec::IntoIter<u8>: Iterator; std::vec::IntoIter<u8>::Item = u8 } // -> "Default Feed for ZST"
+    println!();
+
+    // ZST - Begin, Stage - Finish
+    spec_chain! { zst.begin(3u8); ZST; [u8] => .finish(4i32); Stage; [i32] } // -> "Finish for Stage 3 where T is i32: 4"
+    spec_chain! { zst.begin(3i8); ZST; [i8] => .finish(4u8); Stage; [u8] } // -> "Default Finish for Stage 0"
+    println!();
+
+    // ZST - Tally
+    let (copy, clone) = spec_all! { zst.tally(1i32); ZST; [i32]; i32: Copy + Clone };
+    println!(
+        "Tally for ZST where T implements Copy or Clone: {} and {}",
+        copy, clone
+    ); // -> "Tally for ZST where T implements Copy or Clone: Copy and Clone"
+    let (default,) = spec_all! { zst.tally(1u8); ZST; [u8] };
+    println!("Tally for ZST: {}", default); // -> "Tally for ZST: default"
+    println!();
+
+    // ZST - Greet
+    spec! { zst.greet(1u8); ZST; [u8] } // -> "Greet for ZST where T is u8"
+    spec! { zst.greet(1i32); ZST; [i32] } // -> "Default Greet for ZST"
+    spec! { zst.greet(1i32); ZST; [i32]; i32: Copy } // -> "Greet for ZST where T implements an annotated trait"
+    spec! { zst.greet(1u8); ZST; [u8]; u8: Copy } // -> "Greet for ZST where T is u8"
+    let code = 300u16;
+    spec! { zst.greet(code as u8); ZST; [_] } // -> "Greet for ZST where T is u8"
+    println!();
+
+    // Pair<T, U> - Ping
+    let p = Pair(1u8, 2u8);
+    spec! { p.ping(); Pair<u8, u8>; [] } // -> "Ping for Pair<T, T>"
+    let p = Pair(1u8, 2i32);
+    spec! { p.ping(); Pair<u8, i32>; [] } // -> "Default Ping for Pair<T, U>"
+    println!();
+
+    // &Holder<T> - Ping
+    let h = &Holder(2i32);
+    spec! { h.ping(); &Holder<i32>; [] } // -> "Ping for &Holder<i32>: 2"
+    let h = &Holder(2u8);
+    spec! { h.ping(); &Holder<u8>; [] } // -> "Default Ping for &Holder<T>"
+    println!();
+
+    // [u8; N] - Summable
+    let a: [u8; 0] = [];
+    println!("{}", spec! { a.sum(); [u8; 0]; [] }); // -> "Summable for [u8; 0]", "0"
+    let a = [1u8, 2, 3];
+    println!("{}", spec! { a.sum(); [u8; 3]; [] }); // -> "Default Summable for [u8; N]", "6"
+    println!();
+
+    // [T] - Summable
+    let s: &[u8] = &[1, 2, 3];
+    println!("{}", spec! { s.sum(); [u8]; [] }); // -> "Summable for [T] where T is u8", "6"
+    let s: &[i32] = &[1, 2, 3];
+    println!("{}", spec! { s.sum(); [i32]; [] }); // -> "Default Summable for [T]", "3"
+    println!();
+
+    // ZST - Divide
+    println!("{}", spec! { zst.divide(0u8); ZST; [u8]; arg0 = 0 }); // -> "Divide for ZST where x is 0", "0"
+    println!("{}", spec! { zst.divide(4u8); ZST; [u8] }); // -> "Default Divide for ZST", "25"
+    {
+        spec_trait_names!(Divide);
+        println!("{}", NAMES.len()); // -> "1"
+    }
+    println!();
+
+    // ZST, ZST2 - Label
+    spec! { zst.label(1u8); ZST; [u8] } // -> "Label for spec_trait_bin::ZST where T is u8"
+    spec! { zst.label(1i32); ZST; [i32] } // -> "Default Label for spec_trait_bin::ZST"
+    spec! { zst2.label(1u8); ZST2; [u8] } // -> "Label for spec_trait_bin::ZST2 where T is u8"
+    spec! { zst2.label(1i32); ZST2; [i32] } // -> "Default Label for spec_trait_bin::ZST2"
+    println!();
+
     // T - Foo
     spec! { 1i32.foo(1u8); i32; [u8]; u8 = MyType } // -> "Foo impl T where T is i32 and U is MyType"
     spec! { 1i32.foo(1i8); i32; [i8]; i32: Bar } // -> "Foo impl T where T implements Bar"
     spec! { x.foo(1u8); Vec<i32>; [u8]; u8 = MyType } // -> "Foo impl T where T is Vec<_> and U is MyType"
     spec! { 1i32.foo("str"); i32; [&str] } // -> "Foo impl T where U is &str"
     // spec! { zst.foo("str"); ZST; [&str] } // TODO: fix                                                      // -> "Foo impl T where U is &str"
-    spec! { 1u8.foo(1u8); u8; [u8] } // -> "Foo impl T where T is not i32 or ZST"
+    spec! { 1u8.foo(1u8); u8; [u8] } // -> "Foo impl T where T is not i32 or ZST" ("Foo impl T where T is u8" with the `extra` feature)
+    println!();
+
+    // NoStd - Describe, from the `no_std` library
+    println!("{}", spec_trait_bin::describe_u8(1)); // -> "Describe for NoStd where T is u8"
+    println!("{}", spec_trait_bin::describe_slice(&[1])); // -> "Describe for NoStd where T is &[_]"
 }
@@ -1,15 +1,38 @@
-use proc_macro2::TokenStream;
-use spec_trait_utils::conversions::to_string;
-use spec_trait_utils::parsing::{ParseTypeOrLifetimeOrTrait, parse_type_or_lifetime_or_trait};
+use proc_macro2::{Spacing, Span, TokenStream, TokenTree};
+use quote::quote;
+use spec_trait_utils::conditions::WhenCondition;
+use spec_trait_utils::conversions::{str_to_type_name, to_string};
+use spec_trait_utils::impls::ImplBody;
+use spec_trait_utils::parsing::{
+    ParseTypeOrLifetimeOrTrait, parse_arg_value, parse_len, parse_type_or_lifetime_or_trait,
+};
+use spec_trait_utils::type_aliases::{TypeAliasBody, expand_type_aliases};
+use spec_trait_utils::types::{deref_type, type_contains};
 use std::fmt::Debug;
-use syn::parse::{Parse, ParseStream};
-use syn::{Error, Expr, Ident, Lit, Token, Type, bracketed, parenthesized, token};
+use std::iter;
+use syn::parse::{End, Parse, ParseStream};
+use syn::{
+    Error, Expr, ExprPath, Ident, Lit, Token, Type, braced, bracketed, parenthesized, token,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Annotation {
     Trait(String /* type */, Vec<String> /* traits */),
     Alias(String /* type */, String /* alias */),
     Lifetime(String /* type */, String /* lifetime */),
+    Len(usize /* slice element count */),
+    ArgValue(usize /* argument position */, String /* value */),
+    AssocType(
+        String, /* type */
+        String, /* associated type */
+        String, /* type it is assigned to */
+    ),
+    /// the receiver can be matched through one level of deref
+    Deref,
+    /// the receiver is passed as is instead of borrowed (e.g. `self` in a `&self` method)
+    NoBorrow,
+    /// an impl not selected only because of a missing trait annotation is an error
+    Strict,
 }
 
 #[derive(Debug, PartialEq, Clone, Default)]
@@ -17,11 +40,348 @@ pub struct AnnotationBody {
     pub var: String,
     pub fn_: String,
     pub args: Vec<String>,
+    pub ret_type: Option<String>,
     pub var_type: String,
     pub args_types: Vec<String>,
     pub annotations: Vec<Annotation>,
 }
 
+impl AnnotationBody {
+    /// expand the type aliases with generics registered for the crate in all the types
+    pub fn expand_type_aliases(&mut self, aliases: &[TypeAliasBody]) {
+        let expand = |type_: &mut String| *type_ = expand_type_aliases(type_, aliases);
+
+        expand(&mut self.var_type);
+        self.args_types.iter_mut().for_each(expand);
+        self.ret_type.iter_mut().for_each(expand);
+        for annotation in &mut self.annotations {
+            match annotation {
+                Annotation::Trait(type_, _)
+                | Annotation::Alias(type_, _)
+                | Annotation::Lifetime(type_, _) => expand(type_),
+                Annotation::AssocType(type_, _, assigned) => {
+                    expand(type_);
+                    expand(assigned);
+                }
+                Annotation::Len(_)
+                | Annotation::ArgValue(..)
+                | Annotation::Deref
+                | Annotation::NoBorrow
+                | Annotation::Strict => {}
+            }
+        }
+    }
+
+    /**
+        match the receiver through one level of deref (e.g. `Box<MyType>` as `MyType`), as the method call would do,
+        if it is annotated with `deref`. Returns whether the receiver was replaced.
+    */
+    pub fn deref_receiver(&mut self) -> bool {
+        if !self.annotations.contains(&Annotation::Deref) {
+            return false;
+        }
+
+        match deref_type(&self.var_type) {
+            Some(target) => {
+                self.var_type = target;
+                self.var = format!("*{}", self.var);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// check if the type is the receiver, an argument or the return type of the call, or a part of them
+    fn in_call(&self, type_: &str) -> bool {
+        iter::once(&self.var_type)
+            .chain(&self.args_types)
+            .chain(&self.ret_type)
+            .any(|call_type| type_contains(&str_to_type_name(call_type), type_))
+    }
+
+    /**
+        check that the trait and lifetime annotations are on a type of the call or on an alias declared with `Type = Alias`,
+        otherwise they have no effect.
+        The types are compared with the `type_aliases` of the crate expanded (e.g. `Vec<i32>: Debug` with the receiver `MyVec`
+        for `type MyVec = Vec<i32>;`), as in the selection.
+        The types with wildcards (e.g. `Vec<_>: Debug`) are not checked.
+    */
+    pub fn check_annotated_types(&self, type_aliases: &[TypeAliasBody]) -> Result<(), String> {
+        let mut expanded = self.clone();
+        expanded.expand_type_aliases(type_aliases);
+
+        let aliases = expanded
+            .annotations
+            .iter()
+            .filter_map(|annotation| match annotation {
+                Annotation::Alias(_, alias) => Some(alias),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (annotation, written) in expanded.annotations.iter().zip(&self.annotations) {
+            let (Annotation::Trait(type_, _) | Annotation::Lifetime(type_, _)) = annotation else {
+                continue;
+            };
+            let (Annotation::Trait(written_type, _) | Annotation::Lifetime(written_type, _)) =
+                written
+            else {
+                continue;
+            };
+
+            let has_wildcard = type_contains(&str_to_type_name(type_), "_");
+            if !has_wildcard && !expanded.in_call(type_) && !aliases.contains(&type_) {
+                return Err(format!(
+                    "Annotated type `{}` is neither a type of the call to `{}` nor an alias declared with `Type = Alias`",
+                    written_type.replace(" ", ""),
+                    self.fn_
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+        get the alias annotations (e.g. `u8 = MyType`) that have no effect on the selection among `impls`,
+        because the type is not in the call or the alias is not in any of their conditions.
+    */
+    pub fn unused_aliases(&self, impls: &[ImplBody]) -> Vec<(String, String)> {
+        let condition_types = impls
+            .iter()
+            .filter_map(|impl_| impl_.condition.as_ref())
+            .flat_map(get_condition_types)
+            .map(|type_| str_to_type_name(&type_))
+            .collect::<Vec<_>>();
+
+        self.annotations
+            .iter()
+            .filter_map(|annotation| match annotation {
+                Annotation::Alias(type_, alias) => Some((type_.clone(), alias.clone())),
+                _ => None,
+            })
+            .filter(|(type_, alias)| {
+                !self.in_call(type_)
+                    || !condition_types
+                        .iter()
+                        .any(|condition_type| type_contains(condition_type, alias))
+            })
+            .collect()
+    }
+
+    /**
+        an alias annotation on an associated type of a type of the call (e.g. `MyIter::Item = u8`)
+        assigns the associated type instead, the other annotations are returned as they are.
+    */
+    fn to_assoc_type(&self, annotation: Annotation) -> Annotation {
+        let Annotation::Alias(type_, assigned) = &annotation else {
+            return annotation;
+        };
+        let Type::Path(path) = str_to_type_name(type_) else {
+            return annotation;
+        };
+        if path.qself.is_some() || path.path.segments.len() < 2 {
+            return annotation;
+        }
+
+        let mut owner = path.path.clone();
+        let assoc = owner.segments.pop().unwrap().into_value();
+        owner.segments.pop_punct();
+        let owner = to_string(&owner);
+
+        if !assoc.arguments.is_none() || !self.in_call(&owner) {
+            return annotation;
+        }
+
+        Annotation::AssocType(owner, assoc.ident.to_string(), assigned.clone())
+    }
+
+    /// whether an impl not selected only because of a missing trait annotation is an error
+    pub fn strict(&self) -> bool {
+        self.annotations.contains(&Annotation::Strict)
+    }
+
+    /// the receiver as passed to the generated call, borrowed unless annotated with `no_borrow`
+    pub fn receiver(&self) -> String {
+        if self.annotations.contains(&Annotation::NoBorrow) {
+            self.var.clone()
+        } else {
+            format!("&{}", self.var)
+        }
+    }
+}
+
+/// get the types in the type conditions (e.g. `MyType` for `all(T = MyType, U: Clone)`)
+fn get_condition_types(condition: &WhenCondition) -> Vec<String> {
+    match condition {
+        WhenCondition::Type(_, type_) => vec![type_.clone()],
+        WhenCondition::All(inner) | WhenCondition::Any(inner) => {
+            inner.iter().flat_map(get_condition_types).collect()
+        }
+        WhenCondition::Not(inner) => get_condition_types(inner),
+        _ => vec![],
+    }
+}
+
+/// an associated type name followed by the annotations of a `spec!` call selecting the impl to take it from
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssocTypeAnnotationBody {
+    pub assoc_type: String,
+    pub body: AnnotationBody,
+}
+
+impl TryFrom<TokenStream> for AssocTypeAnnotationBody {
+    type Error = syn::Error;
+
+    fn try_from(tokens: TokenStream) -> Result<Self, Self::Error> {
+        syn::parse2(tokens)
+    }
+}
+
+impl Parse for AssocTypeAnnotationBody {
+    fn parse(input: ParseStream) -> Result<Self, Error> {
+        let assoc_type = input.parse::<Ident>()?.to_string();
+        input.parse::<Token![;]>()?; // consume the ';' token
+        let body = input.parse::<AnnotationBody>()?;
+
+        Ok(AssocTypeAnnotationBody { assoc_type, body })
+    }
+}
+
+/**
+    the annotations of a `spec!` call with the path of the method (e.g. `MyType::my_method`) instead of the call,
+    the arguments are `_` placeholders for the argument types
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub struct FnPathAnnotationBody {
+    pub body: AnnotationBody,
+}
+
+impl TryFrom<TokenStream> for FnPathAnnotationBody {
+    type Error = syn::Error;
+
+    fn try_from(tokens: TokenStream) -> Result<Self, Self::Error> {
+        syn::parse2(tokens)
+    }
+}
+
+impl Parse for FnPathAnnotationBody {
+    fn parse(input: ParseStream) -> Result<Self, Error> {
+        let path = input.parse::<ExprPath>()?;
+        let fn_ = path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .ok_or_else(|| Error::new(input.span(), "Expected the path of a method"))?;
+
+        if input.peek(Token![;]) {
+            input.parse::<Token![;]>()?; // consume the ';' token
+        }
+
+        let ret_type = parse_ret_type(input)?;
+        let (var_type, args_types) = parse_types(input)?;
+        let annotations = parse_annotations(input)?;
+
+        Ok(FnPathAnnotationBody {
+            body: AnnotationBody {
+                var: String::new(),
+                fn_,
+                args: vec!["_".to_string(); args_types.len()],
+                ret_type,
+                var_type,
+                args_types,
+                annotations,
+            },
+        })
+    }
+}
+
+/// a loop variable and the expression it iterates, followed by the annotations of a `spec!` call in the loop
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoopAnnotationBody {
+    pub var: String,
+    pub iter: String,
+    pub body: AnnotationBody,
+}
+
+impl TryFrom<TokenStream> for LoopAnnotationBody {
+    type Error = syn::Error;
+
+    fn try_from(tokens: TokenStream) -> Result<Self, Self::Error> {
+        syn::parse2(tokens)
+    }
+}
+
+impl Parse for LoopAnnotationBody {
+    fn parse(input: ParseStream) -> Result<Self, Error> {
+        let var = input.parse::<Ident>()?.to_string();
+        input.parse::<Token![in]>()?; // consume the 'in' token
+        let iter = to_string(&input.parse::<Expr>()?);
+        input.parse::<Token![;]>()?; // consume the ';' token
+        let body = input.parse::<AnnotationBody>()?;
+
+        Ok(LoopAnnotationBody { var, iter, body })
+    }
+}
+
+/// the receiver of the calls after the first one in a chain, replaced by the previous call
+const CHAIN_RECEIVER: &str = "__spec_chain";
+
+/**
+    the annotations of the calls in a chain, separated by `=>`,
+    the calls after the first one start with `.` and their receiver is the result of the previous call
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChainAnnotationBody {
+    pub steps: Vec<AnnotationBody>,
+}
+
+impl TryFrom<TokenStream> for ChainAnnotationBody {
+    type Error = syn::Error;
+
+    fn try_from(tokens: TokenStream) -> Result<Self, Self::Error> {
+        let trees = tokens.into_iter().collect::<Vec<_>>();
+        let is_arrow = |i: usize| {
+            matches!(&trees[i], TokenTree::Punct(p) if p.as_char() == '=' && p.spacing() == Spacing::Joint)
+                && matches!(trees.get(i + 1), Some(TokenTree::Punct(p)) if p.as_char() == '>')
+        };
+
+        let mut parts = vec![vec![]];
+        let mut i = 0;
+        while i < trees.len() {
+            if is_arrow(i) {
+                parts.push(vec![]);
+                i += 2;
+                continue;
+            }
+            parts.last_mut().unwrap().push(trees[i].clone());
+            i += 1;
+        }
+
+        let mut steps = vec![];
+        for (i, part) in parts.into_iter().enumerate() {
+            let part = part.into_iter().collect::<TokenStream>();
+            let step = if i == 0 {
+                syn::parse2::<AnnotationBody>(part)?
+            } else {
+                let receiver = Ident::new(CHAIN_RECEIVER, Span::call_site());
+                syn::parse2::<AnnotationBody>(quote! { #receiver #part })?
+            };
+            steps.push(step);
+        }
+
+        if steps.len() < 2 {
+            return Err(Error::new(
+                Span::call_site(),
+                "Expected at least two calls separated by `=>`",
+            ));
+        }
+
+        Ok(ChainAnnotationBody { steps })
+    }
+}
+
 struct Annotations(Vec<Annotation>);
 impl ParseTypeOrLifetimeOrTrait<Annotations> for Annotation {
     fn from_type(ident: String, type_name: String) -> Annotations {
@@ -42,11 +402,75 @@ impl ParseTypeOrLifetimeOrTrait<Annotations> for Annotation {
 
 impl Parse for Annotations {
     fn parse(input: ParseStream) -> Result<Self, Error> {
+        if let Some(len) = parse_len(input)? {
+            return Ok(Annotations(vec![Annotation::Len(len)]));
+        }
+
+        if let Some((position, value)) = parse_arg_value(input)? {
+            return Ok(Annotations(vec![Annotation::ArgValue(position, value)]));
+        }
+
+        if input.peek(Ident) && (input.peek2(Token![;]) || input.peek2(End)) {
+            let flag = match input.fork().parse::<Ident>()?.to_string().as_str() {
+                "deref" => Some(Annotation::Deref),
+                "no_borrow" => Some(Annotation::NoBorrow),
+                "strict" => Some(Annotation::Strict),
+                _ => None,
+            };
+            if let Some(flag) = flag {
+                input.parse::<Ident>()?; // consume the flag token
+                return Ok(Annotations(vec![flag]));
+            }
+        }
+
+        // `{TypeName1, TypeName2, ...}: TraitName`, the same annotation for each type
+        if input.peek(token::Brace) {
+            let content;
+            braced!(content in input); // consume the '{' and '}' token pair
+            let types = content.parse_terminated(Type::parse, Token![,])?;
+            if types.is_empty() {
+                return Err(Error::new(content.span(), "Expected at least one type"));
+            }
+
+            let Annotations(annotations) =
+                parse_type_or_lifetime_or_trait::<Annotation, Annotations>("", input)?;
+
+            return Ok(Annotations(
+                types
+                    .iter()
+                    .flat_map(|ty| {
+                        annotations
+                            .iter()
+                            .map(|annotation| annotation.clone().with_type(to_string(ty)))
+                    })
+                    .collect(),
+            ));
+        }
+
         let ty: Type = input.parse()?;
         parse_type_or_lifetime_or_trait::<Annotation, Annotations>(&to_string(&ty), input)
     }
 }
 
+impl Annotation {
+    /// the same annotation for another type
+    fn with_type(self, type_: String) -> Annotation {
+        match self {
+            Annotation::Trait(_, traits) => Annotation::Trait(type_, traits),
+            Annotation::Alias(_, alias) => Annotation::Alias(type_, alias),
+            Annotation::Lifetime(_, lifetime) => Annotation::Lifetime(type_, lifetime),
+            Annotation::Len(len) => Annotation::Len(len),
+            Annotation::ArgValue(position, value) => Annotation::ArgValue(position, value),
+            Annotation::AssocType(_, assoc, assigned) => {
+                Annotation::AssocType(type_, assoc, assigned)
+            }
+            Annotation::Deref => Annotation::Deref,
+            Annotation::NoBorrow => Annotation::NoBorrow,
+            Annotation::Strict => Annotation::Strict,
+        }
+    }
+}
+
 impl TryFrom<TokenStream> for AnnotationBody {
     type Error = syn::Error;
 
@@ -58,6 +482,7 @@ impl TryFrom<TokenStream> for AnnotationBody {
 impl Parse for AnnotationBody {
     fn parse(input: ParseStream) -> Result<Self, Error> {
         let (var, fn_, args) = parse_call(input)?;
+        let ret_type = parse_ret_type(input)?;
         let (var_type, args_types) = parse_types(input)?;
         let annotations = parse_annotations(input)?;
 
@@ -68,24 +493,46 @@ impl Parse for AnnotationBody {
             ));
         }
 
-        Ok(AnnotationBody {
+        // `_` for an argument cast with `as` (e.g. `1 as u8`) is the type it is cast to
+        let args_types = args
+            .iter()
+            .zip(args_types)
+            .map(|(arg, type_)| match get_cast_type(arg) {
+                Some(cast_type) if type_ == "_" => cast_type,
+                _ => type_,
+            })
+            .collect();
+
+        let mut body = AnnotationBody {
             var,
             fn_,
             args,
+            ret_type,
             var_type,
             args_types,
-            annotations,
-        })
+            annotations: vec![],
+        };
+        body.annotations = annotations
+            .into_iter()
+            .map(|annotation| body.to_assoc_type(annotation))
+            .collect();
+
+        Ok(body)
     }
 }
 
 fn parse_call(input: ParseStream) -> Result<(String, String, Vec<String>), Error> {
     let var = if input.peek(Ident) {
         to_string(&input.parse::<Ident>()?)
+    } else if input.peek(Token![self]) {
+        to_string(&input.parse::<Token![self]>()?)
     } else if input.peek(Lit) {
         to_string(&input.parse::<Lit>()?)
     } else {
-        return Err(Error::new(input.span(), "Expected identifier or literal"));
+        return Err(Error::new(
+            input.span(),
+            "Expected identifier, `self` or literal",
+        ));
     };
 
     input.parse::<Token![.]>()?; // consume the '.' token
@@ -104,6 +551,38 @@ fn parse_call(input: ParseStream) -> Result<(String, String, Vec<String>), Error
     Ok((var, fn_.to_string(), args.iter().map(to_string).collect()))
 }
 
+/**
+    get the type an argument is cast to, through the parentheses around it.
+    # Example:
+    `(1 as u8)` -> `Some(u8)`
+*/
+fn get_cast_type(arg: &str) -> Option<String> {
+    let mut expr = syn::parse_str::<Expr>(arg).ok()?;
+    loop {
+        match expr {
+            Expr::Cast(cast) => return Some(to_string(&cast.ty)),
+            Expr::Paren(paren) => expr = *paren.expr,
+            Expr::Group(group) => expr = *group.expr,
+            _ => return None,
+        }
+    }
+}
+
+fn parse_ret_type(input: ParseStream) -> Result<Option<String>, Error> {
+    if !input.peek(Token![->]) {
+        return Ok(None);
+    }
+
+    input.parse::<Token![->]>()?; // consume the '->' token
+    let ret_type: Type = input.parse()?;
+
+    if input.peek(Token![;]) {
+        input.parse::<Token![;]>()?; // consume the ';' token
+    }
+
+    Ok(Some(to_string(&ret_type)))
+}
+
 fn parse_types(input: ParseStream) -> Result<(String, Vec<String>), Error> {
     let var_type: Type = input.parse()?;
 
@@ -141,6 +620,7 @@ fn parse_annotations(input: ParseStream) -> Result<Vec<Annotation>, Error> {
 mod tests {
     use super::*;
     use quote::quote;
+    use spec_trait_utils::conversions::str_to_expr;
 
     #[test]
     fn single_argument() {
@@ -168,6 +648,24 @@ mod tests {
         assert!(result.annotations.is_empty());
     }
 
+    #[test]
+    fn cast_arguments() {
+        let input = quote! { zst.foo(1 as u8, 2i8, (3 as i64), 4 as u16); ZST; [_, i8, _, u32] };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(
+            result.args,
+            vec!["1 as u8", "2i8", "(3 as i64)", "4 as u16"]
+        );
+        assert_eq!(result.args_types, vec!["u8", "i8", "i64", "u32"]);
+
+        // without a cast the type is not inferred
+        let input = quote! { zst.foo(1, &x as &dyn Debug); ZST; [_, _] };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.args_types, vec!["_", "& dyn Debug"]);
+    }
+
     #[test]
     fn arguments_formats() {
         let input = quote! { zst.foo(1, vec![2i8], Vec::new(3), x, (4, 5), "a"); ZST; [i32, Vec<i8>, Vec<i32>, &[i32], (i32, i32), &'static str] };
@@ -201,6 +699,28 @@ mod tests {
         assert!(result.annotations.is_empty());
     }
 
+    #[test]
+    fn struct_literal_arguments() {
+        let input = quote! { zst.foo(Config { size: 3, inner: Inner { x: 1 } }, 2u8); ZST; [Config, u8]; Config: Bar };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(
+            result.args,
+            vec!["Config { size : 3 , inner : Inner { x : 1 } }", "2u8"]
+        );
+        assert_eq!(result.args_types, vec!["Config", "u8"]);
+        assert_eq!(
+            result.annotations,
+            vec![Annotation::Trait("Config".into(), vec!["Bar".into()])]
+        );
+
+        // spliced as is in the generated call
+        assert_eq!(
+            to_string(&str_to_expr(&result.args[0])),
+            "Config { size : 3 , inner : Inner { x : 1 } }"
+        );
+    }
+
     #[test]
     fn no_arguments() {
         let inputs = vec![quote! { zst.foo(); ZST; [] }, quote! { zst.foo(); ZST }];
@@ -216,6 +736,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn return_type() {
+        let input = quote! { zst.get() -> Vec<u8>; ZST; [] };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.var, "zst");
+        assert_eq!(result.fn_, "get");
+        assert!(result.args.is_empty());
+        assert_eq!(result.ret_type, Some("Vec < u8 >".to_string()));
+        assert_eq!(result.var_type, "ZST");
+        assert!(result.args_types.is_empty());
+
+        let input = quote! { zst.get(); ZST; [] };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.ret_type, None);
+    }
+
     #[test]
     fn annotations() {
         let input = quote! {
@@ -240,6 +778,280 @@ mod tests {
         );
     }
 
+    #[test]
+    fn trait_annotation_with_path() {
+        let input = quote! { zst.foo(1i32); ZST; [i32]; i32: std::fmt::Debug + Clone };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(
+            result.annotations,
+            vec![Annotation::Trait(
+                "i32".to_string(),
+                vec!["std::fmt::Debug".to_string(), "Clone".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn grouped_annotations() {
+        let input = quote! {
+            zst.foo(1u8, 2i32); ZST; [u8, i32]; {u8, i32}: Copy + Clone; {u32, Vec<_>} = MyType
+        };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(
+            result.annotations,
+            vec![
+                Annotation::Trait(
+                    "u8".to_string(),
+                    vec!["Copy".to_string(), "Clone".to_string()]
+                ),
+                Annotation::Trait(
+                    "i32".to_string(),
+                    vec!["Copy".to_string(), "Clone".to_string()]
+                ),
+                Annotation::Alias("u32".to_string(), "MyType".to_string()),
+                Annotation::Alias("Vec < _ >".to_string(), "MyType".to_string())
+            ]
+        );
+
+        let input = quote! { zst.foo(1u8); ZST; [u8]; {}: Copy };
+        assert!(AnnotationBody::try_from(input).is_err());
+    }
+
+    #[test]
+    fn len_annotation() {
+        let input = quote! { zst.foo(&[1u8, 2u8, 3u8]); ZST; [&[u8]]; len = 3 };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.annotations, vec![Annotation::Len(3)]);
+    }
+
+    #[test]
+    fn arg_value_annotation() {
+        let input = quote! { zst.foo(0u8, x); ZST; [u8, i32]; arg0 = 0u8; arg1 = -1 };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(
+            result.annotations,
+            vec![
+                Annotation::ArgValue(0, "0".into()),
+                Annotation::ArgValue(1, "-1".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn deref_annotation() {
+        let input = quote! { boxed.foo(1u8); Box<ZST>; [u8]; deref };
+        let mut result = AnnotationBody::try_from(input).unwrap();
+        assert_eq!(result.annotations, vec![Annotation::Deref]);
+
+        assert!(result.deref_receiver());
+        assert_eq!(result.var, "*boxed");
+        assert_eq!(result.var_type, "ZST");
+
+        // a single level of deref
+        assert!(!result.deref_receiver());
+
+        let input = quote! { boxed.foo(1u8); Box<ZST>; [u8]; deref: Debug };
+        let mut result = AnnotationBody::try_from(input).unwrap();
+        assert!(!result.deref_receiver());
+    }
+
+    #[test]
+    fn no_borrow_annotation() {
+        let input = quote! { self.foo(1u8); ZST; [u8]; no_borrow };
+        let result = AnnotationBody::try_from(input).unwrap();
+        assert_eq!(result.var, "self");
+        assert_eq!(result.annotations, vec![Annotation::NoBorrow]);
+
+        // a type named as the flag
+        let input = quote! { self.foo(1u8); ZST; [u8]; no_borrow: Debug };
+        let result = AnnotationBody::try_from(input).unwrap();
+        assert_eq!(
+            result.annotations,
+            vec![Annotation::Trait("no_borrow".into(), vec!["Debug".into()])]
+        );
+    }
+
+    #[test]
+    fn chain() {
+        let input =
+            quote! { x.foo(1u8) -> Mid; ZST; [u8] => .bar(2i32, 3); Mid; [i32, i32]; no_borrow };
+        let result = ChainAnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.steps.len(), 2);
+        assert_eq!(result.steps[0].var, "x");
+        assert_eq!(result.steps[0].fn_, "foo");
+        assert_eq!(result.steps[0].ret_type, Some("Mid".to_string()));
+        assert_eq!(result.steps[1].var, CHAIN_RECEIVER);
+        assert_eq!(result.steps[1].fn_, "bar");
+        assert_eq!(result.steps[1].var_type, "Mid");
+        assert_eq!(result.steps[1].args, vec!["2i32", "3"]);
+        assert_eq!(result.steps[1].annotations, vec![Annotation::NoBorrow]);
+
+        let input = quote! { x.foo(1u8); ZST; [u8] };
+        assert!(ChainAnnotationBody::try_from(input).is_err());
+    }
+
+    #[test]
+    fn assoc_type_annotation() {
+        let input = quote! {
+            x.feed(1u8, it); ZST; [u8, std::vec::IntoIter<u8>];
+            std::vec::IntoIter<u8>::Item = u8; std::string::String = MyString
+        };
+        let result = AnnotationBody::try_from(input).unwrap();
+
+        // the alias on a type that is not in the call is left as is
+        assert_eq!(
+            result.annotations,
+            vec![
+                Annotation::AssocType(
+                    "std :: vec :: IntoIter < u8 >".into(),
+                    "Item".into(),
+                    "u8".into()
+                ),
+                Annotation::Alias("std :: string :: String".into(), "MyString".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn undeclared_alias() {
+        let check = |tokens: TokenStream| {
+            AnnotationBody::try_from(tokens)
+                .unwrap()
+                .check_annotated_types(&[])
+        };
+
+        assert!(check(quote! { zst.foo(1u8); ZST; [u8]; u8: Copy }).is_ok());
+        assert!(check(quote! { zst.foo(vec![1u8]); ZST; [Vec<u8>]; u8: Copy }).is_ok());
+        assert!(check(quote! { zst.foo(1u8) -> i32; ZST; [u8]; i32: Copy }).is_ok());
+        assert!(check(quote! { zst.foo(1u8); ZST; [u8]; u8 = MyType; MyType: Copy }).is_ok());
+        assert!(check(quote! { zst.foo(&1u8); ZST; [&u8]; &u8: 'static }).is_ok());
+        assert!(check(quote! { zst.foo(1u8); ZST; [u8]; Vec<_>: Debug }).is_ok());
+
+        assert_eq!(
+            check(quote! { zst.foo(1u8); ZST; [u8]; MyType: Copy }),
+            Err("Annotated type `MyType` is neither a type of the call to `foo` nor an alias declared with `Type = Alias`".into())
+        );
+        assert!(check(quote! { zst.foo(1u8); ZST; [u8]; i32: 'static }).is_err());
+    }
+
+    #[test]
+    fn annotated_type_through_crate_alias() {
+        let type_aliases =
+            vec![TypeAliasBody::try_from(quote! { type MyVec = Vec<i32>; }).unwrap()];
+        let check = |tokens: TokenStream| {
+            AnnotationBody::try_from(tokens)
+                .unwrap()
+                .check_annotated_types(&type_aliases)
+        };
+
+        // the receiver is written with the alias and the annotation with the type, or vice versa
+        assert!(check(quote! { v.foo(1u8); MyVec; [u8]; Vec<i32>: Debug }).is_ok());
+        assert!(check(quote! { v.foo(1u8); Vec<i32>; [u8]; MyVec: Debug }).is_ok());
+        assert_eq!(
+            check(quote! { v.foo(1u8); ZST; [u8]; MyVec: Debug }),
+            Err("Annotated type `MyVec` is neither a type of the call to `foo` nor an alias declared with `Type = Alias`".into())
+        );
+    }
+
+    #[test]
+    fn unused_aliases() {
+        let impl_ = quote! { impl<T> Foo<T> for ZST { fn foo(&self, x: T) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::Not(Box::new(WhenCondition::Type(
+                    "T".into(),
+                    "Vec<MyType>".into(),
+                )))),
+            ))
+            .unwrap(),
+        ];
+        let unused = |tokens: TokenStream| {
+            AnnotationBody::try_from(tokens)
+                .unwrap()
+                .unused_aliases(&impls)
+        };
+
+        assert!(unused(quote! { zst.foo(1u8); ZST; [u8]; u8 = MyType }).is_empty());
+
+        // the alias is not in any condition
+        assert_eq!(
+            unused(quote! { zst.foo(1u8); ZST; [u8]; u8 = OtherType }),
+            vec![("u8".to_string(), "OtherType".to_string())]
+        );
+
+        // the type is not in the call
+        assert_eq!(
+            unused(quote! { zst.foo(1u8); ZST; [u8]; i32 = MyType }),
+            vec![("i32".to_string(), "MyType".to_string())]
+        );
+    }
+
+    #[test]
+    fn assoc_type() {
+        let input = quote! { Output; zst.foo(1u8); ZST; [u8]; u8: Copy };
+        let result = AssocTypeAnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.assoc_type, "Output");
+        assert_eq!(result.body.fn_, "foo");
+        assert_eq!(result.body.var_type, "ZST");
+        assert_eq!(result.body.args_types, vec!["u8"]);
+        assert_eq!(
+            result.body.annotations,
+            vec![Annotation::Trait(
+                "u8".to_string(),
+                vec!["Copy".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn fn_path() {
+        let input = quote! { ZST::foo; ZST; [u8, &str]; u8: Copy };
+        let result = FnPathAnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.body.fn_, "foo");
+        assert_eq!(result.body.args, vec!["_", "_"]);
+        assert_eq!(result.body.var_type, "ZST");
+        assert_eq!(result.body.args_types, vec!["u8", "& str"]);
+        assert_eq!(
+            result.body.annotations,
+            vec![Annotation::Trait(
+                "u8".to_string(),
+                vec!["Copy".to_string()]
+            )]
+        );
+
+        let input = quote! { ZST::get -> u8; ZST; [] };
+        let result = FnPathAnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.body.fn_, "get");
+        assert!(result.body.args.is_empty());
+        assert_eq!(result.body.ret_type, Some("u8".to_string()));
+    }
+
+    #[test]
+    fn loop_() {
+        let input = quote! { x in items.iter(); x.foo(1u8); ZST; [u8]; no_borrow };
+        let result = LoopAnnotationBody::try_from(input).unwrap();
+
+        assert_eq!(result.var, "x");
+        assert_eq!(result.iter, "items . iter ()");
+        assert_eq!(result.body.var, "x");
+        assert_eq!(result.body.fn_, "foo");
+        assert_eq!(result.body.args, vec!["1u8"]);
+        assert_eq!(result.body.annotations, vec![Annotation::NoBorrow]);
+
+        let input = quote! { x items; x.foo(1u8); ZST; [u8] };
+        assert!(LoopAnnotationBody::try_from(input).is_err());
+    }
+
     #[test]
     fn invalid_argument_count() {
         let input = quote! { zst.foo(1u8, 2u8); ZST; [u8]; };
@@ -1,13 +1,21 @@
 use proc_macro2::TokenStream;
-use spec_trait_utils::conversions::{str_to_generics, str_to_type_name, to_string};
-use spec_trait_utils::parsing::get_generics_types;
-use spec_trait_utils::types::{Aliases, replace_type, strip_lifetimes, type_assignable};
+use serde::{Deserialize, Serialize};
+use spec_trait_utils::conditions::SELF;
+use spec_trait_utils::conversions::{str_to_generics, str_to_type_name};
+use spec_trait_utils::parsing::{get_generics_types, traits_specificity};
+use spec_trait_utils::types::{
+    Aliases, replace_type, specificity, strip_lifetimes, type_assignable, type_depth,
+};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use syn::Type;
 
-/// constraint related to a single generic attribute
-#[derive(Debug, Default, Clone)]
+/**
+    constraint related to a single generic attribute.
+    The comparison operators order the constraints by specificity, so two equal constraints are only equally specific:
+    they can have different fields and serialize differently.
+*/
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Constraint {
     /// the generics (types and lifetimes) that are present in type_ or not_types
     pub generics: String,
@@ -17,16 +25,25 @@ pub struct Constraint {
     pub not_traits: Vec<String>,
 }
 
-#[derive(Debug, Default, Clone)]
+/// the constraints of an impl satisfied by a `spec!` call, compared by specificity as `Constraint`
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Constraints {
     pub inner: HashMap<String /* type definition (generic) */, Constraint>,
+    /// lifetimes required by the condition for the lifetime generics
+    pub lifetimes: HashMap<String /* lifetime generic */, String /* lifetime */>,
+    /// values required by the condition for the const generics
+    pub consts: HashMap<String /* const generic */, String /* value */>,
+    /// slice element count required by the condition
+    pub len: Option<usize>,
+    /// argument values required by the condition
+    pub arg_values: HashMap<usize /* argument position */, String /* value */>,
 }
 
 impl Ord for Constraint {
     fn cmp(&self, other: &Self) -> Ordering {
         cmp_type(self, other)
             .then(cmp_lifetimes(self, other))
-            .then(self.traits.len().cmp(&other.traits.len()))
+            .then(traits_specificity(&self.traits).cmp(&traits_specificity(&other.traits)))
             .then(self.not_types.len().cmp(&other.not_types.len()))
             .then(self.not_traits.len().cmp(&other.not_traits.len()))
     }
@@ -71,7 +88,10 @@ pub fn cmp_type_or_lifetime(
             replace_fn(&mut a, &this.generics);
             replace_fn(&mut b, &other.generics);
 
-            to_string(&a).len().cmp(&to_string(&b).len())
+            // the nesting depth breaks the ties of the node count (e.g. `(Vec<u8>, _)` over `(Vec<_>, u8)`)
+            specificity(&a)
+                .cmp(&specificity(&b))
+                .then_with(|| type_depth(&a).cmp(&type_depth(&b)))
         }
         _ => a.is_some().cmp(&b.is_some()),
     }
@@ -133,7 +153,14 @@ impl Ord for Constraints {
             };
         }
 
+        // with the same constraints on the generics, an impl constrained on `Self`, lifetimes, consts, length or argument values is more specific
+        let self_type = |c: &Self| c.inner.get(SELF).is_some_and(|c| c.type_.is_some());
         sum.cmp(&0)
+            .then(self_type(self).cmp(&self_type(other)))
+            .then(self.lifetimes.len().cmp(&other.lifetimes.len()))
+            .then(self.consts.len().cmp(&other.consts.len()))
+            .then(self.len.is_some().cmp(&other.len.is_some()))
+            .then(self.arg_values.len().cmp(&other.arg_values.len()))
     }
 }
 
@@ -225,6 +252,40 @@ mod tests {
         assert!(c2 < c1);
     }
 
+    #[test]
+    fn ordering_by_type_depth() {
+        // same node count, the deeper type is more specific
+        let c1 = Constraint {
+            generics: "".to_string(),
+            type_: Some("(Vec<u8>, _)".to_string()),
+            ..Default::default()
+        };
+
+        let c2 = Constraint {
+            generics: "".to_string(),
+            type_: Some("(Vec<_>, u8)".to_string()),
+            ..Default::default()
+        };
+
+        assert!(c1 > c2);
+        assert!(c2 < c1);
+
+        // same node count and depth
+        let c1 = Constraint {
+            generics: "<T>".to_string(),
+            type_: Some("(Vec<T>, _)".to_string()),
+            ..Default::default()
+        };
+
+        let c2 = Constraint {
+            generics: "".to_string(),
+            type_: Some("(_, Vec<_>)".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(c1.cmp(&c2), Ordering::Equal);
+    }
+
     #[test]
     fn ordering_by_lifetime() {
         let c1 = Constraint {
@@ -395,8 +456,8 @@ mod tests {
         };
 
         assert_eq!(c1, c2);
-        assert!(!(c1 < c2));
-        assert!(!(c1 > c2));
+        assert!(c1 >= c2);
+        assert!(c1 <= c2);
     }
 
     #[test]
@@ -421,6 +482,35 @@ mod tests {
         assert!(c2 < c1);
     }
 
+    #[test]
+    fn ordering_by_type_with_partial_wildcard() {
+        let get_constraint = |type_: &str| Constraint {
+            generics: "".to_string(),
+            type_: Some(type_.to_string()),
+            traits: vec![],
+            not_types: vec![],
+            not_traits: vec![],
+        };
+
+        let c1 = get_constraint("Result<_, String>");
+        let c2 = get_constraint("Result<_, _>");
+
+        assert!(c1 > c2);
+        assert!(c2 < c1);
+
+        let c1 = get_constraint("Result<u8, String>");
+        let c2 = get_constraint("Result<_, String>");
+
+        assert!(c1 > c2);
+        assert!(c2 < c1);
+
+        // the length of the names does not matter
+        let c1 = get_constraint("Result<u8, _>");
+        let c2 = get_constraint("Result<_, String>");
+
+        assert_eq!(c1.cmp(&c2), Ordering::Equal);
+    }
+
     #[test]
     fn ordering_by_type_with_generics() {
         let c1 = Constraint {
@@ -513,4 +603,45 @@ mod tests {
         assert!(c1 > c2);
         assert!(c2 < c1);
     }
+
+    #[test]
+    fn serialization_round_trip() {
+        let mut constraints = Constraints::default();
+        constraints.inner.insert(
+            "T".to_string(),
+            Constraint {
+                generics: "<'a>".to_string(),
+                type_: Some("&'a TypeA".to_string()),
+                traits: vec!["Trait1".to_string()],
+                not_types: vec!["TypeB".to_string()],
+                not_traits: vec!["Trait2".to_string()],
+            },
+        );
+        constraints
+            .lifetimes
+            .insert("'a".to_string(), "'static".to_string());
+        constraints.consts.insert("N".to_string(), "0".to_string());
+        constraints.len = Some(3);
+        constraints.arg_values.insert(0, "-1".to_string());
+
+        let serialized = serde_json::to_string(&constraints).unwrap();
+        let deserialized = serde_json::from_str::<Constraints>(&serialized).unwrap();
+
+        let constraint = deserialized.inner.get("T").unwrap();
+        assert_eq!(constraint.generics, "<'a>");
+        assert_eq!(constraint.type_, Some("&'a TypeA".to_string()));
+        assert_eq!(constraint.traits, vec!["Trait1".to_string()]);
+        assert_eq!(constraint.not_types, vec!["TypeB".to_string()]);
+        assert_eq!(constraint.not_traits, vec!["Trait2".to_string()]);
+        assert_eq!(deserialized.lifetimes, constraints.lifetimes);
+        assert_eq!(deserialized.consts, constraints.consts);
+        assert_eq!(deserialized.len, Some(3));
+        assert_eq!(deserialized.arg_values, constraints.arg_values);
+
+        // equally specific constraints are equal even if they serialize differently
+        let mut other = constraints.clone();
+        other.inner.get_mut("T").unwrap().traits = vec!["Trait3".to_string()];
+        assert_eq!(other.cmp(&constraints), Ordering::Equal);
+        assert_ne!(serde_json::to_string(&other).unwrap(), serialized);
+    }
 }
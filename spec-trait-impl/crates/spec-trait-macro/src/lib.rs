@@ -1,16 +1,30 @@
 mod annotations;
 mod constraints;
+mod memo;
 mod spec;
 mod vars;
 
+use crate::memo::Selection;
 use crate::spec::SpecBody;
-use annotations::AnnotationBody;
+use annotations::{
+    Annotation, AnnotationBody, AssocTypeAnnotationBody, ChainAnnotationBody, FnPathAnnotationBody,
+    LoopAnnotationBody,
+};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
 use spec_trait_utils::cache;
-use spec_trait_utils::conditions::{self, WhenCondition};
+use spec_trait_utils::conditions;
+use spec_trait_utils::env::{ENV_DEBUG_DNF, ENV_STRICT, ENV_WARN_ALIASES, warn_if_set};
 use spec_trait_utils::impls::ImplBody;
+use spec_trait_utils::traits::{self, TraitBody};
+use spec_trait_utils::type_traits;
+use spec_trait_utils::when;
+use std::env;
+use syn::{Ident, ItemTrait};
+
+/// the attribute of the traits of other crates registered with `spec_trait!`
+const FOREIGN_ATTR: &str = "foreign";
 
 // TODO: check support to other cases
 // TODO: allow blanket impls specialization
@@ -18,18 +32,62 @@ use spec_trait_utils::impls::ImplBody;
 `attr` is a condition in one of these forms:
 - `T: TraitName`
 - `T: TraitName1 + TraitName2`
+- `T: _`, satisfied when the `spec!` call annotates the type with any trait,
+  less specific than a condition on a named trait
+- `T: 'a` or `T: TraitName + 'a`, the lifetime is kept as `T = &'a _` (e.g. `T: 'a + Clone` is `all(T = &'a _, T: Clone)`)
 - `T = _`
 - `T = TypeName`
 - `T = &TypeName`
 - `T = TypeName1<TypeName2, ...>`
 - `T = (TypeName1, TypeName2, ...)`
 - `T = &[TypeName]`
+- `T = U`, for two generics of the impl assigned to the same type (e.g. `impl<T, U> TraitName for Pair<T, U>`)
+- `T = U::AssocName`, for the associated type of another generic of the impl (e.g. `all(U: Iterator, T = U::Item)`),
+  satisfied when the `spec!` call annotates it for the type of `U` (e.g. `MyIter::Item = u8`)
+- `'a = 'static`, for a lifetime generic of the impl used in the type (e.g. `impl<'a> TraitName for TypeName<'a>`)
+- `N = value`, for a const generic of the impl used in the type (e.g. `N = 0` on `impl<const N: usize> TraitName for [u8; N]`)
+- `Self = TypeName`, for the type the impl is for (e.g. `Self = Wrapper<i32>` on `impl<T> TraitName for Wrapper<T>`),
+  more specific than the same conditions on its generics
+- `len = N`, satisfied when the `spec!` call has the same `len = N` annotation
+- `argN = value`, satisfied when the `spec!` call asserts the same integer value for the argument at position `N`
+  (e.g. `arg0 = 0`), more specific than the same conditions without it
 - `all(attr1, attr2, ...)`
 - `any(attr1, attr2, ...)`
 - `not(attr)`
+- `attr1 & attr2`, equivalent to `all(attr1, attr2)`
+- `attr1 | attr2`, equivalent to `any(attr1, attr2)`, `&` binds tighter than `|`
+- `(attr)`, to group conditions (e.g. `(T: Foo | T: Bar) & T: Clone`)
+- empty, i.e. `#[when()]`, equivalent to the default impl without `when`
+
+A condition with more traits on a generic is more specific (e.g. `T: Clone + Debug` over `T: Clone`),
+while conditions with different traits (e.g. `T: Clone` and `T: Debug`) are equally specific:
+they are ambiguous only for the `spec!` calls that satisfy both of them.
+
+The condition can be followed by `priority = N` (e.g. `#[when(T: Foo, priority = 1)]`, 0 if missing):
+when a `spec!` call finds more impls equally specific, the one with the highest priority is selected.
+
+The condition can also be followed by `requires(...)` (e.g. `#[when(T: Foo, requires(Vec<T>: Debug))]`):
+its where predicates are added as they are to the generated impls, next to the bounds from the condition,
+but they are not used to select the impl. They can only refer to the generics kept by the generated impl.
+
+The condition can also be followed by `fallback` or `fallback = path` (e.g. `#[when(T: Foo, fallback = my_fn)]`),
+on one of the impls of the trait for the type: it generates the default impl, without condition,
+whose methods panic or call the fn at `path` with the receiver and the arguments (e.g. `my_fn(self, arg)`),
+so that the `spec!` calls that satisfy no condition select it instead of failing.
+The crate must not have another default impl of the trait for the type, so only one impl of the trait for the type can set `fallback`.
+
+`TypeName` can use the type aliases with generics declared in the crate (e.g. `T = MyResult<_>` with `type MyResult<E> = Result<String, E>;`).
+
+`TraitName` can be written with its path (e.g. `T: std::fmt::Debug`): the traits are compared by name,
+so it is satisfied by the `spec!` calls annotated with either `Debug` or `std::fmt::Debug`.
+
+The condition can also be followed by `for = {TypeName1, TypeName2, ...}` (e.g. `#[when(T: Foo, for = {TypeA, TypeB})]`),
+on an impl for `_`: it generates the impls for each of the types, with the same condition and items,
+as if each type had its own impl with the same `when`.
 
 `item` is an implementation of a trait for a type:
 - `impl<T> TraitName<T> for TypeName { ... }`
+- `impl<T> TraitName<T> for _ { ... }`, with `for = {...}` in the condition
 
 # Examples
 ```ignore
@@ -49,51 +107,121 @@ impl<T> MyTrait<T> for MyType {
     }
 }
 ```
+
+Each conjunction of the condition, once normalized to DNF, generates a separate impl.
+Set the `SPEC_TRAIT_DEBUG_DNF` environment variable to print them as warnings during the build.
 */
 #[proc_macro_attribute]
 pub fn when(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let condition = WhenCondition::try_from(TokenStream2::from(attr))
-        .expect("Failed to parse TokenStream into WhenCondition");
-
-    let mut parts = vec![];
-    for c in conditions::get_conjunctions(condition) {
-        let impl_body = ImplBody::try_from((TokenStream2::from(item.clone()), Some(c)))
-            .expect("Failed to parse TokenStream into ImplBody");
-
-        let trait_body =
-            cache::get_trait_by_name(&impl_body.trait_name).expect("Trait not found in cache");
+    expand_when(TokenStream2::from(attr), TokenStream2::from(item)).into()
+}
 
-        let specialized_trait = trait_body.specialize(&impl_body);
+/// specialize the impl for each conjunction of the condition, registering the impls in the cache for `spec!`
+fn expand_when(attr: TokenStream2, item: TokenStream2) -> TokenStream2 {
+    let expansion = match when::expand_when(
+        attr.clone(),
+        item,
+        &cache::get_type_aliases(),
+        cache::get_trait_by_name,
+    ) {
+        Ok(expansion) => expansion,
+        Err(err) => return err.to_compile_error(),
+    };
 
-        let trait_token_stream = TokenStream2::from(&specialized_trait);
-        let impl_token_stream = TokenStream2::from(&impl_body);
+    // normalized again only when it is printed
+    if let Some(attr) = &expansion.condition
+        && env::var_os(ENV_DEBUG_DNF).is_some()
+    {
+        let dnf = conditions::debug_dnf(attr.clone()).expect("Failed to normalize WhenCondition");
+        for conjunction in dnf.lines() {
+            eprintln!(
+                "warning: #[when({})] generates an impl for `{}`",
+                attr, conjunction
+            );
+        }
+    }
 
-        parts.push(quote! {
-            #trait_token_stream
-            #impl_token_stream
-        });
+    // the default impls generated by two `fallback` would conflict
+    if let Some((impl_body, other)) = expansion
+        .impls
+        .iter()
+        .filter(|impl_body| impl_body.fallback)
+        .find_map(|impl_body| cache::get_other_fallback(impl_body).map(|other| (impl_body, other)))
+    {
+        let message = format!(
+            "Only one impl of `{}` for `{}` can set `fallback`, it is already set by the impl with `#[when({})]`",
+            impl_body.trait_name,
+            impl_body.type_name.replace(" ", ""),
+            other
+                .source_condition
+                .map(|c| c.to_string())
+                .unwrap_or_default()
+        );
+        return syn::Error::new_spanned(attr, message).to_compile_error();
     }
 
-    let combined = quote! { #(#parts)* };
-    combined.into()
+    for impl_body in expansion.impls {
+        cache::register_impl(impl_body);
+    }
+    expansion.tokens
 }
 
 /**
 `item` can be one of these forms:
 - `method_call; variable_type; [args_types]`
 - `method_call; variable_type; [args_types]; annotations`
+- `method_call -> return_type; variable_type; [args_types]`
+- `method_call -> return_type; variable_type; [args_types]; annotations`
 
 `method_call` can be one of these forms:
 - `variable.function(args)`
+- `self.function(args)`, in the body of a method
+
+`return_type` is the expected return type of the `method_call`, used to select among impls that differ only in the return type.
 
 `variable_type` is the type of the variable in the `method_call`.
+A reference (e.g. `&MyType`) is a type of its own, so it selects the impls for `&MyType` instead of the ones for `MyType`.
 
 `args_types` is a colon separated list of types for the arguments in the `method_call`.
+They are checked against the parameters of the selected impl, so a mismatch is reported at the `spec!` call
+instead of inside the generated code (the lifetimes are not compared).
+An argument passed by reference to a parameter taken by value, or vice versa, is reported with the borrow to remove or add.
+The type of an argument cast with `as` can be written `_` (e.g. `x.my_method(1 as u8); MyType; [_]`), it is the type of the cast.
 
 `annotations` is a semi-colon separated list, where each item can be one of these forms:
 - `TypeName: TraitName`
 - `TypeName: TraitName1 + TraitName2`
 - `TypeName = AliasName`
+- `TypeName::AssocName = TypeName2`, the associated type `AssocName` of a type of the call
+  (e.g. `std::vec::IntoIter<u8>::Item = u8`), for the conditions like `T = U::Item`
+- `len = N`, the element count of the slice argument (e.g. `&[1u8, 2, 3]` with `len = 3`)
+- `argN = value`, the integer value of the argument at position `N` (e.g. `x.my_method(0)` with `arg0 = 0`),
+  which is not checked against the argument
+- `deref`, when no impl is for `variable_type`, the impls for the type it derefs to are used
+  (one level of `&`, `Box`, `Rc` or `Arc`, e.g. `Box<MyType>` as `MyType`), called with `&*variable`
+- `no_borrow`, the receiver is passed as is instead of as `&variable`, e.g. `self` in a `&self` method
+- `strict`, an impl that is not selected only because a trait annotation is missing (e.g. `i32: Copy` for `T: Copy`)
+  is an error instead of being skipped. Set the `SPEC_TRAIT_STRICT` environment variable to check every call this way
+- `{TypeName1, TypeName2, ...}: TraitName` or `{TypeName1, TypeName2, ...} = AliasName`, the same annotation for each type
+
+`TypeName` in a trait annotation can contain wildcards (e.g. `Vec<_>: Debug`):
+the traits are then considered implemented by every concrete type assignable to it (e.g. `Vec<u8>`, `Vec<String>`).
+
+`TraitName` can be written with its path (e.g. `i32: std::fmt::Debug`), as in `when`.
+
+A trait or lifetime annotation on a type that is neither in the call nor declared with `TypeName = AliasName` is an error.
+An alias whose type is not in the call, or that no condition of the impls uses, has no effect:
+set the `SPEC_TRAIT_WARN_ALIASES` environment variable to print these aliases as warnings during the build.
+
+Trait annotations registered for the whole crate with `spec_type_traits!` are added to `annotations`.
+
+The types can use the type aliases with generics declared in the crate, as in `when`.
+
+Inside generic code the types are not resolved to the ones the generics are instantiated with:
+a generic of the enclosing fn (e.g. `X` in `fn use_it<X: Clone>(x: X)`) is matched as a type of its own,
+so only the impls without conditions on it, or with negative ones (e.g. `not(T = u8)`), are selected,
+unless its bounds are annotated (e.g. `X: Clone`).
+To select the impls with conditions on the concrete types, call `spec!` where the types are known.
 
 # Examples
 ```ignore
@@ -103,6 +231,14 @@ let x = MyType;
 ...
 spec! { x.my_method(1u8); MyType; [u8] };
 spec! { x.my_method("str", 1); MyType; [&str, i32], i32 = MyAlias  };
+spec! { x.my_method(vec![1u8]); MyType; [Vec<u8>]; Vec<_>: Debug };
+spec! { x.my_method(&[1u8, 2, 3]); MyType; [&[u8]]; len = 3 };
+spec! { boxed.my_method(1u8); Box<MyType>; [u8]; deref };
+spec! { x.my_method(0u8); MyType; [u8]; arg0 = 0 };
+spec! { x.my_method(1 as u8, 2i32); MyType; [_, i32] };
+spec! { self.my_method(1u8); MyType; [u8]; no_borrow };
+spec! { x.my_other_method(1u8, 2i32); MyType; [u8, i32]; {u8, i32}: Copy };
+let y: u8 = spec! { x.my_getter() -> u8; MyType; [] };
 ```
 */
 #[proc_macro]
@@ -110,11 +246,827 @@ pub fn spec(item: TokenStream) -> TokenStream {
     let ann = AnnotationBody::try_from(TokenStream2::from(item))
         .expect("Failed to parse TokenStream into AnnotationBody");
 
+    if let Err(message) = ann.check_annotated_types(&cache::get_type_aliases()) {
+        return quote! { compile_error!(#message) }.into();
+    }
+
+    let spec_body = match get_spec_body(ann) {
+        Ok(spec_body) => spec_body,
+        Err(message) => return quote! { compile_error!(#message) }.into(),
+    };
+
+    if let Err(message) = spec::check_signature(&spec_body) {
+        return quote! { compile_error!(#message) }.into();
+    }
+
+    TokenStream2::from(&spec_body).into()
+}
+
+/**
+`item` has the form `AssocName; spec_item`, where `spec_item` is the input of a `spec!` call.
+
+It expands to the path of the associated type `AssocName` in the impl that the `spec!` call would select,
+so it can be used in type position. The call itself is not emitted.
+
+# Examples
+```ignore
+use spec_trait_macro::spec_type;
+
+let y: spec_type! { Output; x.my_method(1u8); MyType; [u8] } = spec! { x.my_method(1u8); MyType; [u8] };
+```
+*/
+#[proc_macro]
+pub fn spec_type(item: TokenStream) -> TokenStream {
+    let ann = AssocTypeAnnotationBody::try_from(TokenStream2::from(item))
+        .expect("Failed to parse TokenStream into AssocTypeAnnotationBody");
+
+    if let Err(message) = ann.body.check_annotated_types(&cache::get_type_aliases()) {
+        return quote! { compile_error!(#message) }.into();
+    }
+
+    let spec_body = match get_spec_body(ann.body) {
+        Ok(spec_body) => spec_body,
+        Err(message) => return quote! { compile_error!(#message) }.into(),
+    };
+
+    spec::get_assoc_type(&spec_body, &ann.assoc_type).into()
+}
+
+/**
+`item` is the input of a `spec!` call with the path of the method (e.g. `MyType::my_method`) in place of the call:
+- `Type::method_name; variable_type; [args_types]`
+- `Type::method_name; variable_type; [args_types]; annotations`
+- `Type::method_name -> return_type; variable_type; [args_types]; annotations`
+
+It expands to the path of the method in the impl that the `spec!` call would select, without calling it,
+so it can be stored or passed as a function. The receiver is its first argument.
+
+# Examples
+```ignore
+use spec_trait_macro::spec_fn;
+
+let f = spec_fn! { MyType::my_method; MyType; [u8] };
+f(&x, 1u8);
+```
+*/
+#[proc_macro]
+pub fn spec_fn(item: TokenStream) -> TokenStream {
+    let ann = FnPathAnnotationBody::try_from(TokenStream2::from(item))
+        .expect("Failed to parse TokenStream into FnPathAnnotationBody");
+
+    if let Err(message) = ann.body.check_annotated_types(&cache::get_type_aliases()) {
+        return quote! { compile_error!(#message) }.into();
+    }
+
+    let spec_body = match get_spec_body(ann.body) {
+        Ok(spec_body) => spec_body,
+        Err(message) => return quote! { compile_error!(#message) }.into(),
+    };
+
+    if let Err(message) = spec::check_signature(&spec_body) {
+        return quote! { compile_error!(#message) }.into();
+    }
+
+    spec::get_fn_path(&spec_body).into()
+}
+
+/**
+`item` has the form `var in iter; spec_item`, where `spec_item` is the input of a `spec!` call
+using the loop variable `var` (e.g. as the receiver).
+
+It expands to a `for var in iter` loop that calls the method of the impl the `spec!` call would select.
+The method is resolved once to a function (as with `spec_fn!`) before the loop, and each iteration calls it,
+so the annotations must hold for every item: the receiver and arguments have the annotated types in all the iterations.
+The values returned by the calls are discarded.
+
+When `iter` yields references (e.g. `items.iter()`), the receiver is passed as is with `no_borrow`.
+
+# Examples
+```ignore
+use spec_trait_macro::spec_for;
+
+let items = vec![MyType, MyType];
+spec_for! { x in items.iter(); x.my_method(1u8); MyType; [u8]; no_borrow };
+```
+*/
+#[proc_macro]
+pub fn spec_for(item: TokenStream) -> TokenStream {
+    let ann = LoopAnnotationBody::try_from(TokenStream2::from(item))
+        .expect("Failed to parse TokenStream into LoopAnnotationBody");
+
+    if let Err(message) = ann.body.check_annotated_types(&cache::get_type_aliases()) {
+        return quote! { compile_error!(#message) }.into();
+    }
+
+    let spec_body = match get_spec_body(ann.body) {
+        Ok(spec_body) => spec_body,
+        Err(message) => return quote! { compile_error!(#message) }.into(),
+    };
+
+    if let Err(message) = spec::check_signature(&spec_body) {
+        return quote! { compile_error!(#message) }.into();
+    }
+
+    spec::get_loop(&spec_body, &ann.var, &ann.iter).into()
+}
+
+/**
+`item` is a chain of the inputs of `spec!` calls separated by `=>`, where the calls after the first one
+start with `.` and their `variable_type` is the type returned by the previous call:
+- `variable.function1(args); variable_type; [args_types] => .function2(args); return_type_of_function1; [args_types]`
+
+Each call is specialized as with `spec!`, the result of a call is the receiver of the next one,
+borrowed unless annotated with `no_borrow` (e.g. for a method taking `self`).
+The returned types cannot be inferred by the macro, so they must be stated for each call in the chain.
+
+# Examples
+```ignore
+use spec_trait_macro::spec_chain;
+
+let y = spec_chain! { x.my_method(1u8); MyType; [u8] => .my_other_method(2i32); MidType; [i32] };
+```
+*/
+#[proc_macro]
+pub fn spec_chain(item: TokenStream) -> TokenStream {
+    let ann = ChainAnnotationBody::try_from(TokenStream2::from(item))
+        .expect("Failed to parse TokenStream into ChainAnnotationBody");
+
+    expand_chain(ann).into()
+}
+
+/// specialize the calls of the chain, each one with the previous call as receiver
+fn expand_chain(ann: ChainAnnotationBody) -> TokenStream2 {
+    let mut call = TokenStream2::new();
+
+    for mut step in ann.steps {
+        if !call.is_empty() {
+            step.var = format!("({})", call);
+        }
+
+        if let Err(message) = step.check_annotated_types(&cache::get_type_aliases()) {
+            return quote! { compile_error!(#message) };
+        }
+
+        let spec_body = match get_spec_body(step) {
+            Ok(spec_body) => spec_body,
+            Err(message) => return quote! { compile_error!(#message) },
+        };
+
+        if let Err(message) = spec::check_signature(&spec_body) {
+            return quote! { compile_error!(#message) };
+        }
+
+        call = TokenStream2::from(&spec_body);
+    }
+
+    call
+}
+
+/**
+`item` is the input of a `spec!` call.
+
+It expands to a tuple with a call for each of the most specific impls, in the order they are registered,
+instead of failing when more impls are equally specific (e.g. to dispatch among them at runtime).
+With a single most specific impl the tuple has one element. The selection is not shared with the `spec!` calls,
+and the receiver and the arguments are evaluated once per call, so they are usually references or `Copy` values.
+
+# Examples
+```ignore
+use spec_trait_macro::spec_all;
+
+let (a, b) = spec_all! { x.my_method(1u8); MyType; [u8]; u8: Copy + Debug };
+```
+*/
+#[proc_macro]
+pub fn spec_all(item: TokenStream) -> TokenStream {
+    let ann = AnnotationBody::try_from(TokenStream2::from(item))
+        .expect("Failed to parse TokenStream into AnnotationBody");
+
+    expand_all(ann).into()
+}
+
+/// call the method of each of the most specific impls
+fn expand_all(mut ann: AnnotationBody) -> TokenStream2 {
+    if let Err(message) = ann.check_annotated_types(&cache::get_type_aliases()) {
+        return quote! { compile_error!(#message) };
+    }
+
+    extend_annotations(&mut ann);
+    let (impls, traits) = match get_candidates(&mut ann) {
+        Ok(candidates) => candidates,
+        Err(message) => return quote! { compile_error!(#message) },
+    };
+
+    let spec_bodies = match spec::get_most_specific(&impls, &traits, &ann) {
+        Ok(spec_bodies) => spec_bodies,
+        Err(message) => return quote! { compile_error!(#message) },
+    };
+
+    let mut calls = vec![];
+    for spec_body in &spec_bodies {
+        if let Err(message) = spec::check_signature(spec_body) {
+            return quote! { compile_error!(#message) };
+        }
+        calls.push(TokenStream2::from(spec_body));
+    }
+
+    quote! { (#(#calls,)*) }
+}
+
+/**
+    select the most specific impl for the `spec!` annotations.
+    The selection is made once for each call signature, the calls that differ only in the receiver
+    or in the values of the arguments reuse it.
+*/
+fn get_spec_body(mut ann: AnnotationBody) -> Result<SpecBody, String> {
+    extend_annotations(&mut ann);
+
+    let selection = memo::get_or_select(memo::get_key(&ann), || select_impl(ann.clone()))?;
+
+    for (type_, alias) in &selection.unused_aliases {
+        warn_if_set(
+            ENV_WARN_ALIASES,
+            format!(
+                "`{} = {}` has no effect on the `spec!` call to `{}`, the type is not in the call or no condition uses the alias",
+                type_.replace(" ", ""),
+                alias,
+                ann.fn_
+            ),
+        );
+    }
+
+    // the selection can come from a call with another receiver or other arguments
+    let mut spec_body = selection.spec_body;
+    if spec_body.annotations.var_type != ann.var_type {
+        ann.deref_receiver();
+    }
+    spec_body.annotations = ann;
+    Ok(spec_body)
+}
+
+/// add the registered trait annotations and the strict mode to the annotations, and expand their type aliases
+fn extend_annotations(ann: &mut AnnotationBody) {
+    ann.annotations
+        .extend(vars::get_registered_annotations(&cache::get_type_traits()));
+    if env::var_os(ENV_STRICT).is_some() && !ann.strict() {
+        ann.annotations.push(Annotation::Strict);
+    }
+    ann.expand_type_aliases(&cache::get_type_aliases());
+}
+
+fn select_impl(mut ann: AnnotationBody) -> Result<Selection, String> {
+    let (impls, traits) = get_candidates(&mut ann)?;
+
+    Ok(Selection {
+        unused_aliases: ann.unused_aliases(&impls),
+        spec_body: SpecBody::try_from((&impls, &traits, &ann)).expect("Specialization failed"),
+    })
+}
+
+/// get the impls for the receiver of the call and the traits with its method
+fn get_candidates(ann: &mut AnnotationBody) -> Result<(Vec<ImplBody>, Vec<TraitBody>), String> {
+    spec::check_fn_arity(&ann.fn_, ann.args.len(), &cache::get_fn_arities(&ann.fn_))?;
+
     let aliases = vars::get_type_aliases(&ann.annotations);
     let traits = cache::get_traits_by_fn(&ann.fn_, ann.args.len());
-    let impls = cache::get_impls_by_type_and_traits(&ann.var_type, &traits, &aliases);
+    let get_impls =
+        |var_type: &str| cache::get_impls_by_type_and_traits(var_type, &traits, &aliases);
+    let mut impls = get_impls(&ann.var_type)?;
 
-    let spec_body = SpecBody::try_from((&impls, &traits, &ann)).expect("Specialization failed");
+    // without impls for the receiver, the ones for its deref target if annotated with `deref`
+    if impls.is_empty() && ann.deref_receiver() {
+        impls = get_impls(&ann.var_type)?;
+    }
 
-    TokenStream2::from(&spec_body).into()
+    Ok((impls, traits))
+}
+
+/**
+`item` is a trait definition, emitted as is.
+
+The trait is also registered for the crate while the macro expands, so `when` and `spec!` can use it
+without the build step of `spec-trait-order` (e.g. in a crate without `build.rs`).
+In that case `when` registers the impls while expanding, so the trait must be declared before them,
+the impls before the `spec!` calls, and the impls without condition need an empty `#[when()]`.
+
+# Examples
+```ignore
+use spec_trait_macro::{spec, spec_trait, when};
+
+spec_trait! {
+    trait MyTrait<T> { fn my_method(&self, arg: T); }
+}
+
+#[when(T = u8)]
+impl<T> MyTrait<T> for MyType { ... }
+
+spec! { x.my_method(1u8); MyType; [u8] };
+```
+
+A trait of another crate (e.g. `std::fmt::Display`) is registered with the `#[foreign]` attribute and its signature,
+which is not emitted. The impls use its name without path, and the conditional ones implement the specialized trait,
+so they are called with `spec!`.
+
+```ignore
+use std::fmt::{Display, Formatter};
+
+spec_trait! {
+    #[foreign]
+    trait Display { fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result; }
+}
+
+#[when(T: Debug)]
+impl<T: Debug> Display for MyType<T> { ... }
+```
+*/
+#[proc_macro]
+pub fn spec_trait(item: TokenStream) -> TokenStream {
+    declare_trait(TokenStream2::from(item)).into()
+}
+
+/// register the trait in the cache and emit it as is, unless it is `#[foreign]`
+fn declare_trait(item: TokenStream2) -> TokenStream2 {
+    let trait_ =
+        syn::parse2::<ItemTrait>(item.clone()).expect("Failed to parse TokenStream into ItemTrait");
+    let (trait_no_attrs, attrs) = traits::break_attr(&trait_);
+
+    let trait_body = TraitBody::try_from(quote! { #trait_no_attrs })
+        .expect("Failed to parse TokenStream into TraitBody");
+    cache::register_trait(trait_body);
+
+    // the trait is declared by another crate
+    if attrs.iter().any(|attr| attr.path().is_ident(FOREIGN_ATTR)) {
+        return TokenStream2::new();
+    }
+
+    item
+}
+
+/**
+`item` is the name of a trait (e.g. `MyTrait`).
+
+It expands to `const NAMES: &[&str]`, the sorted names of the traits generated by `when` for the impls of the trait
+registered in the crate. The impls without condition implement the trait itself, so they are not listed.
+
+# Examples
+```ignore
+use spec_trait_macro::spec_trait_names;
+
+spec_trait_names!(MyTrait);
+assert_eq!(NAMES.len(), 2);
+```
+*/
+#[proc_macro]
+pub fn spec_trait_names(item: TokenStream) -> TokenStream {
+    trait_names(TokenStream2::from(item)).into()
+}
+
+/// the names of the specialized traits generated for the impls of the trait in the cache
+fn trait_names(item: TokenStream2) -> TokenStream2 {
+    let trait_name = syn::parse2::<Ident>(item)
+        .expect("Failed to parse TokenStream into trait name")
+        .to_string();
+
+    let mut names = cache::list_impls_for_trait(&trait_name)
+        .into_iter()
+        .filter(|imp| imp.condition.is_some())
+        .filter_map(|imp| imp.specialized.map(|s| s.trait_name))
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    quote! { const NAMES: &[&str] = &[#(#names),*]; }
+}
+
+/**
+`item` is a semi-colon separated list, where each item has this form:
+- `TypeName: TraitName1 + TraitName2 + ...`
+
+The traits are registered for the whole crate, so `spec!` calls can omit the trait annotations for these types.
+The registration is collected when building the cache, so the macro expands to nothing.
+
+# Examples
+```ignore
+use spec_trait_macro::spec_type_traits;
+
+spec_type_traits! {
+    u8: Copy + Clone + Debug;
+    MyType: Display;
+}
+```
+*/
+#[proc_macro]
+pub fn spec_type_traits(item: TokenStream) -> TokenStream {
+    type_traits::parse_type_traits(TokenStream2::from(item))
+        .expect("Failed to parse TokenStream into TypeTraitsBody");
+
+    TokenStream::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spec_trait_utils::cache::CrateCache;
+    use spec_trait_utils::conditions::WhenCondition;
+    use spec_trait_utils::env::set_thread_cache_path;
+    use spec_trait_utils::type_aliases::TypeAliasBody;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::{fs, process};
+
+    /// the cache of a test, in a file of its own that is removed when the test ends
+    struct TestCache(PathBuf);
+
+    impl Drop for TestCache {
+        fn drop(&mut self) {
+            set_thread_cache_path(None);
+            let _ = fs::remove_file(&self.0);
+            let _ = fs::remove_file(self.0.with_extension("lock"));
+        }
+    }
+
+    /// start the test with an empty cache, each test runs in a thread of its own with its own cache file
+    fn empty_cache(test: &str) -> TestCache {
+        let path = env::temp_dir().join(format!(
+            "spec-trait-macro-test-{}-{}.json",
+            test,
+            process::id()
+        ));
+        set_thread_cache_path(Some(path.clone()));
+        cache::write_cache(&CrateCache::default(), None);
+        TestCache(path)
+    }
+
+    #[test]
+    fn declared_trait_without_build_step() {
+        // nothing found by the build step
+        let _cache = empty_cache("declared_trait_without_build_step");
+
+        let trait_ = quote! { #[allow(dead_code)] trait Foo<T> { fn foo(&self, x: T); } };
+        assert_eq!(
+            declare_trait(trait_.clone()).to_string(),
+            trait_.to_string()
+        );
+        declare_trait(trait_);
+        assert_eq!(cache::read_cache(None).traits.len(), 1);
+
+        let impl_ = quote! { impl<T> Foo<T> for MyType { fn foo(&self, x: T) {} } };
+        assert_eq!(
+            expand_when(quote! {}, impl_.clone()).to_string(),
+            impl_.to_string()
+        );
+        let specialized = expand_when(quote! { T = u8 }, impl_.clone());
+        expand_when(quote! { T = u8 }, impl_);
+        assert_eq!(cache::read_cache(None).impls.len(), 2);
+        assert_eq!(cache::list_impls_for_trait("Foo").len(), 2);
+        assert!(cache::list_impls_for_trait("Bar").is_empty());
+
+        let ann = AnnotationBody::try_from(quote! { x.foo(1u8); MyType; [u8] }).unwrap();
+        let spec_body = get_spec_body(ann).unwrap();
+        assert_eq!(
+            spec_body.impl_.condition,
+            Some(WhenCondition::Type("T".into(), "u8".into()))
+        );
+        let trait_name = spec_body.impl_.specialized.unwrap().trait_name;
+        assert!(specialized.to_string().contains(trait_name.as_str()));
+
+        let ann = AnnotationBody::try_from(quote! { x.foo(1i32); MyType; [i32] }).unwrap();
+        assert_eq!(get_spec_body(ann).unwrap().impl_.condition, None);
+
+        // only the impl with a condition has a specialized trait
+        assert_eq!(
+            trait_names(quote! { Foo }).to_string(),
+            quote! { const NAMES: &[&str] = &[#trait_name]; }.to_string()
+        );
+        assert_eq!(
+            trait_names(quote! { Bar }).to_string(),
+            quote! { const NAMES: &[&str] = &[]; }.to_string()
+        );
+
+        // through the deref of the receiver
+        let ann =
+            AnnotationBody::try_from(quote! { x.foo(1u8); Box<MyType>; [u8]; deref }).unwrap();
+        let spec_body = get_spec_body(ann).unwrap();
+        assert_eq!(
+            TokenStream2::from(&spec_body).to_string().replace(" ", ""),
+            format!("<MyTypeas{}>::foo(&*x,1u8)", trait_name)
+        );
+
+        assert_eq!(cache::get_fn_arities("foo"), vec![1]);
+        assert_eq!(
+            spec::check_fn_arity("foo", 2, &cache::get_fn_arities("foo")).unwrap_err(),
+            "No trait has a method `foo` with 2 arguments, available with: 1"
+        );
+
+        // reported at the call instead of panicking
+        let ann = AnnotationBody::try_from(quote! { x.foo(1u8, 2u8); MyType; [u8, u8] }).unwrap();
+        assert_eq!(
+            get_spec_body(ann.clone()).unwrap_err(),
+            "No trait has a method `foo` with 2 arguments, available with: 1"
+        );
+        assert!(
+            expand_all(ann)
+                .to_string()
+                .contains("No trait has a method `foo` with 2 arguments")
+        );
+    }
+
+    #[test]
+    fn foreign_trait() {
+        let _cache = empty_cache("foreign_trait");
+        let display = quote! {
+            #[foreign]
+            trait Display { fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result; }
+        };
+        let impl_ = quote! {
+            impl<T: Debug> Display for Labeled<T> {
+                fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "{:?}", self.0) }
+            }
+        };
+
+        // without its signature the trait cannot be specialized
+        let not_found = expand_when(quote! { T: Debug }, impl_.clone()).to_string();
+        assert!(not_found.contains("compile_error"));
+        assert!(not_found.contains("Trait `Display` not found"));
+
+        // the signature is registered but not emitted, the std trait is used
+        assert!(declare_trait(display).is_empty());
+
+        let specialized = expand_when(quote! { T: Debug }, impl_)
+            .to_string()
+            .replace(" ", "");
+        let ann = AnnotationBody::try_from(
+            quote! { l.fmt(f); Labeled<i32>; [&mut Formatter<'_>]; i32: Debug },
+        )
+        .unwrap();
+        let trait_name = get_spec_body(ann)
+            .unwrap()
+            .impl_
+            .specialized
+            .unwrap()
+            .trait_name;
+
+        assert!(specialized.contains(&format!(
+            "trait{}{{fnfmt(&self,f:&mutFormatter<'_>)->std::fmt::Result;}}",
+            trait_name
+        )));
+        assert!(specialized.contains(&format!("impl<T:Debug>{}forLabeled<T>", trait_name)));
+    }
+
+    #[test]
+    fn two_step_chain() {
+        let _cache = empty_cache("two_step_chain");
+        declare_trait(quote! { trait Start<T> { fn start(&self, x: T) -> Mid; } });
+        declare_trait(quote! { trait Finish<T> { fn finish(self, x: T) -> u8; } });
+
+        let start =
+            quote! { impl<T> Start<T> for Chained { fn start(&self, x: T) -> Mid { Mid } } };
+        let start_u8 = expand_when(quote! { T = u8 }, start.clone()).to_string();
+        expand_when(quote! {}, start);
+        let finish = quote! { impl<T> Finish<T> for Mid { fn finish(self, x: T) -> u8 { 0 } } };
+        let finish_i32 = expand_when(quote! { T = i32 }, finish.clone()).to_string();
+        expand_when(quote! {}, finish);
+
+        let trait_name = |expanded: &str| {
+            expanded
+                .split_whitespace()
+                .skip_while(|token| *token != "trait")
+                .nth(1)
+                .unwrap()
+                .to_string()
+        };
+
+        // the first call is the receiver of the second one, passed as is with `no_borrow`
+        let ann = ChainAnnotationBody::try_from(quote! {
+            c.start(1u8); Chained; [u8] => .finish(2i32); Mid; [i32]; no_borrow
+        })
+        .unwrap();
+        assert_eq!(
+            expand_chain(ann).to_string().replace(" ", ""),
+            format!(
+                "<Midas{}>::finish((<Chainedas{}>::start(&c,1u8)),2i32)",
+                trait_name(&finish_i32),
+                trait_name(&start_u8)
+            )
+        );
+
+        // each call selects its own impl
+        let ann = ChainAnnotationBody::try_from(quote! {
+            c.start(1i8); Chained; [i8] => .finish(2u8); Mid; [u8]; no_borrow
+        })
+        .unwrap();
+        assert_eq!(
+            expand_chain(ann).to_string().replace(" ", ""),
+            "<MidasFinish<_>>::finish((<ChainedasStart<_>>::start(&c,1i8)),2u8)"
+        );
+    }
+
+    #[test]
+    fn generated_fallback() {
+        let _cache = empty_cache("generated_fallback");
+        declare_trait(quote! { trait Describe<T> { fn describe(&self, x: T) -> String; } });
+
+        let impl_ = quote! {
+            impl<T> Describe<T> for Described { fn describe(&self, x: T) -> String { "u8".into() } }
+        };
+        let expanded = expand_when(quote! { T = u8, fallback }, impl_.clone())
+            .to_string()
+            .replace(" ", "");
+        assert!(expanded.contains(
+            "impl<T>Describe<T>forDescribed{fndescribe(&self,__arg_1:T)->String{let_=(self,__arg_1,);panic!"
+        ));
+
+        // the call that satisfies no condition selects the generated default impl
+        let ann = AnnotationBody::try_from(quote! { d.describe(1i32); Described; [i32] }).unwrap();
+        let spec_body = get_spec_body(ann).unwrap();
+        assert_eq!(spec_body.impl_.condition, None);
+        assert_eq!(
+            TokenStream2::from(&spec_body).to_string().replace(" ", ""),
+            "<DescribedasDescribe<_>>::describe(&d,1i32)"
+        );
+
+        let ann = AnnotationBody::try_from(quote! { d.describe(1u8); Described; [u8] }).unwrap();
+        assert!(get_spec_body(ann).unwrap().impl_.condition.is_some());
+
+        let no_condition = expand_when(quote! { fallback }, impl_).to_string();
+        assert!(no_condition.contains("`fallback` needs a condition"));
+    }
+
+    #[test]
+    fn generated_for_types() {
+        let _cache = empty_cache("generated_for_types");
+        declare_trait(quote! { trait Greet<T> { fn greet(&self, x: T) -> String; } });
+
+        let expanded = expand_when(
+            quote! { T = u8, for = {TypeA, TypeB} },
+            quote! { impl<T> Greet<T> for _ { fn greet(&self, x: T) -> String { "u8".into() } } },
+        )
+        .to_string()
+        .replace(" ", "");
+        assert!(expanded.contains("forTypeA{"));
+        assert!(expanded.contains("forTypeB{"));
+
+        // one impl for each type, with its own specialized trait
+        let ann = AnnotationBody::try_from(quote! { a.greet(1u8); TypeA; [u8] }).unwrap();
+        let spec_a = TokenStream2::from(&get_spec_body(ann).unwrap()).to_string();
+        let ann = AnnotationBody::try_from(quote! { b.greet(1u8); TypeB; [u8] }).unwrap();
+        let spec_b = TokenStream2::from(&get_spec_body(ann).unwrap()).to_string();
+        assert!(spec_a.replace(" ", "").starts_with("<TypeAasGreet_TypeA_"));
+        assert!(spec_b.replace(" ", "").starts_with("<TypeBasGreet_TypeB_"));
+
+        let not_placeholder = expand_when(
+            quote! { T = u8, for = {TypeA, TypeB} },
+            quote! { impl<T> Greet<T> for TypeA { fn greet(&self, x: T) -> String { "u8".into() } } },
+        )
+        .to_string();
+        assert!(not_placeholder.contains("must be for `_`"));
+    }
+
+    #[test]
+    fn three_tied_impls() {
+        let _cache = empty_cache("three_tied_impls");
+        declare_trait(quote! { trait Render<T> { fn render(&self, x: T) -> u8; } });
+
+        let impl_ =
+            quote! { impl<T> Render<T> for Rendered { fn render(&self, x: T) -> u8 { 0 } } };
+        let trait_names = [quote! { T: Copy }, quote! { T: Clone }, quote! { T: Debug }]
+            .into_iter()
+            .map(|condition| {
+                let expanded = expand_when(condition, impl_.clone()).to_string();
+                expanded
+                    .split_whitespace()
+                    .skip_while(|token| *token != "trait")
+                    .nth(1)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+
+        let ann = AnnotationBody::try_from(quote! {
+            r.render(1u8); Rendered; [u8]; u8: Copy + Clone + Debug
+        })
+        .unwrap();
+        assert_eq!(
+            expand_all(ann).to_string().replace(" ", ""),
+            format!(
+                "(<Renderedas{}<_>>::render(&r,1u8),<Renderedas{}<_>>::render(&r,1u8),<Renderedas{}<_>>::render(&r,1u8),)",
+                trait_names[0], trait_names[1], trait_names[2]
+            )
+        );
+
+        // a single most specific impl is still a tuple
+        let ann =
+            AnnotationBody::try_from(quote! { r.render(1u8); Rendered; [u8]; u8: Copy }).unwrap();
+        assert_eq!(
+            expand_all(ann).to_string().replace(" ", ""),
+            format!("(<Renderedas{}<_>>::render(&r,1u8),)", trait_names[0])
+        );
+
+        let ann = AnnotationBody::try_from(quote! { r.render(1u8); Rendered; [u8] }).unwrap();
+        assert!(
+            expand_all(ann)
+                .to_string()
+                .contains("No valid implementation found for `Rendered`")
+        );
+    }
+
+    #[test]
+    fn aliased_receiver() {
+        let _cache = empty_cache("aliased_receiver");
+        let mut crate_cache = cache::read_cache(None);
+        crate_cache
+            .type_aliases
+            .push(TypeAliasBody::try_from(quote! { type MyVec = Vec<i32>; }).unwrap());
+        cache::write_cache(&crate_cache, None);
+        declare_trait(quote! { trait Count<T> { fn count(&self, x: T) -> usize; } });
+
+        let impl_ =
+            quote! { impl<T, U> Count<T> for Vec<U> { fn count(&self, x: T) -> usize { 0 } } };
+        expand_when(quote! {}, impl_.clone());
+        expand_when(quote! { all(U = i32, T: Copy) }, impl_);
+
+        // the receiver is expanded to `Vec<i32>` before it is matched and before the annotations are checked
+        let ann = AnnotationBody::try_from(quote! { v.count(1u8); MyVec; [u8]; u8: Copy }).unwrap();
+        assert!(
+            ann.check_annotated_types(&cache::get_type_aliases())
+                .is_ok()
+        );
+        let spec_body = get_spec_body(ann).unwrap();
+        assert_eq!(spec_body.annotations.var_type.replace(" ", ""), "Vec<i32>");
+        assert!(spec_body.impl_.condition.is_some());
+
+        let ann = AnnotationBody::try_from(quote! { v.count(1u8); MyVec; [u8]; Vec<i32>: Clone })
+            .unwrap();
+        assert!(
+            ann.check_annotated_types(&cache::get_type_aliases())
+                .is_ok()
+        );
+        assert_eq!(get_spec_body(ann).unwrap().impl_.condition, None);
+    }
+
+    #[test]
+    fn contradictory_condition() {
+        let _cache = empty_cache("contradictory_condition");
+        declare_trait(quote! { trait Measure { fn measure(&self) -> usize; } });
+
+        let impl_ = quote! {
+            impl<T> Measure for Measured<T> { fn measure(&self) -> usize { 0 } }
+        };
+        let expanded = expand_when(quote! { all(T = i32, T = u8) }, impl_.clone()).to_string();
+        assert!(expanded.contains("compile_error"));
+        assert!(expanded.contains("Contradictory condition: `T = i32` and `T = u8`"));
+
+        // more and less specific types for the same generic are not a contradiction
+        let expanded = expand_when(quote! { all(T = Vec<_>, T = Vec<u8>) }, impl_).to_string();
+        assert!(!expanded.contains("compile_error"));
+    }
+
+    #[test]
+    fn types_in_modules() {
+        let _cache = empty_cache("types_in_modules");
+        declare_trait(quote! { trait Show<T> { fn show(&self, x: T); } });
+        expand_when(
+            quote! { T = u8 },
+            quote! { impl<T> Show<T> for Item { fn show(&self, x: T) {} } },
+        );
+        expand_when(
+            quote! { T = u8 },
+            quote! { impl<T> Show<T> for Item { fn show(&self, y: T) {} } },
+        );
+
+        // as found by the build step, `struct Item;` in `a` and in `b`
+        let mut crate_cache = cache::read_cache(None);
+        for (imp, module) in crate_cache.impls.iter_mut().zip(["a", "b"]) {
+            imp.module = module.into();
+            imp.local_type = true;
+        }
+        cache::write_cache(&crate_cache, None);
+        let traits = cache::get_traits_by_fn("show", 1);
+
+        assert_eq!(
+            cache::get_impls_by_type_and_traits("Item", &traits, &HashMap::new()).unwrap_err(),
+            "Type `Item` is ambiguous, write its path in the crate: `a::Item`, `b::Item`"
+        );
+        let impls = cache::get_impls_by_type_and_traits("crate::b::Item", &traits, &HashMap::new())
+            .unwrap();
+        assert_eq!(impls.len(), 1);
+        assert_eq!(impls[0].module, "b");
+
+        // reported at the call instead of panicking
+        let ann = AnnotationBody::try_from(quote! { x.show(1u8); Item; [u8] }).unwrap();
+        assert!(
+            get_spec_body(ann)
+                .unwrap_err()
+                .starts_with("Type `Item` is ambiguous")
+        );
+
+        // the specialized trait is private to the module of the impl, it is called with its path
+        let ann = AnnotationBody::try_from(quote! { x.show(1u8); a::Item; [u8] }).unwrap();
+        let spec_body = get_spec_body(ann).unwrap();
+        let trait_name = spec_body.impl_.specialized.clone().unwrap().trait_name;
+        assert_eq!(
+            TokenStream2::from(&spec_body).to_string().replace(" ", ""),
+            format!("<a::Itemascrate::a::{}>::show(&x,1u8)", trait_name)
+        );
+    }
 }
@@ -0,0 +1,136 @@
+use crate::annotations::AnnotationBody;
+use crate::spec::SpecBody;
+use spec_trait_utils::cache;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// the impl selected for a `spec!` call, with the alias annotations that have no effect on it
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub spec_body: SpecBody,
+    pub unused_aliases: Vec<(String, String)>,
+}
+
+thread_local! {
+    /// the selections made while expanding the crate, by call signature
+    static SELECTIONS: RefCell<HashMap<String, Selection>> = RefCell::new(HashMap::new());
+}
+
+/// the key of a `spec!` call: its signature, for the current crate and the current content of the cache
+pub fn get_key(ann: &AnnotationBody) -> String {
+    format!(
+        "{:?} {:?} {}",
+        std::env::var("CARGO_PKG_NAME").ok(),
+        cache::last_modified(),
+        get_signature(ann)
+    )
+}
+
+/**
+    the signature of a `spec!` call: the annotations without the receiver and the values of the arguments,
+    which do not take part in the selection.
+    The annotations are expected to be already extended with the registered ones and with the aliases expanded.
+*/
+fn get_signature(ann: &AnnotationBody) -> String {
+    let signature = AnnotationBody {
+        var: String::new(),
+        args: vec![String::new(); ann.args.len()],
+        ..ann.clone()
+    };
+
+    format!("{:?}", signature)
+}
+
+/// get the selection for the signature, made with `select` only the first time it is seen (the errors are not kept)
+pub fn get_or_select(
+    key: String,
+    select: impl FnOnce() -> Result<Selection, String>,
+) -> Result<Selection, String> {
+    if let Some(selection) = SELECTIONS.with(|selections| selections.borrow().get(&key).cloned()) {
+        return Ok(selection);
+    }
+
+    let selection = select()?;
+    SELECTIONS.with(|selections| {
+        selections.borrow_mut().insert(key, selection.clone());
+    });
+    Ok(selection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::Constraints;
+    use proc_macro2::TokenStream;
+    use quote::quote;
+    use spec_trait_utils::impls::ImplBody;
+    use spec_trait_utils::traits::TraitBody;
+    use std::cell::Cell;
+
+    fn get_signature_of(tokens: TokenStream) -> String {
+        get_signature(&AnnotationBody::try_from(tokens).unwrap())
+    }
+
+    #[test]
+    fn signature_without_receiver_and_values() {
+        let signature = get_signature_of(quote! { x.foo(1u8); MyType; [u8]; u8: Copy });
+
+        assert_eq!(
+            get_signature_of(quote! { y.foo(2u8); MyType; [u8]; u8: Copy }),
+            signature
+        );
+        assert_ne!(
+            get_signature_of(quote! { x.foo(1u16); MyType; [u16]; u8: Copy }),
+            signature
+        );
+        assert_ne!(
+            get_signature_of(quote! { x.foo(1u8); MyType; [u8] }),
+            signature
+        );
+        assert_ne!(
+            get_signature_of(quote! { x.foo(1u8) -> u8; MyType; [u8]; u8: Copy }),
+            signature
+        );
+    }
+
+    #[test]
+    fn select_once_per_key() {
+        let impl_ = ImplBody::try_from((quote! { impl Foo for MyType { fn foo(&self) {} } }, None))
+            .unwrap();
+        let trait_ = TraitBody::try_from(quote! { trait Foo { fn foo(&self); } })
+            .unwrap()
+            .specialize(&impl_);
+        let selection = Selection {
+            spec_body: SpecBody {
+                impl_,
+                trait_,
+                constraints: Constraints::default(),
+                annotations: AnnotationBody::default(),
+            },
+            unused_aliases: vec![],
+        };
+
+        // counts the selections actually made
+        let selections = Cell::new(0);
+        let select = || {
+            selections.set(selections.get() + 1);
+            Ok(selection.clone())
+        };
+
+        get_or_select("a".into(), select).unwrap();
+        assert_eq!(selections.get(), 1);
+        get_or_select("a".into(), select).unwrap();
+        assert_eq!(selections.get(), 1);
+        get_or_select("b".into(), select).unwrap();
+        assert_eq!(selections.get(), 2);
+
+        // a failed selection is made again
+        let fail = || {
+            selections.set(selections.get() + 1);
+            Err("failed".to_string())
+        };
+        assert!(get_or_select("c".into(), fail).is_err());
+        assert!(get_or_select("c".into(), fail).is_err());
+        assert_eq!(selections.get(), 4);
+    }
+}
@@ -1,17 +1,23 @@
 use crate::annotations::AnnotationBody;
 use crate::constraints::Constraints;
-use crate::vars::VarBody;
-use proc_macro2::TokenStream;
+use crate::vars::{self, VarBody};
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use spec_trait_utils::conditions::WhenCondition;
-use spec_trait_utils::conversions::{str_to_expr, str_to_trait_name, str_to_type_name, to_string};
+use spec_trait_utils::conditions::{self, WhenCondition};
+use spec_trait_utils::conversions::{
+    str_to_expr, str_to_generics, str_to_trait_name, str_to_type_name, to_string,
+};
 use spec_trait_utils::impls::ImplBody;
-use spec_trait_utils::parsing::get_generics_types;
+use spec_trait_utils::parsing::{
+    ANY_TRAIT, get_generic_default, get_generics_types, implements_trait,
+};
 use spec_trait_utils::traits::TraitBody;
 use spec_trait_utils::types::{
-    assign_lifetimes, get_concrete_type, type_assignable, type_assignable_generic_constraints,
+    assign_lifetimes, erase_projections, get_concrete_type, strip_lifetimes, type_assignable,
+    type_assignable_generic_constraints, type_contains, type_contains_outside_projections,
 };
 use std::cmp::Ordering;
+use syn::{Expr, FnArg, Ident, ReturnType, TraitItemFn, Type};
 
 #[derive(Debug, Clone)]
 pub struct SpecBody {
@@ -27,40 +33,71 @@ impl TryFrom<(&Vec<ImplBody>, &Vec<TraitBody>, &AnnotationBody)> for SpecBody {
     fn try_from(
         (impls, traits, ann): (&Vec<ImplBody>, &Vec<TraitBody>, &AnnotationBody),
     ) -> Result<Self, Self::Error> {
-        let mut satisfied_specs = impls
-            .iter()
-            .filter_map(|impl_| {
-                let trait_ = traits.iter().find(|tr| tr.name == impl_.trait_name)?;
-                let specialized_trait = trait_.specialize(impl_);
-                let default = SpecBody {
-                    impl_: impl_.clone(),
-                    trait_: specialized_trait,
-                    constraints: Constraints::default(),
-                    annotations: ann.clone(),
-                };
-                get_constraints(default)
-            })
-            .collect::<Vec<_>>();
-
-        satisfied_specs.sort();
-
-        match satisfied_specs.as_slice() {
-            [] => Err("No valid implementation found".into()),
+        match get_most_specific(impls, traits, ann)?.as_slice() {
             [most_specific] => Ok(most_specific.clone()),
-            [.., second, first] => {
-                if first == second {
-                    Err("Multiple implementations are equally specific".into())
-                } else {
-                    Ok(first.clone())
-                }
-            }
+            _ => Err("Multiple implementations are equally specific, \
+                      set `priority = N` in `when` to select one"
+                .into()),
+        }
+    }
+}
+
+/**
+    get all the impls satisfied by the annotations that are equally the most specific, in the order of the cache,
+    instead of failing when there are more of them.
+    The conjunctions of the same `when` condition are the same impl, so only the last of them is kept.
+*/
+pub fn get_most_specific(
+    impls: &[ImplBody],
+    traits: &[TraitBody],
+    ann: &AnnotationBody,
+) -> Result<Vec<SpecBody>, String> {
+    let mut satisfied_specs = impls
+        .iter()
+        .filter_map(|impl_| {
+            let trait_ = traits.iter().find(|tr| tr.name == impl_.trait_name)?;
+            let specialized_trait = trait_.specialize(impl_);
+            let default = SpecBody {
+                impl_: impl_.clone(),
+                trait_: specialized_trait,
+                constraints: Constraints::default(),
+                annotations: ann.clone(),
+            };
+            get_constraints(default).transpose()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    satisfied_specs.sort();
+
+    let Some(first) = satisfied_specs.last().cloned() else {
+        return Err(format!(
+            "No valid implementation found for `{}`",
+            ann.var_type.replace(" ", "")
+        ));
+    };
+
+    let mut most_specific: Vec<SpecBody> = vec![];
+    for spec in satisfied_specs
+        .into_iter()
+        .rev()
+        .filter(|spec| *spec == first)
+    {
+        if !most_specific
+            .iter()
+            .any(|other| other.impl_.same_source(&spec.impl_))
+        {
+            most_specific.push(spec);
         }
     }
+    most_specific.reverse();
+    Ok(most_specific)
 }
 
 impl Ord for SpecBody {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.constraints.cmp(&other.constraints)
+        self.constraints
+            .cmp(&other.constraints)
+            .then(self.impl_.priority.cmp(&other.impl_.priority))
     }
 }
 
@@ -78,33 +115,175 @@ impl PartialEq for SpecBody {
 
 impl Eq for SpecBody {}
 
-/// if the condition is satisfiable, it inserts the constraints and returns the spec body, otherwise return none
-fn get_constraints(default: SpecBody) -> Option<SpecBody> {
+/**
+    if the condition is satisfiable, it inserts the constraints and returns the spec body, otherwise return none.
+    Returns an error if the annotated types give conflicting types to a generic.
+*/
+fn get_constraints(default: SpecBody) -> Result<Option<SpecBody>, String> {
     match &default.impl_.condition {
         // from spec default
-        None => Some(default),
+        None => Ok(Some(default)),
         // from when macro
         Some(cond) => {
-            let var = VarBody::from(&default); // TODO: handle conflicting vars
+            let var = VarBody::try_from(&default)?;
             let (satisfied, constraints) = satisfies_condition(cond, &var, &default.constraints);
 
             if satisfied {
                 let mut with_constraints = default.clone();
                 with_constraints.constraints = constraints;
-                Some(with_constraints)
+                Ok(Some(with_constraints))
+            } else if default.annotations.strict() {
+                check_missing_traits(cond, &var, &default).map(|_| None)
             } else {
-                None
+                Ok(None)
+            }
+        }
+    }
+}
+
+/**
+    in strict mode, returns an error if the condition is not satisfied only because some trait annotations are missing
+    (e.g. `i32: Copy` for `T: Copy`), since the impl would otherwise be silently skipped.
+*/
+fn check_missing_traits(
+    condition: &WhenCondition,
+    var: &VarBody,
+    spec: &SpecBody,
+) -> Result<(), String> {
+    let mut missing = vec![];
+    collect_missing_traits(condition, var, &mut missing);
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    // the same var with the missing traits annotated
+    let mut annotated = var.clone();
+    for v in &mut annotated.vars {
+        v.traits.extend(
+            missing
+                .iter()
+                .filter(|(type_, _)| *type_ == v.concrete_type)
+                .map(|(_, trait_)| trait_.clone()),
+        );
+    }
+
+    if !satisfies_condition(condition, &annotated, &spec.constraints).0 {
+        return Ok(());
+    }
+
+    let annotations = missing
+        .iter()
+        .map(|(type_, trait_)| format!("`{}: {}`", type_.replace(" ", ""), trait_))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Err(format!(
+        "The impl of `{}` with condition `{}` is not selected because {} {} not annotated",
+        spec.impl_.trait_name,
+        spec.impl_.source_condition.as_ref().unwrap_or(condition),
+        annotations,
+        if missing.len() == 1 { "is" } else { "are" }
+    ))
+}
+
+/// collect the traits required by the condition that are not annotated for the type of the generic, without duplicates
+fn collect_missing_traits(
+    condition: &WhenCondition,
+    var: &VarBody,
+    missing: &mut Vec<(String, String)>,
+) {
+    match condition {
+        WhenCondition::Trait(generic, traits) => {
+            for v in var.vars.iter().filter(|v| v.impl_generic == *generic) {
+                for trait_ in traits {
+                    let annotation = (v.concrete_type.clone(), trait_.clone());
+                    if trait_ != ANY_TRAIT
+                        && !implements_trait(&v.traits, trait_)
+                        && !missing.contains(&annotation)
+                    {
+                        missing.push(annotation);
+                    }
+                }
             }
         }
+        WhenCondition::All(inner) | WhenCondition::Any(inner) => inner
+            .iter()
+            .for_each(|c| collect_missing_traits(c, var, missing)),
+        _ => {}
     }
 }
 
+/// checks the condition after normalizing it to DNF, so that `Not` only wraps type, trait, lifetime, len and argument value conditions
 fn satisfies_condition(
     condition: &WhenCondition,
     var: &VarBody,
     constraints: &Constraints,
+) -> (bool, Constraints) {
+    satisfies_normalized_condition(&conditions::normalize(condition), var, constraints)
+}
+
+fn satisfies_normalized_condition(
+    condition: &WhenCondition,
+    var: &VarBody,
+    constraints: &Constraints,
 ) -> (bool, Constraints) {
     match condition {
+        // `T = U::Item`, resolved with the associated type annotated for the type of `U`
+        WhenCondition::Type(generic, type_)
+            if get_assoc_type_path(type_, &var.generics).is_some() =>
+        {
+            let (other, assoc) = get_assoc_type_path(type_, &var.generics).unwrap();
+            let assigned = var
+                .vars
+                .iter()
+                .find(|v| v.impl_generic == other)
+                .and_then(|v| {
+                    var.assoc_types
+                        .get(&(v.concrete_type.replace(" ", ""), assoc.clone()))
+                });
+
+            match assigned {
+                Some(assigned) => satisfies_normalized_condition(
+                    &WhenCondition::Type(generic.clone(), assigned.clone()),
+                    var,
+                    constraints,
+                ),
+                None => (false, constraints.clone()),
+            }
+        }
+        // `T = U`, two generics of the impl assigned to the same type
+        WhenCondition::Type(generic, other)
+            if other != generic && get_generics_types::<Vec<_>>(&var.generics).contains(other) =>
+        {
+            let concrete_type = |g: &String| {
+                var.vars
+                    .iter()
+                    .find(|v| v.impl_generic == *g)
+                    .map(|v| get_concrete_type(&v.concrete_type, &var.aliases))
+            };
+
+            let mut new_constraints = constraints.clone();
+            let constraint = new_constraints.inner.entry(generic.clone()).or_default();
+
+            let satisfied = match (concrete_type(generic), concrete_type(other)) {
+                (Some(t1), Some(t2)) if t1 == t2 => {
+                    // the generic is already assigned to another type
+                    let conflicts = constraint
+                        .type_
+                        .as_ref()
+                        .is_some_and(|ty| !type_assignable(&t1, ty, &var.generics, &var.aliases));
+                    if !conflicts {
+                        constraint.type_ = Some(t1);
+                    }
+                    !conflicts
+                }
+                _ => false,
+            };
+            constraint.generics = var.generics.clone();
+
+            (satisfied, new_constraints)
+        }
         WhenCondition::Type(generic, type_) => {
             let declared_type = get_concrete_type(type_, &var.aliases);
             let generic_var = var.vars.iter().find(|v: &_| v.impl_generic == *generic);
@@ -135,13 +314,16 @@ fn satisfies_condition(
                             &var.aliases
                         )
                 ) ||
-                // generic parameter is forbidden to be assigned to this type
-                constraint.not_types
-                    .iter()
-                    .any(|t| type_assignable(&declared_type, t, &var.generics, &var.aliases)) ||
+                // generic parameter is forbidden to be assigned to its type, which can match a pattern
+                // with wildcards (e.g. `Vec<u8>` for `not(T = Vec<_>)`) even if the declared type is `_`
+                generic_var.is_some_and(|v|
+                    constraint.not_types
+                        .iter()
+                        .any(|t| type_assignable(&v.concrete_type, t, &var.generics, &var.aliases))
+                ) ||
                 // generic parameter should implement a trait that the type does not implement
                 declared_type_var.is_none_or(|v|
-                    constraint.traits.iter().any(|t| !v.traits.contains(t))
+                    constraint.traits.iter().any(|t| !implements_trait(&v.traits, t))
                 );
 
             constraint.generics = var.generics.clone();
@@ -183,9 +365,9 @@ fn satisfies_condition(
 
             let violates_constraints =
                 // generic parameter is not present in the function parameters or the trait does not match
-                generic_var.is_none_or(|v| traits.iter().any(|t| !v.traits.contains(t))) ||
+                generic_var.is_none_or(|v| traits.iter().any(|t| !implements_trait(&v.traits, t))) ||
                 // generic parameter is forbidden to be implement one of the traits
-                constraint.not_traits.iter().any(|t| traits.contains(t)) ||
+                constraint.not_traits.iter().any(|t| implements_trait(traits, t)) ||
                 // generic parameter is already assigned to a type that does not implement one of the traits
                 constraint.type_.as_ref().is_some_and(|ty| {
                     let declared_type_var = var.vars
@@ -193,7 +375,7 @@ fn satisfies_condition(
                         .find(|v|
                             type_assignable(&v.concrete_type, ty, &var.generics, &var.aliases)
                         );
-                    declared_type_var.is_none_or(|v| traits.iter().any(|tr| !v.traits.contains(tr)))
+                    declared_type_var.is_none_or(|v| traits.iter().any(|tr| !implements_trait(&v.traits, tr)))
                 });
 
             constraint.generics = var.generics.clone();
@@ -205,12 +387,57 @@ fn satisfies_condition(
 
             (!violates_constraints, new_constraints)
         }
+        WhenCondition::Lifetime(generic, lifetime) => {
+            let satisfied = var.lifetimes.get(generic) == Some(lifetime);
+
+            let mut new_constraints = constraints.clone();
+            if satisfied {
+                new_constraints
+                    .lifetimes
+                    .insert(generic.clone(), lifetime.clone());
+            }
+
+            (satisfied, new_constraints)
+        }
+        WhenCondition::Const(generic, value) => {
+            let satisfied = var.consts.get(generic) == Some(value);
+
+            let mut new_constraints = constraints.clone();
+            if satisfied {
+                new_constraints
+                    .consts
+                    .insert(generic.clone(), value.clone());
+            }
+
+            (satisfied, new_constraints)
+        }
+        WhenCondition::Len(len) => {
+            let satisfied = var.len == Some(*len);
+
+            let mut new_constraints = constraints.clone();
+            if satisfied {
+                new_constraints.len = Some(*len);
+            }
+
+            (satisfied, new_constraints)
+        }
+        WhenCondition::Arg(position, value) => {
+            let satisfied = var.arg_values.get(position) == Some(value);
+
+            let mut new_constraints = constraints.clone();
+            if satisfied {
+                new_constraints.arg_values.insert(*position, value.clone());
+            }
+
+            (satisfied, new_constraints)
+        }
         // make sure all the inner conditions are satisfied
         WhenCondition::All(inner) => {
             let mut new_constraints = constraints.clone();
 
             let satisfied = inner.iter().all(|cond| {
-                let (is_satisfied, nc) = satisfies_condition(cond, var, &new_constraints);
+                let (is_satisfied, nc) =
+                    satisfies_normalized_condition(cond, var, &new_constraints);
                 new_constraints = nc;
                 is_satisfied
             });
@@ -223,7 +450,7 @@ fn satisfies_condition(
             let mut new_constraints = constraints.clone();
 
             for cond in inner {
-                let (is_satisfied, nc) = satisfies_condition(cond, var, constraints);
+                let (is_satisfied, nc) = satisfies_normalized_condition(cond, var, constraints);
                 satisfied = satisfied || is_satisfied;
 
                 if is_satisfied && nc > new_constraints {
@@ -233,15 +460,29 @@ fn satisfies_condition(
 
             (satisfied, new_constraints)
         }
-        // negates the constraints on the inner condition
+        // negates the constraints on the inner condition, which is a type, trait, lifetime, len or argument value condition in DNF
         WhenCondition::Not(inner) => {
-            let (satisfied, nc) = satisfies_condition(inner, var, constraints);
+            let (satisfied, nc) = satisfies_normalized_condition(inner, var, constraints);
 
             (!satisfied, nc)
         }
     }
 }
 
+/// the generic of the impl and the associated type of an associated type path (e.g. `U` and `Item` for `U::Item`)
+fn get_assoc_type_path(type_: &str, generics: &str) -> Option<(String, String)> {
+    let Type::Path(path) = str_to_type_name(type_) else {
+        return None;
+    };
+    let [generic, assoc] = path.path.segments.iter().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+
+    let is_generic = get_generics_types::<Vec<_>>(generics).contains(&generic.ident.to_string());
+    (path.qself.is_none() && is_generic && generic.arguments.is_none() && assoc.arguments.is_none())
+        .then(|| (generic.ident.to_string(), assoc.ident.to_string()))
+}
+
 impl From<&SpecBody> for TokenStream {
     fn from(spec_body: &SpecBody) -> Self {
         let impl_body = spec_body
@@ -251,20 +492,10 @@ impl From<&SpecBody> for TokenStream {
             .expect("ImplBody not specialized");
 
         let type_ = str_to_type_name(&spec_body.annotations.var_type);
-        let trait_ = str_to_trait_name(&impl_body.trait_name);
-        let generics = get_types_for_generics(spec_body);
+        let trait_ = str_to_trait_name(&get_trait_path(spec_body, &impl_body.trait_name));
+        let generics = get_types_for_generics(spec_body, true);
         let fn_ = str_to_expr(&spec_body.annotations.fn_);
-        let var = str_to_expr(("&".to_owned() + &spec_body.annotations.var).as_str());
-        let args = spec_body
-            .annotations
-            .args
-            .iter()
-            .map(|arg| str_to_expr(arg))
-            .collect::<Vec<_>>();
-
-        let all_args = std::iter::once(var.clone())
-            .chain(args.iter().cloned())
-            .collect::<Vec<_>>();
+        let all_args = get_call_args(spec_body);
 
         quote! {
             <#type_ as #trait_ #generics>::#fn_(#(#all_args),*)
@@ -272,16 +503,257 @@ impl From<&SpecBody> for TokenStream {
     }
 }
 
-pub fn get_types_for_generics(spec: &SpecBody) -> TokenStream {
+/**
+    get the path of the trait to call: the trait generated by `when` is next to the impl, so it is prefixed
+    by the module of the impl when known (e.g. `crate::a::Foo_MyType_123`), while the trait of an impl
+    without condition is the one declared by the user, used as it is in scope.
+*/
+fn get_trait_path(spec_body: &SpecBody, trait_name: &str) -> String {
+    if spec_body.impl_.condition.is_none() || spec_body.impl_.module.is_empty() {
+        return trait_name.to_owned();
+    }
+
+    format!("crate::{}::{}", spec_body.impl_.module, trait_name)
+}
+
+/**
+    check that a trait has the method of the `spec!` call with its number of arguments (`arities` of the method
+    in all the traits), so that a wrong call is not reported as a missing implementation for the type.
+*/
+pub fn check_fn_arity(fn_: &str, args_len: usize, arities: &[usize]) -> Result<(), String> {
+    match arities {
+        [] => Err(format!("No trait has a method `{}`", fn_)),
+        _ if arities.contains(&args_len) => Ok(()),
+        _ => Err(format!(
+            "No trait has a method `{}` with {} arguments, available with: {}",
+            fn_,
+            args_len,
+            arities
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/**
+    check that the annotated types of the arguments match the params of the fn in the selected impl,
+    so that a wrong annotation is reported at the `spec!` call instead of in the generated call.
+    The params using `Self` or `impl Trait` are not checked.
+*/
+pub fn check_signature(spec_body: &SpecBody) -> Result<(), String> {
+    let trait_body = spec_body
+        .trait_
+        .specialized
+        .as_ref()
+        .expect("TraitBody not specialized");
+    let ann = &spec_body.annotations;
+
+    let Some(trait_fn) = trait_body.find_fn(&ann.fn_, ann.args.len()) else {
+        return Ok(());
+    };
+
+    // the params can use both the generics of the trait and the ones of the fn
+    let mut generics = str_to_generics(&trait_body.generics);
+    generics
+        .params
+        .extend(trait_fn.sig.generics.params.iter().cloned());
+
+    // the lifetimes and the associated types of the generics (e.g. `A::Item`) are left to the compiler,
+    // they are not always annotated
+    let strip = |type_: &str| {
+        let mut type_ = str_to_type_name(type_);
+        strip_lifetimes(&mut type_, &generics);
+        strip_lifetimes(&mut type_, &str_to_generics("<'static>"));
+        for generic in get_generics_types::<Vec<_>>(&to_string(&generics)) {
+            erase_projections(&mut type_, &generic);
+        }
+        to_string(&type_)
+    };
+    let generics_str = to_string(&generics);
+
+    let aliases = vars::get_type_aliases(&ann.annotations);
+    let assignable = |concrete_type: &str, declared_type: &str| {
+        type_assignable(
+            &strip(concrete_type),
+            &strip(declared_type),
+            &generics_str,
+            &aliases,
+        )
+    };
+
+    for ((arg, concrete_type), declared_type) in ann
+        .args
+        .iter()
+        .zip(&ann.args_types)
+        .zip(vars::get_param_types(&trait_fn))
+    {
+        let checkable = !type_contains(&str_to_type_name(&declared_type), "Self")
+            && !matches!(str_to_type_name(&declared_type), Type::ImplTrait(_));
+
+        if checkable && !assignable(concrete_type, &declared_type) {
+            let suggestion = get_borrow_suggestion(arg, concrete_type, &declared_type, assignable)
+                .map(|suggestion| format!(", {}", suggestion))
+                .unwrap_or_default();
+
+            return Err(format!(
+                "Mismatched type for argument `{}` of `{}`: annotated `{}`, but the selected impl expects `{}`{}",
+                arg,
+                ann.fn_,
+                concrete_type.replace(" ", ""),
+                declared_type.replace(" ", ""),
+                suggestion
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/**
+    suggest to remove the borrow of an argument passed by reference to a param taken by value, or to add it in the opposite case.
+    # Example:
+    `&x` annotated as `&u8` for a param `u8` -> `pass it by value instead: `x` annotated as `u8``
+*/
+fn get_borrow_suggestion(
+    arg: &str,
+    concrete_type: &str,
+    declared_type: &str,
+    assignable: impl Fn(&str, &str) -> bool,
+) -> Option<String> {
+    match (
+        str_to_type_name(concrete_type),
+        str_to_type_name(declared_type),
+    ) {
+        (Type::Reference(reference), _)
+            if assignable(&to_string(&reference.elem), declared_type) =>
+        {
+            let value = match str_to_expr(arg) {
+                Expr::Reference(expr) => to_string(&expr.expr),
+                Expr::Path(_) => format!("*{}", arg),
+                _ => format!("*({})", arg),
+            };
+            Some(format!(
+                "pass it by value instead: `{}` annotated as `{}`",
+                value.replace(" ", ""),
+                to_string(&reference.elem).replace(" ", "")
+            ))
+        }
+        (_, Type::Reference(reference))
+            if assignable(concrete_type, &to_string(&reference.elem)) =>
+        {
+            let borrow = if reference.mutability.is_some() {
+                "&mut "
+            } else {
+                "&"
+            };
+            Some(format!(
+                "pass it by reference instead: `{}{}` annotated as `{}{}`",
+                borrow,
+                arg.replace(" ", ""),
+                borrow,
+                concrete_type.replace(" ", "")
+            ))
+        }
+        _ => None,
+    }
+}
+
+/**
+    the path of the associated type `assoc_type` in the selected impl.
+    # Example:
+    `<MyType as MyTrait_MyType_123>::Output`
+*/
+pub fn get_assoc_type(spec_body: &SpecBody, assoc_type: &str) -> TokenStream {
+    let impl_body = spec_body
+        .impl_
+        .specialized
+        .as_ref()
+        .expect("ImplBody not specialized");
+
+    let type_ = str_to_type_name(&spec_body.annotations.var_type);
+    let trait_ = str_to_trait_name(&get_trait_path(spec_body, &impl_body.trait_name));
+    // in type position the compiler cannot infer the generics from the call
+    let generics = get_types_for_generics(spec_body, false);
+    let assoc_type = Ident::new(assoc_type, Span::call_site());
+
+    quote! {
+        <#type_ as #trait_ #generics>::#assoc_type
+    }
+}
+
+/**
+    the path of the method in the selected impl, without the call.
+    # Example:
+    `<MyType as MyTrait_MyType_123>::my_method`
+*/
+pub fn get_fn_path(spec_body: &SpecBody) -> TokenStream {
+    let impl_body = spec_body
+        .impl_
+        .specialized
+        .as_ref()
+        .expect("ImplBody not specialized");
+
+    let type_ = str_to_type_name(&spec_body.annotations.var_type);
+    let trait_ = str_to_trait_name(&get_trait_path(spec_body, &impl_body.trait_name));
+    // as in the call, the compiler infers the generics left as `_` from the uses of the path
+    let generics = get_types_for_generics(spec_body, true);
+    let fn_ = Ident::new(&spec_body.annotations.fn_, Span::call_site());
+
+    quote! {
+        <#type_ as #trait_ #generics>::#fn_
+    }
+}
+
+/// the receiver followed by the arguments of the `spec!` call
+fn get_call_args(spec_body: &SpecBody) -> Vec<Expr> {
+    std::iter::once(spec_body.annotations.receiver())
+        .chain(spec_body.annotations.args.iter().cloned())
+        .map(|arg| str_to_expr(&arg))
+        .collect()
+}
+
+/**
+    a loop over `iter` binding each item to `var` that calls the method of the selected impl,
+    the path of the method is resolved once before the loop and reused in every iteration.
+*/
+pub fn get_loop(spec_body: &SpecBody, var: &str, iter: &str) -> TokenStream {
+    let fn_path = get_fn_path(spec_body);
+    let var = Ident::new(var, Span::call_site());
+    let iter = str_to_expr(iter);
+    let all_args = get_call_args(spec_body);
+
+    quote! {
+        {
+            let __spec_fn = #fn_path;
+            for #var in #iter {
+                __spec_fn(#(#all_args),*);
+            }
+        }
+    }
+}
+
+/// the types of the trait generics, `_` is used for the ones the compiler can infer if `allow_infer`
+pub fn get_types_for_generics(spec: &SpecBody, allow_infer: bool) -> TokenStream {
     let trait_body = spec
         .trait_
         .specialized
         .as_ref()
         .expect("TraitBody not specialized");
 
+    let trait_fn = trait_body.find_fn(&spec.annotations.fn_, spec.annotations.args.len());
+    let with_return = spec.annotations.ret_type.is_some();
+
     let types = get_generics_types::<Vec<_>>(&trait_body.generics)
         .iter()
-        .map(|g| get_type(g.trim(), &spec.constraints))
+        .map(|g| {
+            let inferable = allow_infer
+                && trait_fn
+                    .as_ref()
+                    .is_some_and(|f| is_inferable(g.trim(), f, with_return));
+            get_type(g.trim(), &trait_body.generics, &spec.constraints, inferable)
+        })
         .map(|t| str_to_type_name(&t))
         .collect::<Vec<_>>();
 
@@ -292,21 +764,54 @@ pub fn get_types_for_generics(spec: &SpecBody) -> TokenStream {
     }
 }
 
-fn get_type(generic: &str, constraints: &Constraints) -> String {
+/**
+    get the type of a generic from the constraints, falling back to `_`.
+    If the generic is not `inferable` by the compiler, its default is used instead of `_`.
+*/
+fn get_type(generic: &str, generics: &str, constraints: &Constraints, inferable: bool) -> String {
     constraints
         .inner
         .get(generic)
         .and_then(|constraint| constraint.type_.clone())
+        .or_else(|| {
+            if inferable {
+                None
+            } else {
+                get_generic_default(generics, generic)
+            }
+        })
         .unwrap_or_else(|| "_".into())
 }
 
+/**
+    a generic is inferable if it is used in the params of `trait_fn`, or in its return type when annotated.
+    A generic only used in associated type projections (e.g. `A::Item`, `<A as Trait>::Output`) is not inferable.
+*/
+fn is_inferable(generic: &str, trait_fn: &TraitItemFn, with_return: bool) -> bool {
+    let params = trait_fn.sig.inputs.iter().filter_map(|arg| match arg {
+        FnArg::Typed(pat_type) => Some(pat_type.ty.as_ref()),
+        FnArg::Receiver(_) => None,
+    });
+    let output = match &trait_fn.sig.output {
+        ReturnType::Type(_, ty) if with_return => Some(ty.as_ref()),
+        _ => None,
+    };
+
+    params
+        .chain(output)
+        .any(|ty| type_contains_outside_projections(ty, generic))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::annotations::Annotation;
     use crate::constraints::Constraint;
-    use crate::vars::VarInfo;
+    use crate::vars::{VarInfo, get_registered_annotations};
+    use spec_trait_utils::type_aliases::{TypeAliasBody, expand_condition_aliases};
+    use spec_trait_utils::type_traits::TypeTraitsBody;
     use spec_trait_utils::types::Aliases;
+    use std::collections::HashMap;
     use std::vec;
 
     fn get_var_body() -> VarBody {
@@ -321,6 +826,11 @@ mod tests {
                 concrete_type: "&'a MyType".into(),
                 traits: vec!["MyTrait".into()],
             }],
+            lifetimes: HashMap::new(),
+            consts: HashMap::new(),
+            len: None,
+            arg_values: HashMap::new(),
+            assoc_types: HashMap::new(),
         }
     }
 
@@ -363,11 +873,66 @@ mod tests {
 
         assert!(satisfies);
 
-        let c = constraints.inner.get("T".into()).unwrap();
+        let c = constraints.inner.get("T").unwrap();
         assert_eq!(c.type_, Some("& 'b MyType".into()));
         assert!(c.traits.contains(&"MyTrait".into()));
     }
 
+    #[test]
+    fn not_all_condition() {
+        // not(all(A, B)) -> any(not(A), not(B))
+        let condition = WhenCondition::Not(Box::new(WhenCondition::All(vec![
+            WhenCondition::Type("T".into(), "&MyType".into()),
+            WhenCondition::Type("T".into(), "&AnotherType".into()),
+        ])));
+        let var = get_var_body();
+
+        let (satisfies, constraints) =
+            satisfies_condition(&condition, &var, &Constraints::default());
+
+        assert!(satisfies);
+        let c = constraints.inner.get("T").unwrap();
+        assert_eq!(c.type_, None);
+        assert_eq!(c.not_types, vec!["& AnotherType".to_string()]);
+
+        let condition = WhenCondition::Not(Box::new(WhenCondition::All(vec![
+            WhenCondition::Type("T".into(), "&MyType".into()),
+            WhenCondition::Trait("T".into(), vec!["MyTrait".into()]),
+        ])));
+
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+
+        assert!(!satisfies);
+    }
+
+    #[test]
+    fn not_any_condition() {
+        // not(any(A, B)) -> all(not(A), not(B))
+        let condition = WhenCondition::Not(Box::new(WhenCondition::Any(vec![
+            WhenCondition::Type("T".into(), "&AnotherType".into()),
+            WhenCondition::Trait("T".into(), vec!["AnotherTrait".into()]),
+        ])));
+        let var = get_var_body();
+
+        let (satisfies, constraints) =
+            satisfies_condition(&condition, &var, &Constraints::default());
+
+        assert!(satisfies);
+        let c = constraints.inner.get("T").unwrap();
+        assert_eq!(c.type_, None);
+        assert_eq!(c.not_types, vec!["& AnotherType".to_string()]);
+        assert_eq!(c.not_traits, vec!["AnotherTrait".to_string()]);
+
+        let condition = WhenCondition::Not(Box::new(WhenCondition::Any(vec![
+            WhenCondition::Type("T".into(), "&AnotherType".into()),
+            WhenCondition::Trait("T".into(), vec!["MyTrait".into()]),
+        ])));
+
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+
+        assert!(!satisfies);
+    }
+
     #[test]
     fn type_not_respected() {
         let condition = WhenCondition::Type("T".into(), "AnotherType".into());
@@ -417,6 +982,30 @@ mod tests {
         assert!(satisfies);
     }
 
+    #[test]
+    fn trait_and_lifetime_condition() {
+        let condition = WhenCondition::try_from(quote! { T: 'a + MyTrait }).unwrap();
+        let mut var = get_var_body();
+
+        let (satisfies, constraints) =
+            satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(satisfies);
+        let constraint = constraints.inner.get("T").unwrap();
+        assert_eq!(constraint.type_, Some("& 'a _".into()));
+        assert_eq!(constraint.traits, vec!["MyTrait".to_string()]);
+
+        // the lifetime is not dropped: a type that is not a reference does not satisfy it
+        var.vars[0].concrete_type = "MyType".into();
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(!satisfies);
+
+        // nor does a reference without the trait
+        var.vars[0].concrete_type = "&'a MyType".into();
+        var.vars[0].traits = vec![];
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(!satisfies);
+    }
+
     #[test]
     fn lifetime_not_respected() {
         let condition = WhenCondition::Type("T".into(), "&'static _".into());
@@ -441,6 +1030,30 @@ mod tests {
         assert!(!satisfies);
     }
 
+    #[test]
+    fn trait_with_path_respected() {
+        // condition `T: std::fmt::Debug`, annotation `MyTrait + Debug`
+        let condition = WhenCondition::Trait("T".into(), vec!["std::fmt::Debug".into()]);
+        let mut var = get_var_body();
+        var.vars[0].traits.push("Debug".into());
+
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(satisfies);
+
+        // condition `T: Debug`, annotation `std::fmt::Debug`
+        let condition = WhenCondition::Trait("T".into(), vec!["Debug".into()]);
+        var.vars[0].traits = vec!["std::fmt::Debug".into()];
+
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(satisfies);
+
+        // condition `not(T: Debug)`
+        let condition = WhenCondition::Not(Box::new(condition));
+
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(!satisfies);
+    }
+
     #[test]
     fn type_forbidden() {
         let condition = WhenCondition::All(vec![
@@ -483,6 +1096,11 @@ mod tests {
                 concrete_type: "Vec<MyType>".into(),
                 traits: vec![],
             }],
+            lifetimes: HashMap::new(),
+            consts: HashMap::new(),
+            len: None,
+            arg_values: HashMap::new(),
+            assoc_types: HashMap::new(),
         };
 
         let (satisfies, constraints) =
@@ -490,7 +1108,7 @@ mod tests {
 
         assert!(satisfies);
 
-        let c = constraints.inner.get("T".into()).unwrap();
+        let c = constraints.inner.get("T").unwrap();
         assert_eq!(
             c.type_.clone().unwrap().replace(" ", ""),
             "Vec<MyType>".to_string()
@@ -526,12 +1144,68 @@ mod tests {
             satisfies_condition(&condition, &var, &Constraints::default());
 
         assert!(satisfies);
-        let c = constraints.inner.get("T".into()).unwrap();
+        let c = constraints.inner.get("T").unwrap();
         assert_eq!(c.type_, Some("& MyType".into()));
         assert!(c.not_types.contains(&"i32".to_string()));
         assert!(c.not_types.contains(&"u32".to_string()));
     }
 
+    #[test]
+    fn negated_partial_wildcard() {
+        let var = |concrete_type: &str| VarBody {
+            aliases: Aliases::default(),
+            generics: "<T>".to_string(),
+            vars: vec![VarInfo {
+                impl_generic: "T".into(),
+                trait_generic: Some("A".into()),
+                concrete_type: concrete_type.into(),
+                traits: vec![],
+            }],
+            lifetimes: HashMap::new(),
+            consts: HashMap::new(),
+            len: None,
+            arg_values: HashMap::new(),
+            assoc_types: HashMap::new(),
+        };
+        let satisfies = |condition: &WhenCondition, concrete_type: &str| {
+            satisfies_condition(condition, &var(concrete_type), &Constraints::default()).0
+        };
+        let not_vec =
+            WhenCondition::Not(Box::new(WhenCondition::Type("T".into(), "Vec<_>".into())));
+
+        assert!(!satisfies(&not_vec, "Vec<u8>"));
+        assert!(!satisfies(&not_vec, "Vec<Vec<u8>>"));
+        assert!(satisfies(&not_vec, "u8"));
+        assert!(satisfies(&not_vec, "&Vec<u8>"));
+        assert!(satisfies(&not_vec, "Option<Vec<u8>>"));
+
+        let (_, constraints) = satisfies_condition(&not_vec, &var("u8"), &Constraints::default());
+        let c = constraints.inner.get("T").unwrap();
+        assert_eq!(c.not_types, vec!["Vec < _ >".to_string()]);
+
+        // only the excluded pattern
+        let not_nested_vec = WhenCondition::Not(Box::new(WhenCondition::Type(
+            "T".into(),
+            "Vec<Vec<_>>".into(),
+        )));
+        assert!(satisfies(&not_nested_vec, "Vec<u8>"));
+        assert!(!satisfies(&not_nested_vec, "Vec<Vec<u8>>"));
+
+        // combined with a type condition on the same generic
+        let condition = WhenCondition::All(vec![
+            not_vec.clone(),
+            WhenCondition::Type("T".into(), "Vec<u8>".into()),
+        ]);
+        assert!(!satisfies(&condition, "Vec<u8>"));
+
+        let condition = WhenCondition::All(vec![
+            not_vec.clone(),
+            WhenCondition::Type("T".into(), "_".into()),
+        ]);
+        assert!(satisfies(&condition, "u8"));
+        assert!(!satisfies(&condition, "Vec<u8>"));
+    }
+
     #[test]
     fn default_impl() {
         let impls = vec![get_impl_body(None)];
@@ -561,7 +1235,7 @@ mod tests {
         let spec_body = result.unwrap();
         assert_eq!(spec_body.impl_.trait_name, "MyTrait");
         assert_eq!(
-            spec_body.constraints.inner.get("T".into()),
+            spec_body.constraints.inner.get("T"),
             Some(
                 &(Constraint {
                     generics: "<T, U>".to_string(),
@@ -592,7 +1266,7 @@ mod tests {
         let spec_body = result.unwrap();
         assert_eq!(spec_body.impl_.trait_name, "MyTrait");
         assert_eq!(
-            spec_body.constraints.inner.get("T".into()),
+            spec_body.constraints.inner.get("T"),
             Some(
                 &(Constraint {
                     generics: "<T, U>".to_string(),
@@ -619,54 +1293,1552 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err(),
-            "Multiple implementations are equally specific"
+            "Multiple implementations are equally specific, set `priority = N` in `when` to select one"
         );
     }
 
     #[test]
-    fn no_valid_impl() {
+    fn all_most_specific_impls() {
         let impls = vec![
-            get_impl_body(Some(WhenCondition::Type("T".into(), "&MyOtherType".into()))),
             get_impl_body(Some(WhenCondition::Trait(
                 "T".into(),
-                vec!["MyOtherTrait".into()],
+                vec!["MyTrait".into()],
             ))),
+            get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Copy".into()]))),
+            get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Clone".into()]))),
+            get_impl_body(None),
         ];
-        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
-        let annotations = get_annotation_body();
+        let traits = impls.iter().map(get_trait_body).collect::<Vec<_>>();
+        let mut annotations = get_annotation_body();
+        annotations.annotations.push(Annotation::Trait(
+            "&MyType".to_string(),
+            vec!["Copy".to_string(), "Clone".to_string()],
+        ));
 
-        let result = SpecBody::try_from((&impls, &traits, &annotations));
+        assert!(SpecBody::try_from((&impls, &traits, &annotations)).is_err());
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "No valid implementation found");
+        let result = get_most_specific(&impls, &traits, &annotations).unwrap();
+
+        assert_eq!(
+            result
+                .iter()
+                .map(|spec_body| spec_body.impl_.condition.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                WhenCondition::Trait("T".into(), vec!["MyTrait".into()]),
+                WhenCondition::Trait("T".into(), vec!["Copy".into()]),
+                WhenCondition::Trait("T".into(), vec!["Clone".into()]),
+            ]
+        );
     }
 
     #[test]
-    fn impl_with_wildcard() {
-        let impls = vec![get_impl_body(Some(WhenCondition::Type(
-            "T".into(),
-            "Vec<_>".into(),
-        )))];
-        let traits = vec![get_trait_body(&impls[0])];
-        let mut annotations = get_annotation_body();
+    fn sibling_conjunctions_equally_specific() {
+        // `any(T: MyTrait, T: Copy)` generates an impl for each conjunction, both satisfied by `&MyType`
+        let source = WhenCondition::Any(vec![
+            WhenCondition::Trait("T".into(), vec!["MyTrait".into()]),
+            WhenCondition::Trait("T".into(), vec!["Copy".into()]),
+        ]);
+        let impls = conditions::get_conjunctions(source.clone())
+            .into_iter()
+            .map(|conjunction| ImplBody {
+                source_condition: Some(source.clone()),
+                ..get_impl_body(Some(conjunction))
+            })
+            .collect::<Vec<_>>();
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+        let mut annotations = get_annotation_body();
+        annotations.annotations.push(Annotation::Trait(
+            "&MyType".to_string(),
+            vec!["Copy".to_string()],
+        ));
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert!(impls[0].same_source(&result.unwrap().impl_));
+
+        // the same conjunctions written as separate impls are still ambiguous
+        let impls = impls
+            .into_iter()
+            .map(|impl_| ImplBody {
+                source_condition: None,
+                ..impl_
+            })
+            .collect::<Vec<_>>();
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reference_wildcard_specificity() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Type("T".into(), "_".into()))),
+            get_impl_body(Some(WhenCondition::Type("T".into(), "&_".into()))),
+            get_impl_body(Some(WhenCondition::Type("T".into(), "&u8".into()))),
+        ];
+        let traits = impls.iter().map(get_trait_body).collect::<Vec<_>>();
+
+        // `&u8` is more specific than `&_`, which is more specific than `_`
+        for (arg_type, expected) in [
+            ("&u8", 2),
+            ("&'static u8", 2),
+            ("&i32", 1),
+            ("&&u8", 1),
+            ("i32", 0),
+        ] {
+            let mut annotations = get_annotation_body();
+            annotations.args_types = vec![arg_type.to_string()];
+
+            let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+            assert_eq!(
+                result.impl_.condition, impls[expected].condition,
+                "{}",
+                arg_type
+            );
+        }
+    }
+
+    #[test]
+    fn disjoint_trait_conditions() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Clone".into()]))),
+            get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Debug".into()]))),
+        ];
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+
+        // only the satisfied condition is considered
+        let mut annotations = get_annotation_body();
+        annotations.annotations.push(Annotation::Trait(
+            "&MyType".to_string(),
+            vec!["Debug".to_string()],
+        ));
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        // the traits are not comparable, so the conditions are equally specific when both are satisfied
+        annotations.annotations.push(Annotation::Trait(
+            "&MyType".to_string(),
+            vec!["Clone".to_string()],
+        ));
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+        assert_eq!(
+            result.unwrap_err(),
+            "Multiple implementations are equally specific, set `priority = N` in `when` to select one"
+        );
+    }
+
+    #[test]
+    fn priority_breaks_tie() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Trait(
+                "T".into(),
+                vec!["MyTrait".into()],
+            ))),
+            ImplBody {
+                priority: 1,
+                ..get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Copy".into()])))
+            },
+        ];
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+        let mut annotations = get_annotation_body();
+        annotations.annotations.push(Annotation::Trait(
+            "&MyType".to_string(),
+            vec!["Copy".to_string()],
+        ));
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        // a negative priority loses against the default one
+        let impls = vec![
+            impls[0].clone(),
+            ImplBody {
+                priority: -1,
+                ..impls[1].clone()
+            },
+        ];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[0].condition);
+    }
+
+    #[test]
+    fn any_trait_impl() {
+        let any_trait = WhenCondition::Trait("T".into(), vec![ANY_TRAIT.into()]);
+        let impls = vec![
+            get_impl_body(None),
+            get_impl_body(Some(any_trait.clone())),
+            get_impl_body(Some(WhenCondition::Trait(
+                "T".into(),
+                vec!["MyTrait".into()],
+            ))),
+        ];
+        let traits = vec![get_trait_body(&impls[0])];
+
+        // a concrete trait is more specific than the wildcard
+        let annotations = get_annotation_body();
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, impls[2].condition);
+
+        // the wildcard is satisfied by any annotated trait and is more specific than the default impl
+        let mut annotations = get_annotation_body();
+        annotations.annotations = vec![Annotation::Trait("&MyType".into(), vec!["Debug".into()])];
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, impls[1].condition);
+
+        // without annotated traits only the default impl is satisfied
+        annotations.annotations = vec![];
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, None);
+
+        let condition = WhenCondition::Not(Box::new(any_trait));
+        let mut var = get_var_body();
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(!satisfies);
+
+        var.vars[0].traits = vec![];
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(satisfies);
+    }
+
+    #[test]
+    fn slice_len_impl() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Type("T".into(), "&MyType".into()))),
+            get_impl_body(Some(WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "&MyType".into()),
+                WhenCondition::Len(3),
+            ]))),
+            get_impl_body(Some(WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "&MyType".into()),
+                WhenCondition::Len(4),
+            ]))),
+        ];
+        let traits = vec![get_trait_body(&impls[0])];
+
+        // without the annotation only the unconstrained impl is satisfied
+        let annotations = get_annotation_body();
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, impls[0].condition);
+        assert_eq!(spec_body.constraints.len, None);
+
+        // the length-constrained impl is more specific than the unconstrained one
+        let mut annotations = get_annotation_body();
+        annotations.annotations.push(Annotation::Len(3));
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, impls[1].condition);
+        assert_eq!(spec_body.constraints.len, Some(3));
+    }
+
+    #[test]
+    fn arg_value_impl() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Type("T".into(), "&MyType".into()))),
+            get_impl_body(Some(WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "&MyType".into()),
+                WhenCondition::Arg(0, "0".into()),
+            ]))),
+        ];
+        let traits = vec![get_trait_body(&impls[0])];
+
+        // without the annotation only the unconstrained impl is satisfied
+        let annotations = get_annotation_body();
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, impls[0].condition);
+
+        // another value does not satisfy the condition
+        let mut annotations = get_annotation_body();
+        annotations
+            .annotations
+            .push(Annotation::ArgValue(0, "1".into()));
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, impls[0].condition);
+
+        // the impl for the sentinel value is more specific than the unconstrained one
+        let mut annotations = get_annotation_body();
+        annotations
+            .annotations
+            .push(Annotation::ArgValue(0, "0".into()));
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(spec_body.impl_.condition, impls[1].condition);
+        assert_eq!(
+            spec_body.constraints.arg_values,
+            HashMap::from([(0, "0".to_string())])
+        );
+    }
+
+    #[test]
+    fn not_slice_len() {
+        let condition = WhenCondition::Not(Box::new(WhenCondition::Len(3)));
+        let mut var = get_var_body();
+
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(satisfies);
+
+        var.len = Some(3);
+        let (satisfies, _) = satisfies_condition(&condition, &var, &Constraints::default());
+        assert!(!satisfies);
+    }
+
+    #[test]
+    fn no_valid_impl() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Type("T".into(), "&MyOtherType".into()))),
+            get_impl_body(Some(WhenCondition::Trait(
+                "T".into(),
+                vec!["MyOtherTrait".into()],
+            ))),
+        ];
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+        let mut annotations = get_annotation_body();
+        annotations.var_type = "MyType".to_string();
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            "No valid implementation found for `MyType`"
+        );
+    }
+
+    #[test]
+    fn generic_of_enclosing_fn() {
+        // `fn use_it<X: MyTrait>(x: X) { spec! { my_type.foo(x); MyType; [X]; ... } }`
+        let impls = vec![
+            get_impl_body(None),
+            get_impl_body(Some(WhenCondition::Type("T".into(), "&MyType".into()))),
+            get_impl_body(Some(WhenCondition::Trait(
+                "T".into(),
+                vec!["MyTrait".into()],
+            ))),
+        ];
+        let traits = impls.iter().map(get_trait_body).collect::<Vec<_>>();
+        let mut annotations = get_annotation_body();
+        annotations.args_types = vec!["X".to_string()];
+
+        // `X` is a type name of its own, so only the impl without conditions is satisfied
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+
+        // the bounds of `X` can be annotated to select the impls with conditions on them
+        annotations.annotations = vec![Annotation::Trait(
+            "X".to_string(),
+            vec!["MyTrait".to_string()],
+        )];
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[2].condition);
+    }
+
+    #[test]
+    fn impl_with_wildcard() {
+        let impls = vec![get_impl_body(Some(WhenCondition::Type(
+            "T".into(),
+            "Vec<_>".into(),
+        )))];
+        let traits = vec![get_trait_body(&impls[0])];
+        let mut annotations = get_annotation_body();
         annotations.args_types = vec!["Vec<MyType>".to_string()];
 
         let result = SpecBody::try_from((&impls, &traits, &annotations));
 
         assert!(result.is_ok());
-        let spec_body = result.unwrap();
-        assert_eq!(spec_body.impl_.trait_name, "MyTrait");
+        let spec_body = result.unwrap();
+        assert_eq!(spec_body.impl_.trait_name, "MyTrait");
+        assert_eq!(
+            spec_body
+                .constraints
+                .inner
+                .get("T")
+                .unwrap()
+                .type_
+                .clone()
+                .unwrap()
+                .replace(" ", ""),
+            "Vec<_>".to_string()
+        );
+    }
+
+    #[test]
+    fn impl_with_partial_wildcard_result() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Type("T".into(), "Result<_, _>".into()))),
+            get_impl_body(Some(WhenCondition::Type(
+                "T".into(),
+                "Result<_, String>".into(),
+            ))),
+            get_impl_body(Some(WhenCondition::Type(
+                "T".into(),
+                "Result<_, u8>".into(),
+            ))),
+        ];
+        let traits = impls.iter().map(get_trait_body).collect::<Vec<_>>();
+        let mut annotations = get_annotation_body();
+        annotations.args_types = vec!["Result<i32, String>".to_string()];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Type("T".into(), "Result<_, String>".into()))
+        );
+
+        annotations.args_types = vec!["Result<i32, i32>".to_string()];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Type("T".into(), "Result<_, _>".into()))
+        );
+    }
+
+    #[test]
+    fn impl_with_partial_wildcard_option() {
+        let impls = vec![
+            get_impl_body(None),
+            get_impl_body(Some(WhenCondition::Type("T".into(), "Option<_>".into()))),
+            get_impl_body(Some(WhenCondition::Type(
+                "T".into(),
+                "Option<String>".into(),
+            ))),
+        ];
+        let traits = impls.iter().map(get_trait_body).collect::<Vec<_>>();
+        let mut annotations = get_annotation_body();
+        annotations.args_types = vec!["Option<String>".to_string()];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Type("T".into(), "Option<String>".into()))
+        );
+
+        annotations.args_types = vec!["Option<u8>".to_string()];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Type("T".into(), "Option<_>".into()))
+        );
+    }
+
+    #[test]
+    fn impl_with_partial_wildcards_equally_specific() {
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Type(
+                "T".into(),
+                "Result<u8, _>".into(),
+            ))),
+            get_impl_body(Some(WhenCondition::Type(
+                "T".into(),
+                "Result<_, String>".into(),
+            ))),
+        ];
+        let traits = impls.iter().map(get_trait_body).collect::<Vec<_>>();
+        let mut annotations = get_annotation_body();
+        annotations.args_types = vec!["Result<u8, String>".to_string()];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            "Multiple implementations are equally specific, set `priority = N` in `when` to select one"
+        );
+    }
+
+    #[test]
+    fn wildcard_trait_annotation() {
+        let impls = vec![
+            get_impl_body(None),
+            get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Debug".into()]))),
+        ];
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+        let mut annotations = get_annotation_body();
+        annotations.args_types = vec!["Vec<u8>".to_string()];
+        annotations.annotations = vec![Annotation::Trait(
+            "Vec<_>".to_string(),
+            vec!["Debug".to_string()],
+        )];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        let spec_body = result.unwrap();
+        assert_eq!(
+            spec_body.impl_.condition,
+            Some(WhenCondition::Trait("T".into(), vec!["Debug".into()]))
+        );
+        assert!(
+            spec_body
+                .constraints
+                .inner
+                .get("T")
+                .unwrap()
+                .traits
+                .contains(&"Debug".to_string())
+        );
+    }
+
+    #[test]
+    fn wildcard_trait_annotation_not_matching() {
+        let impls = vec![
+            get_impl_body(None),
+            get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Debug".into()]))),
+        ];
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+        let mut annotations = get_annotation_body();
+        annotations.args_types = vec!["Option<u8>".to_string()];
+        annotations.annotations = vec![Annotation::Trait(
+            "Vec<_>".to_string(),
+            vec!["Debug".to_string()],
+        )];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().impl_.condition, None);
+    }
+
+    #[test]
+    fn impl_by_return_type() {
+        let get_impl_body = |condition| {
+            let impl_ = quote! { impl<T> MyTrait<T> for MyType { fn get(&self) -> T { todo!() } } };
+            ImplBody::try_from((impl_, condition)).unwrap()
+        };
+        let get_trait_body = |impl_: &ImplBody| {
+            let trait_ = quote! { trait MyTrait<A> { fn get(&self) -> A; } };
+            TraitBody::try_from(trait_).unwrap().specialize(impl_)
+        };
+
+        let impls = vec![
+            get_impl_body(Some(WhenCondition::Type("T".into(), "u8".into()))),
+            get_impl_body(Some(WhenCondition::Type("T".into(), "String".into()))),
+        ];
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+        let mut annotations = AnnotationBody {
+            fn_: "get".to_string(),
+            var: "x".to_string(),
+            var_type: "MyType".to_string(),
+            ..Default::default()
+        };
+
+        annotations.ret_type = Some("String".to_string());
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Type("T".into(), "String".into()))
+        );
+
+        annotations.ret_type = Some("u8".to_string());
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Type("T".into(), "u8".into()))
+        );
+
+        annotations.ret_type = None;
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn trait_generic_default() {
+        let impl_ = quote! { impl<T> MyTrait<T> for MyType { fn get(&self) -> T { todo!() } } };
+        let impls = vec![ImplBody::try_from((impl_, None)).unwrap()];
+        let trait_ = quote! { trait MyTrait<A = u8> { fn get(&self) -> A; } };
+        let traits = vec![TraitBody::try_from(trait_).unwrap().specialize(&impls[0])];
+        let mut annotations = AnnotationBody {
+            fn_: "get".to_string(),
+            var: "x".to_string(),
+            var_type: "MyType".to_string(),
+            ..Default::default()
+        };
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+        assert!(result.is_ok());
+        assert_eq!(
+            TokenStream::from(&result.unwrap())
+                .to_string()
+                .replace(" ", ""),
+            "<MyTypeasMyTrait<u8>>::get(&x)"
+        );
+
+        annotations.ret_type = Some("String".to_string());
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+        assert!(result.is_ok());
+        assert_eq!(
+            TokenStream::from(&result.unwrap())
+                .to_string()
+                .replace(" ", ""),
+            "<MyTypeasMyTrait<_>>::get(&x)"
+        );
+    }
+
+    #[test]
+    fn assoc_return_type() {
+        let get_spec = |trait_: TokenStream, impl_: TokenStream, ret_type: Option<&str>| {
+            let impls = vec![ImplBody::try_from((impl_, None)).unwrap()];
+            let traits = vec![TraitBody::try_from(trait_).unwrap().specialize(&impls[0])];
+            let annotations = AnnotationBody {
+                fn_: "get".to_string(),
+                var: "x".to_string(),
+                var_type: "MyType".to_string(),
+                ret_type: ret_type.map(String::from),
+                ..Default::default()
+            };
+            let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+            TokenStream::from(&result).to_string().replace(" ", "")
+        };
+
+        // `Self::Out` does not depend on the generics of the trait
+        let trait_ = quote! { trait MyTrait<A = u8> { type Out; fn get(&self) -> Self::Out; } };
+        let impl_ = quote! { impl<T> MyTrait<T> for MyType { type Out = T; fn get(&self) -> T { todo!() } } };
+        assert_eq!(
+            get_spec(trait_, impl_, Some("String")),
+            "<MyTypeasMyTrait<u8>>::get(&x)"
+        );
+
+        // the generic cannot be inferred from the projection of its associated type, so the default is used
+        let trait_ = quote! { trait MyTrait<A: IntoIterator = Vec<u8>> { fn get(&self) -> Option<<A as IntoIterator>::Item>; } };
+        let impl_ = quote! { impl<T: IntoIterator> MyTrait<T> for MyType { fn get(&self) -> Option<T::Item> { todo!() } } };
+        assert_eq!(
+            get_spec(trait_, impl_, Some("Option<u8>")),
+            "<MyTypeasMyTrait<Vec<u8>>>::get(&x)"
+        );
+
+        let trait_ =
+            quote! { trait MyTrait<A: IntoIterator = Vec<u8>> { fn get(&self) -> A::Item; } };
+        let impl_ = quote! { impl<T: IntoIterator> MyTrait<T> for MyType { fn get(&self) -> T::Item { todo!() } } };
+        assert_eq!(
+            get_spec(trait_, impl_, Some("u8")),
+            "<MyTypeasMyTrait<Vec<u8>>>::get(&x)"
+        );
+
+        // the generic is still inferable from the rest of the return type
+        let trait_ =
+            quote! { trait MyTrait<A: IntoIterator = Vec<u8>> { fn get(&self) -> (A, A::Item); } };
+        let impl_ = quote! { impl<T: IntoIterator> MyTrait<T> for MyType { fn get(&self) -> (T, T::Item) { todo!() } } };
+        assert_eq!(
+            get_spec(trait_, impl_, Some("(Vec<u8>, u8)")),
+            "<MyTypeasMyTrait<_>>::get(&x)"
+        );
+    }
+
+    #[test]
+    fn assoc_type_of_selected_impl() {
+        let get_impl = |impl_: TokenStream, type_: &str| {
+            let condition = WhenCondition::Type("T".into(), type_.into());
+            ImplBody::try_from((impl_, Some(condition))).unwrap()
+        };
+        let impls = vec![
+            get_impl(
+                quote! { impl<T> MyTrait<T> for MyType { type Out = u16; fn foo(&self, x: T) -> u16 { 1 } } },
+                "u8",
+            ),
+            get_impl(
+                quote! { impl<T> MyTrait<T> for MyType { type Out = String; fn foo(&self, x: T) -> String { x } } },
+                "String",
+            ),
+        ];
+        let trait_ = quote! { trait MyTrait<A> { type Out; fn foo(&self, x: A) -> Self::Out; } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        for (arg_type, impl_) in [("u8", &impls[0]), ("String", &impls[1])] {
+            let annotations = AnnotationBody {
+                fn_: "foo".to_string(),
+                var: "x".to_string(),
+                var_type: "MyType".to_string(),
+                args: vec!["y".to_string()],
+                args_types: vec![arg_type.to_string()],
+                ..Default::default()
+            };
+
+            let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+            let trait_name = &impl_.specialized.as_ref().unwrap().trait_name;
+
+            assert_eq!(
+                get_assoc_type(&spec_body, "Out")
+                    .to_string()
+                    .replace(" ", ""),
+                format!("<MyTypeas{}>::Out", trait_name)
+            );
+        }
+    }
+
+    #[test]
+    fn fn_path_of_selected_impl() {
+        let impl_ = quote! { impl<T> MyTrait<T> for MyType { fn foo(&self, x: T) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "u8".into()))))
+                .unwrap(),
+        ];
+        let trait_ = quote! { trait MyTrait<A> { fn foo(&self, x: A); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        for (arg_type, generics, impl_) in [("u8", "", &impls[1]), ("i32", "<_>", &impls[0])] {
+            let annotations = AnnotationBody {
+                fn_: "foo".to_string(),
+                var_type: "MyType".to_string(),
+                args: vec!["_".to_string()],
+                args_types: vec![arg_type.to_string()],
+                ..Default::default()
+            };
+
+            let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+            let trait_name = &impl_.specialized.as_ref().unwrap().trait_name;
+
+            assert_eq!(
+                get_fn_path(&spec_body).to_string().replace(" ", ""),
+                format!("<MyTypeas{}{}>::foo", trait_name, generics)
+            );
+        }
+    }
+
+    #[test]
+    fn associated_type_condition() {
+        let impl_ =
+            quote! { impl<T, U> Feed<T, U> for MyType { fn feed(&self, item: T, items: U) {} } };
+        let condition = WhenCondition::All(vec![
+            WhenCondition::Trait("U".into(), vec!["Iterator".into()]),
+            WhenCondition::Type("T".into(), "U::Item".into()),
+        ]);
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(condition))).unwrap(),
+        ];
+        let trait_ = quote! { trait Feed<A, B> { fn feed(&self, item: A, items: B); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let get_condition = |item: &str, annotations: Vec<Annotation>| {
+            let annotations = AnnotationBody {
+                fn_: "feed".to_string(),
+                var_type: "MyType".to_string(),
+                args: vec!["item".to_string(), "items".to_string()],
+                args_types: vec![item.to_string(), "MyIter".to_string()],
+                annotations,
+                ..Default::default()
+            };
+            let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+            assert!(check_signature(&spec_body).is_ok());
+            spec_body.impl_.condition
+        };
+        let iterator = Annotation::Trait("MyIter".into(), vec!["Iterator".into()]);
+        let item =
+            |type_: &str| Annotation::AssocType("MyIter".into(), "Item".into(), type_.into());
+
+        assert!(get_condition("u8", vec![iterator.clone(), item("u8")]).is_some());
+        // the annotated associated type is another one
+        assert!(get_condition("u8", vec![iterator.clone(), item("i32")]).is_none());
+        // the associated type is not annotated
+        assert!(get_condition("u8", vec![iterator]).is_none());
+    }
+
+    #[test]
+    fn loop_resolves_once() {
+        let impl_ = quote! { impl<T> MyTrait<T> for MyType { fn foo(&self, x: T) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "u8".into()))))
+                .unwrap(),
+        ];
+        let trait_ = quote! { trait MyTrait<A> { fn foo(&self, x: A); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let annotations = AnnotationBody {
+            var: "x".to_string(),
+            fn_: "foo".to_string(),
+            var_type: "MyType".to_string(),
+            args: vec!["1u8".to_string()],
+            args_types: vec!["u8".to_string()],
+            ..Default::default()
+        };
+
+        let spec_body = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        let result = get_loop(&spec_body, "x", "items")
+            .to_string()
+            .replace(" ", "");
+
+        // the path is resolved before the loop, the loop only calls it
+        assert_eq!(
+            result,
+            format!(
+                "{{let__spec_fn=<MyTypeas{}>::foo;forxinitems{{__spec_fn(&x,1u8);}}}}",
+                trait_name
+            )
+        );
+        assert_eq!(result.matches("::foo").count(), 1);
+    }
+
+    #[test]
+    fn no_argument_method() {
+        let impl_ = quote! { impl<T> Ping for T { fn ping(&self) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "u8".into()))))
+                .unwrap(),
+        ];
+        let trait_ = quote! { trait Ping { fn ping(&self); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+        let annotations = AnnotationBody::try_from(quote! { x.ping(); u8; [] }).unwrap();
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<u8as{}>::ping(&x)", trait_name)
+        );
+    }
+
+    #[test]
+    fn owned_and_borrowed_receiver() {
+        let impl_ = quote! { impl<T> Kind for T { fn kind(&self) {} } };
+        let impls = vec![
+            ImplBody::try_from((
+                impl_.clone(),
+                Some(WhenCondition::Type("T".into(), "MyType".into())),
+            ))
+            .unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::Type("T".into(), "&MyType".into())),
+            ))
+            .unwrap(),
+        ];
+        let trait_ = quote! { trait Kind { fn kind(&self); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let annotations = AnnotationBody::try_from(quote! { x.kind(); MyType; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[0].condition);
+
+        let annotations = AnnotationBody::try_from(quote! { x.kind(); &MyType; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<&MyTypeas{}>::kind(&x)", trait_name)
+        );
+    }
+
+    #[test]
+    fn strict_missing_trait() {
+        let impl_ = quote! { impl<T> Foo<T> for MyType { fn foo(&self, x: T) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_.clone(),
+                Some(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+            ))
+            .unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::All(vec![
+                    WhenCondition::Type("T".into(), "u8".into()),
+                    WhenCondition::Trait("T".into(), vec!["Clone".into()]),
+                ])),
+            ))
+            .unwrap(),
+        ];
+        let trait_ = quote! { trait Foo<A> { fn foo(&self, x: A); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+        let get_spec = |annotations: TokenStream| {
+            let annotations = AnnotationBody::try_from(annotations).unwrap();
+            SpecBody::try_from((&impls, &traits, &annotations))
+        };
+
+        // the default impl is silently selected
+        let result = get_spec(quote! { x.foo(1i32); MyType; [i32] }).unwrap();
+        assert_eq!(result.impl_.condition, None);
+
+        // unless in strict mode
+        let result = get_spec(quote! { x.foo(1i32); MyType; [i32]; strict });
+        assert_eq!(
+            result.unwrap_err(),
+            "The impl of `Foo` with condition `T: Copy` is not selected because `i32: Copy` is not annotated"
+        );
+
+        let result = get_spec(quote! { x.foo(1i32); MyType; [i32]; strict; i32: Copy }).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        // an impl that does not match the type is not reported
+        let result = get_spec(quote! { x.foo(1i32); MyType; [i32]; i32: Copy; strict }).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+        // while the one that matches it is
+        let result = get_spec(quote! { x.foo(1u8); MyType; [u8]; strict; u8: Copy });
+        assert_eq!(
+            result.unwrap_err(),
+            "The impl of `Foo` with condition `all(T = u8, T: Clone)` is not selected because `u8: Clone` is not annotated"
+        );
+    }
+
+    #[test]
+    fn deref_receiver() {
+        let impl_ = quote! { impl<T> Foo<T> for MyType { fn foo(&self, x: T) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "u8".into()))))
+                .unwrap(),
+        ];
+        let trait_ = quote! { trait Foo<A> { fn foo(&self, x: A); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let mut annotations =
+            AnnotationBody::try_from(quote! { boxed.foo(1u8); Box<MyType>; [u8]; deref }).unwrap();
+        assert!(annotations.deref_receiver());
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<MyTypeas{}>::foo(&*boxed,1u8)", trait_name)
+        );
+    }
+
+    #[test]
+    fn self_receiver() {
+        let impl_ = quote! { impl<T> Foo<T> for MyType { fn foo(&self, x: T) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "u8".into()))))
+                .unwrap(),
+        ];
+        let trait_ = quote! { trait Foo<A> { fn foo(&self, x: A); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+
+        // in a `&self` method, `self` is already a reference
+        let annotations =
+            AnnotationBody::try_from(quote! { self.foo(1u8); MyType; [u8]; no_borrow }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<MyTypeas{}>::foo(self,1u8)", trait_name)
+        );
+
+        // in a `self` method it is borrowed
+        let annotations = AnnotationBody::try_from(quote! { self.foo(1u8); MyType; [u8] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<MyTypeas{}>::foo(&self,1u8)", trait_name)
+        );
+    }
+
+    #[test]
+    fn receiver_inner_generic_trait() {
+        let impl_ = quote! { impl<U> Foo for Wrapper<U> { fn foo(&self) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::Trait("U".into(), vec!["Debug".into()])),
+            ))
+            .unwrap(),
+        ];
+        let trait_ = quote! { trait Foo { fn foo(&self); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+        let get_condition = |annotations: TokenStream| {
+            let annotations = AnnotationBody::try_from(annotations).unwrap();
+            let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+
+            // `U` is bound from the receiver type, with the traits annotated for its type
+            let var = VarBody::try_from(&result).unwrap();
+            let u = var.vars.iter().find(|v| v.impl_generic == "U").unwrap();
+            (result.impl_.condition, u.concrete_type.replace(" ", ""))
+        };
+
+        assert_eq!(
+            get_condition(quote! { x.foo(); Wrapper<i32>; []; i32: Debug }),
+            (impls[1].condition.clone(), "i32".into())
+        );
+        assert_eq!(
+            get_condition(quote! { x.foo(); Wrapper<i32>; [] }),
+            (None, "i32".into())
+        );
+        assert_eq!(
+            get_condition(quote! { x.foo(); Wrapper<Vec<u8>>; []; Vec<_>: Debug }),
+            (impls[1].condition.clone(), "Vec<u8>".into())
+        );
+    }
+
+    #[test]
+    fn reference_receiver() {
+        let impl_ = quote! { impl<T> Foo<T> for &Wrapper<T> { fn foo(&self, x: T) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_.clone(),
+                Some(WhenCondition::Type("T".into(), "u8".into())),
+            ))
+            .unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::Type("Self".into(), "&Wrapper<i32>".into())),
+            ))
+            .unwrap(),
+        ];
+        let trait_ = quote! { trait Foo<A> { fn foo(&self, x: A); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let annotations =
+            AnnotationBody::try_from(quote! { x.foo(1u8); &Wrapper<u8>; [u8] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<&Wrapper<u8>as{}>::foo(&x,1u8)", trait_name)
+        );
+
+        let annotations =
+            AnnotationBody::try_from(quote! { x.foo(1i32); &Wrapper<i32>; [i32] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[2].condition);
+
+        let annotations =
+            AnnotationBody::try_from(quote! { x.foo(1i8); &Wrapper<i8>; [i8] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+    }
+
+    #[test]
+    fn elided_trait_generic_default() {
+        let impl_ = quote! { impl<T> Scale for T { fn scale(&self, x: u8) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "i32".into()))))
+                .unwrap(),
+        ];
+        let trait_ = quote! { trait Scale<A = u8> { fn scale(&self, x: A); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+        let annotations = AnnotationBody::try_from(quote! { x.scale(1u8); i32; [u8] }).unwrap();
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+
+        // the default of `A` is applied in the specialized trait, so no generics are given to it
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<i32as{}>::scale(&x,1u8)", trait_name)
+        );
+    }
+
+    #[test]
+    fn wrong_arity() {
+        assert!(check_fn_arity("foo", 1, &[1, 2]).is_ok());
+        assert_eq!(
+            check_fn_arity("foo", 3, &[1, 2]).unwrap_err(),
+            "No trait has a method `foo` with 3 arguments, available with: 1, 2"
+        );
+        assert_eq!(
+            check_fn_arity("bar", 0, &[]).unwrap_err(),
+            "No trait has a method `bar`"
+        );
+    }
+
+    #[test]
+    fn signature_mismatch() {
+        let impl_ =
+            quote! { impl<T> MyTrait<T> for MyType { fn foo(&self, x: T, y: &'static str) {} } };
+        let impls = vec![ImplBody::try_from((impl_, None)).unwrap()];
+        let trait_ = quote! { trait MyTrait<A> { fn foo(&self, x: A, y: &'static str); } };
+        let traits = vec![TraitBody::try_from(trait_).unwrap().specialize(&impls[0])];
+
+        // the lifetimes are not compared
+        let annotations =
+            AnnotationBody::try_from(quote! { x.foo(1u8, "a"); MyType; [u8, &str] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert!(check_signature(&result).is_ok());
+
+        let annotations =
+            AnnotationBody::try_from(quote! { x.foo(1u8, 2u8); MyType; [u8, u8] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(
+            check_signature(&result),
+            Err("Mismatched type for argument `2u8` of `foo`: annotated `u8`, but the selected impl expects `&'staticstr`".into())
+        );
+    }
+
+    #[test]
+    fn borrow_mismatch() {
+        let impl_ = quote! { impl MyTrait for MyType { fn foo(&self, x: u8, y: &u8) {} fn bar(&self, v: &mut Vec<u8>) {} } };
+        let impls = vec![ImplBody::try_from((impl_, None)).unwrap()];
+        let trait_ = quote! { trait MyTrait { fn foo(&self, x: u8, y: &u8); fn bar(&self, v: &mut Vec<u8>); } };
+        let traits = vec![TraitBody::try_from(trait_).unwrap().specialize(&impls[0])];
+        let check = |tokens: TokenStream| {
+            let annotations = AnnotationBody::try_from(tokens).unwrap();
+            let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+            check_signature(&result)
+        };
+
+        // passed by reference, taken by value
+        assert_eq!(
+            check(quote! { x.foo(&a, &b); MyType; [&u8, &u8] }),
+            Err("Mismatched type for argument `& a` of `foo`: annotated `&u8`, but the selected impl expects `u8`, pass it by value instead: `a` annotated as `u8`".into())
+        );
+        assert_eq!(
+            check(quote! { x.foo(r, &b); MyType; [&u8, &u8] }),
+            Err("Mismatched type for argument `r` of `foo`: annotated `&u8`, but the selected impl expects `u8`, pass it by value instead: `*r` annotated as `u8`".into())
+        );
+
+        // passed by value, taken by reference
+        assert_eq!(
+            check(quote! { x.foo(a, b); MyType; [u8, u8] }),
+            Err("Mismatched type for argument `b` of `foo`: annotated `u8`, but the selected impl expects `&u8`, pass it by reference instead: `&b` annotated as `&u8`".into())
+        );
+        assert_eq!(
+            check(quote! { x.bar(v); MyType; [Vec<u8>] }),
+            Err("Mismatched type for argument `v` of `bar`: annotated `Vec<u8>`, but the selected impl expects `&mutVec<u8>`, pass it by reference instead: `&mut v` annotated as `&mut Vec<u8>`".into())
+        );
+
+        // not only a borrow away
+        assert_eq!(
+            check(quote! { x.foo(&a, &b); MyType; [&i32, &u8] }),
+            Err("Mismatched type for argument `& a` of `foo`: annotated `&i32`, but the selected impl expects `u8`".into())
+        );
+    }
+
+    #[test]
+    fn fn_with_lifetime_generic() {
+        let impl_ = quote! { impl<'a, T> MyTrait<'a, T> for MyType { fn get<'b>(&'b self, x: &'b T, y: &'a T) -> &'b T { x } } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "u8".into()))))
+                .unwrap(),
+        ];
+        let trait_ =
+            quote! { trait MyTrait<'a, A> { fn get<'b>(&'b self, x: &'b A, y: &'a A) -> &'b A; } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+        let annotations =
+            AnnotationBody::try_from(quote! { x.get(&1u8, &2u8); MyType; [&u8, &'static u8] })
+                .unwrap();
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+
+        assert_eq!(result.impl_.condition, impls[1].condition);
+        assert!(check_signature(&result).is_ok());
+
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<MyTypeas{}>::get(&x,&1u8,&2u8)", trait_name)
+        );
+    }
+
+    #[test]
+    fn self_condition() {
+        let impl_ = quote! { impl<T> Ping for Wrapper<T> { fn ping(&self) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_.clone(),
+                Some(WhenCondition::Type("T".into(), "i32".into())),
+            ))
+            .unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::Type("Self".into(), "Wrapper<i32>".into())),
+            ))
+            .unwrap(),
+        ];
+        let trait_ = quote! { trait Ping { fn ping(&self); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        // `Self = Wrapper<i32>` is more specific than `T = i32`
+        let annotations = AnnotationBody::try_from(quote! { x.ping(); Wrapper<i32>; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[2].condition);
+
+        let trait_name = &impls[2].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<Wrapper<i32>as{}>::ping(&x)", trait_name)
+        );
+
+        let annotations = AnnotationBody::try_from(quote! { x.ping(); Wrapper<u8>; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+    }
+
+    #[test]
+    fn assoc_type_receiver() {
+        let impl_ = quote! { impl<T: Source> Ping for T::Assoc { fn ping(&self) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::Type("T".into(), "Vec<u8>".into())),
+            ))
+            .unwrap(),
+        ];
+        let trait_ = quote! { trait Ping { fn ping(&self); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let specialized = impls[1].specialized.as_ref().unwrap();
+        assert_eq!(specialized.type_name.replace(" ", ""), "<Vec<u8>>::Assoc");
+
+        let annotations =
+            AnnotationBody::try_from(quote! { x.ping(); <Vec<u8> as Source>::Assoc; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        let annotations =
+            AnnotationBody::try_from(quote! { x.ping(); <u8 as Source>::Assoc; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+    }
+
+    #[test]
+    fn equal_generics_condition() {
+        let impl_ = quote! { impl<T, U> Ping for Pair<T, U> { fn ping(&self) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Type("T".into(), "U".into())))).unwrap(),
+        ];
+        let trait_ = quote! { trait Ping { fn ping(&self); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        // the impl is specialized for a single generic
+        let specialized = impls[1].specialized.as_ref().unwrap();
+        assert_eq!(specialized.type_name.replace(" ", ""), "Pair<U,U>");
+
+        let annotations = AnnotationBody::try_from(quote! { x.ping(); Pair<u8, u8>; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+        assert_eq!(
+            result.constraints.inner.get("T").unwrap().type_,
+            Some("u8".into())
+        );
+
+        let annotations = AnnotationBody::try_from(quote! { x.ping(); Pair<u8, i32>; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+
+        // an alias is the same type
+        let annotations =
+            AnnotationBody::try_from(quote! { x.ping(); Pair<u8, MyType>; []; u8 = MyType })
+                .unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+    }
+
+    #[test]
+    fn const_generic_condition() {
+        let impl_ =
+            quote! { impl<const N: usize> Summable for [u8; N] { fn sum(&self) -> u32 { 0 } } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((impl_, Some(WhenCondition::Const("N".into(), "0".into()))))
+                .unwrap(),
+        ];
+        let trait_ = quote! { trait Summable { fn sum(&self) -> u32; } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let annotations = AnnotationBody::try_from(quote! { x.sum(); [u8; 0usize]; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        let trait_name = &impls[1].specialized.as_ref().unwrap().trait_name;
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<[u8;0usize]as{}>::sum(&x)", trait_name)
+        );
+
+        let annotations = AnnotationBody::try_from(quote! { x.sum(); [u8; 3]; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+    }
+
+    #[test]
+    fn lifetime_only_impl() {
+        let impl_ = quote! { impl<'a> Ping for Foo<'a> { fn ping(&self) {} } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::Lifetime("'a".into(), "'static".into())),
+            ))
+            .unwrap(),
+        ];
+        let trait_ = quote! { trait Ping { fn ping(&self); } };
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let specialized = impls[1].specialized.as_ref().unwrap();
+        assert_eq!(specialized.impl_generics, "");
+        assert_eq!(specialized.type_name.replace(" ", ""), "Foo<'static>");
+
+        let annotations = AnnotationBody::try_from(quote! { x.ping(); Foo<'static>; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+        assert_eq!(
+            TokenStream::from(&result).to_string().replace(" ", ""),
+            format!("<Foo<'static>as{}>::ping(&x)", specialized.trait_name)
+        );
+
+        let annotations = AnnotationBody::try_from(quote! { x.ping(); Foo<'b>; [] }).unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+    }
+
+    #[test]
+    fn impl_with_parametric_alias() {
+        let aliases = vec![
+            TypeAliasBody::try_from(quote! { type MyResult<E> = Result<String, E>; }).unwrap(),
+        ];
+        let get_impl_body = |condition| {
+            let impl_ = quote! { impl<T, E> MyTrait<T> for MyType { fn foo(&self, x: T) {} } };
+            let condition = expand_condition_aliases(condition, &aliases);
+            ImplBody::try_from((impl_, Some(condition))).unwrap()
+        };
+        let get_trait_body = |impl_: &ImplBody| {
+            let trait_ = quote! { trait MyTrait<A> { fn foo(&self, x: A); } };
+            TraitBody::try_from(trait_).unwrap().specialize(impl_)
+        };
+
+        let impls = vec![
+            get_impl_body(WhenCondition::Type("T".into(), "MyResult<E>".into())),
+            get_impl_body(WhenCondition::Type("T".into(), "Result<u8, E>".into())),
+        ];
+        let traits = vec![get_trait_body(&impls[0]), get_trait_body(&impls[1])];
+        let mut annotations = AnnotationBody {
+            fn_: "foo".to_string(),
+            var: "x".to_string(),
+            var_type: "MyType".to_string(),
+            args: vec!["y".to_string()],
+            args_types: vec!["MyResult<i32>".to_string()],
+            ..Default::default()
+        };
+        annotations.expand_type_aliases(&aliases);
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
         assert_eq!(
-            spec_body
-                .constraints
-                .inner
-                .get("T".into())
-                .unwrap()
-                .type_
-                .clone()
-                .unwrap()
-                .replace(" ", ""),
-            "Vec<_>".to_string()
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Type(
+                "T".into(),
+                "Result < String , E >".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn grouped_trait_annotation() {
+        let aliases = vec![TypeAliasBody::try_from(quote! { type MyVec<X> = Vec<X>; }).unwrap()];
+        let impl_ = quote! { impl<T, U> MyTrait<T, U> for MyType { fn foo(&self, x: T, y: U) {} } };
+        let trait_ = quote! { trait MyTrait<A, B> { fn foo(&self, x: A, y: B); } };
+        let impls = vec![
+            ImplBody::try_from((impl_.clone(), None)).unwrap(),
+            ImplBody::try_from((
+                impl_,
+                Some(WhenCondition::All(vec![
+                    WhenCondition::Trait("T".into(), vec!["Copy".into()]),
+                    WhenCondition::Trait("U".into(), vec!["Copy".into()]),
+                ])),
+            ))
+            .unwrap(),
+        ];
+        let traits = impls
+            .iter()
+            .map(|impl_| {
+                TraitBody::try_from(trait_.clone())
+                    .unwrap()
+                    .specialize(impl_)
+            })
+            .collect::<Vec<_>>();
+
+        let mut annotations = AnnotationBody::try_from(
+            quote! { x.foo(y, z); MyType; [MyVec<u8>, i32]; {MyVec<u8>, i32}: Copy },
+        )
+        .unwrap();
+        annotations.expand_type_aliases(&aliases);
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, impls[1].condition);
+
+        let annotations =
+            AnnotationBody::try_from(quote! { x.foo(y, z); MyType; [u8, i32]; {u8}: Copy })
+                .unwrap();
+        let result = SpecBody::try_from((&impls, &traits, &annotations)).unwrap();
+        assert_eq!(result.impl_.condition, None);
+    }
+
+    #[test]
+    fn impl_with_registered_type_traits() {
+        let impls = vec![
+            get_impl_body(None),
+            get_impl_body(Some(WhenCondition::Trait("T".into(), vec!["Copy".into()]))),
+        ];
+        let traits = impls.iter().map(get_trait_body).collect::<Vec<_>>();
+        let mut annotations = get_annotation_body();
+        annotations.args_types = vec!["u8".to_string()];
+        annotations.annotations = vec![];
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().impl_.condition, None);
+
+        annotations
+            .annotations
+            .extend(get_registered_annotations(&[TypeTraitsBody {
+                type_name: "u8".into(),
+                traits: vec!["Copy".into(), "Clone".into()],
+            }]));
+
+        let result = SpecBody::try_from((&impls, &traits, &annotations));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().impl_.condition,
+            Some(WhenCondition::Trait("T".into(), vec!["Copy".into()]))
         );
     }
 
@@ -689,7 +2861,7 @@ mod tests {
             spec_body
                 .constraints
                 .inner
-                .get("T".into())
+                .get("T")
                 .unwrap()
                 .type_
                 .clone()
@@ -718,7 +2890,7 @@ mod tests {
             spec_body
                 .constraints
                 .inner
-                .get("T".into())
+                .get("T")
                 .unwrap()
                 .type_
                 .clone()
@@ -730,7 +2902,7 @@ mod tests {
             spec_body
                 .constraints
                 .inner
-                .get("U".into())
+                .get("U")
                 .unwrap()
                 .traits
                 .contains(&"MyTrait".to_string())
@@ -749,6 +2921,6 @@ mod tests {
 
         let result = SpecBody::try_from((&impls, &traits, &annotations));
 
-        assert!(!result.is_ok());
+        assert!(result.is_err());
     }
 }
@@ -1,17 +1,19 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::SpecBody;
 use crate::annotations::{Annotation, AnnotationBody};
+use spec_trait_utils::conditions::SELF;
 use spec_trait_utils::conversions::{
     str_to_generics, str_to_lifetime, str_to_type_name, to_string,
 };
-use spec_trait_utils::impls::ImplBody;
-use spec_trait_utils::parsing::get_generics_types;
+use spec_trait_utils::impls::{ImplBody, get_trait_types};
+use spec_trait_utils::parsing::{get_generics_lifetimes, get_generics_types};
 use spec_trait_utils::traits::TraitBody;
+use spec_trait_utils::type_traits::TypeTraitsBody;
 use spec_trait_utils::types::{
     Aliases, get_concrete_type, type_assignable, type_assignable_generic_constraints, type_contains,
 };
-use syn::{FnArg, TraitItemFn, Type};
+use syn::{FnArg, GenericParam, Generics, ReturnType, TraitItemFn, Type};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct VarInfo {
@@ -25,7 +27,7 @@ pub struct VarInfo {
     pub traits: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VarBody {
     /// map from concrete type to type aliases
     pub aliases: Aliases,
@@ -33,18 +35,62 @@ pub struct VarBody {
     pub generics: String,
     /// map from type definition (e.g. generic) to VarInfo
     pub vars: Vec<VarInfo>,
+    /// map from lifetime generic of the impl to the lifetime it takes in the variable type
+    pub lifetimes: HashMap<String, String>,
+    /// map from const generic of the impl to the value it takes in the variable type
+    pub consts: HashMap<String, String>,
+    /// slice element count, got from the `len` annotation
+    pub len: Option<usize>,
+    /// map from argument position to the value asserted for it, got from the `argN = value` annotations
+    pub arg_values: HashMap<usize, String>,
+    /// map from a type and one of its associated types to the type it is, got from the `Type::Assoc = Type` annotations
+    pub assoc_types: HashMap<(String, String), String>,
 }
 
-impl From<&SpecBody> for VarBody {
-    fn from(spec: &SpecBody) -> Self {
+impl TryFrom<&SpecBody> for VarBody {
+    type Error = String;
+
+    fn try_from(spec: &SpecBody) -> Result<Self, Self::Error> {
         let aliases = get_type_aliases(&spec.annotations.annotations);
         let generics = spec.impl_.impl_generics.clone();
-        let vars = get_vars(&spec.annotations, &spec.impl_, &spec.trait_, &aliases);
-        VarBody {
+        let mut vars = get_vars(&spec.annotations, &spec.impl_, &spec.trait_, &aliases)?;
+        vars.extend(get_self_var(&spec.annotations, &aliases));
+        let lifetimes = get_lifetimes(&spec.annotations, &spec.impl_, &aliases);
+        let consts = get_consts(&spec.annotations, &spec.impl_, &aliases);
+        let len = spec.annotations.annotations.iter().find_map(|a| match a {
+            Annotation::Len(len) => Some(*len),
+            _ => None,
+        });
+        let arg_values = spec
+            .annotations
+            .annotations
+            .iter()
+            .filter_map(|a| match a {
+                Annotation::ArgValue(position, value) => Some((*position, value.clone())),
+                _ => None,
+            })
+            .collect();
+        let assoc_types = spec
+            .annotations
+            .annotations
+            .iter()
+            .filter_map(|a| match a {
+                Annotation::AssocType(type_, assoc, assigned) => {
+                    Some(((type_.replace(" ", ""), assoc.clone()), assigned.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        Ok(VarBody {
             aliases,
             generics,
             vars,
-        }
+            lifetimes,
+            consts,
+            len,
+            arg_values,
+            assoc_types,
+        })
     }
 }
 
@@ -68,10 +114,12 @@ fn get_vars(
     impl_: &ImplBody,
     trait_: &TraitBody,
     aliases: &Aliases,
-) -> Vec<VarInfo> {
-    get_generics_types::<Vec<_>>(&impl_.impl_generics)
+) -> Result<Vec<VarInfo>, String> {
+    let (impl_trait_generics, trait_types) = get_trait_types(&impl_.trait_generics);
+
+    let vars = get_generics_types::<Vec<_>>(&impl_.impl_generics)
         .iter()
-        .flat_map(|g| {
+        .map(|g| {
             let from_type = get_generic_constraints_from_type(g, impl_, ann, aliases);
             let from_type_specialized = get_generic_constraints_from_type(
                 g,
@@ -80,7 +128,7 @@ fn get_vars(
                 aliases,
             );
 
-            match trait_.get_corresponding_generic(&str_to_generics(&impl_.trait_generics), g) {
+            match trait_.get_corresponding_generic(&impl_trait_generics, g) {
                 // get type
                 Some(trait_generic) => {
                     let from_trait = get_generic_constraints_from_trait(
@@ -89,9 +137,25 @@ fn get_vars(
                         impl_,
                         ann,
                         aliases,
-                    );
+                    )?;
+                    check_bindings_agree(g, &from_trait, &from_type, aliases)?;
 
-                    from_trait.into_iter().chain(from_type).collect::<Vec<_>>()
+                    Ok(from_trait.into_iter().chain(from_type).collect::<Vec<_>>())
+                }
+
+                // get from the types passed to the trait that contain it (e.g. `Vec<T>`)
+                None if trait_types.iter().any(|(_, type_)| type_contains(type_, g)) => {
+                    let from_trait = get_generic_constraints_from_trait_types(
+                        g,
+                        (&impl_trait_generics, &trait_types),
+                        trait_,
+                        impl_,
+                        ann,
+                        aliases,
+                    )?;
+                    check_bindings_agree(g, &from_trait, &from_type, aliases)?;
+
+                    Ok(from_trait.into_iter().chain(from_type).collect::<Vec<_>>())
                 }
 
                 // get from specialized instead
@@ -112,25 +176,138 @@ fn get_vars(
                             impl_.specialized.as_ref().unwrap(),
                             ann,
                             aliases,
-                        );
+                        )?;
+                        check_bindings_agree(g, &from_trait, &from_type_specialized, aliases)?;
 
-                        from_trait
+                        Ok(from_trait
                             .into_iter()
                             .chain(from_type_specialized)
-                            .collect::<Vec<_>>()
+                            .collect::<Vec<_>>())
                     } else {
                         // get from type only
-                        from_type
+                        Ok(from_type
                             .into_iter()
                             .chain(from_type_specialized)
-                            .collect::<Vec<_>>()
+                            .collect::<Vec<_>>())
                     }
                 }
             }
         })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(vars
+        .into_iter()
+        .flatten()
         .collect::<HashSet<_>>()
         .into_iter()
-        .collect()
+        .collect())
+}
+
+/**
+   check that the types bound to the impl generic by the arguments and by the variable type agree.
+   # Example
+   `impl<T> MyTrait<T> for Container<T> { fn put(&self, item: T) }` with `Container<u8>` and `item: i32` is an error
+*/
+fn check_bindings_agree(
+    impl_generic: &str,
+    from_trait: &[VarInfo],
+    from_type: &[VarInfo],
+    aliases: &Aliases,
+) -> Result<(), String> {
+    let bound_to = |vars: &[VarInfo]| {
+        vars.iter()
+            .filter(|v| v.impl_generic == impl_generic)
+            .map(|v| v.concrete_type.clone())
+            .collect::<Vec<_>>()
+    };
+
+    for from_args in bound_to(from_trait) {
+        for from_var in bound_to(from_type) {
+            if !type_assignable(&from_args, &from_var, "", aliases)
+                && !type_assignable(&from_var, &from_args, "", aliases)
+            {
+                return Err(format!(
+                    "Conflicting types for generic `{}`: `{}` from the variable type and `{}` from the arguments",
+                    impl_generic,
+                    from_var.replace(" ", ""),
+                    from_args.replace(" ", "")
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// the variable type, matched by the conditions on `Self` (e.g. `Self = MyType<i32>`)
+fn get_self_var(ann: &AnnotationBody, aliases: &Aliases) -> Option<VarInfo> {
+    if ann.var_type.is_empty() {
+        return None;
+    }
+
+    Some(VarInfo {
+        impl_generic: SELF.to_string(),
+        trait_generic: None,
+        concrete_type: get_concrete_type_with_lifetime(&ann.var_type, &ann.annotations, aliases),
+        traits: get_type_traits(&ann.var_type, &ann.annotations, aliases),
+    })
+}
+
+/**
+   Get the lifetimes assigned to the lifetime generics of the impl by the variable type.
+   # Example
+   `impl<'a> Foo for MyType<'a>` with `MyType<'static>` returns `{'a: 'static}`
+*/
+fn get_lifetimes(
+    ann: &AnnotationBody,
+    impl_: &ImplBody,
+    aliases: &Aliases,
+) -> HashMap<String, String> {
+    if get_generics_lifetimes::<Vec<String>>(&impl_.impl_generics).is_empty() {
+        return HashMap::new();
+    }
+
+    type_assignable_generic_constraints(
+        &ann.var_type,
+        &impl_.type_name,
+        &impl_.impl_generics,
+        aliases,
+    )
+    .into_iter()
+    .flat_map(|generics_map| generics_map.lifetimes.into_iter())
+    .filter_map(|(generic, lifetime)| lifetime.map(|lt| (generic, lt)))
+    .collect()
+}
+
+/**
+   Get the values assigned to the const generics of the impl by the variable type.
+   # Example
+   `impl<const N: usize> Foo for [u8; N]` with `[u8; 3]` returns `{N: 3}`
+*/
+fn get_consts(
+    ann: &AnnotationBody,
+    impl_: &ImplBody,
+    aliases: &Aliases,
+) -> HashMap<String, String> {
+    let generics = str_to_generics(&impl_.impl_generics);
+    if !generics
+        .params
+        .iter()
+        .any(|p| matches!(p, GenericParam::Const(_)))
+    {
+        return HashMap::new();
+    }
+
+    type_assignable_generic_constraints(
+        &ann.var_type,
+        &impl_.type_name,
+        &impl_.impl_generics,
+        aliases,
+    )
+    .into_iter()
+    .flat_map(|generics_map| generics_map.consts.into_iter())
+    .filter_map(|(generic, value)| value.map(|v| (generic, v)))
+    .collect()
 }
 
 /**
@@ -138,7 +315,7 @@ fn get_vars(
    # Example
    `fn foo(&self, x: T, y: u32);` returns `vec!["T", "u32"]`
 */
-fn get_param_types(trait_fn: &TraitItemFn) -> Vec<String> {
+pub fn get_param_types(trait_fn: &TraitItemFn) -> Vec<String> {
     trait_fn
         .sig
         .inputs
@@ -150,59 +327,154 @@ fn get_param_types(trait_fn: &TraitItemFn) -> Vec<String> {
         .collect()
 }
 
+/**
+   Get the return type from a trait function.
+   # Example
+   `fn foo(&self) -> T;` returns `Some("T")`
+*/
+fn get_return_type(trait_fn: &TraitItemFn) -> Option<String> {
+    match &trait_fn.sig.output {
+        ReturnType::Type(_, ty) => Some(to_string(ty)),
+        ReturnType::Default => None,
+    }
+}
+
 fn get_generic_constraints_from_trait(
     trait_generic: &str,
     trait_: &TraitBody,
     impl_: &ImplBody,
     ann: &AnnotationBody,
     aliases: &Aliases,
-) -> Vec<VarInfo> {
+) -> Result<Vec<VarInfo>, String> {
     let trait_fn = trait_.find_fn(&ann.fn_, ann.args.len()).unwrap();
-    let param_types = get_param_types(&trait_fn);
 
-    // find all params that use the generic
-    let params_with_trait_generic = param_types
-        .iter()
-        .enumerate()
-        .filter(|(_, p)| type_contains(&str_to_type_name(p), trait_generic))
+    // pairs of (declared type, concrete type), params first then the return type if annotated
+    let declared_and_concrete_types = get_param_types(&trait_fn)
+        .into_iter()
+        .zip(ann.args_types.iter().cloned())
+        .chain(get_return_type(&trait_fn).zip(ann.ret_type.clone()))
         .collect::<Vec<_>>();
 
-    // generic passed but not used
-    if params_with_trait_generic.is_empty() {
-        return vec![];
-    }
+    // map from trait generic to the concrete type it is constrained to, the first position wins
+    let mut constraints = HashMap::<String, String>::new();
 
-    let (pos, trait_type_definition) = params_with_trait_generic.first().unwrap();
-    let concrete_type = &ann.args_types[*pos];
-
-    let mut res = HashSet::new();
+    // every position that uses the generic must agree on the types of the generics it contains
+    for (trait_type_definition, concrete_type) in declared_and_concrete_types
+        .iter()
+        .filter(|(declared, _)| type_contains(&str_to_type_name(declared), trait_generic))
+    {
+        let constrained_generics = type_assignable_generic_constraints(
+            concrete_type,
+            trait_type_definition,
+            &trait_.generics,
+            aliases,
+        );
 
-    let constrained_generics = type_assignable_generic_constraints(
-        concrete_type,
-        trait_type_definition,
-        &trait_.generics,
-        aliases,
-    );
+        let Some(generics_map) = constrained_generics else {
+            continue;
+        };
 
-    if let Some(generics_map) = constrained_generics {
         for (generic, constraint) in generics_map.types {
-            if let Some(constraint) = constraint {
-                let impl_generic = impl_
-                    .get_corresponding_generic(&str_to_generics(&trait_.generics), &generic)
-                    .unwrap();
-                res.insert((constraint, impl_generic, generic));
+            let Some(constraint) = constraint else {
+                continue;
+            };
+
+            match constraints.get(&generic) {
+                Some(existing)
+                    if !type_assignable(existing, &constraint, "", aliases)
+                        && !type_assignable(&constraint, existing, "", aliases) =>
+                {
+                    return Err(format!(
+                        "Conflicting types for generic `{}`: `{}` and `{}`",
+                        generic, existing, constraint
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    constraints.insert(generic, constraint);
+                }
             }
         }
     }
 
-    res.into_iter()
-        .map(|(constraint, impl_generic, trait_generic)| VarInfo {
-            impl_generic,
-            trait_generic: Some(trait_generic),
-            concrete_type: get_concrete_type_with_lifetime(&constraint, &ann.annotations, aliases),
-            traits: get_type_traits(&constraint, &ann.annotations, aliases),
+    // generic passed but not used leaves no constraints
+    Ok(constraints
+        .into_iter()
+        .map(|(trait_generic, constraint)| {
+            let impl_generic = impl_
+                .get_corresponding_generic(&str_to_generics(&trait_.generics), &trait_generic)
+                .unwrap();
+            VarInfo {
+                impl_generic,
+                trait_generic: Some(trait_generic),
+                concrete_type: get_concrete_type_with_lifetime(
+                    &constraint,
+                    &ann.annotations,
+                    aliases,
+                ),
+                traits: get_type_traits(&constraint, &ann.annotations, aliases),
+            }
         })
-        .collect::<Vec<_>>()
+        .collect())
+}
+
+/**
+   get the types bound to the impl generic by the arguments of the trait generics that the impl passes as types.
+   # Example
+   `impl<T> MyTrait<Vec<T>> for MyType { fn put(&self, items: Vec<T>) }` with `items: Vec<u8>` binds `T` to `u8`
+*/
+fn get_generic_constraints_from_trait_types(
+    impl_generic: &str,
+    (impl_trait_generics, trait_types): (&Generics, &[(String, Type)]),
+    trait_: &TraitBody,
+    impl_: &ImplBody,
+    ann: &AnnotationBody,
+    aliases: &Aliases,
+) -> Result<Vec<VarInfo>, String> {
+    let mut vars = vec![];
+
+    for (placeholder, type_) in trait_types
+        .iter()
+        .filter(|(_, type_)| type_contains(type_, impl_generic))
+    {
+        let Some(trait_generic) =
+            trait_.get_corresponding_generic(impl_trait_generics, placeholder)
+        else {
+            continue;
+        };
+
+        for var in get_generic_constraints_from_trait(&trait_generic, trait_, impl_, ann, aliases)?
+            .into_iter()
+            .filter(|var| var.trait_generic.as_ref() == Some(&trait_generic))
+        {
+            let constrained_generics = type_assignable_generic_constraints(
+                &var.concrete_type,
+                &to_string(type_),
+                &impl_.impl_generics,
+                aliases,
+            );
+
+            vars.extend(
+                constrained_generics
+                    .into_iter()
+                    .flat_map(|generics_map| generics_map.types.into_iter())
+                    .filter(|(generic, _)| generic == impl_generic)
+                    .filter_map(|(_, constraint)| constraint)
+                    .map(|constraint| VarInfo {
+                        impl_generic: impl_generic.to_string(),
+                        trait_generic: None,
+                        concrete_type: get_concrete_type_with_lifetime(
+                            &constraint,
+                            &ann.annotations,
+                            aliases,
+                        ),
+                        traits: get_type_traits(&constraint, &ann.annotations, aliases),
+                    }),
+            );
+        }
+    }
+
+    Ok(vars)
 }
 
 fn get_generic_constraints_from_type(
@@ -235,7 +507,16 @@ fn get_generic_constraints_from_type(
         .collect::<Vec<_>>()
 }
 
+/// Get the trait annotations for the types registered with `spec_type_traits!`.
+pub fn get_registered_annotations(type_traits: &[TypeTraitsBody]) -> Vec<Annotation> {
+    type_traits
+        .iter()
+        .map(|tt| Annotation::Trait(tt.type_name.clone(), tt.traits.clone()))
+        .collect()
+}
+
 /// Get the traits associated with a type from annotations.
+/// Annotations on types with wildcards (e.g. `Vec<_>: Debug`) apply to every type assignable to them.
 fn get_type_traits(type_: &str, ann: &[Annotation], aliases: &Aliases) -> Vec<String> {
     ann.iter()
         .flat_map(|a| match a {
@@ -317,6 +598,17 @@ mod tests {
         assert_eq!(result, vec!["T".to_string(), "u32".to_string()]);
     }
 
+    #[test]
+    fn test_get_return_type() {
+        let trait_fn: TraitItemFn = syn::parse_str("fn foo(&self) -> Vec<T>;").unwrap();
+        let result = get_return_type(&trait_fn);
+        assert_eq!(result, Some("Vec < T >".to_string()));
+
+        let trait_fn: TraitItemFn = syn::parse_str("fn foo(&self);").unwrap();
+        let result = get_return_type(&trait_fn);
+        assert_eq!(result, None);
+    }
+
     #[test]
     fn test_get_type_traits() {
         let ann = vec![
@@ -365,6 +657,7 @@ mod tests {
                 "Vec<&'static i32>".to_string(),
             ],
             args: vec!["1i32".to_string(), "2u32".to_string(), "vec![]".to_string()],
+            ret_type: None,
             var: "x".to_string(),
             var_type: "MyType".to_string(),
             annotations: vec![Annotation::Trait("i32".into(), vec!["Debug".into()])],
@@ -372,7 +665,7 @@ mod tests {
 
         let aliases = Aliases::new();
 
-        let result = get_vars(&ann, &impl_body, &trait_body, &aliases);
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
 
         assert_eq!(result.len(), 3);
         let t = result.iter().find(|v| v.impl_generic == "T").unwrap();
@@ -407,6 +700,250 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_vars_generic_in_multiple_params() {
+        let impl_body = ImplBody::try_from((
+            syn::parse_str::<TokenStream>(
+                "impl<T> MyTrait<T> for MyType { fn foo(&self, a: u32, b: T, c: Vec<T>) {} }",
+            )
+            .unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        let trait_body = TraitBody::try_from(
+            syn::parse_str::<TokenStream>(
+                "trait MyTrait<A> { fn foo(&self, a: u32, b: A, c: Vec<A>); }",
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .specialize(&impl_body);
+
+        let get_ann = |b: &str, c: &str| AnnotationBody {
+            fn_: "foo".to_string(),
+            args_types: vec!["u32".to_string(), b.to_string(), c.to_string()],
+            args: vec!["1".to_string(), "2".to_string(), "vec![]".to_string()],
+            ret_type: None,
+            var: "x".to_string(),
+            var_type: "MyType".to_string(),
+            annotations: vec![],
+        };
+
+        let aliases = Aliases::new();
+
+        let ann = get_ann("u8", "Vec<u8>");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
+        let t = result.iter().find(|v| v.impl_generic == "T").unwrap();
+        assert_eq!(t.concrete_type, "u8");
+
+        let ann = get_ann("u8", "Vec<i32>");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases);
+        assert_eq!(
+            result.unwrap_err(),
+            "Conflicting types for generic `A`: `u8` and `i32`"
+        );
+    }
+
+    #[test]
+    fn test_get_vars_generic_in_reference_param() {
+        let impl_body = ImplBody::try_from((
+            syn::parse_str::<TokenStream>(
+                "impl<T> MyTrait<T> for MyType { fn foo(&self, x: &T) {} }",
+            )
+            .unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        let trait_body = TraitBody::try_from(
+            syn::parse_str::<TokenStream>("trait MyTrait<A> { fn foo(&self, x: &A); }").unwrap(),
+        )
+        .unwrap()
+        .specialize(&impl_body);
+
+        let get_ann = |x: &str| AnnotationBody {
+            fn_: "foo".to_string(),
+            args_types: vec![x.to_string()],
+            args: vec!["&1".to_string()],
+            ret_type: None,
+            var: "x".to_string(),
+            var_type: "MyType".to_string(),
+            annotations: vec![],
+        };
+
+        let aliases = Aliases::new();
+
+        // the reference of the param is peeled from the argument type
+        let result = get_vars(&get_ann("&i32"), &impl_body, &trait_body, &aliases).unwrap();
+        let t = result.iter().find(|v| v.impl_generic == "T").unwrap();
+        assert_eq!(t.concrete_type, "i32");
+
+        let result = get_vars(&get_ann("&'static str"), &impl_body, &trait_body, &aliases).unwrap();
+        let t = result.iter().find(|v| v.impl_generic == "T").unwrap();
+        assert_eq!(t.concrete_type, "str");
+
+        // without the reference the argument does not match the param, so nothing is bound
+        let result = get_vars(&get_ann("i32"), &impl_body, &trait_body, &aliases).unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_get_vars_generic_in_receiver_and_params() {
+        let impl_body = ImplBody::try_from((
+            syn::parse_str::<TokenStream>(
+                "impl<T> MyTrait<T> for Container<T> { fn put(&self, item: T) {} }",
+            )
+            .unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        let trait_body = TraitBody::try_from(
+            syn::parse_str::<TokenStream>("trait MyTrait<A> { fn put(&self, item: A); }").unwrap(),
+        )
+        .unwrap()
+        .specialize(&impl_body);
+
+        let get_ann = |var_type: &str, item: &str| AnnotationBody {
+            fn_: "put".to_string(),
+            args_types: vec![item.to_string()],
+            args: vec!["item".to_string()],
+            ret_type: None,
+            var: "x".to_string(),
+            var_type: var_type.to_string(),
+            annotations: vec![],
+        };
+
+        let aliases = Aliases::new();
+
+        let ann = get_ann("Container<u8>", "u8");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
+        assert!(result.iter().all(|v| v.concrete_type == "u8"));
+
+        let ann = get_ann("Container<u8>", "i32");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases);
+        assert_eq!(
+            result.unwrap_err(),
+            "Conflicting types for generic `T`: `u8` from the variable type and `i32` from the arguments"
+        );
+    }
+
+    #[test]
+    fn test_get_vars_generic_in_reference_receiver() {
+        let impl_body = ImplBody::try_from((
+            syn::parse_str::<TokenStream>(
+                "impl<'a, T> MyTrait<T> for &'a Container<T> { fn put(&self, item: T) {} }",
+            )
+            .unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        let trait_body = TraitBody::try_from(
+            syn::parse_str::<TokenStream>("trait MyTrait<A> { fn put(&self, item: A); }").unwrap(),
+        )
+        .unwrap()
+        .specialize(&impl_body);
+
+        let get_ann = |var_type: &str, item: &str| AnnotationBody {
+            fn_: "put".to_string(),
+            args_types: vec![item.to_string()],
+            args: vec!["item".to_string()],
+            ret_type: None,
+            var: "x".to_string(),
+            var_type: var_type.to_string(),
+            annotations: vec![],
+        };
+
+        let aliases = Aliases::new();
+
+        let ann = get_ann("&Container<u8>", "u8");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
+        assert!(result.iter().all(|v| v.concrete_type == "u8"));
+
+        let ann = get_ann("&'static Container<u8>", "u8");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
+        assert!(result.iter().all(|v| v.concrete_type == "u8"));
+
+        let ann = get_ann("&Container<u8>", "i32");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases);
+        assert_eq!(
+            result.unwrap_err(),
+            "Conflicting types for generic `T`: `u8` from the variable type and `i32` from the arguments"
+        );
+    }
+
+    #[test]
+    fn test_get_vars_generic_in_slice_receiver() {
+        let impl_body = ImplBody::try_from((
+            syn::parse_str::<TokenStream>("impl<T> MyTrait for [T] { fn sum(&self) {} }").unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        let trait_body = TraitBody::try_from(
+            syn::parse_str::<TokenStream>("trait MyTrait { fn sum(&self); }").unwrap(),
+        )
+        .unwrap()
+        .specialize(&impl_body);
+
+        let get_ann = |var_type: &str| AnnotationBody {
+            fn_: "sum".to_string(),
+            args_types: vec![],
+            args: vec![],
+            ret_type: None,
+            var: "x".to_string(),
+            var_type: var_type.to_string(),
+            annotations: vec![],
+        };
+
+        let aliases = Aliases::new();
+
+        let ann = get_ann("[u8]");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
+        let t = result.iter().find(|v| v.impl_generic == "T").unwrap();
+        assert_eq!(t.concrete_type, "u8");
+
+        let ann = get_ann("[Vec<u8>]");
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
+        let t = result.iter().find(|v| v.impl_generic == "T").unwrap();
+        assert_eq!(t.concrete_type.replace(" ", ""), "Vec<u8>");
+    }
+
+    #[test]
+    fn test_get_vars_generic_in_trait_type() {
+        let impl_body = ImplBody::try_from((
+            syn::parse_str::<TokenStream>(
+                "impl<T> MyTrait<Vec<T>> for MyType { fn put(&self, items: Vec<T>) {} }",
+            )
+            .unwrap(),
+            Some(WhenCondition::Type("T".into(), "u8".into())),
+        ))
+        .unwrap();
+
+        let trait_body = TraitBody::try_from(
+            syn::parse_str::<TokenStream>("trait MyTrait<A> { fn put(&self, items: A); }").unwrap(),
+        )
+        .unwrap()
+        .specialize(&impl_body);
+
+        let ann = AnnotationBody {
+            fn_: "put".to_string(),
+            args_types: vec!["Vec<u8>".to_string()],
+            args: vec!["items".to_string()],
+            ret_type: None,
+            var: "x".to_string(),
+            var_type: "MyType".to_string(),
+            annotations: vec![],
+        };
+
+        // `T` is taken from the argument of the trait generic `A = Vec<T>`
+        let result = get_vars(&ann, &impl_body, &trait_body, &Aliases::new()).unwrap();
+        let t = result.iter().find(|v| v.impl_generic == "T").unwrap();
+        assert_eq!(t.concrete_type, "u8");
+    }
+
     #[test]
     fn test_get_vars_different_formats() {
         let impl_body = ImplBody::try_from((
@@ -449,6 +986,7 @@ mod tests {
                 "z".to_string(),
                 "w".to_string(),
             ],
+            ret_type: None,
             var: "x".to_string(),
             var_type: "Vec<MyType>".to_string(),
             annotations: vec![
@@ -459,7 +997,7 @@ mod tests {
 
         let aliases = Aliases::new();
 
-        let result = get_vars(&ann, &impl_body, &trait_body, &aliases);
+        let result = get_vars(&ann, &impl_body, &trait_body, &aliases).unwrap();
         println!("{:#?}", result);
 
         assert_eq!(result.len(), 5);
@@ -0,0 +1,17 @@
+use spec_trait_utils::env::ENV_CACHE_PATH;
+use std::{env, fs, process};
+
+#[test]
+fn ui() {
+    // the macros of the cases use a cache of their own, removed at the end
+    let path = env::temp_dir().join(format!("spec-trait-macro-ui-{}.json", process::id()));
+    // SAFETY: the only test of this binary, no other thread reads the environment
+    unsafe { env::set_var(ENV_CACHE_PATH, &path) };
+
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+    drop(cases);
+
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("lock"));
+}
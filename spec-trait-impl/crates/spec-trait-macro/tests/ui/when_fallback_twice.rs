@@ -0,0 +1,21 @@
+use spec_trait_macro::{spec_trait, when};
+
+struct Parrot;
+
+spec_trait! {
+    trait Speak<T> {
+        fn speak(&self, x: T);
+    }
+}
+
+#[when(T = u8, fallback)]
+impl<T> Speak<T> for Parrot {
+    fn speak(&self, _x: T) {}
+}
+
+#[when(T = i32, fallback)]
+impl<T> Speak<T> for Parrot {
+    fn speak(&self, _x: T) {}
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use spec_trait_macro::when;
+
+struct MyType;
+
+trait Foo<T> {
+    fn foo(&self, x: T);
+}
+
+#[when(all(T = i32, U = ))]
+impl<T, U> Foo<T> for MyType {
+    fn foo(&self, _x: T) {}
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use spec_trait_macro::when;
+
+struct MyType;
+struct OtherType;
+
+trait Foo<T> {
+    fn foo(&self, x: T);
+}
+
+#[when(T = i32, for = {MyType, OtherType})]
+impl<T> Foo<T> for MyType {
+    fn foo(&self, _x: T) {}
+}
+
+fn main() {}
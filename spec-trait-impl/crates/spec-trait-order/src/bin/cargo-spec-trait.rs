@@ -0,0 +1,53 @@
+//! `cargo spec-trait check [DIR]` validates the `when` conditions of the crates in `DIR` (the current directory by default),
+//! exiting with a nonzero code if there are errors.
+//!
+//! The crates are found as in the build step, but outside of it the features are not enabled,
+//! so the items with `#[cfg(feature = "...")]` are skipped.
+
+use spec_trait_order::{Severity, check};
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: cargo spec-trait check [DIR]";
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+
+    // `cargo spec-trait` passes the name of the subcommand as the first argument
+    if args.first().is_some_and(|arg| arg == "spec-trait") {
+        args.remove(0);
+    }
+
+    let dir = match args.as_slice() {
+        [command] if command == "check" => PathBuf::from("."),
+        [command, dir] if command == "check" => PathBuf::from(dir),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if !dir.join("Cargo.toml").is_file() {
+        eprintln!("error: no Cargo.toml in {}", dir.display());
+        return ExitCode::from(2);
+    }
+
+    let diagnostics = check(&dir);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    eprintln!("{} error(s), {} warning(s)", errors, warnings);
+
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,188 @@
+use std::env;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, Item, Lit, Meta, Token};
+
+/**
+    check if the `cfg` attributes of an item are active for the current build.
+    The cfgs are looked up in the environment that cargo sets for build scripts,
+    i.e. `CARGO_FEATURE_<NAME>` for the features and `CARGO_CFG_<NAME>` for the rest (e.g. `target_os`, `unix`).
+    Predicates that cannot be evaluated (e.g. `test`, which is not visible to build scripts) are considered active.
+*/
+pub fn is_active(item: &Item) -> bool {
+    is_active_with(get_attrs(item), &|key| env::var(key).ok())
+}
+
+fn get_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Impl(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+fn is_active_with(attrs: &[Attribute], lookup: &dyn Fn(&str) -> Option<String>) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .all(|attr| {
+            attr.parse_args::<Meta>()
+                .ok()
+                .and_then(|predicate| evaluate(&predicate, lookup))
+                .unwrap_or(true)
+        })
+}
+
+/// evaluate a cfg predicate, `None` if it cannot be evaluated
+fn evaluate(predicate: &Meta, lookup: &dyn Fn(&str) -> Option<String>) -> Option<bool> {
+    match predicate {
+        // `unix`, `debug_assertions`
+        Meta::Path(path) => match path.get_ident() {
+            Some(ident) if ident == "test" => None,
+            Some(ident) => Some(lookup(&get_cfg_var(&ident.to_string())).is_some()),
+            None => None,
+        },
+
+        // `feature = "x"`, `target_os = "linux"`
+        Meta::NameValue(name_value) => {
+            let (
+                Some(ident),
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(value),
+                    ..
+                }),
+            ) = (name_value.path.get_ident(), &name_value.value)
+            else {
+                return None;
+            };
+
+            if ident == "feature" {
+                let feature = value.value().to_uppercase().replace('-', "_");
+                Some(lookup(&format!("CARGO_FEATURE_{}", feature)).is_some())
+            } else {
+                // multiple values are separated by commas (e.g. `CARGO_CFG_TARGET_FEATURE=sse,sse2`)
+                Some(
+                    lookup(&get_cfg_var(&ident.to_string()))
+                        .is_some_and(|values| values.split(',').any(|v| v == value.value())),
+                )
+            }
+        }
+
+        // `all(...)`, `any(...)`, `not(...)`
+        Meta::List(list) => {
+            let Ok(inner) = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            else {
+                return None;
+            };
+            let values = inner
+                .iter()
+                .map(|p| evaluate(p, lookup))
+                .collect::<Vec<_>>();
+
+            match list
+                .path
+                .get_ident()
+                .map(|ident| ident.to_string())
+                .as_deref()
+            {
+                // a false predicate decides `all` even if the others cannot be evaluated
+                Some("all") if values.contains(&Some(false)) => Some(false),
+                Some("all") => values.into_iter().collect::<Option<Vec<_>>>().map(|_| true),
+                // a true predicate decides `any` even if the others cannot be evaluated
+                Some("any") if values.contains(&Some(true)) => Some(true),
+                Some("any") => values
+                    .into_iter()
+                    .collect::<Option<Vec<_>>>()
+                    .map(|_| false),
+                Some("not") if values.len() == 1 => values[0].map(|value| !value),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn get_cfg_var(name: &str) -> String {
+    format!("CARGO_CFG_{}", name.to_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn is_active_in(item: &str, vars: &[(&str, &str)]) -> bool {
+        let item = syn::parse_str::<Item>(item).unwrap();
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        is_active_with(get_attrs(&item), &|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn features() {
+        let item = "#[cfg(feature = \"my-feature\")] impl Foo for MyStruct {}";
+
+        assert!(is_active_in(item, &[("CARGO_FEATURE_MY_FEATURE", "1")]));
+        assert!(!is_active_in(item, &[]));
+    }
+
+    #[test]
+    fn target_cfgs() {
+        let item = "#[cfg(target_os = \"linux\")] impl Foo for MyStruct {}";
+        assert!(is_active_in(item, &[("CARGO_CFG_TARGET_OS", "linux")]));
+        assert!(!is_active_in(item, &[("CARGO_CFG_TARGET_OS", "windows")]));
+
+        let item = "#[cfg(target_feature = \"sse2\")] impl Foo for MyStruct {}";
+        assert!(is_active_in(
+            item,
+            &[("CARGO_CFG_TARGET_FEATURE", "sse,sse2")]
+        ));
+
+        let item = "#[cfg(unix)] impl Foo for MyStruct {}";
+        assert!(is_active_in(item, &[("CARGO_CFG_UNIX", "")]));
+        assert!(!is_active_in(item, &[]));
+    }
+
+    #[test]
+    fn combined_predicates() {
+        let item = "#[cfg(all(feature = \"a\", not(any(feature = \"b\", windows))))] impl Foo for MyStruct {}";
+
+        assert!(is_active_in(item, &[("CARGO_FEATURE_A", "1")]));
+        assert!(!is_active_in(item, &[]));
+        assert!(!is_active_in(
+            item,
+            &[("CARGO_FEATURE_A", "1"), ("CARGO_FEATURE_B", "1")]
+        ));
+        assert!(!is_active_in(
+            item,
+            &[("CARGO_FEATURE_A", "1"), ("CARGO_CFG_WINDOWS", "")]
+        ));
+    }
+
+    #[test]
+    fn unknown_predicates() {
+        assert!(is_active_in("impl Foo for MyStruct {}", &[]));
+        assert!(is_active_in("#[cfg(test)] impl Foo for MyStruct {}", &[]));
+        assert!(is_active_in(
+            "#[cfg(not(test))] impl Foo for MyStruct {}",
+            &[]
+        ));
+        assert!(is_active_in(
+            "#[cfg(any(test, feature = \"a\"))] impl Foo for MyStruct {}",
+            &[]
+        ));
+        assert!(!is_active_in(
+            "#[cfg(all(test, feature = \"a\"))] impl Foo for MyStruct {}",
+            &[]
+        ));
+        assert!(is_active_in(
+            "#[cfg(accessible(::std::vec))] impl Foo for MyStruct {}",
+            &[]
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::aliases::{collect_when_aliases, is_when_macro};
+use crate::crates::{self, Crate};
+use crate::files;
+use quote::quote;
+use spec_trait_utils::conditions::{self, SELF, WhenCondition};
+use spec_trait_utils::impls::{self, ImplBody};
+use spec_trait_utils::overlaps::find_overlaps;
+use spec_trait_utils::types::{type_contains, type_contains_lifetime};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::{Attribute, GenericParam, Item, ItemImpl, Meta, Type};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// the position of a `when` attribute, the column starts from 1
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+/// an issue found in the `when` condition of an impl
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub location: Location,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{}: {}\n  --> {}",
+            self.severity, self.message, self.location
+        )
+    }
+}
+
+/// an impl with a `when` attribute and the position of the attribute
+struct LocatedImpl<'a> {
+    impl_: &'a ItemImpl,
+    attr: &'a Attribute,
+    location: Location,
+}
+
+/**
+    validate the `when` conditions of all the crates in `dir`, as found by the build step.
+    - error: a condition that cannot be parsed
+    - error: a condition on a generic that is not declared by the impl
+    - warning: a condition on a generic that is not used by the impl, so it has no effect
+    - warning: impls that can be equally specific for the same types (see `find_overlaps`)
+
+    The diagnostics are sorted by location.
+*/
+pub fn check(dir: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = crates::get_crates(dir)
+        .iter()
+        .flat_map(check_crate)
+        .collect::<Vec<_>>();
+
+    diagnostics.sort_by(|a, b| a.location.cmp(&b.location));
+    diagnostics
+}
+
+fn check_crate(crate_: &Crate) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut conjunctions = vec![];
+
+    for path in &crate_.files {
+        let file = files::read_file(path);
+        let module = files::get_file_module(&crate_.src_dir, path);
+
+        for (module, items) in files::get_scopes(&file.items, &module) {
+            let when_aliases = collect_when_aliases(&items);
+            let declared_types = files::get_declared_types(&items);
+
+            for located in get_located_impls(&items, path, &when_aliases) {
+                let Some(condition) = check_condition(&located, &mut diagnostics) else {
+                    continue;
+                };
+                check_generics(&located, &condition, &mut diagnostics);
+
+                conjunctions.extend(
+                    files::get_impl_conjunctions(
+                        located.impl_,
+                        &when_aliases,
+                        &crate_.content.type_aliases,
+                    )
+                    .into_iter()
+                    .map(|impl_| {
+                        (
+                            located.location.clone(),
+                            files::set_module(impl_, &module, &declared_types),
+                        )
+                    }),
+                );
+            }
+        }
+    }
+
+    for overlap in find_overlaps(&crate_.content.impls) {
+        let find_location = |condition: &WhenCondition| {
+            conjunctions.iter().find_map(|(location, impl_)| {
+                (impl_.trait_name == overlap.trait_name
+                    && impl_.full_type_name() == overlap.type_name
+                    && impl_.condition.as_ref() == Some(condition))
+                .then(|| location.clone())
+            })
+        };
+
+        if let (Some(first), Some(second)) = (
+            find_location(&overlap.first),
+            find_location(&overlap.second),
+        ) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: format!("{} (the other impl is at {})", overlap, second),
+                location: first,
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// get the impls with a `when` attribute in `items`
+fn get_located_impls<'a>(
+    items: &'a [Item],
+    path: &Path,
+    when_aliases: &HashSet<String>,
+) -> Vec<LocatedImpl<'a>> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Impl(impl_) => Some(impl_),
+            _ => None,
+        })
+        .filter_map(|impl_| {
+            let attr = impl_
+                .attrs
+                .iter()
+                .find(|attr| is_when_macro(attr.path(), when_aliases))?;
+            let start = attr.span().start();
+
+            Some(LocatedImpl {
+                impl_,
+                attr,
+                location: Location {
+                    file: path.to_path_buf(),
+                    line: start.line,
+                    column: start.column + 1,
+                },
+            })
+        })
+        .collect()
+}
+
+/// parse the condition of the `when` attribute, `None` if it is empty or invalid
+fn check_condition(
+    located: &LocatedImpl,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<WhenCondition> {
+    let tokens = match &located.attr.meta {
+        Meta::List(meta_list) => meta_list.tokens.clone(),
+        _ => return None,
+    };
+
+    let result = conditions::split_requires(tokens.clone())
+        .and_then(|(tokens, _)| conditions::split_fallback(tokens))
+        .and_then(|(tokens, _)| conditions::split_priority(tokens))
+        .and_then(|(tokens, _)| conditions::parse_condition(tokens));
+
+    match result {
+        Ok(condition) => condition,
+        Err(err) => {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                message: format!("invalid condition `{}`: {}", tokens, err),
+                location: located.location.clone(),
+            });
+            None
+        }
+    }
+}
+
+/// check that the generics in the condition are declared and used by the impl
+fn check_generics(
+    located: &LocatedImpl,
+    condition: &WhenCondition,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let declared = located
+        .impl_
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Type(tp) => tp.ident.to_string(),
+            GenericParam::Lifetime(lp) => lp.lifetime.to_string(),
+            GenericParam::Const(cp) => cp.ident.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    let (impl_no_attrs, _) = impls::break_attr(located.impl_);
+    let unused = ImplBody::try_from((quote! { #impl_no_attrs }, None))
+        .map(|impl_body| impl_body.unused_generics())
+        .unwrap_or_default();
+
+    // the generics in the trait (e.g. `T` in `impl<T> Foo<T> for MyType`) are used to select the impl
+    let trait_ = located
+        .impl_
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| syn::parse2::<Type>(quote! { #path }).ok());
+    let in_trait = |generic: &str| {
+        trait_.as_ref().is_some_and(|trait_| {
+            if generic.starts_with('\'') {
+                type_contains_lifetime(trait_, generic)
+            } else {
+                type_contains(trait_, generic)
+            }
+        })
+    };
+
+    for generic in get_condition_generics(condition) {
+        if generic == SELF {
+            continue;
+        }
+
+        let message = if !declared.contains(&generic) {
+            Some((
+                Severity::Error,
+                format!("`{}` is not a generic of the impl", generic),
+            ))
+        } else if unused.contains(&generic) && !in_trait(&generic) {
+            Some((
+                Severity::Warning,
+                format!(
+                    "`{}` is not used by the impl, so the condition on it has no effect",
+                    generic
+                ),
+            ))
+        } else {
+            None
+        };
+
+        if let Some((severity, message)) = message {
+            diagnostics.push(Diagnostic {
+                severity,
+                message,
+                location: located.location.clone(),
+            });
+        }
+    }
+}
+
+/// get the generics the condition is on, without duplicates (e.g. `T` and `'a` for `all(T: Clone, 'a = 'static)`)
+fn get_condition_generics(condition: &WhenCondition) -> Vec<String> {
+    let mut generics = vec![];
+    collect_condition_generics(condition, &mut generics);
+    generics
+}
+
+fn collect_condition_generics(condition: &WhenCondition, generics: &mut Vec<String>) {
+    match condition {
+        WhenCondition::Type(generic, _)
+        | WhenCondition::Trait(generic, _)
+        | WhenCondition::Lifetime(generic, _)
+        | WhenCondition::Const(generic, _) => {
+            if !generics.contains(generic) {
+                generics.push(generic.clone());
+            }
+        }
+        WhenCondition::Len(_) | WhenCondition::Arg(_, _) => {}
+        WhenCondition::All(inner) | WhenCondition::Any(inner) => inner
+            .iter()
+            .for_each(|c| collect_condition_generics(c, generics)),
+        WhenCondition::Not(inner) => collect_condition_generics(inner, generics),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn condition_generics() {
+        let condition = conditions::parse_condition(
+            quote! { all(T: Clone, any('a = 'static, not(T = u8)), N = 0, len = 1) },
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(get_condition_generics(&condition), vec!["T", "'a", "N"]);
+    }
+}
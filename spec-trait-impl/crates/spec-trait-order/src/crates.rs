@@ -8,8 +8,9 @@ use std::path::{Path, PathBuf};
 pub struct Crate {
     pub name: String,
     pub content: CrateCache,
-    #[cfg(test)]
-    files: Vec<PathBuf>,
+    /// the `src` directory of the crate
+    pub src_dir: PathBuf,
+    pub files: Vec<PathBuf>,
 }
 
 /// Get all crates in the given directory, considering both single-package and workspace setups
@@ -31,14 +32,14 @@ fn get_crate_from_package(value: &toml::Value, dir: &Path) -> Option<Crate> {
     let package = value.get("package")?;
     let name = package.get("name")?.as_str()?;
 
-    let src_path = dir.join("src");
-    let files = get_dir_rs_files(&src_path);
-    let content = files::parse_all(&files);
+    let src_dir = dir.join("src");
+    let files = get_dir_rs_files(&src_dir);
+    let content = files::parse_all(&src_dir, &files);
 
     Some(Crate {
         name: name.to_string(),
         content,
-        #[cfg(test)]
+        src_dir,
         files,
     })
 }
@@ -200,7 +201,7 @@ members = [{}]
         let td = tempdir().unwrap();
         let root = td.path();
 
-        make_package(&root, "root", &[("lib.rs", "pub fn main(){}")]);
+        make_package(root, "root", &[("lib.rs", "pub fn main(){}")]);
         make_package(
             &root.join("crates").join("foo"),
             "foo",
@@ -1,49 +1,195 @@
 use crate::aliases::{collect_when_aliases, is_when_macro};
+use crate::cfg;
 use quote::quote;
 use spec_trait_utils::cache::CrateCache;
-use spec_trait_utils::conditions::{self, WhenCondition};
+use spec_trait_utils::conditions::{self, Fallback, WhenCondition};
+use spec_trait_utils::conversions::str_to_type_name;
 use spec_trait_utils::impls::{self, ImplBody};
 use spec_trait_utils::traits::{self, TraitBody};
+use spec_trait_utils::type_aliases::{TypeAliasBody, expand_condition_aliases};
+use spec_trait_utils::type_traits::{TypeTraitsBody, parse_type_traits};
 use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
-use syn::{Attribute, Item, Meta};
+use std::iter;
+use std::path::{Path, PathBuf};
+use syn::{Attribute, File, Item, ItemFn, ItemImpl, ItemMod, ItemTrait, Meta, Stmt, Type};
 
-/// get CrateCache by parsing all the files in `paths`
-pub fn parse_all(paths: &[PathBuf]) -> CrateCache {
-    let mut traits = Vec::new();
-    let mut impls = Vec::new();
+/// get CrateCache by parsing all the files in `paths`, which are inside `src_dir`
+pub fn parse_all(src_dir: &Path, paths: &[PathBuf]) -> CrateCache {
+    let scopes = paths
+        .iter()
+        .flat_map(|path| get_scopes(&read_file(path).items, &get_file_module(src_dir, path)))
+        .collect::<Vec<_>>();
 
-    for path in paths {
-        let crate_cache = parse(path);
-        traits.extend(crate_cache.traits);
-        impls.extend(crate_cache.impls);
-    }
+    // aliases can be used in conditions in any file of the crate
+    let type_aliases = scopes
+        .iter()
+        .flat_map(|(_, items)| get_type_aliases(items))
+        .collect::<Vec<_>>();
 
-    CrateCache { traits, impls }
+    parse_scopes(&scopes, type_aliases)
 }
 
-/// get CrateCache by parsing a single file in `path`
-pub fn parse(path: &PathBuf) -> CrateCache {
+pub fn read_file(path: &PathBuf) -> File {
     let content = fs::read_to_string(path).expect("failed to read file");
-    let file = syn::parse_file(&content).expect("failed to parse content");
+    syn::parse_file(&content).expect("failed to parse content")
+}
+
+/// get CrateCache from the items of each module, expanding `type_aliases` in the conditions
+fn parse_scopes(scopes: &[(String, Vec<Item>)], type_aliases: Vec<TypeAliasBody>) -> CrateCache {
+    let mut crate_cache = CrateCache::default();
+
+    for (module, items) in scopes {
+        let declared_types = get_declared_types(items);
+
+        crate_cache.traits.extend(get_traits(items));
+        crate_cache.impls.extend(
+            get_impls(items, &type_aliases)
+                .into_iter()
+                .map(|impl_| set_module(impl_, module, &declared_types)),
+        );
+        crate_cache.type_traits.extend(get_type_traits(items));
+    }
+
+    crate_cache.type_aliases = type_aliases;
+    crate_cache
+}
+
+/// set the module of an impl in it, with `declared_types` the types declared in the module
+pub fn set_module(impl_: ImplBody, module: &str, declared_types: &HashSet<String>) -> ImplBody {
+    ImplBody {
+        module: module.to_string(),
+        local_type: is_declared(&impl_.type_name, declared_types),
+        ..impl_
+    }
+}
+
+/// get the names of the types declared in the items, which are the types of the module
+pub fn get_declared_types(items: &[Item]) -> HashSet<String> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Struct(item) => Some(item.ident.to_string()),
+            Item::Enum(item) => Some(item.ident.to_string()),
+            Item::Union(item) => Some(item.ident.to_string()),
+            Item::Type(item) => Some(item.ident.to_string()),
+            _ => None,
+        })
+        .collect()
+}
 
-    CrateCache {
-        traits: get_traits(&file.items),
-        impls: get_impls(&file.items),
+/// check if the type is one of `declared_types`, possibly with generics (e.g. `Foo<u8>` for `Foo`)
+fn is_declared(type_name: &str, declared_types: &HashSet<String>) -> bool {
+    match str_to_type_name(type_name) {
+        Type::Path(type_path) => {
+            type_path.qself.is_none()
+                && type_path.path.leading_colon.is_none()
+                && type_path.path.segments.len() == 1
+                && declared_types.contains(&type_path.path.segments[0].ident.to_string())
+        }
+        _ => false,
     }
 }
 
+/**
+    get the module of a file from its path relative to `src_dir`.
+    # Example:
+    - `src/lib.rs`, `src/main.rs` -> ``
+    - `src/a.rs`, `src/a/mod.rs` -> `a`
+    - `src/a/b.rs` -> `a::b`
+*/
+pub fn get_file_module(src_dir: &Path, path: &Path) -> String {
+    let relative = path
+        .strip_prefix(src_dir)
+        .unwrap_or(path)
+        .with_extension("");
+
+    let mut segments = relative
+        .iter()
+        .map(|segment| segment.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+
+    let is_root = segments.len() == 1 && (segments[0] == "lib" || segments[0] == "main");
+    if is_root || segments.last().is_some_and(|segment| segment == "mod") {
+        segments.pop();
+    }
+
+    segments.join("::")
+}
+
+/**
+    get the items of `module` together with the items of the nested modules, each with the path of its module.
+    Items declared inside functions belong to the module of the function.
+    The first scope is always `module` itself.
+*/
+pub fn get_scopes(items: &[Item], module: &str) -> Vec<(String, Vec<Item>)> {
+    let mut module_items = Vec::new();
+    let mut nested_scopes = Vec::new();
+
+    // the items disabled by a `cfg` are not compiled, so they cannot be selected
+    for item in items.iter().filter(|item| cfg::is_active(item)) {
+        match item {
+            Item::Mod(ItemMod {
+                ident,
+                content: Some((_, mod_items)),
+                ..
+            }) => {
+                let nested_module = if module.is_empty() {
+                    ident.to_string()
+                } else {
+                    format!("{}::{}", module, ident)
+                };
+                nested_scopes.extend(get_scopes(mod_items, &nested_module));
+            }
+            Item::Fn(ItemFn { block, .. }) => {
+                let fn_items = block
+                    .stmts
+                    .iter()
+                    .filter_map(|stmt| match stmt {
+                        Stmt::Item(item) => Some(item.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut fn_scopes = get_scopes(&fn_items, module).into_iter();
+                if let Some((_, fn_module_items)) = fn_scopes.next() {
+                    module_items.extend(fn_module_items);
+                }
+                nested_scopes.extend(fn_scopes);
+            }
+            _ => module_items.push(item.clone()),
+        }
+    }
+
+    iter::once((module.to_string(), module_items))
+        .chain(nested_scopes)
+        .collect()
+}
+
 /// get traits from items
 fn get_traits(items: &[Item]) -> Vec<TraitBody> {
     items
         .iter()
         .filter_map(|item| match item {
-            Item::Trait(trait_item) => Some(trait_item),
+            Item::Trait(trait_item) => Some(trait_item.clone()),
+            // declared with `spec_trait!`
+            Item::Macro(macro_item)
+                if macro_item
+                    .mac
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "spec_trait") =>
+            {
+                Some(
+                    syn::parse2::<ItemTrait>(macro_item.mac.tokens.clone())
+                        .expect("Failed to parse TokenStream into ItemTrait"),
+                )
+            }
             _ => None,
         })
         .map(|trait_| {
-            let (trait_no_attrs, _) = traits::break_attr(trait_);
+            let (trait_no_attrs, _) = traits::break_attr(&trait_);
             let tokens = quote! { #trait_no_attrs };
             TraitBody::try_from(tokens).expect("Failed to parse TokenStream into TraitBody")
         })
@@ -51,7 +197,7 @@ fn get_traits(items: &[Item]) -> Vec<TraitBody> {
 }
 
 /// get impls from items
-fn get_impls(items: &[Item]) -> Vec<ImplBody> {
+fn get_impls(items: &[Item], type_aliases: &[TypeAliasBody]) -> Vec<ImplBody> {
     let when_aliases = collect_when_aliases(items);
 
     items
@@ -60,42 +206,127 @@ fn get_impls(items: &[Item]) -> Vec<ImplBody> {
             Item::Impl(impl_item) => Some(impl_item),
             _ => None,
         })
-        .flat_map(|impl_| {
-            let (impl_no_attrs, impl_attrs) = impls::break_attr(impl_);
-            let tokens = quote! { #impl_no_attrs };
+        .flat_map(|impl_| get_impl_conjunctions(impl_, &when_aliases, type_aliases))
+        .collect()
+}
 
-            let conditions = match get_condition(&impl_attrs, &when_aliases) {
-                Some(condition) => conditions::get_conjunctions(condition)
-                    .into_iter()
-                    .map(Some)
-                    .collect(),
-                None => vec![None],
-            };
+/**
+    get an ImplBody for each DNF conjunction of the `when` condition of the impl, or a single one without condition.
+    With `for = {...}` in `when` the ImplBodies are repeated for each of the types.
+*/
+pub fn get_impl_conjunctions(
+    impl_: &ItemImpl,
+    when_aliases: &HashSet<String>,
+    type_aliases: &[TypeAliasBody],
+) -> Vec<ImplBody> {
+    let (impl_no_attrs, impl_attrs) = impls::break_attr(impl_);
+
+    let (condition, priority, fallback, types) = get_condition(&impl_attrs, when_aliases);
+    let condition = condition.map(|condition| expand_condition_aliases(condition, type_aliases));
+
+    // the default impl generated by `fallback`, only for an impl with a condition
+    let fallback = fallback.filter(|_| condition.is_some());
+
+    let conditions = match condition.clone() {
+        Some(condition) => conditions::get_conjunctions(condition)
+            .into_iter()
+            .map(Some)
+            .collect(),
+        None => vec![None],
+    };
+
+    let impls_no_attrs = match types.is_empty() {
+        true => vec![impl_no_attrs],
+        false => impls::impls_for_types(&impl_no_attrs, &types).unwrap_or_default(),
+    };
+
+    impls_no_attrs
+        .iter()
+        .flat_map(|impl_no_attrs| {
+            let tokens = quote! { #impl_no_attrs };
+            let fallback_impl = fallback.as_ref().map(|fallback| {
+                let fallback_impl = impls::fallback_impl(impl_no_attrs, fallback);
+                ImplBody::try_from((quote! { #fallback_impl }, None))
+                    .expect("Failed to parse TokenStream into ImplBody")
+            });
 
             conditions
-                .into_iter()
-                .map(|condition| {
-                    ImplBody::try_from((tokens.clone(), condition))
+                .iter()
+                .map(|conjunction| ImplBody {
+                    source_condition: condition.clone(),
+                    priority,
+                    fallback: fallback.is_some(),
+                    ..ImplBody::try_from((tokens.clone(), conjunction.clone()))
                         .expect("Failed to parse TokenStream into ImplBody")
                 })
+                .chain(fallback_impl)
                 .collect::<Vec<_>>()
         })
         .collect()
 }
 
-/// get WhenCondition from impl attributes
-fn get_condition(attrs: &[Attribute], when_aliases: &HashSet<String>) -> Option<WhenCondition> {
+/// get the types traits registered with `spec_type_traits!` from items
+fn get_type_traits(items: &[Item]) -> Vec<TypeTraitsBody> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Macro(macro_item) => Some(&macro_item.mac),
+            _ => None,
+        })
+        .filter(|mac| {
+            mac.path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "spec_type_traits")
+        })
+        .flat_map(|mac| {
+            parse_type_traits(mac.tokens.clone())
+                .expect("Failed to parse TokenStream into TypeTraitsBody")
+        })
+        .collect()
+}
+
+/// get the type aliases with generics from items
+fn get_type_aliases(items: &[Item]) -> Vec<TypeAliasBody> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Type(type_item) if !type_item.generics.params.is_empty() => Some(type_item),
+            _ => None,
+        })
+        .map(|type_| {
+            TypeAliasBody::try_from(quote! { #type_ })
+                .expect("Failed to parse TokenStream into TypeAliasBody")
+        })
+        .collect()
+}
+
+/// get the condition of the `when` attribute, its priority, its fallback and the types of `for = {...}`
+fn get_condition(
+    attrs: &[Attribute],
+    when_aliases: &HashSet<String>,
+) -> (Option<WhenCondition>, i32, Option<Fallback>, Vec<String>) {
     attrs
         .iter()
         .find(|attr| is_when_macro(attr.path(), when_aliases))
         .and_then(|attr| match attr.clone().meta {
             Meta::List(meta_list) => {
                 let params = meta_list.tokens;
-                let tokens = quote! { #params };
-                WhenCondition::try_from(tokens).ok()
+                let (tokens, types) = conditions::split_for(quote! { #params }).ok()?;
+                // `requires` is not used to select the impl
+                let (tokens, _) = conditions::split_requires(tokens).ok()?;
+                let (tokens, fallback) = conditions::split_fallback(tokens).ok()?;
+                let (tokens, priority) = conditions::split_priority(tokens).ok()?;
+                Some((
+                    conditions::parse_condition(tokens).ok().flatten(),
+                    priority,
+                    fallback,
+                    types,
+                ))
             }
             _ => None,
         })
+        .unwrap_or((None, 0, None, vec![]))
 }
 
 #[cfg(test)]
@@ -107,7 +338,7 @@ mod tests {
     use tempfile::tempdir;
 
     fn make_file(file_path: &Path, content: &str) {
-        fs::write(&file_path, content).expect("write file");
+        fs::write(file_path, content).expect("write file");
     }
 
     #[test]
@@ -123,7 +354,7 @@ mod tests {
 
         make_file(&file_path, content);
 
-        let crate_cache = parse(&file_path);
+        let crate_cache = parse_all(root, &[file_path]);
 
         assert_eq!(crate_cache.traits.len(), 1);
         assert_eq!(crate_cache.impls.len(), 1);
@@ -140,11 +371,147 @@ mod tests {
         make_file(&file1_path, "trait Foo { fn foo(&self); }");
         make_file(&file2_path, "trait Bar { fn bar(&self); }");
 
-        let crate_cache = parse_all(&[file1_path, file2_path]);
+        let crate_cache = parse_all(dir.path(), &[file1_path, file2_path]);
+
+        assert_eq!(crate_cache.traits.len(), 2);
+        assert!(crate_cache.traits.iter().any(|t| t.name == "Foo"));
+        assert!(crate_cache.traits.iter().any(|t| t.name == "Bar"));
+    }
+
+    #[test]
+    fn parse_nested_modules() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("m.rs");
+
+        let content = "
+            use spec_trait_macro::when;
+            trait Foo { fn foo(&self); }
+            mod a {
+                struct MyStruct;
+                impl super::Foo for MyStruct { fn foo(&self) {} }
+                impl super::Foo for super::Shared { fn foo(&self) {} }
+                mod b {
+                    use spec_trait_macro::when;
+                    struct MyStruct<T>(T);
+                    #[when(T = i32)]
+                    impl<T> super::super::Foo for MyStruct<T> { fn foo(&self) {} }
+                    impl super::super::Foo for Vec<u8> { fn foo(&self) {} }
+                }
+            }
+            mod c {
+                trait Bar { fn bar(&self); }
+                struct MyStruct;
+                impl Bar for MyStruct { fn bar(&self) {} }
+            }
+            struct Shared;
+            fn main() {
+                struct Local;
+                impl Foo for Local { fn foo(&self) {} }
+            }
+        ";
+
+        make_file(&file_path, content);
+
+        let crate_cache = parse_all(dir.path(), &[file_path]);
 
         assert_eq!(crate_cache.traits.len(), 2);
         assert!(crate_cache.traits.iter().any(|t| t.name == "Foo"));
         assert!(crate_cache.traits.iter().any(|t| t.name == "Bar"));
+
+        let impls = crate_cache
+            .impls
+            .iter()
+            .map(|impl_| (impl_.full_type_name(), impl_.condition.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            impls,
+            vec![
+                ("m::Local".to_string(), false),
+                ("m::a::MyStruct".to_string(), false),
+                ("m::Shared".to_string(), false),
+                ("m::a::b::MyStruct < T >".to_string(), true),
+                ("Vec < u8 >".to_string(), false),
+                ("m::c::MyStruct".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_cfg_gated_items() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("gated.rs");
+
+        let content = "
+            use spec_trait_macro::when;
+            trait Foo { fn foo(&self); }
+            #[cfg(feature = \"not-enabled\")]
+            #[when(T = i32)]
+            impl<T> Foo for T { fn foo(&self) {} }
+            #[cfg(not(feature = \"not-enabled\"))]
+            #[when(T = u8)]
+            impl<T> Foo for T { fn foo(&self) {} }
+            #[cfg(feature = \"not-enabled\")]
+            mod gated {
+                impl super::Foo for MyStruct { fn foo(&self) {} }
+            }
+        ";
+
+        make_file(&file_path, content);
+
+        let crate_cache = parse_all(dir.path(), &[file_path]);
+
+        assert_eq!(crate_cache.impls.len(), 1);
+        assert_eq!(
+            crate_cache.impls[0].condition,
+            Some(WhenCondition::Type("T".to_string(), "u8".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_type_aliases_across_files() {
+        let dir = tempdir().unwrap();
+        let file1_path = dir.path().join("file1.rs");
+        let file2_path = dir.path().join("file2.rs");
+
+        make_file(
+            &file1_path,
+            "
+            use spec_trait_macro::when;
+            #[when(T = MyResult<E>)]
+            impl<T, E> Foo<T> for MyStruct { fn foo(&self, x: T) {} }
+        ",
+        );
+        make_file(
+            &file2_path,
+            "
+            type MyResult<E> = Result<String, E>;
+            type NotGeneric = u8;
+        ",
+        );
+
+        let crate_cache = parse_all(dir.path(), &[file1_path, file2_path]);
+
+        assert_eq!(crate_cache.type_aliases.len(), 1);
+        assert_eq!(crate_cache.type_aliases[0].name, "MyResult");
+        assert_eq!(crate_cache.impls.len(), 1);
+        assert_eq!(
+            crate_cache.impls[0].condition,
+            Some(WhenCondition::Type(
+                "T".to_string(),
+                "Result < String , E >".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_get_file_module() {
+        let src = Path::new("src");
+
+        assert_eq!(get_file_module(src, Path::new("src/lib.rs")), "");
+        assert_eq!(get_file_module(src, Path::new("src/main.rs")), "");
+        assert_eq!(get_file_module(src, Path::new("src/a.rs")), "a");
+        assert_eq!(get_file_module(src, Path::new("src/a/mod.rs")), "a");
+        assert_eq!(get_file_module(src, Path::new("src/a/b.rs")), "a::b");
     }
 
     #[test]
@@ -154,13 +521,18 @@ mod tests {
             syn::parse_str::<Item>("trait Foo { fn foo(&self); }").unwrap(),
             syn::parse_str::<Item>("#[test] trait Bar { fn bar(&self); }").unwrap(),
             syn::parse_str::<Item>("impl Foo for MyStruct { fn foo(&self) {} }").unwrap(),
+            syn::parse_str::<Item>(
+                "spec_trait! { #[allow(dead_code)] trait Baz<T> { fn baz(&self, x: T); } }",
+            )
+            .unwrap(),
         ];
 
         let traits = get_traits(&items);
 
-        assert_eq!(traits.len(), 2);
+        assert_eq!(traits.len(), 3);
         assert!(traits.iter().any(|t| t.name == "Foo"));
         assert!(traits.iter().any(|t| t.name == "Bar"));
+        assert!(traits.iter().any(|t| t.name == "Baz"));
     }
 
     #[test]
@@ -172,13 +544,33 @@ mod tests {
             syn::parse_str::<Item>("#[test] impl Bar for MyStruct { fn bar(&self) {} }").unwrap(),
         ];
 
-        let impls = get_impls(&items);
+        let impls = get_impls(&items, &[]);
 
         assert_eq!(impls.len(), 2);
         assert!(impls.iter().any(|t| t.trait_name == "Foo"));
         assert!(impls.iter().any(|t| t.trait_name == "Bar"));
     }
 
+    #[test]
+    fn test_get_type_traits() {
+        let items = vec![
+            syn::parse_str::<Item>("struct MyStruct;").unwrap(),
+            syn::parse_str::<Item>("spec_type_traits! { u8: Copy + Clone; MyStruct: Display; }")
+                .unwrap(),
+            syn::parse_str::<Item>("spec_trait_macro::spec_type_traits! { i32: Copy }").unwrap(),
+            syn::parse_str::<Item>("println! { \"not registered\" }").unwrap(),
+        ];
+
+        let type_traits = get_type_traits(&items);
+
+        assert_eq!(type_traits.len(), 3);
+        assert_eq!(type_traits[0].type_name, "u8");
+        assert_eq!(type_traits[0].traits, vec!["Copy", "Clone"]);
+        assert_eq!(type_traits[1].type_name, "MyStruct");
+        assert_eq!(type_traits[1].traits, vec!["Display"]);
+        assert_eq!(type_traits[2].type_name, "i32");
+    }
+
     #[test]
     fn test_get_condition() {
         let impl_ = syn::parse_str::<ItemImpl>(
@@ -191,7 +583,7 @@ mod tests {
         let mut aliases = HashSet::new();
         aliases.insert("when".to_string());
 
-        let condition = get_condition(&attributes, &aliases);
+        let (condition, priority, _, _) = get_condition(&attributes, &aliases);
 
         assert!(condition.is_some());
         let condition = condition.unwrap();
@@ -199,5 +591,92 @@ mod tests {
             condition,
             WhenCondition::Type("T".to_string(), "i32".to_string())
         );
+        assert_eq!(priority, 0);
+    }
+
+    #[test]
+    fn test_get_condition_with_priority() {
+        let impl_ = syn::parse_str::<ItemImpl>(
+            "#[when(T = i32, priority = 2)] impl Foo<T> for MyStruct { fn foo(&self, x: T) {} }",
+        )
+        .unwrap();
+
+        let (_, attributes) = impls::break_attr(&impl_);
+
+        let mut aliases = HashSet::new();
+        aliases.insert("when".to_string());
+
+        let (condition, priority, _, _) = get_condition(&attributes, &aliases);
+
+        assert_eq!(
+            condition,
+            Some(WhenCondition::Type("T".to_string(), "i32".to_string()))
+        );
+        assert_eq!(priority, 2);
+    }
+
+    #[test]
+    fn test_get_impls_with_fallback() {
+        let items = vec![
+            syn::parse_str::<Item>("use spec_trait_macro::when;").unwrap(),
+            syn::parse_str::<Item>(
+                "#[when(T = i32, fallback = default_foo)] impl<T> Foo<T> for MyStruct { fn foo(&self, x: T) {} }",
+            )
+            .unwrap(),
+        ];
+
+        let impls = get_impls(&items, &[]);
+
+        assert_eq!(impls.len(), 2);
+        assert_eq!(
+            impls[0].condition,
+            Some(WhenCondition::Type("T".to_string(), "i32".to_string()))
+        );
+        assert_eq!(impls[1].condition, None);
+        assert_eq!(
+            impls[1].items[0].replace(" ", ""),
+            "fnfoo(&self,__arg_1:T){default_foo(self,__arg_1)}"
+        );
+    }
+
+    #[test]
+    fn test_get_impls_for_types() {
+        let items = vec![
+            syn::parse_str::<Item>("use spec_trait_macro::when;").unwrap(),
+            syn::parse_str::<Item>(
+                "#[when(any(T = i32, T = u8), for = {TypeA, TypeB}, fallback)] impl<T> Foo<T> for _ { fn foo(&self, x: T) {} }",
+            )
+            .unwrap(),
+        ];
+
+        let impls = get_impls(&items, &[]);
+
+        // two conjunctions and the fallback impl for each type
+        assert_eq!(impls.len(), 6);
+        assert_eq!(
+            impls
+                .iter()
+                .map(|i| i.type_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["TypeA", "TypeA", "TypeA", "TypeB", "TypeB", "TypeB"]
+        );
+        assert_eq!(impls[0].condition, impls[3].condition);
+        assert_eq!(impls[2].condition, None);
+        assert_eq!(impls[5].condition, None);
+    }
+
+    #[test]
+    fn test_get_empty_condition() {
+        let items = vec![
+            syn::parse_str::<Item>("use spec_trait_macro::when;").unwrap(),
+            syn::parse_str::<Item>("#[when()] impl Foo<T> for MyStruct { fn foo(&self, x: T) {} }")
+                .unwrap(),
+        ];
+
+        let impls = get_impls(&items, &[]);
+
+        assert_eq!(impls.len(), 1);
+        assert_eq!(impls[0].condition, None);
+        assert_eq!(impls[0].trait_name, "Foo");
     }
 }
@@ -1,21 +1,43 @@
 mod aliases;
+mod cfg;
+mod check;
 mod crates;
 mod files;
 
+pub use check::{Diagnostic, Location, Severity, check};
+pub use spec_trait_utils::cache::{Format, set_format};
+
 use spec_trait_utils::cache;
-use spec_trait_utils::env::get_cache_path;
+use spec_trait_utils::env::{ENV_CACHE_PATH, ENV_CHECK_OVERLAPS, get_cache_path};
+use spec_trait_utils::overlaps::find_overlaps;
+use std::env;
 use std::path::Path;
 
 /// It is assumed to be used in `build.rs` or similar context.
+///
+/// The items disabled by a `cfg` attribute for the build (e.g. `#[cfg(feature = "x")]` without the feature) are skipped.
+///
+/// Set the `SPEC_TRAIT_CHECK_OVERLAPS` environment variable to print as warnings the impls whose conditions
+/// can be equally specific for the same types, i.e. the ones that can make a `spec!` call ambiguous.
+///
+/// The cache is written as JSON, call `set_format` before to write it in another format (e.g. `Format::Bincode`).
+/// It is written to `/tmp/spec_trait_macro_cache.json`, or to the path in the `SPEC_TRAIT_CACHE_PATH` environment variable.
 pub fn handle_order() {
     println!("cargo:rerun-if-changed={}", get_cache_path().display());
     println!("cargo:rerun-if-changed=.");
+    println!("cargo:rerun-if-env-changed={}", ENV_CHECK_OVERLAPS);
+    println!("cargo:rerun-if-env-changed={}", ENV_CACHE_PATH);
 
     cache::reset();
 
     crates::get_crates(Path::new("."))
         .into_iter()
         .for_each(|crate_| {
+            if env::var_os(ENV_CHECK_OVERLAPS).is_some() {
+                for overlap in find_overlaps(&crate_.content.impls) {
+                    println!("cargo:warning={}", overlap);
+                }
+            }
             cache::add_crate(&crate_.name, crate_.content);
         });
 }
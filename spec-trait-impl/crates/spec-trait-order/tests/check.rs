@@ -0,0 +1,59 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run_check(workspace: &str) -> Output {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("workspaces")
+        .join(workspace);
+
+    Command::new(env!("CARGO_BIN_EXE_cargo-spec-trait"))
+        .args(["spec-trait", "check"])
+        .arg(dir)
+        .output()
+        .expect("failed to run cargo-spec-trait")
+}
+
+#[test]
+fn workspace_with_errors() {
+    let output = run_check("issues");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("error: `U` is not a generic of the impl\n  --> "));
+    assert!(stderr.contains("src/lib.rs:16:1\n"));
+    assert!(stderr.contains("error: invalid condition `T ==`"));
+    assert!(stderr.contains("src/lib.rs:22:1\n"));
+    assert!(
+        stderr.contains(
+            "warning: `'a` is not used by the impl, so the condition on it has no effect"
+        )
+    );
+    assert!(stderr.contains("src/lib.rs:28:1\n"));
+    assert!(stderr.contains("2 error(s)"));
+}
+
+#[test]
+fn workspace_with_warnings() {
+    let output = run_check("warnings");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr);
+    assert!(stderr.contains(
+        "warning: impls of `Bar` for `overlaps::Wrapper<T>` with `#[when(T: Clone)]` and `#[when(T: Copy)]`"
+    ));
+    assert!(stderr.contains("src/overlaps.rs:15:1)"));
+    assert!(stderr.contains("src/overlaps.rs:10:1\n"));
+    assert!(stderr.contains("0 error(s), 1 warning(s)"));
+}
+
+#[test]
+fn usage() {
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-spec-trait"))
+        .args(["spec-trait", "build"])
+        .output()
+        .expect("failed to run cargo-spec-trait");
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("usage:"));
+}
@@ -0,0 +1,31 @@
+use spec_trait_macro::when;
+
+trait Foo {
+    fn foo(&self);
+}
+
+struct Wrapper<T>(T);
+struct MyType;
+
+#[when(T: Clone)]
+impl<T> Foo for Wrapper<T> {
+    fn foo(&self) {}
+}
+
+// `U` is not a generic of the impl
+#[when(U = u8)]
+impl<T> Foo for Wrapper<T> {
+    fn foo(&self) {}
+}
+
+// the condition cannot be parsed
+#[when(T ==)]
+impl<T> Foo for Wrapper<T> {
+    fn foo(&self) {}
+}
+
+// `'a` is not used, so the condition has no effect
+#[when('a = 'static)]
+impl<'a> Foo for MyType {
+    fn foo(&self) {}
+}
@@ -0,0 +1,18 @@
+use spec_trait_macro::when;
+
+trait Bar {
+    fn bar(&self);
+}
+
+struct Wrapper<T>(T);
+
+// both impls are selected for a type that is `Clone` and `Copy`
+#[when(T: Clone)]
+impl<T> Bar for Wrapper<T> {
+    fn bar(&self) {}
+}
+
+#[when(T: Copy)]
+impl<T> Bar for Wrapper<T> {
+    fn bar(&self) {}
+}
@@ -1,68 +1,286 @@
+use crate::conditions::WhenCondition;
+use crate::conversions::{str_to_type_name, to_string};
 use crate::env::get_cache_path;
 use crate::impls::ImplBody;
 use crate::traits::TraitBody;
+use crate::type_aliases::TypeAliasBody;
+use crate::type_traits::TypeTraitsBody;
 use crate::types::{Aliases, type_assignable};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::fs;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::process;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use syn::Type;
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct CrateCache {
     pub traits: Vec<TraitBody>,
     pub impls: Vec<ImplBody>,
+    #[serde(default)]
+    pub type_traits: Vec<TypeTraitsBody>,
+    #[serde(default)]
+    pub type_aliases: Vec<TypeAliasBody>,
 }
 
-pub type Cache = HashMap<String, CrateCache>;
+/// the caches of the crates, ordered by name so that the file written is the same for the same content
+pub type Cache = BTreeMap<String, CrateCache>;
 
-fn read_top_level_cache() -> Cache {
+/// the format the cache is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// human-readable, the default
+    #[default]
+    Json,
+    /// compact, the file starts with `BINCODE_HEADER`
+    Bincode,
+}
+
+const BINCODE_HEADER: &[u8] = b"spec-trait-bincode\n";
+
+static FORMAT: Mutex<Option<Format>> = Mutex::new(None);
+
+/**
+    set the format the cache is written in by this process (e.g. in `build.rs` before the build step).
+    Without it the cache is written in the format of the existing file, JSON if there is none.
+    The reader detects the format from the file, so the macros do not need to set it.
+*/
+pub fn set_format(format: Format) {
+    *FORMAT.lock().unwrap() = Some(format);
+}
+
+fn serialize(cache: &Cache, format: Format) -> Vec<u8> {
+    match format {
+        Format::Json => serde_json::to_vec(cache).expect("Failed to serialize cache"),
+        Format::Bincode => {
+            let serialized = bincode::serialize(cache).expect("Failed to serialize cache");
+            [BINCODE_HEADER, &serialized].concat()
+        }
+    }
+}
+
+/// the format of the cache written in `bytes`
+fn get_format(bytes: &[u8]) -> Format {
+    if bytes.starts_with(BINCODE_HEADER) {
+        Format::Bincode
+    } else {
+        Format::Json
+    }
+}
+
+/// the cache written in `bytes`, an error if it is not a valid cache (e.g. a file written by another program)
+fn deserialize(bytes: &[u8]) -> Result<Cache, String> {
+    match bytes.strip_prefix(BINCODE_HEADER) {
+        Some(bytes) => bincode::deserialize(bytes).map_err(|err| err.to_string()),
+        None => serde_json::from_slice(bytes).map_err(|err| err.to_string()),
+    }
+}
+
+/**
+    run `f` holding a lock on the cache, exclusive to write it and shared to read it,
+    so that the macros and the build steps of crates compiled in parallel do not overwrite each other's changes.
+    The lock is released when the lock file is closed, at the end of `f`. The calls must not be nested.
+*/
+fn with_lock<R>(exclusive: bool, f: impl FnOnce() -> R) -> R {
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(get_cache_path().with_extension("lock"))
+        .expect("Failed to open the cache lock");
+    let locked = if exclusive {
+        lock_file.lock()
+    } else {
+        lock_file.lock_shared()
+    };
+    locked.expect("Failed to lock the cache");
+
+    f()
+}
+
+/// the bytes of the cache file, empty if it does not exist
+fn read_file() -> Vec<u8> {
     let path = get_cache_path();
-    let file_cache = fs::read(&path).unwrap_or_default();
-    serde_json::from_slice::<Cache>(&file_cache).unwrap_or_default()
+    match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+        Err(err) => panic!("Failed to read the cache `{}`: {}", path.display(), err),
+    }
+}
+
+/// the cache in the file, it fails instead of discarding the content of a cache that cannot be read
+fn read_top_level_cache(bytes: &[u8]) -> Cache {
+    if bytes.is_empty() {
+        return Cache::new();
+    }
+
+    deserialize(bytes).unwrap_or_else(|err| {
+        panic!(
+            "The cache `{}` is invalid ({}), delete it and rebuild",
+            get_cache_path().display(),
+            err
+        )
+    })
 }
 
-fn write_top_level_cache(cache: &Cache) {
+/**
+    write the cache in the format set with `set_format`, otherwise in the format of the file it replaces (`previous`).
+    It is written to a temporary file first and then renamed, so that the readers never see it half written.
+*/
+fn write_top_level_cache(cache: &Cache, previous: &[u8]) {
     let path = get_cache_path();
-    let serialized = serde_json::to_string(cache).expect("Failed to serialize cache");
-    fs::write(&path, serialized).expect("Failed to write into cache");
+    let format = FORMAT
+        .lock()
+        .unwrap()
+        .unwrap_or_else(|| get_format(previous));
+
+    let temp_path = path.with_extension(format!("{}.tmp", process::id()));
+    fs::write(&temp_path, serialize(cache, format)).expect("Failed to write into cache");
+    fs::rename(&temp_path, &path).expect("Failed to write into cache");
+}
+
+/// change the cache of the crate with `f`, that returns whether it changed it, so that an unchanged cache is not written
+fn update_cache(crate_name: Option<String>, f: impl FnOnce(&mut CrateCache) -> bool) {
+    let crate_name = crate_name.unwrap_or_else(|| std::env::var("CARGO_PKG_NAME").unwrap());
+
+    with_lock(true, || {
+        let bytes = read_file();
+        let mut top_level_cache = read_top_level_cache(&bytes);
+        let cache = top_level_cache.entry(crate_name).or_default();
+
+        if f(cache) {
+            write_top_level_cache(&top_level_cache, &bytes);
+        }
+    });
+}
+
+/// the time of the last write of the cache, `None` if it does not exist
+pub fn last_modified() -> Option<SystemTime> {
+    fs::metadata(get_cache_path())
+        .and_then(|metadata| metadata.modified())
+        .ok()
 }
 
 pub fn read_cache(crate_name: Option<String>) -> CrateCache {
     let crate_name = crate_name.unwrap_or_else(|| std::env::var("CARGO_PKG_NAME").unwrap());
-    let cache = read_top_level_cache();
+    let cache = with_lock(false, || read_top_level_cache(&read_file()));
     cache.get(&crate_name).cloned().unwrap_or_default()
 }
 
 pub fn write_cache(cache: &CrateCache, crate_name: Option<String>) {
-    let crate_name = crate_name.unwrap_or_else(|| std::env::var("CARGO_PKG_NAME").unwrap());
-
-    let mut top_level_cache = read_top_level_cache();
-    top_level_cache.insert(crate_name, cache.clone());
-
-    write_top_level_cache(&top_level_cache);
+    update_cache(crate_name, |crate_cache| {
+        *crate_cache = cache.clone();
+        true
+    });
 }
 
+/// empty the cache of all the crates, also when it cannot be read
 pub fn reset() {
-    let empty_cache = Cache::new();
-    write_top_level_cache(&empty_cache);
+    with_lock(true, || write_top_level_cache(&Cache::new(), &read_file()));
 }
 
 pub fn add_crate(crate_name: &str, crate_cache: CrateCache) {
-    let mut cache = read_cache(Some(crate_name.to_string()));
-    cache.traits.extend(crate_cache.traits);
-    cache.impls.extend(crate_cache.impls);
-    write_cache(&cache, Some(crate_name.to_string()));
+    update_cache(Some(crate_name.to_string()), |cache| {
+        cache.traits.extend(crate_cache.traits);
+        cache.impls.extend(crate_cache.impls);
+        cache.type_traits.extend(crate_cache.type_traits);
+        cache.type_aliases.extend(crate_cache.type_aliases);
+        true
+    });
 }
 
 pub fn add_trait(tr: TraitBody) {
-    let mut cache = read_cache(None);
-    cache.traits.push(tr);
-    write_cache(&cache, None);
+    update_cache(None, |cache| {
+        cache.traits.push(tr);
+        true
+    });
 }
 
 pub fn add_impl(imp: ImplBody) {
-    let mut cache = read_cache(None);
-    cache.impls.push(imp);
-    write_cache(&cache, None);
+    update_cache(None, |cache| {
+        cache.impls.push(imp);
+        true
+    });
+}
+
+/**
+    add the trait declared with `spec_trait!` to the cache of the crate, replacing the one with the same name.
+    The cache is left untouched if it already contains the trait (e.g. found by the build step),
+    so that expanding the macro again does not change it.
+*/
+pub fn register_trait(tr: TraitBody) {
+    update_cache(None, |cache| {
+        if cache.traits.iter().any(|other| same_trait(other, &tr)) {
+            return false;
+        }
+
+        cache.traits.retain(|other| other.name != tr.name);
+        cache.traits.push(tr);
+        true
+    });
+}
+
+/**
+    add the impl expanded by `when` to the cache of the crate, unless it already contains it
+    (e.g. found by the build step, in any module).
+*/
+pub fn register_impl(imp: ImplBody) {
+    update_cache(None, |cache| {
+        if cache.impls.iter().any(|other| same_impl(other, &imp)) {
+            return false;
+        }
+
+        cache.impls.push(imp);
+        true
+    });
+}
+
+// the build step and the macros print the same tokens with different spacing
+fn same_tokens(a: &str, b: &str) -> bool {
+    a.split_whitespace().collect::<String>() == b.split_whitespace().collect::<String>()
+}
+
+fn same_items(a: &[String], b: &[String]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_tokens(a, b))
+}
+
+fn same_condition(a: &Option<WhenCondition>, b: &Option<WhenCondition>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_tokens(&a.to_string(), &b.to_string()),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_trait(a: &TraitBody, b: &TraitBody) -> bool {
+    a.name == b.name && same_tokens(&a.generics, &b.generics) && same_items(&a.items, &b.items)
+}
+
+fn same_impl(a: &ImplBody, b: &ImplBody) -> bool {
+    a.trait_name == b.trait_name
+        && same_condition(&a.condition, &b.condition)
+        && same_condition(&a.source_condition, &b.source_condition)
+        && a.priority == b.priority
+        && same_tokens(&a.impl_generics, &b.impl_generics)
+        && same_tokens(&a.trait_generics, &b.trait_generics)
+        && same_tokens(&a.type_name, &b.type_name)
+        && same_items(&a.items, &b.items)
+}
+
+/**
+    get an impl with `fallback` of the same trait for the same type as `imp`, generated from another `when`,
+    i.e. whose default impl would conflict with the one of `imp`
+*/
+pub fn get_other_fallback(imp: &ImplBody) -> Option<ImplBody> {
+    read_cache(None).impls.into_iter().find(|other| {
+        other.fallback
+            && other.trait_name == imp.trait_name
+            && same_tokens(&other.full_type_name(), &imp.full_type_name())
+            && !(same_condition(&other.source_condition, &imp.source_condition)
+                && same_items(&other.items, &imp.items))
+    })
 }
 
 pub fn get_trait_by_name(trait_name: &str) -> Option<TraitBody> {
@@ -70,6 +288,16 @@ pub fn get_trait_by_name(trait_name: &str) -> Option<TraitBody> {
     cache.traits.into_iter().find(|tr| tr.name == trait_name)
 }
 
+pub fn get_type_traits() -> Vec<TypeTraitsBody> {
+    let cache = read_cache(None);
+    cache.type_traits
+}
+
+pub fn get_type_aliases() -> Vec<TypeAliasBody> {
+    let cache = read_cache(None);
+    cache.type_aliases
+}
+
 pub fn get_traits_by_fn(fn_name: &str, args_len: usize) -> Vec<TraitBody> {
     let cache = read_cache(None);
     cache
@@ -79,19 +307,188 @@ pub fn get_traits_by_fn(fn_name: &str, args_len: usize) -> Vec<TraitBody> {
         .collect()
 }
 
+/// the numbers of arguments of the functions with the given name in all the traits, sorted and without duplicates
+pub fn get_fn_arities(fn_name: &str) -> Vec<usize> {
+    let cache = read_cache(None);
+    let mut arities = cache
+        .traits
+        .iter()
+        .flat_map(|tr| tr.fn_arities(fn_name))
+        .collect::<Vec<_>>();
+    arities.sort();
+    arities.dedup();
+    arities
+}
+
+/// the impls of the trait with the given name, for any type
+pub fn list_impls_for_trait(trait_name: &str) -> Vec<ImplBody> {
+    let cache = read_cache(None);
+    cache
+        .impls
+        .into_iter()
+        .filter(|imp| imp.trait_name == trait_name)
+        .collect()
+}
+
+/**
+    get the impls of the traits for the type, matched by its path in the crate (e.g. `a::Foo` or `crate::a::Foo`).
+    A type declared in a module can also be written without path (e.g. `Foo`), as long as the impls found this way
+    are all for the same type: the module of the `spec!` call is not known, so `a::Foo` and `b::Foo` are ambiguous.
+    The impls matched through the path of their type have it as `type_name`, as written in the call.
+*/
 pub fn get_impls_by_type_and_traits(
     type_name: &str,
     traits: &[TraitBody],
     aliases: &Aliases,
-) -> Vec<ImplBody> {
+) -> Result<Vec<ImplBody>, String> {
     let cache = read_cache(None);
     let traits_names = traits.iter().map(|tr| &tr.name).collect::<HashSet<_>>();
-    cache
-        .impls
-        .into_iter()
-        .filter(|imp| {
-            traits_names.contains(&imp.trait_name)
-                && type_assignable(type_name, &imp.type_name, &imp.impl_generics, aliases)
-        })
-        .collect()
+    let type_name = strip_crate(type_name);
+
+    let mut impls = vec![];
+    let mut named_type = false;
+    let mut paths_by_name = BTreeSet::new();
+
+    for imp in cache.impls {
+        if !traits_names.contains(&imp.trait_name) {
+            continue;
+        }
+
+        let full_type_name = imp.full_type_name();
+        let in_crate = full_type_name != imp.type_name;
+
+        if type_assignable(&type_name, &full_type_name, &imp.impl_generics, aliases) {
+            named_type |= in_crate || imp.local_type;
+            let imp = ImplBody {
+                type_name: full_type_name,
+                local_type: false,
+                ..imp
+            };
+            impls.push((imp, false));
+        } else if in_crate
+            && type_assignable(&type_name, &imp.type_name, &imp.impl_generics, aliases)
+        {
+            paths_by_name.insert(get_path_without_generics(&full_type_name));
+            impls.push((imp, true));
+        }
+    }
+
+    // written with its path, the impls for the types with the same name in other modules are for other types
+    if named_type {
+        impls.retain(|(_, by_name)| !by_name);
+    } else if paths_by_name.len() > 1 {
+        return Err(format!(
+            "Type `{}` is ambiguous, write its path in the crate: {}",
+            type_name.replace(" ", ""),
+            paths_by_name
+                .iter()
+                .map(|path| format!("`{}`", path))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(impls.into_iter().map(|(imp, _)| imp).collect())
+}
+
+/// remove the `crate::` at the start of the path of a type, the paths in the cache are relative to the crate root
+fn strip_crate(type_name: &str) -> String {
+    match str_to_type_name(type_name) {
+        Type::Path(mut type_path)
+            if type_path.qself.is_none()
+                && type_path.path.segments.len() > 1
+                && type_path.path.segments[0].ident == "crate" =>
+        {
+            type_path.path.segments = type_path.path.segments.into_iter().skip(1).collect();
+            to_string(&type_path)
+        }
+        _ => type_name.to_owned(),
+    }
+}
+
+/// the path of a type without its generics (e.g. `a::Foo` for `a::Foo<T>`)
+fn get_path_without_generics(type_name: &str) -> String {
+    match str_to_type_name(type_name) {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("::"),
+        _ => type_name.replace(" ", ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macro2::TokenStream;
+    use quote::quote;
+
+    #[test]
+    fn round_trip_formats() {
+        let impl_ = ImplBody::try_from((
+            quote! { impl<T> Foo<T> for MyType { fn foo(&self, x: T) {} } },
+            Some(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+        ))
+        .unwrap();
+        let trait_ = TraitBody::try_from(quote! { trait Foo<T> { fn foo(&self, x: T); } }).unwrap();
+        let cache = Cache::from([(
+            "my_crate".to_string(),
+            CrateCache {
+                traits: vec![trait_],
+                impls: vec![impl_],
+                ..Default::default()
+            },
+        )]);
+
+        for format in [Format::Json, Format::Bincode] {
+            let bytes = serialize(&cache, format);
+            let read = deserialize(&bytes).unwrap();
+
+            assert_eq!(get_format(&bytes), format);
+            assert_eq!(format!("{:?}", read), format!("{:?}", cache));
+        }
+
+        // a cache that cannot be read is an error, not an empty cache that would replace it
+        assert!(deserialize(b"{\"my_crate\": {\"traits\"").is_err());
+        assert!(deserialize(&[BINCODE_HEADER, b"invalid"].concat()).is_err());
+        assert!(read_top_level_cache(b"").is_empty());
+    }
+
+    #[test]
+    fn same_impl_with_different_spacing() {
+        let impl_ = |tokens: &str| {
+            let condition = WhenCondition::Type("T".into(), "(i32, _)".into());
+            ImplBody::try_from((tokens.parse().unwrap(), Some(condition))).unwrap()
+        };
+
+        let a = impl_("impl<T> Foo<T> for MyType { fn foo(&self, x: T) {} }");
+        let b = ImplBody {
+            module: "a::b".into(),
+            ..impl_("impl < T > Foo < T > for MyType {\n fn foo (& self , x : T) { }\n }")
+        };
+        assert!(same_impl(&a, &b));
+
+        let c = impl_("impl<T> Foo<T> for MyType { fn foo(&self, y: T) {} }");
+        assert!(!same_impl(&a, &c));
+
+        let d = ImplBody {
+            priority: 1,
+            ..a.clone()
+        };
+        assert!(!same_impl(&a, &d));
+    }
+
+    #[test]
+    fn same_trait_with_different_spacing() {
+        let a = TraitBody::try_from(quote! { trait Foo<T> { fn foo(&self, x: T); } }).unwrap();
+        let tokens = "trait Foo < T > {\n fn foo (& self , x : T) ;\n }".parse::<TokenStream>();
+        let b = TraitBody::try_from(tokens.unwrap()).unwrap();
+        assert!(same_trait(&a, &b));
+
+        let c = TraitBody::try_from(quote! { trait Foo<T> { fn bar(&self, x: T); } }).unwrap();
+        assert!(!same_trait(&a, &c));
+    }
 }
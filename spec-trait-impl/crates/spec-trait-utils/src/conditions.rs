@@ -1,11 +1,23 @@
-use crate::parsing::{ParseTypeOrLifetimeOrTrait, parse_type_or_lifetime_or_trait};
-use proc_macro2::TokenStream;
+use crate::conversions::{str_to_type_name, to_string};
+use crate::parsing::{
+    ParseTypeOrLifetimeOrTrait, parse_arg_value, parse_len, parse_type_or_lifetime_or_trait,
+};
+use crate::types::{
+    Aliases, normalize_const_value, replace_type, type_assignable, type_contains, type_implies,
+};
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 use std::hash::{Hash, Hasher};
-use syn::parse::{Parse, ParseStream};
-use syn::{Error, Ident, Token, parenthesized};
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{
+    Error, ExprBlock, Ident, Lifetime, Lit, LitInt, Token, WherePredicate, parenthesized, token,
+};
+
+/// the key of the conditions on the type the impl is for (e.g. `Self = MyType<i32>`)
+pub const SELF: &str = "Self";
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq)]
 pub enum WhenCondition {
@@ -14,6 +26,13 @@ pub enum WhenCondition {
         String, /* type (without lifetime) */
     ),
     Trait(String /* generic */, Vec<String> /* traits */),
+    Lifetime(
+        String, /* lifetime generic */
+        String, /* lifetime */
+    ),
+    Const(String /* const generic */, String /* value */),
+    Len(usize /* slice element count */),
+    Arg(usize /* argument position */, String /* value */),
     All(Vec<WhenCondition>),
     Any(Vec<WhenCondition>),
     Not(Box<WhenCondition>),
@@ -32,12 +51,16 @@ impl Display for WhenCondition {
                 .join(", ")
         }
         match self {
-            WhenCondition::Type(generic, ty) => write!(f, "{} = {}", generic, ty.replace(" ", "")),
+            WhenCondition::Type(generic, ty) => write!(f, "{} = {}", generic, compact(ty)),
             WhenCondition::Trait(generic, traits) => {
                 let mut sorted_traits = traits.to_vec();
                 sorted_traits.sort();
                 write!(f, "{}: {}", generic, sorted_traits.join(" + "))
             }
+            WhenCondition::Lifetime(generic, lifetime) => write!(f, "{} = {}", generic, lifetime),
+            WhenCondition::Const(generic, value) => write!(f, "{} = {}", generic, value),
+            WhenCondition::Len(len) => write!(f, "len = {}", len),
+            WhenCondition::Arg(position, value) => write!(f, "arg{} = {}", position, value),
             WhenCondition::All(conditions) => write!(f, "all({})", to_string(conditions)),
             WhenCondition::Any(conditions) => write!(f, "any({})", to_string(conditions)),
             WhenCondition::Not(condition) => write!(f, "not({})", condition),
@@ -45,9 +68,25 @@ impl Display for WhenCondition {
     }
 }
 
+/// removes the spaces in a type, except the ones separating two words (e.g. `&'a u8`, `dyn Trait`)
+fn compact(type_: &str) -> String {
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let chars = type_.chars().collect::<Vec<_>>();
+
+    chars
+        .iter()
+        .enumerate()
+        .filter(|(i, c)| {
+            **c != ' '
+                || (*i > 0 && chars.get(i + 1).is_some_and(is_word) && is_word(&chars[i - 1]))
+        })
+        .map(|(_, c)| c)
+        .collect()
+}
+
 impl Hash for WhenCondition {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.to_string().hash(state);
+        self.canonical().to_string().hash(state);
     }
 }
 
@@ -58,8 +97,19 @@ impl PartialEq for WhenCondition {
             (WhenCondition::Trait(g1, tr1), WhenCondition::Trait(g2, tr2)) => {
                 g1 == g2 && tr1.iter().collect::<HashSet<_>>() == tr2.iter().collect::<HashSet<_>>()
             }
-            (WhenCondition::All(c1), WhenCondition::All(c2))
-            | (WhenCondition::Any(c1), WhenCondition::Any(c2)) => {
+            (WhenCondition::Lifetime(g1, l1), WhenCondition::Lifetime(g2, l2))
+            | (WhenCondition::Const(g1, l1), WhenCondition::Const(g2, l2)) => g1 == g2 && l1 == l2,
+            (WhenCondition::Len(l1), WhenCondition::Len(l2)) => l1 == l2,
+            (WhenCondition::Arg(p1, v1), WhenCondition::Arg(p2, v2)) => p1 == p2 && v1 == v2,
+            (WhenCondition::All(_), WhenCondition::All(_)) => {
+                match (self.canonical(), other.canonical()) {
+                    (WhenCondition::All(c1), WhenCondition::All(c2)) => {
+                        c1.iter().collect::<HashSet<_>>() == c2.iter().collect::<HashSet<_>>()
+                    }
+                    _ => false,
+                }
+            }
+            (WhenCondition::Any(c1), WhenCondition::Any(c2)) => {
                 c1.iter().collect::<HashSet<_>>() == c2.iter().collect::<HashSet<_>>()
             }
             (WhenCondition::Not(c1), WhenCondition::Not(c2)) => c1 == c2,
@@ -92,24 +142,501 @@ impl ParseTypeOrLifetimeOrTrait<WhenCondition> for WhenCondition {
     }
 }
 
+impl WhenCondition {
+    /**
+        remove the sub-conditions implied by the others in the same `all`,
+        i.e. a type condition with wildcards subsumed by a more concrete one on the same generic.
+        # Example:
+        `all(T = _, T = i32, T: Clone)` -> `all(T = i32, T: Clone)`
+    */
+    pub fn simplify(self) -> WhenCondition {
+        match self {
+            WhenCondition::All(inner) => {
+                let inner = inner
+                    .into_iter()
+                    .map(WhenCondition::simplify)
+                    .collect::<Vec<_>>();
+
+                let kept = inner
+                    .iter()
+                    .filter(|cond| !inner.iter().any(|other| subsumes(other, cond)))
+                    .cloned()
+                    .collect();
+
+                flatten_and_deduplicate(kept, WhenCondition::All)
+            }
+            WhenCondition::Any(inner) => flatten_and_deduplicate(
+                inner.into_iter().map(WhenCondition::simplify).collect(),
+                WhenCondition::Any,
+            ),
+            WhenCondition::Not(inner) => WhenCondition::Not(Box::new(inner.simplify())),
+            _ => self,
+        }
+    }
+}
+
+impl WhenCondition {
+    /**
+        the condition with the generics introduced by its type conditions renamed in order of appearance,
+        so that the conditions differing only in their names are the same.
+        # Example:
+        `all(T = Vec<U>, U: Debug)` and `all(T = Vec<X>, X: Debug)` -> `all(T = Vec<__G_0__>, __G_0__: Debug)`
+    */
+    fn canonical(&self) -> WhenCondition {
+        match self {
+            WhenCondition::All(inner) => {
+                let inner = inner.iter().map(|c| c.canonical()).collect::<Vec<_>>();
+                let introduced = get_introduced_generics(&inner);
+                if introduced.is_empty() {
+                    return WhenCondition::All(inner);
+                }
+
+                // the order of the conditions must not depend on the names being replaced
+                let masked = introduced
+                    .iter()
+                    .map(|g| (g.clone(), "__G__".to_string()))
+                    .collect::<HashMap<_, _>>();
+                let mut sorted = inner.iter().collect::<Vec<_>>();
+                sorted.sort_by_cached_key(|c| c.rename_generics(&masked).to_string());
+
+                let mut names = HashMap::new();
+                for word in sorted.iter().flat_map(|c| words(&c.to_string())) {
+                    if introduced.contains(&word) && !names.contains_key(&word) {
+                        let name = format!("__G_{}__", names.len());
+                        names.insert(word, name);
+                    }
+                }
+
+                WhenCondition::All(inner.iter().map(|c| c.rename_generics(&names)).collect())
+            }
+            WhenCondition::Any(inner) => {
+                WhenCondition::Any(inner.iter().map(|c| c.canonical()).collect())
+            }
+            WhenCondition::Not(inner) => WhenCondition::Not(Box::new(inner.canonical())),
+            _ => self.clone(),
+        }
+    }
+
+    fn rename_generics(&self, names: &HashMap<String, String>) -> WhenCondition {
+        let rename = |generic: &String| names.get(generic).unwrap_or(generic).clone();
+
+        match self {
+            WhenCondition::Type(generic, type_) => {
+                let mut ty = str_to_type_name(type_);
+                for (prev, new) in names {
+                    replace_type(&mut ty, prev, &str_to_type_name(new));
+                }
+                WhenCondition::Type(rename(generic), to_string(&ty))
+            }
+            WhenCondition::Trait(generic, traits) => {
+                WhenCondition::Trait(rename(generic), traits.clone())
+            }
+            WhenCondition::All(inner) => {
+                WhenCondition::All(inner.iter().map(|c| c.rename_generics(names)).collect())
+            }
+            WhenCondition::Any(inner) => {
+                WhenCondition::Any(inner.iter().map(|c| c.rename_generics(names)).collect())
+            }
+            WhenCondition::Not(inner) => WhenCondition::Not(Box::new(inner.rename_generics(names))),
+            _ => self.clone(),
+        }
+    }
+}
+
+/// the generics constrained in the conditions that are also used in the type of another generic (e.g. `U` in `T = Vec<U>`)
+fn get_introduced_generics(conditions: &[WhenCondition]) -> HashSet<String> {
+    let types = conditions
+        .iter()
+        .filter_map(|c| match c {
+            WhenCondition::Type(generic, type_) => Some((generic, type_)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    conditions
+        .iter()
+        .filter_map(|c| match c {
+            WhenCondition::Type(generic, _) | WhenCondition::Trait(generic, _) => Some(generic),
+            _ => None,
+        })
+        .filter(|generic| {
+            types.iter().any(|(other, type_)| {
+                other != generic
+                    && words(type_).contains(generic)
+                    && type_contains(&str_to_type_name(type_), generic)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+fn words(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// whether `condition` always holds when `other` holds on the same generic
+fn subsumes(other: &WhenCondition, condition: &WhenCondition) -> bool {
+    match (other, condition) {
+        (WhenCondition::Type(g1, t1), WhenCondition::Type(g2, t2)) => {
+            g1 == g2 && t1 != t2 && type_implies(t1, t2)
+        }
+        _ => false,
+    }
+}
+
 impl TryFrom<TokenStream> for WhenCondition {
     type Error = syn::Error;
 
     fn try_from(tokens: TokenStream) -> Result<Self, Self::Error> {
-        let parsed_condition = syn::parse2(tokens)?;
-        Ok(normalize(&parsed_condition))
+        let parsed_condition: WhenCondition = syn::parse2(tokens)?;
+        Ok(normalize(&parsed_condition.simplify()))
     }
 }
 
 impl Parse for WhenCondition {
     fn parse(input: ParseStream) -> Result<Self, Error> {
+        parse_or(input)
+    }
+}
+
+/// Parses conditions separated by `|`, which binds less than `&`
+fn parse_or(input: ParseStream) -> Result<WhenCondition, Error> {
+    let mut conditions = vec![parse_and(input)?];
+
+    while input.peek(Token![|]) {
+        input.parse::<Token![|]>()?; // consume the '|' token
+        conditions.push(parse_and(input)?);
+    }
+
+    match conditions.len() {
+        1 => Ok(conditions.remove(0)),
+        _ => Ok(WhenCondition::Any(conditions)),
+    }
+}
+
+/// Parses conditions separated by `&`
+fn parse_and(input: ParseStream) -> Result<WhenCondition, Error> {
+    let mut conditions = vec![parse_atom(input)?];
+
+    while input.peek(Token![&]) {
+        input.parse::<Token![&]>()?; // consume the '&' token
+        conditions.push(parse_atom(input)?);
+    }
+
+    match conditions.len() {
+        1 => Ok(conditions.remove(0)),
+        _ => Ok(WhenCondition::All(conditions)),
+    }
+}
+
+/// Parses a parenthesized condition, an aggregation or a single condition
+fn parse_atom(input: ParseStream) -> Result<WhenCondition, Error> {
+    if input.peek(token::Paren) {
+        let content;
+        parenthesized!(content in input); // consume the '(' and ')' token pair
+        return content.parse::<WhenCondition>();
+    }
+
+    let is_aggregation = input.peek(Ident)
+        && input.peek2(token::Paren)
+        && matches!(
+            input.fork().parse::<Ident>()?.to_string().as_str(),
+            "all" | "any" | "not"
+        );
+    if is_aggregation {
         let ident = input.parse::<Ident>()?;
+        return parse_aggregation(ident, input);
+    }
+
+    // the error points at the whole condition (e.g. `U =` in `all(T = i32, U =)`)
+    let term = get_term(input);
+    parse_term(input).map_err(|err| {
+        Error::new_spanned(
+            &term,
+            format!("Invalid condition `{}`: {}", to_string(&term), err),
+        )
+    })
+}
+
+/// get the tokens of the condition at the start of the input, up to the next `,`, without consuming them
+fn get_term(input: ParseStream) -> TokenStream {
+    let mut cursor = input.cursor();
+    let mut tokens = vec![];
+
+    while let Some((tree, next)) = cursor.token_tree() {
+        if matches!(&tree, TokenTree::Punct(p) if p.as_char() == ',') {
+            break;
+        }
+        tokens.push(tree);
+        cursor = next;
+    }
+
+    tokens.into_iter().collect()
+}
+
+/// Parses a len condition, an argument value condition, a lifetime condition, a const condition or a type or trait condition
+fn parse_term(input: ParseStream) -> Result<WhenCondition, Error> {
+    if input.peek(Lifetime) {
+        let generic = input.parse::<Lifetime>()?;
+        input.parse::<Token![=]>()?; // consume the '=' token
+        let lifetime = input.parse::<Lifetime>()?;
+        return Ok(WhenCondition::Lifetime(
+            generic.to_string(),
+            lifetime.to_string(),
+        ));
+    }
+
+    if let Some(len) = parse_len(input)? {
+        return Ok(WhenCondition::Len(len));
+    }
+
+    if let Some((position, value)) = parse_arg_value(input)? {
+        return Ok(WhenCondition::Arg(position, value));
+    }
+
+    // `Self = TypeName`, on the type the impl is for
+    if input.peek(Token![Self]) {
+        input.parse::<Token![Self]>()?; // consume the 'Self' token
+        if !input.peek(Token![=]) {
+            return Err(Error::new(input.span(), "Expected '=' after `Self`"));
+        }
+        return parse_type_or_lifetime_or_trait::<WhenCondition, WhenCondition>(SELF, input);
+    }
+
+    if let Some(condition) = parse_const(input)? {
+        return Ok(condition);
+    }
+
+    let ident = input.parse::<Ident>()?;
+    parse_type_or_lifetime_or_trait::<WhenCondition, WhenCondition>(&ident.to_string(), input)
+}
+
+/**
+   Parses a const generic condition in the form `N = value`, where the value is a literal (e.g. `0`, `-1`, `true`)
+   or a block (e.g. `{ 1 + 2 }`).
+   Returns none without consuming anything if the input does not start with it.
+*/
+fn parse_const(input: ParseStream) -> Result<Option<WhenCondition>, Error> {
+    let is_const = input.peek(Ident)
+        && input.peek2(Token![=])
+        && (input.peek3(Lit) || input.peek3(Token![-]) || input.peek3(token::Brace));
+
+    if !is_const {
+        return Ok(None);
+    }
+
+    let generic = input.parse::<Ident>()?.to_string();
+    input.parse::<Token![=]>()?; // consume the '=' token
+
+    let value = if input.peek(token::Brace) {
+        to_string(&input.parse::<ExprBlock>()?)
+    } else {
+        let negative = input.parse::<Option<Token![-]>>()?.is_some();
+        let lit = input.parse::<Lit>()?;
+        format!("{}{}", if negative { "-" } else { "" }, to_string(&lit))
+    };
+
+    Ok(Some(WhenCondition::Const(
+        generic,
+        normalize_const_value(&value),
+    )))
+}
+
+/**
+    parse the condition of a `when` attribute, an empty condition (e.g. `#[when()]`) means no condition,
+    i.e. the impl is the default one.
+*/
+pub fn parse_condition(tokens: TokenStream) -> Result<Option<WhenCondition>, Error> {
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    WhenCondition::try_from(tokens).map(Some)
+}
+
+/**
+    split the optional `priority = N` key, used to order impls that are equally specific, from the tokens of a `when` attribute.
+    The priority is 0 when it is missing.
+    # Example:
+    `T = i32, priority = 2` -> (`T = i32`, 2)
+*/
+pub fn split_priority(tokens: TokenStream) -> Result<(TokenStream, i32), Error> {
+    let trees = tokens.into_iter().collect::<Vec<_>>();
+
+    let is_priority = |i: usize| {
+        matches!(&trees[i], TokenTree::Ident(ident) if ident == "priority")
+            && matches!(trees.get(i + 1), Some(TokenTree::Punct(p)) if p.as_char() == '=')
+            && (i == 0 || matches!(&trees[i - 1], TokenTree::Punct(p) if p.as_char() == ','))
+    };
+
+    let Some(position) = (0..trees.len()).find(|&i| is_priority(i)) else {
+        return Ok((trees.into_iter().collect(), 0));
+    };
+
+    let parse_priority = |input: ParseStream| {
+        let negative = input.parse::<Option<Token![-]>>()?.is_some();
+        let priority = input.parse::<LitInt>()?.base10_parse::<i32>()?;
+        Ok(if negative { -priority } else { priority })
+    };
+    let priority = parse_priority.parse2(trees[position + 2..].iter().cloned().collect())?;
+
+    // the ',' before the key is dropped
+    let condition = trees[..position.saturating_sub(1)]
+        .iter()
+        .cloned()
+        .collect();
+
+    Ok((condition, priority))
+}
+
+/**
+    split the optional `requires(...)` key from the tokens of a `when` attribute.
+    Its where predicates are added as they are to the where clause of the generated impls,
+    they are not used to select the impl.
+    # Example:
+    `T: Clone, requires(Vec<T>: Debug)` -> (`T: Clone`, [`Vec<T>: Debug`])
+*/
+pub fn split_requires(tokens: TokenStream) -> Result<(TokenStream, Vec<String>), Error> {
+    let mut trees = tokens.into_iter().collect::<Vec<_>>();
+
+    let is_requires = |i: usize| {
+        matches!(&trees[i], TokenTree::Ident(ident) if ident == "requires")
+            && matches!(trees.get(i + 1), Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis)
+            && (i == 0 || matches!(&trees[i - 1], TokenTree::Punct(p) if p.as_char() == ','))
+    };
+
+    let Some(position) = (0..trees.len()).find(|&i| is_requires(i)) else {
+        return Ok((trees.into_iter().collect(), vec![]));
+    };
+
+    let TokenTree::Group(group) = &trees[position + 1] else {
+        unreachable!("`requires` is followed by a group");
+    };
+    let predicates = Punctuated::<WherePredicate, Token![,]>::parse_terminated
+        .parse2(group.stream())?
+        .iter()
+        .map(to_string)
+        .collect();
+
+    // the ',' before the key is dropped, or the one after it when the key comes first
+    let has_next_comma =
+        matches!(trees.get(position + 2), Some(TokenTree::Punct(p)) if p.as_char() == ',');
+    let range = match position {
+        0 if has_next_comma => 0..3,
+        0 => 0..2,
+        _ => position - 1..position + 2,
+    };
+    trees.drain(range);
+
+    Ok((trees.into_iter().collect(), predicates))
+}
+
+/// the default impl generated for the impls of a `when` with the `fallback` key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fallback {
+    /// the methods panic
+    Panic,
+    /// the methods call the fn at the path with the receiver and the arguments
+    Delegate(String),
+}
+
+/**
+    split the optional `fallback` or `fallback = path` key from the tokens of a `when` attribute.
+    # Example:
+    `T: Clone, fallback = my_fn` -> (`T: Clone`, `Fallback::Delegate("my_fn")`)
+*/
+pub fn split_fallback(tokens: TokenStream) -> Result<(TokenStream, Option<Fallback>), Error> {
+    let mut trees = tokens.into_iter().collect::<Vec<_>>();
+
+    let is_comma = |tree: &TokenTree| matches!(tree, TokenTree::Punct(p) if p.as_char() == ',');
+    let is_fallback = |i: usize| {
+        matches!(&trees[i], TokenTree::Ident(ident) if ident == "fallback")
+            && matches!(trees.get(i + 1), None | Some(TokenTree::Punct(_)))
+            && (i == 0 || is_comma(&trees[i - 1]))
+    };
 
-        match ident.to_string().as_str() {
-            "all" | "any" | "not" => parse_aggregation(ident, input),
-            _ => parse_type_or_lifetime_or_trait::<Self, Self>(&ident.to_string(), input),
+    let Some(position) = (0..trees.len()).find(|&i| is_fallback(i)) else {
+        return Ok((trees.into_iter().collect(), None));
+    };
+
+    // the key ends at the next ','
+    let end = (position + 1..trees.len())
+        .find(|&i| is_comma(&trees[i]))
+        .unwrap_or(trees.len());
+
+    let fallback = match &trees[position + 1..end] {
+        [] => Fallback::Panic,
+        [TokenTree::Punct(p), path @ ..] if p.as_char() == '=' => {
+            let path = syn::parse2::<syn::Path>(path.iter().cloned().collect())?;
+            Fallback::Delegate(to_string(&path))
+        }
+        _ => {
+            return Err(Error::new(
+                trees[position].span(),
+                "Expected `fallback` or `fallback = path`",
+            ));
         }
+    };
+
+    // the ',' before the key is dropped, or the one after it when the key comes first
+    let range = match position {
+        0 if end < trees.len() => 0..end + 1,
+        0 => 0..end,
+        _ => position - 1..end,
+    };
+    trees.drain(range);
+
+    Ok((trees.into_iter().collect(), Some(fallback)))
+}
+
+/**
+    split the optional `for = {Type1, Type2, ...}` key from the tokens of a `when` attribute.
+    The impl is for `_`, and it is generated once for each of the types.
+    # Example:
+    `T: Clone, for = {A, B<u8>}` -> (`T: Clone`, [`A`, `B<u8>`])
+*/
+pub fn split_for(tokens: TokenStream) -> Result<(TokenStream, Vec<String>), Error> {
+    let mut trees = tokens.into_iter().collect::<Vec<_>>();
+
+    let is_for = |i: usize| {
+        matches!(&trees[i], TokenTree::Ident(ident) if ident == "for")
+            && matches!(trees.get(i + 1), Some(TokenTree::Punct(p)) if p.as_char() == '=')
+            && matches!(trees.get(i + 2), Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace)
+            && (i == 0 || matches!(&trees[i - 1], TokenTree::Punct(p) if p.as_char() == ','))
+    };
+
+    let Some(position) = (0..trees.len()).find(|&i| is_for(i)) else {
+        return Ok((trees.into_iter().collect(), vec![]));
+    };
+
+    let TokenTree::Group(group) = &trees[position + 2] else {
+        unreachable!("`for =` is followed by a group");
+    };
+    let types = Punctuated::<syn::Type, Token![,]>::parse_terminated
+        .parse2(group.stream())?
+        .iter()
+        .map(to_string)
+        .collect::<Vec<_>>();
+    if types.is_empty() {
+        return Err(Error::new(
+            group.span(),
+            "Expected at least one type in `for = {...}`",
+        ));
     }
+
+    // the ',' before the key is dropped, or the one after it when the key comes first
+    let has_next_comma =
+        matches!(trees.get(position + 3), Some(TokenTree::Punct(p)) if p.as_char() == ',');
+    let range = match position {
+        0 if has_next_comma => 0..4,
+        0 => 0..3,
+        _ => position - 1..position + 3,
+    };
+    trees.drain(range);
+
+    Ok((trees.into_iter().collect(), types))
 }
 
 /// Parses an aggregation function (all, any, not) and its arguments
@@ -151,7 +678,8 @@ fn parse_aggregation(ident: Ident, input: ParseStream) -> Result<WhenCondition,
     }
 }
 
-fn normalize(condition: &WhenCondition) -> WhenCondition {
+/// convert a condition to its disjunctive normal form
+pub fn normalize(condition: &WhenCondition) -> WhenCondition {
     let mut current = condition.clone();
     loop {
         let next = to_dnf(&current);
@@ -167,7 +695,7 @@ fn to_dnf(condition: &WhenCondition) -> WhenCondition {
         WhenCondition::All(inner) => all_to_dnf(inner),
         WhenCondition::Any(inner) => any_to_dnf(inner),
         WhenCondition::Not(inner) => not_to_dnf(inner),
-        // type and trait conditions are already in dnf
+        // type, trait, lifetime, const, len and argument value conditions are already in dnf
         _ => condition.clone(),
     }
 }
@@ -278,9 +806,58 @@ pub fn get_conjunctions(condition: WhenCondition) -> Vec<WhenCondition> {
     }
 }
 
+/**
+    get the first pair of type conditions of a conjunction that assign unrelated types to the same generic,
+    so that the conjunction can never hold.
+    # Example:
+    for `all(T = i32, T = u8)` -> `Some((T = i32, T = u8))`
+*/
+pub fn get_conflicting_conditions(
+    condition: &WhenCondition,
+    generics: &str,
+) -> Option<(WhenCondition, WhenCondition)> {
+    let WhenCondition::All(conditions) = condition else {
+        return None;
+    };
+
+    conditions.iter().enumerate().find_map(|(i, c)| {
+        let WhenCondition::Type(g, t) = c else {
+            return None;
+        };
+        conditions[i + 1..].iter().find_map(|other| match other {
+            WhenCondition::Type(other_g, other_t)
+                if other_g == g
+                    && !type_assignable(t, other_t, generics, &Aliases::default())
+                    && !type_assignable(other_t, t, generics, &Aliases::default()) =>
+            {
+                Some((c.clone(), other.clone()))
+            }
+            _ => None,
+        })
+    })
+}
+
+/**
+    parse a condition and return its normalized DNF, one conjunctive term per line.
+    Each line corresponds to a separate specialized impl generated by `when`.
+    # Example:
+    `all(T: Clone, any(T = u8, U = i32))` -> `"all(T = u8, T: Clone)\nall(T: Clone, U = i32)"`
+*/
+pub fn debug_dnf(tokens: TokenStream) -> Result<String, Error> {
+    let condition = WhenCondition::try_from(tokens)?;
+
+    Ok(get_conjunctions(condition)
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::conversions::{str_to_type_name, to_hash, to_string};
+    use proptest::prelude::*;
     use quote::quote;
 
     #[test]
@@ -290,6 +867,22 @@ mod tests {
         assert_eq!(condition, WhenCondition::Type("T".into(), "u32".into()));
     }
 
+    #[test]
+    fn parse_self_condition() {
+        let input = quote! { Self = Wrapper<i32> };
+        let condition = WhenCondition::try_from(input).unwrap();
+        assert_eq!(
+            condition,
+            WhenCondition::Type("Self".into(), "Wrapper < i32 >".into())
+        );
+
+        let input = quote! { all(not(Self = Wrapper<u8>), T: Copy) };
+        assert!(WhenCondition::try_from(input).is_ok());
+
+        let input = quote! { Self: Copy };
+        assert!(WhenCondition::try_from(input).is_err());
+    }
+
     #[test]
     fn parse_type_formats() {
         let inputs = vec![
@@ -308,6 +901,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_empty_condition() {
+        let condition = parse_condition(quote! {}).unwrap();
+        assert_eq!(condition, None);
+
+        let condition = parse_condition(quote! { T = u32 }).unwrap();
+        assert_eq!(
+            condition,
+            Some(WhenCondition::Type("T".into(), "u32".into()))
+        );
+    }
+
     #[test]
     fn parse_single_trait_condition() {
         let input = quote! { T: Clone };
@@ -358,6 +963,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lifetime_and_traits_condition_round_trip() {
+        let condition = WhenCondition::try_from(quote! { T: 'a + Clone }).unwrap();
+        assert_eq!(condition.to_string(), "all(T = &'a _, T: Clone)");
+
+        let tokens = condition.to_string().parse::<TokenStream>().unwrap();
+        assert_eq!(WhenCondition::try_from(tokens).unwrap(), condition);
+
+        let condition = WhenCondition::try_from(quote! { not(T: Clone + 'static) }).unwrap();
+        assert_eq!(
+            normalize(&condition).to_string(),
+            "any(not(T = &'static _), not(T: Clone))"
+        );
+    }
+
     #[test]
     fn parse_all_condition() {
         let input = quote! { all(T: Clone, U = u32) };
@@ -397,6 +1017,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_arg_value_condition() {
+        let input = quote! { all(T = i32, arg0 = 0, not(arg1 = -1)) };
+        let condition = WhenCondition::try_from(input).unwrap();
+        assert_eq!(
+            condition,
+            WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "i32".into()),
+                WhenCondition::Arg(0, "0".into()),
+                WhenCondition::Not(Box::new(WhenCondition::Arg(1, "-1".into())))
+            ])
+        );
+        assert_eq!(
+            condition.to_string(),
+            "all(T = i32, arg0 = 0, not(arg1 = -1))"
+        );
+
+        // other identifiers are still const generics
+        let condition = WhenCondition::try_from(quote! { args = 0 }).unwrap();
+        assert_eq!(condition, WhenCondition::Const("args".into(), "0".into()));
+    }
+
+    #[test]
+    fn parse_len_condition() {
+        let input = quote! { all(T = &[u8], len = 3) };
+        let condition = WhenCondition::try_from(input).unwrap();
+        assert_eq!(
+            condition,
+            WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "& [u8]".into()),
+                WhenCondition::Len(3)
+            ])
+        );
+        assert_eq!(condition.to_string(), "all(T = &[u8], len = 3)");
+    }
+
+    #[test]
+    fn parse_lifetime_generic_condition() {
+        let input = quote! { all('a = 'static, T = &'a u8) };
+        let condition = WhenCondition::try_from(input).unwrap();
+        assert_eq!(
+            condition,
+            WhenCondition::All(vec![
+                WhenCondition::Lifetime("'a".into(), "'static".into()),
+                WhenCondition::Type("T".into(), "& 'a u8".into())
+            ])
+        );
+        assert_eq!(
+            WhenCondition::Lifetime("'a".into(), "'static".into()).to_string(),
+            "'a = 'static"
+        );
+
+        assert!(WhenCondition::try_from(quote! { 'a = u8 }).is_err());
+    }
+
+    #[test]
+    fn parse_const_generic_condition() {
+        let input = quote! { all(N = 0usize, M = -1, K = { 1 + 2 }, T = u8) };
+        let condition = WhenCondition::try_from(input).unwrap();
+        assert_eq!(
+            condition,
+            WhenCondition::All(vec![
+                WhenCondition::Const("N".into(), "0".into()),
+                WhenCondition::Const("M".into(), "-1".into()),
+                WhenCondition::Const("K".into(), "{1+2}".into()),
+                WhenCondition::Type("T".into(), "u8".into())
+            ])
+        );
+        assert_eq!(
+            WhenCondition::Const("N".into(), "0".into()).to_string(),
+            "N = 0"
+        );
+    }
+
+    #[test]
+    fn parse_infix_condition() {
+        let input = quote! { T: Clone | U = u32 & T = i32 };
+        let condition = WhenCondition::try_from(input).unwrap();
+        assert_eq!(
+            condition,
+            WhenCondition::Any(vec![
+                WhenCondition::Trait("T".into(), vec!["Clone".into()]),
+                WhenCondition::All(vec![
+                    WhenCondition::Type("U".into(), "u32".into()),
+                    WhenCondition::Type("T".into(), "i32".into())
+                ])
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_invalid_sub_condition() {
+        let err = parse_condition(quote! { all(T = i32, U = ) }).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid condition `U =`: "));
+
+        let err = parse_condition(quote! { any(T: Copy, not(U = &)) }).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid condition `U = &`: "));
+
+        // the aggregations are not reported as a whole
+        let err = parse_condition(quote! { all(T = i32) & U }).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid condition `U`: Expected ':' or '=' after identifier"
+        );
+    }
+
+    #[test]
+    fn parse_grouped_condition() {
+        let input = quote! { (T: A | T: B) & T: C };
+        assert_eq!(
+            debug_dnf(input).unwrap(),
+            "all(T: A, T: C)\nall(T: B, T: C)"
+        );
+
+        let input = quote! { not(T = &u8 | (U: A & U = &[u8])) };
+        assert_eq!(
+            debug_dnf(input).unwrap(),
+            "all(not(T = &u8), not(U: A))\nall(not(T = &u8), not(U = &[u8]))"
+        );
+
+        let input = quote! { all((T: A), any(U = u8 | U = i8, V: B)) };
+        assert_eq!(
+            WhenCondition::try_from(input).unwrap(),
+            WhenCondition::try_from(quote! { all(T: A, any(U = u8, U = i8, V: B)) }).unwrap()
+        );
+    }
+
     #[test]
     fn flatten() {
         let inputs = vec![
@@ -454,6 +1201,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn simplify_subsumed_conditions() {
+        let inputs = vec![
+            (
+                quote! { all(T = i32, T = i32, T: Clone) },
+                "all(T = i32, T: Clone)",
+            ),
+            (
+                quote! { all(T = _, T = i32, T: Clone) },
+                "all(T = i32, T: Clone)",
+            ),
+            (quote! { all(T = Vec<_>, T = Vec<u8>) }, "T = Vec<u8>"),
+            (
+                quote! { all(T = (_, u8), T = (i32, u8), U = _) },
+                "all(T = (i32,u8), U = _)",
+            ),
+            (
+                quote! { any(all(T = _, T = &u8), U = u8) },
+                "any(T = &u8, U = u8)",
+            ),
+            (quote! { not(all(T = _, T = u8)) }, "not(T = u8)"),
+        ];
+
+        for (input, expected) in inputs {
+            let condition = WhenCondition::try_from(input).unwrap();
+            assert_eq!(condition.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn simplify_keeps_unrelated_conditions() {
+        let inputs = vec![
+            // different generics
+            quote! { all(T = _, U = i32) },
+            // neither type is more specific
+            quote! { all(T = (_, u8), T = (i32, _)) },
+            // `V` is a generic bound by the condition, not a wildcard
+            quote! { all(T = Vec<V>, T = Vec<u8>) },
+            // a negated type condition is not subsumed
+            quote! { all(not(T = _), T = i32) },
+        ];
+
+        for input in inputs {
+            let condition: WhenCondition = syn::parse2(input).unwrap();
+            assert_eq!(condition.clone().simplify(), condition);
+        }
+    }
+
+    #[test]
+    fn alpha_equivalent_conditions() {
+        let a = WhenCondition::try_from(quote! { all(T = Vec<U>, U: Debug) }).unwrap();
+        let b = WhenCondition::try_from(quote! { all(X: Debug, T = Vec<X>) }).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(to_hash(&a), to_hash(&b));
+
+        let condition = WhenCondition::try_from(
+            quote! { any(all(T = Vec<U>, U: Debug), all(T = Vec<X>, X: Debug)) },
+        )
+        .unwrap();
+        assert_eq!(condition, a);
+
+        let a = WhenCondition::try_from(quote! { all(T = (U, V), U: Copy, V = u8) }).unwrap();
+        let b = WhenCondition::try_from(quote! { all(T = (A, B), A: Copy, B = u8) }).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn not_alpha_equivalent_conditions() {
+        let inputs = vec![
+            (
+                quote! { all(T = Vec<U>, U: Debug) },
+                quote! { all(T = Vec<X>, X: Clone) },
+            ),
+            // `U` and `X` can be types, they are not constrained
+            (quote! { T = Vec<U> }, quote! { T = Vec<X> }),
+            (
+                quote! { all(T = Vec<U>, U: Debug, V: Clone) },
+                quote! { all(T = Vec<V>, V: Debug, U: Clone) },
+            ),
+            (
+                quote! { all(T = (U, V), U: Copy, V = u8) },
+                quote! { all(T = (U, V), V: Copy, U = u8) },
+            ),
+        ];
+
+        for (a, b) in inputs {
+            let a = WhenCondition::try_from(a).unwrap();
+            let b = WhenCondition::try_from(b).unwrap();
+            assert_ne!(a, b);
+        }
+    }
+
     #[test]
     fn normalization() {
         let input = quote! { any(not(all(T = A, all(T = B, T = C), any(U = D, U = C), not(not(T = A)), all(T = D), any(U = D))), all(T = A, any(T = B, T = C), T = D), any(all(T = A, T = B), all(T = B, T = A))) };
@@ -485,4 +1324,174 @@ mod tests {
         ]);
         assert_eq!(condition, expected);
     }
+
+    #[test]
+    fn debug_dnf_nested_condition() {
+        let input = quote! { all(T: Clone, any(T = u8, not(all(U = i32, U: Copy)))) };
+        let dnf = debug_dnf(input).unwrap();
+        assert_eq!(
+            dnf,
+            "all(T = u8, T: Clone)\nall(T: Clone, not(U = i32))\nall(T: Clone, not(U: Copy))"
+        );
+    }
+
+    #[test]
+    fn debug_dnf_invalid_condition() {
+        let input = quote! { not(T = u8, U = i32) };
+        assert!(debug_dnf(input).is_err());
+    }
+
+    #[test]
+    fn split_condition_priority() {
+        let (condition, priority) = split_priority(quote! { T = i32, priority = 2 }).unwrap();
+        assert_eq!(condition.to_string(), "T = i32");
+        assert_eq!(priority, 2);
+
+        let (condition, priority) =
+            split_priority(quote! { any(T: Copy, priority = u8), priority = -1 }).unwrap();
+        assert_eq!(condition.to_string(), "any (T : Copy , priority = u8)");
+        assert_eq!(priority, -1);
+
+        let (condition, priority) = split_priority(quote! { priority = 3 }).unwrap();
+        assert!(condition.is_empty());
+        assert_eq!(priority, 3);
+
+        let (condition, priority) = split_priority(quote! { T = i32 }).unwrap();
+        assert_eq!(condition.to_string(), "T = i32");
+        assert_eq!(priority, 0);
+
+        assert!(split_priority(quote! { T = i32, priority = high }).is_err());
+    }
+
+    #[test]
+    fn split_condition_requires() {
+        let (condition, requires) =
+            split_requires(quote! { T: Clone, requires(Vec<T>: Debug, T: 'static), priority = 1 })
+                .unwrap();
+        assert_eq!(condition.to_string(), "T : Clone , priority = 1");
+        assert_eq!(requires, vec!["Vec < T > : Debug", "T : 'static"]);
+
+        let (condition, requires) = split_requires(quote! { requires(T: Copy), T = i32 }).unwrap();
+        assert_eq!(condition.to_string(), "T = i32");
+        assert_eq!(requires, vec!["T : Copy"]);
+
+        let (condition, requires) = split_requires(quote! { any(T: requires, U: Copy) }).unwrap();
+        assert_eq!(condition.to_string(), "any (T : requires , U : Copy)");
+        assert!(requires.is_empty());
+
+        assert!(split_requires(quote! { T = i32, requires(T) }).is_err());
+    }
+
+    #[test]
+    fn split_condition_fallback() {
+        let (condition, fallback) =
+            split_fallback(quote! { T: Clone, fallback, priority = 1 }).unwrap();
+        assert_eq!(condition.to_string(), "T : Clone , priority = 1");
+        assert_eq!(fallback, Some(Fallback::Panic));
+
+        let (condition, fallback) =
+            split_fallback(quote! { fallback = crate::default_foo, T = i32 }).unwrap();
+        assert_eq!(condition.to_string(), "T = i32");
+        assert_eq!(
+            fallback,
+            Some(Fallback::Delegate("crate :: default_foo".to_string()))
+        );
+
+        let (condition, fallback) = split_fallback(quote! { T: fallback }).unwrap();
+        assert_eq!(condition.to_string(), "T : fallback");
+        assert!(fallback.is_none());
+
+        assert!(split_fallback(quote! { T = i32, fallback = 1 }).is_err());
+    }
+
+    #[test]
+    fn split_condition_for() {
+        let (condition, types) =
+            split_for(quote! { T: Clone, for = {A, B<u8>}, priority = 1 }).unwrap();
+        assert_eq!(condition.to_string(), "T : Clone , priority = 1");
+        assert_eq!(types, vec!["A", "B < u8 >"]);
+
+        let (condition, types) = split_for(quote! { for = {A}, T = i32 }).unwrap();
+        assert_eq!(condition.to_string(), "T = i32");
+        assert_eq!(types, vec!["A"]);
+
+        let (condition, types) = split_for(quote! { T: for<'a> Fn(&'a u8) }).unwrap();
+        assert_eq!(condition.to_string(), "T : for < 'a > Fn (& 'a u8)");
+        assert!(types.is_empty());
+
+        assert!(split_for(quote! { T = i32, for = {} }).is_err());
+        assert!(split_for(quote! { T = i32, for = {1} }).is_err());
+    }
+
+    /// random conditions over a few generics, with the types in the form produced by the parser
+    fn condition_strategy() -> impl Strategy<Value = WhenCondition> {
+        let generic = prop::sample::select(vec!["T", "U"]);
+        let type_ = prop::sample::select(vec!["u8", "Vec<_>", "&'a u8", "(i32, _)", "&[u8]"]);
+        let traits = prop::sample::subsequence(vec!["Clone", "Copy", "Debug"], 1..=2);
+
+        let leaf = prop_oneof![
+            (generic.clone(), type_).prop_map(|(g, t)| {
+                WhenCondition::Type(g.into(), to_string(&str_to_type_name(t)))
+            }),
+            (generic, traits).prop_map(|(g, traits)| {
+                WhenCondition::Trait(g.into(), traits.into_iter().map(String::from).collect())
+            }),
+            prop::sample::select(vec!["'static", "'b"])
+                .prop_map(|lt| WhenCondition::Lifetime("'a".into(), lt.into())),
+            (1usize..4).prop_map(WhenCondition::Len),
+        ];
+
+        leaf.prop_recursive(3, 16, 3, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 1..4).prop_map(WhenCondition::All),
+                prop::collection::vec(inner.clone(), 1..4).prop_map(WhenCondition::Any),
+                inner.prop_map(|c| WhenCondition::Not(Box::new(c))),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn normalize_idempotent(condition in condition_strategy()) {
+            let normalized = normalize(&condition);
+            prop_assert_eq!(normalize(&normalized), normalized);
+        }
+
+        #[test]
+        fn display_parse_round_trip(condition in condition_strategy()) {
+            let normalized = normalize(&condition);
+            let tokens = normalized.to_string().parse::<TokenStream>().unwrap();
+            prop_assert_eq!(WhenCondition::try_from(tokens).unwrap(), normalized);
+        }
+    }
+
+    #[test]
+    fn get_conflicting_conditions_contradiction() {
+        let condition = WhenCondition::All(vec![
+            WhenCondition::Trait("T".into(), vec!["Copy".into()]),
+            WhenCondition::Type("T".into(), "i32".into()),
+            WhenCondition::Type("T".into(), "u8".into()),
+        ]);
+
+        let res = get_conflicting_conditions(&condition, "<T>");
+
+        assert_eq!(
+            res,
+            Some((
+                WhenCondition::Type("T".into(), "i32".into()),
+                WhenCondition::Type("T".into(), "u8".into())
+            ))
+        );
+    }
+
+    #[test]
+    fn get_conflicting_conditions_compatible_types() {
+        let condition = WhenCondition::All(vec![
+            WhenCondition::Type("T".into(), "Vec<_>".into()),
+            WhenCondition::Type("T".into(), "Vec<i32>".into()),
+            WhenCondition::Type("U".into(), "u8".into()),
+        ]);
+
+        assert_eq!(get_conflicting_conditions(&condition, "<T, U>"), None);
+    }
 }
@@ -10,7 +10,10 @@ use syn::{
 use crate::conditions::WhenCondition;
 
 pub fn str_to_generics(str: &str) -> Generics {
-    syn::parse_str(str).expect("Failed to parse generics")
+    let mut generics: Generics = syn::parse_str(str).expect("Failed to parse generics");
+    // a trailing comma (e.g. `<T, U,>`) would be kept when converting the generics back to a string
+    generics.params.pop_punct();
+    generics
 }
 
 pub fn str_to_trait_name(str: &str) -> Path {
@@ -37,6 +40,12 @@ pub fn strs_to_trait_items(strs: &[String]) -> Vec<TraitItem> {
         .collect()
 }
 
+pub fn strs_to_where_predicates(strs: &[String]) -> Vec<WherePredicate> {
+    strs.iter()
+        .map(|p| syn::parse_str(p).expect("Failed to parse where predicate"))
+        .collect()
+}
+
 pub fn str_to_expr(str: &str) -> Expr {
     syn::parse_str(str).expect("Failed to parse expr")
 }
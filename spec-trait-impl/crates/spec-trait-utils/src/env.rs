@@ -1,8 +1,49 @@
+use std::cell::RefCell;
+use std::env;
+use std::fmt::Display;
 use std::path::{Path, PathBuf};
 
 pub const FOLDER_CACHE: &str = "/tmp";
 pub const FILE_CACHE: &str = "spec_trait_macro_cache.json";
 
+/// when set, `when` prints the normalized DNF of its condition as a compiler warning
+pub const ENV_DEBUG_DNF: &str = "SPEC_TRAIT_DEBUG_DNF";
+
+/// when set, the cache build prints the impls whose conditions can be equally specific for the same types
+pub const ENV_CHECK_OVERLAPS: &str = "SPEC_TRAIT_CHECK_OVERLAPS";
+
+/// when set, every `spec!` call is checked as if annotated with `strict`
+pub const ENV_STRICT: &str = "SPEC_TRAIT_STRICT";
+
+/// when set, `spec!` prints the alias annotations that have no effect on the call as compiler warnings
+pub const ENV_WARN_ALIASES: &str = "SPEC_TRAIT_WARN_ALIASES";
+
+/// when set, the path of the cache file, used by the build step and the macros instead of `/tmp/spec_trait_macro_cache.json`
+pub const ENV_CACHE_PATH: &str = "SPEC_TRAIT_CACHE_PATH";
+
+thread_local! {
+    /// the path of the cache for the current thread, set by the tests so that they leave the cache of the builds alone
+    static THREAD_CACHE_PATH: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// use the cache at `path` in the current thread, over the one of the environment, `None` to go back to it
+pub fn set_thread_cache_path(path: Option<PathBuf>) {
+    THREAD_CACHE_PATH.with(|thread_path| *thread_path.borrow_mut() = path);
+}
+
 pub fn get_cache_path() -> PathBuf {
-    Path::new(&FOLDER_CACHE).join(FILE_CACHE)
+    if let Some(path) = THREAD_CACHE_PATH.with(|thread_path| thread_path.borrow().clone()) {
+        return path;
+    }
+
+    env::var_os(ENV_CACHE_PATH)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&FOLDER_CACHE).join(FILE_CACHE))
+}
+
+/// print `message` as a warning of the build, only when the environment variable `var` is set
+pub fn warn_if_set(var: &str, message: impl Display) {
+    if env::var_os(var).is_some() {
+        eprintln!("warning: {}", message);
+    }
 }
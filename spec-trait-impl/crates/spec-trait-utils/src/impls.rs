@@ -1,17 +1,20 @@
-use crate::conditions::WhenCondition;
+use crate::conditions::{Fallback, WhenCondition};
 use crate::conversions::{
-    str_to_generics, str_to_trait_name, str_to_type_name, strs_to_impl_items, to_hash, to_string,
-    tokens_to_impl, trait_condition_to_generic_predicate, trait_to_string,
+    str_to_expr, str_to_generics, str_to_trait_name, str_to_type_name, strs_to_impl_items,
+    strs_to_where_predicates, to_hash, to_string, tokens_to_impl,
+    trait_condition_to_generic_predicate, trait_to_string,
 };
 use crate::parsing::{
-    get_generics_lifetimes, get_generics_types, get_relevant_generics_names, handle_type_predicate,
-    parse_generics,
+    ANY_TRAIT, get_generics_lifetimes, get_generics_types, get_relevant_generics_names,
+    handle_type_predicate, parse_generics,
 };
 use crate::specialize::{
-    Specializable, add_generic_lifetime, add_generic_type, apply_type_condition,
-    get_assignable_conditions, get_used_generics, remove_generic,
+    ConstReplacer, Specializable, add_generic_lifetime, add_generic_type,
+    add_generics_used_in_bounds, apply_const_condition, apply_lifetime_condition,
+    apply_type_condition, expand_self_condition, get_assignable_conditions, get_used_generics,
+    remove_generic,
 };
-use crate::types::{replace_type, type_contains, type_contains_lifetime};
+use crate::types::{replace_lifetime, replace_type, type_contains, type_contains_lifetime};
 use proc_macro2::TokenStream;
 use quote::quote;
 use serde::{Deserialize, Serialize};
@@ -19,7 +22,10 @@ use std::collections::HashSet;
 use std::fmt::Debug;
 use syn::visit::Visit;
 use syn::visit_mut::VisitMut;
-use syn::{Attribute, Generics, ItemImpl};
+use syn::{
+    AngleBracketedGenericArguments, Attribute, FnArg, GenericArgument, Generics, Ident, ImplItem,
+    ItemImpl, Path, PathArguments, Type, parse_quote,
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ImplBody {
@@ -30,6 +36,24 @@ pub struct ImplBody {
     pub type_name: String,
     pub items: Vec<String>,
     pub specialized: Option<Box<ImplBody>>,
+    /// path of the module containing the impl (e.g. `a::b`), empty for the crate root
+    #[serde(default)]
+    pub module: String,
+    /// whether the type is declared in `module` (e.g. `struct Foo;` next to `impl Bar for Foo`), set by the build step
+    #[serde(default)]
+    pub local_type: bool,
+    /// the whole `when` condition, shared by the impls generated from each of its DNF conjunctions
+    #[serde(default)]
+    pub source_condition: Option<WhenCondition>,
+    /// set with `priority = N` in `when`, orders the impls that are equally specific
+    #[serde(default)]
+    pub priority: i32,
+    /// set with `requires(...)` in `when`, where predicates added as they are to the generated impl
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// set with `fallback` in `when`, the impl also generates the default impl of the trait for the type
+    #[serde(default)]
+    pub fallback: bool,
 }
 
 impl TryFrom<(TokenStream, Option<WhenCondition>)> for ImplBody {
@@ -38,7 +62,10 @@ impl TryFrom<(TokenStream, Option<WhenCondition>)> for ImplBody {
     fn try_from(
         (tokens, condition): (TokenStream, Option<WhenCondition>),
     ) -> Result<Self, Self::Error> {
-        let bod = tokens_to_impl(tokens)?;
+        let mut bod = tokens_to_impl(tokens)?;
+        if let Some((_, path, _)) = &mut bod.trait_ {
+            strip_turbofish(path);
+        }
 
         let impl_generics = to_string(&parse_generics(bod.generics.clone()));
         let trait_with_generics = trait_to_string(&bod.trait_);
@@ -55,11 +82,26 @@ impl TryFrom<(TokenStream, Option<WhenCondition>)> for ImplBody {
             type_name,
             items,
             specialized: None,
+            module: String::new(),
+            local_type: false,
+            source_condition: None,
+            priority: 0,
+            requires: vec![],
+            fallback: false,
         })
         .specialize())
     }
 }
 
+/// `Trait::<u8>` becomes `Trait<u8>`, so that the generics are split from the trait name
+fn strip_turbofish(path: &mut Path) {
+    for segment in &mut path.segments {
+        if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
+            args.colon2_token = None;
+        }
+    }
+}
+
 fn get_trait_name_without_generics(trait_with_generics: &str) -> String {
     trait_with_generics
         .split('<')
@@ -71,6 +113,9 @@ fn get_trait_name_without_generics(trait_with_generics: &str) -> String {
 
 impl From<&ImplBody> for TokenStream {
     fn from(impl_body: &ImplBody) -> Self {
+        let requires = strs_to_where_predicates(&impl_body.requires);
+        let where_clause = (!requires.is_empty()).then(|| quote! { where #(#requires),* });
+
         let impl_body = impl_body
             .specialized
             .as_ref()
@@ -82,8 +127,10 @@ impl From<&ImplBody> for TokenStream {
         let type_name = str_to_type_name(&impl_body.type_name);
         let items = strs_to_impl_items(&impl_body.items);
 
+        // the generated generics (e.g. `__G_0__`) are not camel case
         quote! {
-            impl #impl_generics #trait_name #trait_generics for #type_name {
+            #[allow(non_camel_case_types, non_snake_case)]
+            impl #impl_generics #trait_name #trait_generics for #type_name #where_clause {
                 #(#items)*
             }
         }
@@ -114,9 +161,76 @@ impl Specializable for ImplBody {
 }
 
 impl ImplBody {
+    /**
+        check if both impls are generated from different conjunctions of the same `when` condition on the same impl,
+        i.e. they are semantically the same impl.
+    */
+    pub fn same_source(&self, other: &ImplBody) -> bool {
+        self.source_condition.is_some()
+            && self.source_condition == other.source_condition
+            && self.trait_name == other.trait_name
+            && self.full_type_name() == other.full_type_name()
+            && self.items == other.items
+    }
+
+    /**
+        get the path of the type in the crate, used to tell apart types with the same name in different modules.
+        Only the types declared in the module of the impl and the paths starting with `crate`, `self` or `super`
+        are resolved, the other types (e.g. `Vec<u8>` or a generic) are left as they are.
+        # Example:
+        in module `a::b`, `Foo` declared in it -> `a::b::Foo`, `super::Foo` -> `a::Foo`, `Vec<Foo>` -> `Vec<Foo>`
+    */
+    pub fn full_type_name(&self) -> String {
+        let Type::Path(type_path) = str_to_type_name(&self.type_name) else {
+            return self.type_name.to_owned();
+        };
+        if type_path.qself.is_some() || type_path.path.leading_colon.is_some() {
+            return self.type_name.to_owned();
+        }
+
+        let mut path = self
+            .module
+            .split("::")
+            .filter(|segment| !segment.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let mut segments = type_path.path.segments.iter().peekable();
+
+        match segments.peek().map(|segment| segment.ident.to_string()) {
+            Some(first) if first == "crate" => {
+                path.clear();
+                segments.next();
+            }
+            Some(first) if first == "self" => {
+                segments.next();
+            }
+            Some(first) if first == "super" => {
+                while segments
+                    .next_if(|segment| segment.ident == "super")
+                    .is_some()
+                {
+                    path.pop();
+                }
+            }
+            _ if self.local_type => {}
+            _ => return self.type_name.to_owned(),
+        }
+
+        path.extend(segments.map(to_string));
+        path.join("::")
+    }
+
     fn get_spec_trait_name(&self) -> String {
+        // only the identifier parts of the type are kept, e.g. `Foo_a` for `Foo<'a>`
+        let type_name = self
+            .type_name
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_");
+
         match &self.condition {
-            Some(c) => format!("{}_{}_{}", self.trait_name, self.type_name, to_hash(c)),
+            Some(c) => format!("{}_{}_{}", self.trait_name, type_name, to_hash(c)),
             None => self.trait_name.to_owned(),
         }
     }
@@ -128,16 +242,23 @@ impl ImplBody {
         // set specialized trait name
         specialized.trait_name = specialized.get_spec_trait_name();
 
+        // the trait arguments that are not generics (e.g. `Vec<T>`) are replaced in the specialized trait
+        let (mut trait_generics, trait_types) = get_trait_types(&specialized.trait_generics);
+        if !trait_types.is_empty() {
+            remove_trait_types(&mut trait_generics, &trait_types);
+            specialized.trait_generics = to_string(&trait_generics);
+        }
+
         // apply condition
         if let Some(condition) = &self.condition {
-            specialized.apply_condition(condition);
+            let condition = expand_self_condition(condition, &self.type_name, &self.impl_generics);
+            specialized.apply_condition(&condition);
         }
 
         // set missing generics
         let mut trait_generics = str_to_generics(&specialized.trait_generics);
-        let curr_generics_types = get_generics_types::<HashSet<_>>(&specialized.trait_generics);
-        let curr_generics_lifetimes =
-            get_generics_lifetimes::<HashSet<_>>(&specialized.trait_generics);
+        let curr_generics_types = get_generics_types::<Vec<_>>(&specialized.trait_generics);
+        let curr_generics_lifetimes = get_generics_lifetimes::<Vec<_>>(&specialized.trait_generics);
         for generic in get_generics_types::<Vec<_>>(&specialized.impl_generics) {
             if !curr_generics_types.contains(&generic) {
                 add_generic_type(&mut trait_generics, &generic);
@@ -151,25 +272,20 @@ impl ImplBody {
         specialized.trait_generics = to_string(&trait_generics);
 
         // clean unused generics
-        let used_generics =
-            get_used_generics(&specialized, &str_to_generics(&specialized.impl_generics));
+        let used_generics = specialized.used_generics(false);
+        let unused_generics = specialized.unused_generics();
 
         let mut impl_generics = str_to_generics(&specialized.impl_generics);
         let mut trait_generics = str_to_generics(&specialized.trait_generics);
-        for generic in get_generics_lifetimes::<Vec<_>>(&specialized.impl_generics) {
+        for generic in get_generics_lifetimes::<Vec<_>>(&specialized.impl_generics)
+            .into_iter()
+            .chain(get_generics_types::<Vec<_>>(&specialized.impl_generics))
+        {
             if !used_generics.contains(&generic) {
                 remove_generic(&mut trait_generics, &generic);
-                if !type_contains_lifetime(&str_to_type_name(&specialized.type_name), &generic) {
-                    remove_generic(&mut impl_generics, &generic);
-                }
             }
-        }
-        for generic in get_generics_types::<Vec<_>>(&specialized.impl_generics) {
-            if !used_generics.contains(&generic) {
-                remove_generic(&mut trait_generics, &generic);
-                if !type_contains(&str_to_type_name(&specialized.type_name), &generic) {
-                    remove_generic(&mut impl_generics, &generic);
-                }
+            if unused_generics.contains(&generic) {
+                remove_generic(&mut impl_generics, &generic);
             }
         }
         specialized.impl_generics = to_string(&impl_generics);
@@ -179,6 +295,44 @@ impl ImplBody {
         new_impl
     }
 
+    /// get the generics used in the items (and in the type, if `with_type`) and the generics their bounds depend on
+    fn used_generics(&self, with_type: bool) -> HashSet<String> {
+        let generics = str_to_generics(&self.impl_generics);
+        let mut used_generics = get_used_generics(self, &generics);
+
+        if with_type {
+            let type_name = str_to_type_name(&self.type_name);
+            used_generics.extend(
+                get_generics_lifetimes::<Vec<_>>(&self.impl_generics)
+                    .into_iter()
+                    .filter(|g| type_contains_lifetime(&type_name, g)),
+            );
+            used_generics.extend(
+                get_generics_types::<Vec<_>>(&self.impl_generics)
+                    .into_iter()
+                    .filter(|g| type_contains(&type_name, g)),
+            );
+        }
+
+        add_generics_used_in_bounds(&generics, &mut used_generics);
+        used_generics
+    }
+
+    /**
+       get the generics of the impl that are not used in the items, in the type or in the bounds of the used generics
+       # Example:
+       for `impl<T, U: Clone> Foo<T> for MyType { fn foo(&self, x: T) {} }` -> `vec![U]`
+    */
+    pub fn unused_generics(&self) -> Vec<String> {
+        let used_generics = self.used_generics(true);
+
+        get_generics_lifetimes::<Vec<_>>(&self.impl_generics)
+            .into_iter()
+            .chain(get_generics_types::<Vec<_>>(&self.impl_generics))
+            .filter(|g| !used_generics.contains(g))
+            .collect()
+    }
+
     /// apply a condition to the impl body, modifying its generics and items
     fn apply_condition(&mut self, condition: &WhenCondition) {
         match condition {
@@ -208,9 +362,60 @@ impl ImplBody {
                 self.type_name = to_string(&impl_type);
             }
 
-            WhenCondition::Trait(_, _) => {
+            WhenCondition::Lifetime(generic, lifetime) => {
+                let mut generics = str_to_generics(&self.impl_generics);
+                let mut other_generics = str_to_generics(&self.trait_generics);
+
+                apply_lifetime_condition(
+                    self,
+                    &mut generics,
+                    &mut other_generics,
+                    generic,
+                    lifetime,
+                );
+
+                let mut impl_type = str_to_type_name(&self.type_name);
+                replace_lifetime(&mut impl_type, generic, lifetime);
+
+                self.impl_generics = to_string(&generics);
+                self.trait_generics = to_string(&other_generics);
+                self.type_name = to_string(&impl_type);
+            }
+
+            WhenCondition::Const(generic, value) => {
+                let mut generics = str_to_generics(&self.impl_generics);
+                let mut other_generics = str_to_generics(&self.trait_generics);
+
+                apply_const_condition(self, &mut generics, &mut other_generics, generic, value);
+
+                let mut impl_type = str_to_type_name(&self.type_name);
+                ConstReplacer {
+                    generic: generic.to_owned(),
+                    value: str_to_expr(value),
+                }
+                .visit_type_mut(&mut impl_type);
+
+                self.impl_generics = to_string(&generics);
+                self.trait_generics = to_string(&other_generics);
+                self.type_name = to_string(&impl_type);
+            }
+
+            WhenCondition::Trait(generic, traits) => {
+                // the wildcard only selects the annotated types, it is not a bound
+                let traits = traits
+                    .iter()
+                    .filter(|t| *t != ANY_TRAIT)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if traits.is_empty() {
+                    return;
+                }
+
                 let mut generics = str_to_generics(&self.impl_generics);
-                let predicate = trait_condition_to_generic_predicate(condition);
+                let predicate = trait_condition_to_generic_predicate(&WhenCondition::Trait(
+                    generic.clone(),
+                    traits,
+                ));
 
                 handle_type_predicate(&predicate, &mut generics);
 
@@ -233,7 +438,7 @@ impl ImplBody {
         trait_generics: &Generics,
         trait_generic: &str,
     ) -> Option<String> {
-        let impl_generics = str_to_generics(&self.trait_generics);
+        let (impl_generics, _) = get_trait_types(&self.trait_generics);
 
         let trait_generic_param = get_relevant_generics_names(trait_generics, trait_generic)
             .iter()
@@ -245,6 +450,49 @@ impl ImplBody {
     }
 }
 
+/// the prefix of the placeholders of the trait arguments that are not generics, see `get_trait_types`
+const TRAIT_TYPE_PREFIX: &str = "__TT_";
+
+/**
+    get the trait arguments of an impl as generics, where each type that is not a single name
+    (e.g. `Vec<T>` in `impl<T> Foo<Vec<T>> for MyType<T>`) is replaced by a placeholder to keep the positions,
+    and the replaced types with their placeholders.
+    # Example:
+    `<Vec<T>, U>` -> (`<__TT_0__, U>`, [(`__TT_0__`, `Vec<T>`)])
+*/
+pub fn get_trait_types(trait_generics: &str) -> (Generics, Vec<(String, Type)>) {
+    let Ok(args) = syn::parse_str::<AngleBracketedGenericArguments>(trait_generics) else {
+        return (str_to_generics(trait_generics), vec![]);
+    };
+
+    let mut params = vec![];
+    let mut types = vec![];
+    for arg in args.args {
+        match arg {
+            GenericArgument::Type(Type::Path(p))
+                if p.qself.is_none() && p.path.get_ident().is_some() =>
+            {
+                params.push(to_string(&p));
+            }
+            GenericArgument::Type(type_) => {
+                let placeholder = format!("{}{}__", TRAIT_TYPE_PREFIX, types.len());
+                params.push(placeholder.clone());
+                types.push((placeholder, type_));
+            }
+            arg => params.push(to_string(&arg)),
+        }
+    }
+
+    (str_to_generics(&format!("<{}>", params.join(", "))), types)
+}
+
+/// remove the placeholders of `get_trait_types` from the generics
+pub fn remove_trait_types(generics: &mut Generics, types: &[(String, Type)]) {
+    for (placeholder, _) in types {
+        remove_generic(generics, placeholder);
+    }
+}
+
 /// from an ItemImpl returns the ItemImpl without attributes and the attributes as a Vec
 pub fn break_attr(impl_: &ItemImpl) -> (ItemImpl, Vec<Attribute>) {
     let attrs = impl_.attrs.clone();
@@ -253,6 +501,85 @@ pub fn break_attr(impl_: &ItemImpl) -> (ItemImpl, Vec<Attribute>) {
     (impl_no_attrs, attrs)
 }
 
+/**
+    the default impl generated from an impl (without attributes) for the `fallback` key of `when`,
+    its methods panic or call the fallback fn with the receiver and the arguments.
+    The arguments are numbered by their position, counting the receiver, so the first one is `__arg_1`.
+    # Example:
+    for `impl<T> Foo<T> for MyType { fn foo(&self, x: T) { ... } }` and `fallback = my_fn`
+    -> `impl<T> Foo<T> for MyType { fn foo(&self, __arg_1: T) { my_fn(self, __arg_1) } }`
+*/
+pub fn fallback_impl(impl_: &ItemImpl, fallback: &Fallback) -> ItemImpl {
+    let mut fallback_impl = impl_.clone();
+    let trait_ = fallback_impl
+        .trait_
+        .as_ref()
+        .map(|(_, path, _)| to_string(path).replace(" ", ""))
+        .unwrap_or_default();
+    let type_ = to_string(&fallback_impl.self_ty).replace(" ", "");
+
+    for item in &mut fallback_impl.items {
+        let ImplItem::Fn(fn_) = item else {
+            continue;
+        };
+
+        let mut args = vec![];
+        for (i, input) in fn_.sig.inputs.iter_mut().enumerate() {
+            match input {
+                FnArg::Receiver(_) => args.push(quote! { self }),
+                FnArg::Typed(pat_type) => {
+                    let arg = Ident::new(&format!("__arg_{}", i), proc_macro2::Span::call_site());
+                    *pat_type.pat = parse_quote! { #arg };
+                    args.push(quote! { #arg });
+                }
+            }
+        }
+
+        fn_.block = match fallback {
+            Fallback::Panic => {
+                let message = format!(
+                    "No implementation of `{}` for `{}` matches the call to `{}`",
+                    trait_, type_, fn_.sig.ident
+                );
+                parse_quote! {{
+                    let _ = (#(#args,)*);
+                    panic!(#message)
+                }}
+            }
+            Fallback::Delegate(path) => {
+                let path = str_to_expr(path);
+                parse_quote! {{ #path(#(#args),*) }}
+            }
+        };
+    }
+
+    fallback_impl
+}
+
+/**
+    the impls (without attributes) for the `for = {...}` key of `when`, one for each of the types
+    in place of the `_` the impl is for.
+    # Example:
+    for `impl<T> Foo<T> for _ { ... }` and [`A`, `B`] -> `impl<T> Foo<T> for A { ... }`, `impl<T> Foo<T> for B { ... }`
+*/
+pub fn impls_for_types(impl_: &ItemImpl, types: &[String]) -> Result<Vec<ItemImpl>, syn::Error> {
+    if !matches!(*impl_.self_ty, Type::Infer(_)) {
+        return Err(syn::Error::new_spanned(
+            &impl_.self_ty,
+            "An impl with `for = {...}` in `when` must be for `_`, the types replace it",
+        ));
+    }
+
+    Ok(types
+        .iter()
+        .map(|type_| {
+            let mut impl_ = impl_.clone();
+            impl_.self_ty = Box::new(str_to_type_name(type_));
+            impl_
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -285,6 +612,325 @@ mod tests {
         );
     }
 
+    #[test]
+    fn turbofish_trait() {
+        let get_impl_body = |tokens| {
+            ImplBody::try_from((
+                tokens,
+                Some(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+            ))
+            .unwrap()
+        };
+
+        let turbofish =
+            get_impl_body(quote! { impl<T> Foo::<u8, T> for MyType { fn foo(&self, x: T) {} } });
+        let plain =
+            get_impl_body(quote! { impl<T> Foo<u8, T> for MyType { fn foo(&self, x: T) {} } });
+
+        assert_eq!(turbofish.trait_name, "Foo");
+        assert_eq!(format!("{:?}", turbofish), format!("{:?}", plain));
+    }
+
+    #[test]
+    fn trait_types() {
+        let (generics, types) = get_trait_types("<'a, Vec<T>, U, (A, B)>");
+
+        assert_eq!(
+            to_string(&generics).replace(" ", ""),
+            "<'a,__TT_0__,U,__TT_1__>"
+        );
+        assert_eq!(
+            types
+                .iter()
+                .map(|(placeholder, type_)| (
+                    placeholder.as_str(),
+                    to_string(type_).replace(" ", "")
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("__TT_0__", "Vec<T>".to_string()),
+                ("__TT_1__", "(A,B)".to_string())
+            ]
+        );
+
+        let (generics, types) = get_trait_types("");
+        assert!(generics.params.is_empty());
+        assert!(types.is_empty());
+    }
+
+    #[test]
+    fn corresponding_generic_after_trait_type() {
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<'a, T, U> Foo<'a, Vec<T>, U> for MyType<T> {
+                    fn foo(&self, x: Vec<T>, y: U) {}
+                }
+            },
+            None,
+        ))
+        .unwrap();
+        let trait_generics = str_to_generics("<'b, A, B>");
+
+        // `Vec<T>` keeps its position, so `B` is still given `U`
+        assert_eq!(
+            impl_body.get_corresponding_generic(&trait_generics, "B"),
+            Some("U".to_string())
+        );
+        assert_eq!(
+            impl_body.get_corresponding_generic(&trait_generics, "'b"),
+            Some("'a".to_string())
+        );
+        assert_eq!(
+            impl_body
+                .get_corresponding_generic(&trait_generics, "A")
+                .map(|generic| generic.starts_with(TRAIT_TYPE_PREFIX)),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn fallback_methods() {
+        let impl_ = tokens_to_impl(quote! {
+            impl<T> Foo<T> for MyType {
+                type Bar = ();
+                fn foo(&self, (a, b): (T, u8)) -> T { a }
+            }
+        })
+        .unwrap();
+
+        let panic = to_string(&fallback_impl(&impl_, &Fallback::Panic)).replace(" ", "");
+        assert!(panic.contains("typeBar=();"));
+        assert!(panic.contains(
+            "fnfoo(&self,__arg_1:(T,u8))->T{let_=(self,__arg_1,);panic!(\"Noimplementationof`Foo<T>`for`MyType`matchesthecallto`foo`\")}"
+        ));
+
+        let delegate = to_string(&fallback_impl(
+            &impl_,
+            &Fallback::Delegate("default_foo".to_string()),
+        ))
+        .replace(" ", "");
+        assert!(delegate.contains("fnfoo(&self,__arg_1:(T,u8))->T{default_foo(self,__arg_1)}"));
+    }
+
+    #[test]
+    fn requires_in_where_clause() {
+        let impl_body = ImplBody {
+            requires: vec!["Vec<T>: Debug".into()],
+            ..ImplBody::try_from((
+                quote! {
+                    impl<T> Foo for MyType<T> {
+                        fn foo(&self) {}
+                    }
+                },
+                Some(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+            ))
+            .unwrap()
+        };
+
+        // the bound is added as it is, next to the one from the condition
+        let tokens = TokenStream::from(&impl_body).to_string().replace(" ", "");
+        assert!(tokens.contains("impl<T:Copy>"));
+        assert!(tokens.contains("forMyType<T>whereVec<T>:Debug{"));
+    }
+
+    #[test]
+    fn apply_trait_condition_with_existing_bounds() {
+        let get_impl_body = |condition| {
+            ImplBody::try_from((
+                quote! {
+                    impl<T: Debug> Foo for T {
+                        fn foo(&self) {}
+                    }
+                },
+                Some(condition),
+            ))
+            .unwrap()
+            .specialized
+            .unwrap()
+        };
+
+        let impl_body = get_impl_body(WhenCondition::Trait("T".into(), vec!["Clone".into()]));
+        assert_eq!(
+            impl_body.impl_generics.replace(" ", ""),
+            "<T: Debug + Clone>".to_string().replace(" ", "")
+        );
+
+        let impl_body = get_impl_body(WhenCondition::Trait(
+            "T".into(),
+            vec!["Clone".into(), "Debug".into(), "Clone".into()],
+        ));
+        assert_eq!(
+            impl_body.impl_generics.replace(" ", ""),
+            "<T: Debug + Clone>".to_string().replace(" ", "")
+        );
+
+        // the wildcard is not added as a bound
+        let impl_body = get_impl_body(WhenCondition::Trait(
+            "T".into(),
+            vec![ANY_TRAIT.into(), "Clone".into()],
+        ));
+        assert_eq!(
+            impl_body.impl_generics.replace(" ", ""),
+            "<T: Debug + Clone>".to_string().replace(" ", "")
+        );
+
+        let impl_body = get_impl_body(WhenCondition::Trait("T".into(), vec![ANY_TRAIT.into()]));
+        assert_eq!(impl_body.impl_generics.replace(" ", ""), "<T:Debug>");
+    }
+
+    #[test]
+    fn apply_trait_condition_with_associated_type_predicate() {
+        let condition = WhenCondition::Trait("T".into(), vec!["Clone".into()]);
+
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T> Foo<T> for MyType where T: Iterator<Item = u8> {
+                    fn foo(&self, arg: T) {}
+                }
+            },
+            Some(condition),
+        ))
+        .unwrap()
+        .specialized
+        .unwrap();
+
+        assert_eq!(
+            impl_body.impl_generics.replace(" ", ""),
+            "<T: Iterator<Item = u8> + Clone>"
+                .to_string()
+                .replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn full_type_name() {
+        let mut impl_body =
+            ImplBody::try_from((quote! { impl Foo for MyType { fn foo(&self) {} } }, None))
+                .unwrap();
+        assert_eq!(impl_body.full_type_name(), "MyType");
+
+        // only a type declared in the module is in the module
+        impl_body.module = "a::b".into();
+        assert_eq!(impl_body.full_type_name(), "MyType");
+        impl_body.local_type = true;
+        assert_eq!(impl_body.full_type_name(), "a::b::MyType");
+
+        let full_type_name = |type_name: &str| {
+            ImplBody {
+                type_name: type_name.into(),
+                local_type: false,
+                ..impl_body.clone()
+            }
+            .full_type_name()
+        };
+        assert_eq!(full_type_name("& MyType"), "& MyType");
+        assert_eq!(full_type_name("Vec < u8 >"), "Vec < u8 >");
+        assert_eq!(full_type_name("T"), "T");
+        assert_eq!(full_type_name("super :: MyType"), "a::MyType");
+        assert_eq!(full_type_name("super :: super :: MyType"), "MyType");
+        assert_eq!(full_type_name("self :: MyType < T >"), "a::b::MyType < T >");
+        assert_eq!(full_type_name("crate :: c :: MyType"), "c::MyType");
+        assert_eq!(full_type_name(":: other :: MyType"), ":: other :: MyType");
+    }
+
+    #[test]
+    fn unused_generics() {
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<'a, 'b, T: 'b, U: Clone> Foo<T> for MyType {
+                    fn foo(&self, x: T) {}
+                }
+            },
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(impl_body.unused_generics(), vec!["'a", "U"]);
+    }
+
+    #[test]
+    fn unused_generics_used_in_bounds() {
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T: From<U>, U, V> Foo<T> for Vec<V> {
+                    fn foo(&self, x: T) {}
+                }
+            },
+            None,
+        ))
+        .unwrap();
+
+        assert!(impl_body.unused_generics().is_empty());
+        assert_eq!(
+            impl_body
+                .specialized
+                .unwrap()
+                .impl_generics
+                .replace(" ", ""),
+            "<T: From<U>, U, V>".to_string().replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn unused_generics_phantom_data() {
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T, U> Foo for Marker<U> {
+                    const MARKER: std::marker::PhantomData<T> = std::marker::PhantomData;
+                    fn foo(&self) {}
+                }
+            },
+            None,
+        ))
+        .unwrap();
+
+        assert!(impl_body.unused_generics().is_empty());
+
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T> Foo for PhantomData<T> {
+                    fn foo(&self) {}
+                }
+            },
+            Some(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+        ))
+        .unwrap()
+        .specialized
+        .unwrap();
+
+        assert!(impl_body.unused_generics().is_empty());
+        assert_eq!(
+            impl_body.impl_generics.replace(" ", ""),
+            "<T:Copy>".to_string()
+        );
+        assert_eq!(impl_body.type_name.replace(" ", ""), "PhantomData<T>");
+    }
+
+    #[test]
+    fn unused_generics_after_type_condition() {
+        let condition = WhenCondition::Type("T".into(), "String".into());
+
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<'a, T, U: Copy> Foo<T> for MyType {
+                    fn foo(&self, x: T) {}
+                }
+            },
+            Some(condition),
+        ))
+        .unwrap()
+        .specialized
+        .unwrap();
+
+        assert!(impl_body.unused_generics().is_empty());
+        assert_eq!(impl_body.impl_generics.replace(" ", ""), "");
+        assert_eq!(impl_body.trait_generics.replace(" ", ""), "");
+        assert_eq!(
+            impl_body.items[0].replace(" ", ""),
+            "fnfoo(&self,x:String){}"
+        );
+    }
+
     #[test]
     fn apply_type_condition() {
         let condition = WhenCondition::Type("T".into(), "String".into());
@@ -297,7 +943,7 @@ mod tests {
             "<U: Copy>".to_string().replace(" ", "")
         );
         assert_eq!(
-            impl_body.trait_generics.replace(" ", "").replace(",>", ">"),
+            impl_body.trait_generics.replace(" ", ""),
             "<U>".to_string().replace(" ", "")
         );
         assert_eq!(
@@ -329,7 +975,7 @@ mod tests {
             "<U: Copy, __G_0__>".to_string().replace(" ", "")
         );
         assert_eq!(
-            impl_body.trait_generics.replace(" ", "").replace(",>", ">"),
+            impl_body.trait_generics.replace(" ", ""),
             "<U, __G_0__>".to_string().replace(" ", "")
         );
         assert_eq!(
@@ -358,7 +1004,7 @@ mod tests {
             "<'a, U: Copy, __G_0__>".to_string().replace(" ", "")
         );
         assert_eq!(
-            impl_body.trait_generics.replace(" ", "").replace(",>", ">"),
+            impl_body.trait_generics.replace(" ", ""),
             "<'a, U, __G_0__>".to_string().replace(" ", "")
         );
         assert_eq!(
@@ -375,6 +1021,151 @@ mod tests {
         );
     }
 
+    #[test]
+    fn apply_trait_and_lifetime_condition() {
+        let condition = WhenCondition::try_from(quote! { T: 'a + Clone }).unwrap();
+
+        let impl_body = get_impl_body(Some(condition)).specialized.unwrap();
+
+        // the lifetime bound is kept in the type the generic is replaced with
+        assert_eq!(impl_body.type_name, "& 'a __G_0__".to_string());
+        assert_eq!(
+            impl_body.trait_generics.replace(" ", ""),
+            "<'a, U, __G_0__>".to_string().replace(" ", "")
+        );
+        assert_eq!(
+            impl_body.items[1].replace(" ", ""),
+            "fn foo(&self, arg1: Vec<&'a __G_0__>, arg2: U) -> &'a __G_0__ { let x: &'a __G_0__ = arg1[0].clone(); x }"
+                .replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn apply_self_condition() {
+        let impl_ = quote! {
+            impl<T: Copy, U> Foo<U> for Wrapper<T> {
+                fn foo(&self, arg1: U) -> T { self.0 }
+            }
+        };
+        let condition = WhenCondition::All(vec![
+            WhenCondition::Type("Self".into(), "Wrapper<i32>".into()),
+            WhenCondition::Trait("U".into(), vec!["Clone".into()]),
+        ]);
+
+        let impl_body = ImplBody::try_from((impl_, Some(condition)))
+            .unwrap()
+            .specialized
+            .unwrap();
+
+        assert_eq!(impl_body.type_name.replace(" ", ""), "Wrapper<i32>");
+        assert_eq!(impl_body.impl_generics.replace(" ", ""), "<U:Clone>");
+        assert_eq!(impl_body.trait_generics.replace(" ", ""), "<U>");
+        assert_eq!(
+            impl_body.items[0].replace(" ", ""),
+            "fn foo(&self, arg1: U) -> i32 { self.0 }".replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn apply_const_condition() {
+        let impl_ = quote! {
+            impl<const N: usize, T: Copy> Foo<T> for [u8; N] {
+                fn foo(&self, arg1: [T; N]) -> usize { N }
+            }
+        };
+        let condition = WhenCondition::Const("N".into(), "0".into());
+
+        let impl_body = ImplBody::try_from((impl_, Some(condition)))
+            .unwrap()
+            .specialized
+            .unwrap();
+
+        assert_eq!(impl_body.type_name.replace(" ", ""), "[u8;0]");
+        assert_eq!(impl_body.impl_generics.replace(" ", ""), "<T:Copy>");
+        assert_eq!(impl_body.trait_generics.replace(" ", ""), "<T>");
+        assert_eq!(
+            impl_body.items[0].replace(" ", ""),
+            "fn foo(&self, arg1: [T; 0]) -> usize { 0 }".replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn apply_condition_reference_receiver() {
+        let impl_ = quote! {
+            impl<'a, T: Copy> Foo for &'a Wrapper<T> {
+                fn foo(&self) -> T { self.0 }
+            }
+        };
+
+        let condition = WhenCondition::Type("T".into(), "u8".into());
+        let impl_body = ImplBody::try_from((impl_.clone(), Some(condition)))
+            .unwrap()
+            .specialized
+            .unwrap();
+        assert_eq!(impl_body.type_name.replace(" ", ""), "&'aWrapper<u8>");
+        assert_eq!(impl_body.impl_generics.replace(" ", ""), "<'a>");
+
+        let condition = WhenCondition::Type("Self".into(), "&Wrapper<i32>".into());
+        let impl_body = ImplBody::try_from((impl_, Some(condition)))
+            .unwrap()
+            .specialized
+            .unwrap();
+        assert_eq!(impl_body.type_name.replace(" ", ""), "&'aWrapper<i32>");
+        assert_eq!(
+            impl_body.items[0].replace(" ", ""),
+            "fn foo(&self) -> i32 { self.0 }".replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn apply_condition_associated_const() {
+        let impl_ = quote! {
+            impl<T: Default> Foo<T> for MyType {
+                const SIZE: usize = std::mem::size_of::<T>();
+                const EMPTY: Option<T> = None::<T>;
+                const ZERO: T = T::ZERO;
+                fn foo(&self, x: T) -> T { T::default() }
+            }
+        };
+
+        let condition = WhenCondition::Type("T".into(), "u32".into());
+        let impl_body = ImplBody::try_from((impl_.clone(), Some(condition)))
+            .unwrap()
+            .specialized
+            .unwrap();
+
+        assert_eq!(impl_body.impl_generics.replace(" ", ""), "");
+        assert_eq!(
+            impl_body
+                .items
+                .iter()
+                .map(|item| item.replace(" ", ""))
+                .collect::<Vec<_>>(),
+            vec![
+                "const SIZE: usize = std::mem::size_of::<u32>();".replace(" ", ""),
+                "const EMPTY: Option<u32> = None::<u32>;".replace(" ", ""),
+                "const ZERO: u32 = u32::ZERO;".replace(" ", ""),
+                "fn foo(&self, x: u32) -> u32 { u32::default() }".replace(" ", ""),
+            ]
+        );
+
+        // a type that is not an identifier is qualified in the paths
+        let condition = WhenCondition::Type("T".into(), "Vec<u8>".into());
+        let impl_body = ImplBody::try_from((impl_, Some(condition)))
+            .unwrap()
+            .specialized
+            .unwrap();
+
+        assert_eq!(
+            impl_body.items[2].replace(" ", ""),
+            "const ZERO: Vec<u8> = <Vec<u8>>::ZERO;".replace(" ", "")
+        );
+        assert_eq!(
+            impl_body.items[3].replace(" ", ""),
+            "fn foo(&self, x: Vec<u8>) -> Vec<u8> { <Vec<u8>>::default() }".replace(" ", "")
+        );
+    }
+
     #[test]
     fn apply_type_condition_all() {
         let condition = WhenCondition::All(vec![
@@ -394,7 +1185,7 @@ mod tests {
             "<U: Copy>".to_string().replace(" ", "")
         );
         assert_eq!(
-            impl_body.trait_generics.replace(" ", "").replace(",>", ">"),
+            impl_body.trait_generics.replace(" ", ""),
             "<U>".to_string().replace(" ", "")
         );
         assert_eq!(
@@ -425,7 +1216,7 @@ mod tests {
             "<T: Clone, U: Copy>".to_string().replace(" ", "")
         );
         assert_eq!(
-            impl_body.trait_generics.replace(" ", "").replace(",>", ">"),
+            impl_body.trait_generics.replace(" ", ""),
             "<T, U>".to_string().replace(" ", "")
         );
         assert_eq!(
@@ -442,4 +1233,32 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn impls_for_each_type() {
+        let impl_ = tokens_to_impl(quote! {
+            impl<T> Foo<T> for _ {
+                fn foo(&self, x: T) {}
+            }
+        })
+        .unwrap();
+
+        let impls = impls_for_types(&impl_, &["A".into(), "B<u8>".into()]).unwrap();
+
+        assert_eq!(
+            impls
+                .iter()
+                .map(|impl_| to_string(&impl_.self_ty).replace(" ", ""))
+                .collect::<Vec<_>>(),
+            vec!["A", "B<u8>"]
+        );
+        assert!(
+            impls
+                .iter()
+                .all(|i| to_string(&i.items[0]) == to_string(&impl_.items[0]))
+        );
+
+        let impl_ = tokens_to_impl(quote! { impl Foo for A {} }).unwrap();
+        assert!(impls_for_types(&impl_, &["B".into()]).is_err());
+    }
 }
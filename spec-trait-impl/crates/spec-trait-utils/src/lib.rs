@@ -3,7 +3,12 @@ pub mod conditions;
 pub mod conversions;
 pub mod env;
 pub mod impls;
+pub mod overlaps;
 pub mod parsing;
+pub mod pretty;
 mod specialize;
 pub mod traits;
+pub mod type_aliases;
+pub mod type_traits;
 pub mod types;
+pub mod when;
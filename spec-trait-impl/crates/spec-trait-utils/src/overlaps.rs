@@ -0,0 +1,586 @@
+use crate::conditions::WhenCondition;
+use crate::conversions::{str_to_generics, str_to_type_name, to_string};
+use crate::impls::ImplBody;
+use crate::parsing::{ANY_TRAIT, contains_trait, get_generics_types, traits_specificity};
+use crate::types::{Aliases, replace_type, specificity, strip_lifetimes, type_assignable};
+use proc_macro2::TokenStream;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::hash::Hash;
+use syn::{AngleBracketedGenericArguments, GenericArgument, Type};
+
+/// two impls of the same trait for the same type whose conditions can be satisfied by the same types with equal specificity
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub trait_name: String,
+    pub type_name: String,
+    pub first: WhenCondition,
+    pub second: WhenCondition,
+}
+
+impl Display for Overlap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "impls of `{}` for `{}` with `#[when({})]` and `#[when({})]` can be equally specific for the same types",
+            self.trait_name,
+            self.type_name.replace(" ", ""),
+            self.first,
+            self.second
+        )
+    }
+}
+
+/// the conditions on a single generic in a conjunction
+#[derive(Debug, Default)]
+struct GenericConditions {
+    types: Vec<String>,
+    traits: Vec<String>,
+    not_types: Vec<String>,
+    not_traits: Vec<String>,
+}
+
+/**
+    find the pairs of impls with a condition, for the same trait and type, that could make a `spec!` call ambiguous
+    whatever the call site is.
+    The check is conservative on traits: any type could implement them, so only explicit negations exclude an overlap.
+*/
+pub fn find_overlaps(impls: &[ImplBody]) -> Vec<Overlap> {
+    let conditional = impls
+        .iter()
+        .filter(|impl_| impl_.condition.is_some())
+        .collect::<Vec<_>>();
+
+    let mut overlaps = vec![];
+
+    for (i, first) in conditional.iter().enumerate() {
+        for second in &conditional[i + 1..] {
+            if first.trait_name == second.trait_name
+                && first.full_type_name() == second.full_type_name()
+                && !first.same_source(second)
+                && first.priority == second.priority
+                && may_overlap(first, second)
+            {
+                overlaps.push(Overlap {
+                    trait_name: first.trait_name.clone(),
+                    type_name: first.full_type_name(),
+                    first: first.condition.clone().unwrap(),
+                    second: second.condition.clone().unwrap(),
+                });
+            }
+        }
+    }
+
+    overlaps
+}
+
+fn may_overlap(first: &ImplBody, second: &ImplBody) -> bool {
+    let first_conditions = get_generics_conditions(first);
+    let second_conditions = get_generics_conditions(second);
+
+    let compatible = first_conditions.iter().all(|(generic, c1)| {
+        second_conditions.get(generic).is_none_or(|c2| {
+            compatible(c1, &first.impl_generics, c2, &second.impl_generics)
+                && compatible(c2, &second.impl_generics, c1, &first.impl_generics)
+        })
+    });
+
+    let (first_len, first_not_lens) = get_len_conditions(first);
+    let (second_len, second_not_lens) = get_len_conditions(second);
+
+    let lens_compatible = first_len.is_none_or(|l| {
+        second_len.is_none_or(|other_l| l == other_l) && !second_not_lens.contains(&l)
+    }) && second_len.is_none_or(|l| !first_not_lens.contains(&l));
+
+    let (first_lifetimes, first_not_lifetimes) = get_lifetime_conditions(first);
+    let (second_lifetimes, second_not_lifetimes) = get_lifetime_conditions(second);
+
+    let (first_args, first_not_args) = get_arg_value_conditions(first);
+    let (second_args, second_not_args) = get_arg_value_conditions(second);
+
+    compatible
+        && lens_compatible
+        && values_compatible(&first_lifetimes, &second_lifetimes, &second_not_lifetimes)
+        && values_compatible(&second_lifetimes, &first_lifetimes, &first_not_lifetimes)
+        && values_compatible(&first_args, &second_args, &second_not_args)
+        && values_compatible(&second_args, &first_args, &first_not_args)
+        && cmp_specificity(
+            &first_conditions,
+            &first.impl_generics,
+            &second_conditions,
+            &second.impl_generics,
+        )
+        .then(first_lifetimes.len().cmp(&second_lifetimes.len()))
+        .then(first_len.is_some().cmp(&second_len.is_some()))
+        .then(first_args.len().cmp(&second_args.len()))
+            == Ordering::Equal
+}
+
+/// whether the values required by an impl (e.g. for its lifetime generics) are allowed by the ones of the other impl
+fn values_compatible<K: Eq + Hash + Clone>(
+    this: &HashMap<K, String>,
+    other: &HashMap<K, String>,
+    other_not: &[(K, String)],
+) -> bool {
+    this.iter().all(|(key, value)| {
+        other.get(key).is_none_or(|v| v == value)
+            && !other_not.contains(&(key.clone(), value.clone()))
+    })
+}
+
+/// the atomic conditions of the conjunction of the impl
+fn get_atoms(impl_: &ImplBody) -> Vec<WhenCondition> {
+    match &impl_.condition {
+        Some(WhenCondition::All(inner)) => inner.clone(),
+        Some(condition) => vec![condition.clone()],
+        None => vec![],
+    }
+}
+
+/// the lifetimes required by the conditions of the impl for its lifetime generics and the ones it excludes
+fn get_lifetime_conditions(impl_: &ImplBody) -> (HashMap<String, String>, Vec<(String, String)>) {
+    let mut lifetimes = HashMap::new();
+    let mut not_lifetimes = vec![];
+
+    for atom in get_atoms(impl_) {
+        match atom {
+            WhenCondition::Lifetime(generic, lifetime) => {
+                lifetimes.insert(generic, lifetime);
+            }
+            WhenCondition::Not(inner) => {
+                if let WhenCondition::Lifetime(generic, lifetime) = *inner {
+                    not_lifetimes.push((generic, lifetime));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (lifetimes, not_lifetimes)
+}
+
+/// the slice element count required by the conditions of the impl and the ones it excludes
+fn get_len_conditions(impl_: &ImplBody) -> (Option<usize>, Vec<usize>) {
+    let mut len = None;
+    let mut not_lens = vec![];
+
+    for atom in get_atoms(impl_) {
+        match atom {
+            WhenCondition::Len(l) => len = Some(l),
+            WhenCondition::Not(inner) => {
+                if let WhenCondition::Len(l) = *inner {
+                    not_lens.push(l);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (len, not_lens)
+}
+
+/// the argument values required by the conditions of the impl and the ones it excludes
+fn get_arg_value_conditions(impl_: &ImplBody) -> (HashMap<usize, String>, Vec<(usize, String)>) {
+    let mut values = HashMap::new();
+    let mut not_values = vec![];
+
+    for atom in get_atoms(impl_) {
+        match atom {
+            WhenCondition::Arg(position, value) => {
+                values.insert(position, value);
+            }
+            WhenCondition::Not(inner) => {
+                if let WhenCondition::Arg(position, value) = *inner {
+                    not_values.push((position, value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (values, not_values)
+}
+
+/**
+    group the conditions of the impl by generic.
+    The generics used as trait arguments are named after their position, so that impls with different names for
+    the same generic can be compared.
+    # Example:
+    for `#[when(all(T = u8, U: Copy))] impl<T, U> Foo<T, U> for MyType` -> `{0: [T = u8], 1: [U: Copy]}`
+*/
+fn get_generics_conditions(impl_: &ImplBody) -> HashMap<String, GenericConditions> {
+    let positions = syn::parse_str::<AngleBracketedGenericArguments>(&impl_.trait_generics)
+        .map(|args| {
+            args.args
+                .iter()
+                .enumerate()
+                .filter_map(|(position, arg)| match arg {
+                    GenericArgument::Type(Type::Path(p)) => {
+                        Some((p.path.get_ident()?.to_string(), position.to_string()))
+                    }
+                    _ => None,
+                })
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+
+    let mut conditions = HashMap::<String, GenericConditions>::new();
+
+    for atom in get_atoms(impl_) {
+        let (generic, negated, inner) = match atom {
+            WhenCondition::Not(inner) => match *inner {
+                WhenCondition::Type(ref generic, _) | WhenCondition::Trait(ref generic, _) => {
+                    (generic.clone(), true, *inner)
+                }
+                _ => continue,
+            },
+            WhenCondition::Type(ref generic, _) | WhenCondition::Trait(ref generic, _) => {
+                (generic.clone(), false, atom)
+            }
+            _ => continue,
+        };
+
+        let key = positions.get(&generic).unwrap_or(&generic).clone();
+        let entry = conditions.entry(key).or_default();
+
+        match (inner, negated) {
+            (WhenCondition::Type(_, type_), false) => entry.types.push(type_),
+            (WhenCondition::Type(_, type_), true) => entry.not_types.push(type_),
+            (WhenCondition::Trait(_, traits), false) => entry.traits.extend(traits),
+            (WhenCondition::Trait(_, traits), true) => entry.not_traits.extend(traits),
+            _ => {}
+        }
+    }
+
+    conditions
+}
+
+/// check that no condition in `other` excludes all the types satisfying `this`
+fn compatible(
+    this: &GenericConditions,
+    this_generics: &str,
+    other: &GenericConditions,
+    other_generics: &str,
+) -> bool {
+    let aliases = Aliases::default();
+
+    let types_unify = this.types.iter().all(|t1| {
+        other.types.iter().all(|t2| {
+            type_assignable(t1, t2, other_generics, &aliases)
+                || type_assignable(t2, t1, this_generics, &aliases)
+        })
+    });
+
+    // `_` and generics can be any type, so they must not match the excluded type on their own
+    let types_not_excluded = this.types.iter().all(|t1| {
+        let mut t1 = str_to_type_name(t1);
+        let any = str_to_type_name("__Any__");
+        replace_type(&mut t1, "_", &any);
+        for g in get_generics_types::<Vec<_>>(this_generics) {
+            replace_type(&mut t1, &g, &any);
+        }
+        let t1 = to_string(&t1);
+
+        other
+            .not_types
+            .iter()
+            .all(|nt| !type_assignable(&t1, nt, other_generics, &aliases))
+    });
+
+    // the wildcard excludes every trait
+    let traits_not_excluded = this.traits.iter().all(|t| {
+        !contains_trait(&other.not_traits, t) && !other.not_traits.iter().any(|nt| nt == ANY_TRAIT)
+    });
+
+    types_unify && types_not_excluded && traits_not_excluded
+}
+
+/// compare the conditions as `spec!` compares the constraints of the impls it selects
+fn cmp_specificity(
+    first: &HashMap<String, GenericConditions>,
+    first_generics: &str,
+    second: &HashMap<String, GenericConditions>,
+    second_generics: &str,
+) -> Ordering {
+    let default = GenericConditions::default();
+
+    let mut keys = first.keys().chain(second.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+
+    let sum: i32 = keys
+        .into_iter()
+        .map(|key| {
+            let c1 = first.get(key).unwrap_or(&default);
+            let c2 = second.get(key).unwrap_or(&default);
+
+            let ord = type_specificity(c1, first_generics, false)
+                .cmp(&type_specificity(c2, second_generics, false))
+                .then(
+                    type_specificity(c1, first_generics, true).cmp(&type_specificity(
+                        c2,
+                        second_generics,
+                        true,
+                    )),
+                )
+                .then(traits_specificity(&c1.traits).cmp(&traits_specificity(&c2.traits)))
+                .then(c1.not_types.len().cmp(&c2.not_types.len()))
+                .then(c1.not_traits.len().cmp(&c2.not_traits.len()));
+
+            match ord {
+                Ordering::Greater => 1,
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+            }
+        })
+        .sum();
+
+    sum.cmp(&0)
+}
+
+/// the specificity of the most specific type, without wildcards, generics and lifetimes (except `'static` if `with_static`)
+fn type_specificity(
+    conditions: &GenericConditions,
+    generics: &str,
+    with_static: bool,
+) -> Option<usize> {
+    conditions
+        .types
+        .iter()
+        .map(|type_| {
+            let mut ty = str_to_type_name(type_);
+            let empty_type = Type::Verbatim(TokenStream::new());
+
+            replace_type(&mut ty, "_", &empty_type);
+            strip_lifetimes(&mut ty, &str_to_generics(generics));
+            if !with_static {
+                strip_lifetimes(&mut ty, &str_to_generics("<'static>"));
+            }
+            for g in get_generics_types::<Vec<_>>(generics) {
+                replace_type(&mut ty, &g, &empty_type);
+            }
+
+            specificity(&ty)
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    fn get_impl_body(impl_: TokenStream, condition: WhenCondition) -> ImplBody {
+        ImplBody::try_from((impl_, Some(condition))).unwrap()
+    }
+
+    fn get_foo_impl(condition: WhenCondition) -> ImplBody {
+        get_impl_body(
+            quote! { impl<T> Foo<T> for MyType { fn foo(&self, x: T) {} } },
+            condition,
+        )
+    }
+
+    #[test]
+    fn overlapping_pair() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::Type("T".into(), "(i32, _)".into())),
+            get_foo_impl(WhenCondition::Type("T".into(), "(_, u8)".into())),
+        ];
+
+        let overlaps = find_overlaps(&impls);
+
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].trait_name, "Foo");
+        assert_eq!(overlaps[0].type_name, "MyType");
+        assert_eq!(overlaps[0].first, impls[0].condition.clone().unwrap());
+        assert_eq!(overlaps[0].second, impls[1].condition.clone().unwrap());
+        assert_eq!(
+            overlaps[0].to_string(),
+            "impls of `Foo` for `MyType` with `#[when(T = (i32,_))]` and `#[when(T = (_,u8))]` can be equally specific for the same types"
+        );
+    }
+
+    #[test]
+    fn sibling_conjunctions_not_overlapping() {
+        let source = WhenCondition::Any(vec![
+            WhenCondition::Trait("T".into(), vec!["Copy".into()]),
+            WhenCondition::Trait("T".into(), vec!["Debug".into()]),
+        ]);
+        let impls = crate::conditions::get_conjunctions(source.clone())
+            .into_iter()
+            .map(|conjunction| ImplBody {
+                source_condition: Some(source.clone()),
+                ..get_foo_impl(conjunction)
+            })
+            .collect::<Vec<_>>();
+
+        assert!(find_overlaps(&impls).is_empty());
+    }
+
+    #[test]
+    fn different_priorities_not_overlapping() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+            ImplBody {
+                priority: 1,
+                ..get_foo_impl(WhenCondition::Trait("T".into(), vec!["Debug".into()]))
+            },
+        ];
+
+        assert!(find_overlaps(&impls).is_empty());
+    }
+
+    #[test]
+    fn overlapping_traits_with_renamed_generics() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+            get_impl_body(
+                quote! { impl<U> Foo<U> for MyType { fn foo(&self, x: U) {} } },
+                WhenCondition::Trait("U".into(), vec!["Debug".into()]),
+            ),
+        ];
+
+        assert_eq!(find_overlaps(&impls).len(), 1);
+    }
+
+    #[test]
+    fn more_specific_pair() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::Type("T".into(), "Vec<_>".into())),
+            get_foo_impl(WhenCondition::Type("T".into(), "Vec<u8>".into())),
+            get_foo_impl(WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "Vec<_>".into()),
+                WhenCondition::Trait("T".into(), vec!["Debug".into()]),
+            ])),
+        ];
+
+        assert!(find_overlaps(&impls).is_empty());
+    }
+
+    #[test]
+    fn static_lifetime_is_more_specific() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::Type("T".into(), "&'static _".into())),
+            get_foo_impl(WhenCondition::Type("T".into(), "&_".into())),
+        ];
+
+        assert!(find_overlaps(&impls).is_empty());
+    }
+
+    #[test]
+    fn disjoint_pairs() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::Type("T".into(), "u8".into())),
+            get_foo_impl(WhenCondition::Type("T".into(), "i32".into())),
+            get_foo_impl(WhenCondition::Not(Box::new(WhenCondition::Type(
+                "T".into(),
+                "Vec<_>".into(),
+            )))),
+            get_foo_impl(WhenCondition::Not(Box::new(WhenCondition::Type(
+                "T".into(),
+                "String".into(),
+            )))),
+        ];
+
+        // `u8` and `i32` are disjoint, and the negations are less specific than them
+        let overlaps = find_overlaps(&impls);
+
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].first.to_string(), "not(T = Vec<_>)".to_string());
+        assert_eq!(overlaps[0].second.to_string(), "not(T = String)");
+    }
+
+    #[test]
+    fn excluded_by_negation() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "Vec<_>".into()),
+                WhenCondition::Trait("T".into(), vec!["Copy".into()]),
+            ])),
+            get_foo_impl(WhenCondition::All(vec![
+                WhenCondition::Not(Box::new(WhenCondition::Type("T".into(), "Vec<_>".into()))),
+                WhenCondition::Trait("T".into(), vec!["Clone".into()]),
+            ])),
+            get_foo_impl(WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "Vec<u8>".into()),
+                WhenCondition::Not(Box::new(WhenCondition::Trait(
+                    "T".into(),
+                    vec!["Copy".into()],
+                ))),
+            ])),
+        ];
+
+        assert!(find_overlaps(&impls).is_empty());
+    }
+
+    #[test]
+    fn different_types() {
+        let impls = vec![
+            get_foo_impl(WhenCondition::Type("T".into(), "u8".into())),
+            get_impl_body(
+                quote! { impl<T> Foo<T> for OtherType { fn foo(&self, x: T) {} } },
+                WhenCondition::Type("T".into(), "u8".into()),
+            ),
+        ];
+
+        assert!(find_overlaps(&impls).is_empty());
+    }
+
+    #[test]
+    fn slice_len_conditions() {
+        let slice = WhenCondition::Type("T".into(), "&[u8]".into());
+        let with_len = |len| WhenCondition::All(vec![slice.clone(), WhenCondition::Len(len)]);
+
+        // an impl constrained on the element count is more specific than an unconstrained one
+        let impls = vec![get_foo_impl(slice.clone()), get_foo_impl(with_len(3))];
+        assert!(find_overlaps(&impls).is_empty());
+
+        // different element counts exclude each other
+        let impls = vec![get_foo_impl(with_len(3)), get_foo_impl(with_len(4))];
+        assert!(find_overlaps(&impls).is_empty());
+
+        let impls = vec![get_foo_impl(with_len(3)), get_foo_impl(with_len(3))];
+        assert_eq!(find_overlaps(&impls).len(), 1);
+    }
+
+    #[test]
+    fn arg_value_conditions() {
+        let type_ = WhenCondition::Type("T".into(), "i32".into());
+        let with_arg = |value: &str| {
+            WhenCondition::All(vec![type_.clone(), WhenCondition::Arg(0, value.into())])
+        };
+
+        // an impl constrained on the argument value is more specific than an unconstrained one
+        let impls = vec![get_foo_impl(type_.clone()), get_foo_impl(with_arg("0"))];
+        assert!(find_overlaps(&impls).is_empty());
+
+        // different values exclude each other
+        let impls = vec![get_foo_impl(with_arg("0")), get_foo_impl(with_arg("1"))];
+        assert!(find_overlaps(&impls).is_empty());
+
+        let impls = vec![get_foo_impl(with_arg("0")), get_foo_impl(with_arg("0"))];
+        assert_eq!(find_overlaps(&impls).len(), 1);
+    }
+
+    #[test]
+    fn lifetime_generic_conditions() {
+        let get_impl = |condition: WhenCondition| {
+            get_impl_body(
+                quote! { impl<'a> Foo for MyType<'a> { fn foo(&self) {} } },
+                condition,
+            )
+        };
+        let lifetime = |lt: &str| WhenCondition::Lifetime("'a".into(), lt.into());
+
+        let impls = vec![get_impl(lifetime("'static")), get_impl(lifetime("'static"))];
+        assert_eq!(find_overlaps(&impls).len(), 1);
+
+        let impls = vec![
+            get_impl(lifetime("'static")),
+            get_impl(WhenCondition::Not(Box::new(lifetime("'static")))),
+        ];
+        assert!(find_overlaps(&impls).is_empty());
+    }
+}
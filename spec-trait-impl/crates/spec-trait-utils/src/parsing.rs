@@ -1,12 +1,17 @@
 use crate::conversions::{str_to_generics, to_string};
 use crate::specialize::{add_generic_type, collect_generics_lifetimes, collect_generics_types};
+use crate::types::normalize_const_value;
 use quote::ToTokens;
 use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
 use syn::{
-    Error, GenericParam, Generics, Ident, Lifetime, PredicateLifetime, PredicateType, Token, Type,
-    TypeParam, WherePredicate,
+    Error, GenericParam, Generics, Ident, Lifetime, LitInt, Path, PredicateLifetime, PredicateType,
+    Token, Type, TypeParam, WherePredicate,
 };
 
+/// the wildcard trait of `T: _`, implemented by the types annotated with at least one trait
+pub const ANY_TRAIT: &str = "_";
+
 pub trait ParseTypeOrLifetimeOrTrait<T> {
     fn from_type(ident: String, type_name: String) -> T;
     fn from_trait(ident: String, traits: Vec<String>, lifetime: Option<String>) -> T;
@@ -34,6 +39,56 @@ pub fn parse_type_or_lifetime_or_trait<T: ParseTypeOrLifetimeOrTrait<U>, U>(
     }
 }
 
+/**
+   Parses a slice element count in the form `len = N`.
+   Returns none without consuming anything if the input does not start with it.
+*/
+pub fn parse_len(input: ParseStream) -> Result<Option<usize>, Error> {
+    let is_len = input.peek(Ident)
+        && input.peek2(Token![=])
+        && input.peek3(LitInt)
+        && input.fork().parse::<Ident>()? == "len";
+
+    if !is_len {
+        return Ok(None);
+    }
+
+    input.parse::<Ident>()?; // consume the 'len' token
+    input.parse::<Token![=]>()?; // consume the '=' token
+    input.parse::<LitInt>()?.base10_parse().map(Some)
+}
+
+/**
+   Parses a value asserted for an argument of the method in the form `argN = value`, where `N` is the position of the
+   argument (starting from 0) and the value is an integer literal (e.g. `arg0 = 0`, `arg1 = -1`).
+   Returns none without consuming anything if the input does not start with it.
+*/
+pub fn parse_arg_value(input: ParseStream) -> Result<Option<(usize, String)>, Error> {
+    let position = |ident: &Ident| {
+        ident
+            .to_string()
+            .strip_prefix("arg")
+            .and_then(|n| n.parse::<usize>().ok())
+    };
+
+    let is_arg = input.peek(Ident)
+        && input.peek2(Token![=])
+        && (input.peek3(LitInt) || input.peek3(Token![-]))
+        && position(&input.fork().parse::<Ident>()?).is_some();
+
+    if !is_arg {
+        return Ok(None);
+    }
+
+    let position = position(&input.parse::<Ident>()?).unwrap();
+    input.parse::<Token![=]>()?; // consume the '=' token
+    let negative = input.parse::<Option<Token![-]>>()?.is_some();
+    let lit = input.parse::<LitInt>()?;
+    let value = format!("{}{}", if negative { "-" } else { "" }, to_string(&lit));
+
+    Ok(Some((position, normalize_const_value(&value))))
+}
+
 fn parse_type<T: ParseTypeOrLifetimeOrTrait<U>, U>(
     ident: &str,
     input: ParseStream,
@@ -52,7 +107,12 @@ fn parse_trait<T: ParseTypeOrLifetimeOrTrait<U>, U>(
     let mut traits = vec![];
     let mut lifetime = None;
 
-    while !input.is_empty() && !input.peek(Token![,]) && !input.peek(Token![;]) {
+    while !input.is_empty()
+        && !input.peek(Token![,])
+        && !input.peek(Token![;])
+        && !input.peek(Token![&])
+        && !input.peek(Token![|])
+    {
         if input.peek(Lifetime) {
             if lifetime.is_some() {
                 return Err(Error::new(
@@ -61,8 +121,11 @@ fn parse_trait<T: ParseTypeOrLifetimeOrTrait<U>, U>(
                 ));
             }
             lifetime = Some(input.parse::<Lifetime>()?.to_string());
+        } else if input.peek(Token![_]) {
+            input.parse::<Token![_]>()?; // consume the '_' token
+            traits.push(ANY_TRAIT.to_string());
         } else {
-            traits.push(input.parse::<Ident>()?.to_string());
+            traits.push(parse_trait_path(input)?);
         }
 
         if input.peek(Token![+]) {
@@ -80,6 +143,47 @@ fn parse_trait<T: ParseTypeOrLifetimeOrTrait<U>, U>(
     Ok(T::from_trait(ident.to_string(), traits, lifetime))
 }
 
+/**
+   Parses a trait, either by its name or by its path (e.g. `Debug`, `std::fmt::Debug`).
+   The path is kept as written, use `trait_name` to compare the traits.
+*/
+pub fn parse_trait_path(input: ParseStream) -> Result<String, Error> {
+    let path = Path::parse_mod_style(input)?;
+    Ok(to_string(&path).replace(" ", ""))
+}
+
+/**
+   the name of a trait without its path, used to compare the traits written in different forms.
+   # Example:
+   `std::fmt::Debug` -> `Debug`
+*/
+pub fn trait_name(trait_: &str) -> &str {
+    trait_.rsplit("::").next().unwrap_or(trait_).trim()
+}
+
+/// check if `traits` contains `trait_`, even if one of them is written with its path
+pub fn contains_trait(traits: &[String], trait_: &str) -> bool {
+    traits.iter().any(|t| trait_name(t) == trait_name(trait_))
+}
+
+/// check if the annotated `traits` of a type include `trait_`, the wildcard is included by any trait
+pub fn implements_trait(traits: &[String], trait_: &str) -> bool {
+    if trait_ == ANY_TRAIT {
+        !traits.is_empty()
+    } else {
+        contains_trait(traits, trait_)
+    }
+}
+
+/**
+    the specificity of the traits required for a generic: the concrete traits are more specific than the wildcard,
+    which is more specific than no trait
+*/
+pub fn traits_specificity(traits: &[String]) -> (usize, bool) {
+    let concrete = traits.iter().filter(|t| *t != ANY_TRAIT).count();
+    (concrete, concrete < traits.len())
+}
+
 /**
     adds the generics in the where clause in the params
 
@@ -92,6 +196,7 @@ fn parse_trait<T: ParseTypeOrLifetimeOrTrait<U>, U>(
     <T: Clone + Debug + Default, U: Copy>
     ```
 
+    The predicates on other types (e.g. `Vec<T>: Clone` or `T::Item: Debug`) are kept in the where clause.
 */
 pub fn parse_generics(mut generics: Generics) -> Generics {
     let predicates = generics
@@ -100,32 +205,44 @@ pub fn parse_generics(mut generics: Generics) -> Generics {
         .map(|wc| wc.predicates.clone())
         .unwrap_or_default();
 
+    let mut retained = Punctuated::<WherePredicate, Token![,]>::new();
+
     for predicate in predicates {
         match predicate {
             WherePredicate::Type(predicate) => {
-                handle_type_predicate(&predicate, &mut generics);
+                if !handle_type_predicate(&predicate, &mut generics) {
+                    retained.push(WherePredicate::Type(predicate));
+                }
             }
             WherePredicate::Lifetime(predicate) => {
                 handle_lifetime_predicate(&predicate, &mut generics);
             }
-            _ => {}
+            predicate => retained.push(predicate),
         }
     }
 
+    if let Some(wc) = generics.where_clause.as_mut() {
+        wc.predicates = retained;
+    }
+
     generics
 }
 
-pub fn handle_type_predicate(predicate: &PredicateType, generics: &mut Generics) {
+/// adds the bounds of the predicate to the generic it is on, `false` if it is not on a generic (e.g. `Vec<T>: Clone`)
+pub fn handle_type_predicate(predicate: &PredicateType, generics: &mut Generics) -> bool {
     let ident = match &predicate.bounded_ty {
-        Type::Path(tp) => &tp.path.segments.first().unwrap().ident.to_string(),
-        _ => panic!("Ident not found in bounded type"),
+        Type::Path(tp) if tp.qself.is_none() => match tp.path.get_ident() {
+            Some(ident) => ident.to_string(),
+            None => return false,
+        },
+        _ => return false,
     };
 
-    let param = match find_type_param_mut(generics, ident) {
+    let param = match find_type_param_mut(generics, &ident) {
         Some(p) => p,
         None => {
-            add_generic_type(generics, ident);
-            find_type_param_mut(generics, ident).unwrap()
+            add_generic_type(generics, &ident);
+            find_type_param_mut(generics, &ident).unwrap()
         }
     };
 
@@ -139,6 +256,8 @@ pub fn handle_type_predicate(predicate: &PredicateType, generics: &mut Generics)
             param.bounds.push(bound);
         }
     }
+
+    true
 }
 
 pub fn find_type_param_mut<'a>(
@@ -180,6 +299,21 @@ pub fn get_generics_lifetimes<T: FromIterator<String>>(generics_str: &str) -> T
     collect_generics_lifetimes(&generics)
 }
 
+/// get the default type of a generic, e.g. `u8` for `T` in `<T = u8>`
+pub fn get_generic_default(generics_str: &str, generic: &str) -> Option<String> {
+    let generics = str_to_generics(generics_str);
+    generics.params.iter().find_map(|p| match p {
+        GenericParam::Type(tp) if tp.ident == generic => tp.default.as_ref().map(to_string),
+        _ => None,
+    })
+}
+
+/**
+    the names of the type generics, or of the lifetime generics if `generic` is a lifetime, in order,
+    used to align the generics of a trait with the arguments given by an impl by position.
+    The arguments that are not a single name (e.g. `Vec<T>`) are replaced by placeholders
+    with `impls::get_trait_types` before, so that they keep their position.
+*/
 pub fn get_relevant_generics_names(generics: &Generics, generic: &str) -> Vec<String> {
     let get_lifetimes = generic.starts_with('\'');
     let get_types = !get_lifetimes;
@@ -275,6 +409,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_trait_with_path() {
+        let input = quote! { MyType: std::fmt::Debug + Clone };
+        let result: MockTypeOrTrait = parse2(input).unwrap();
+
+        assert_eq!(
+            result,
+            MockTypeOrTrait::Trait(
+                "MyType".to_string(),
+                vec!["std::fmt::Debug".to_string(), "Clone".to_string()],
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn compare_trait_paths() {
+        assert_eq!(trait_name("std::fmt::Debug"), "Debug");
+        assert_eq!(trait_name("::std::fmt::Debug"), "Debug");
+        assert_eq!(trait_name("Debug"), "Debug");
+
+        let traits = vec!["std::fmt::Debug".to_string(), "Clone".to_string()];
+        assert!(contains_trait(&traits, "Debug"));
+        assert!(contains_trait(&traits, "core::clone::Clone"));
+        assert!(!contains_trait(&traits, "Copy"));
+    }
+
+    #[test]
+    fn parse_any_trait() {
+        let input = quote! { MyType: _ + Clone };
+        let result: MockTypeOrTrait = parse2(input).unwrap();
+
+        assert_eq!(
+            result,
+            MockTypeOrTrait::Trait(
+                "MyType".to_string(),
+                vec![ANY_TRAIT.to_string(), "Clone".to_string()],
+                None
+            )
+        );
+
+        let traits = vec!["Clone".to_string()];
+        assert!(implements_trait(&traits, ANY_TRAIT));
+        assert!(!implements_trait(&[], ANY_TRAIT));
+        assert!(!implements_trait(&[ANY_TRAIT.to_string()], "Clone"));
+
+        assert!(traits_specificity(&traits) > traits_specificity(&[ANY_TRAIT.to_string()]));
+        assert!(traits_specificity(&[ANY_TRAIT.to_string()]) > traits_specificity(&[]));
+    }
+
     #[test]
     fn parse_lifetime_single() {
         let input = quote! { MyType: 'a };
@@ -372,6 +556,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_generics_trait_with_associated_type() {
+        let mut generics: Generics = parse2(quote! { <T: Clone> }).unwrap();
+        generics.where_clause = Some(parse2(quote! { where T: Iterator<Item = u8> }).unwrap());
+
+        let res = parse_generics(generics);
+
+        assert_eq!(
+            to_string(&res).replace(" ", ""),
+            "<T: Clone + Iterator<Item = u8>>"
+                .to_string()
+                .replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn parse_generics_trait_with_different_associated_types() {
+        let mut generics: Generics = parse2(quote! { <T: Iterator<Item = u8>> }).unwrap();
+        generics.where_clause = Some(
+            parse2(quote! { where T: Iterator<Item = u8> + IntoIterator<Item = u8, IntoIter = T> })
+                .unwrap(),
+        );
+
+        let res = parse_generics(generics);
+
+        assert_eq!(
+            to_string(&res).replace(" ", ""),
+            "<T: Iterator<Item = u8> + IntoIterator<Item = u8, IntoIter = T>>"
+                .to_string()
+                .replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn parse_generics_predicate_on_other_type() {
+        let mut generics: Generics = parse2(quote! { <T> }).unwrap();
+        generics.where_clause = Some(parse2(quote! { where T: Copy, Vec<T>: Clone }).unwrap());
+
+        let res = parse_generics(generics);
+
+        assert_eq!(to_string(&res).replace(" ", ""), "<T:Copy>");
+        assert_eq!(
+            to_string(&res.where_clause).replace(" ", ""),
+            "whereVec<T>:Clone"
+        );
+    }
+
+    #[test]
+    fn parse_generics_predicate_on_associated_type() {
+        let mut generics: Generics = parse2(quote! { <T: Iterator> }).unwrap();
+        generics.where_clause = Some(parse2(quote! { where T::Item: Debug }).unwrap());
+
+        let res = parse_generics(generics);
+
+        assert_eq!(to_string(&res).replace(" ", ""), "<T:Iterator>");
+        assert_eq!(
+            to_string(&res.where_clause).replace(" ", ""),
+            "whereT::Item:Debug"
+        );
+    }
+
+    #[test]
+    fn generic_default() {
+        let generics = "<'a, T: Clone, U = Vec<T>>";
+
+        assert_eq!(get_generic_default(generics, "T"), None);
+        assert_eq!(
+            get_generic_default(generics, "U").map(|d| d.replace(" ", "")),
+            Some("Vec<T>".to_string())
+        );
+        assert_eq!(get_generic_default(generics, "V"), None);
+    }
+
     #[test]
     fn parse_generics_lifetime() {
         let mut generics: Generics = parse2(quote! { <'a, 'b> }).unwrap();
@@ -0,0 +1,149 @@
+use crate::impls::ImplBody;
+use crate::traits::TraitBody;
+use crate::type_aliases::TypeAliasBody;
+use crate::when::expand_when;
+use proc_macro2::TokenStream;
+
+/**
+    the formatted source of the specialized traits and impls that `when` emits for `impl_` with `condition`,
+    one pair for each conjunction of the condition, without running the macro.
+    `condition` is the whole input of `when`, with `priority`, `requires` and `fallback`,
+    and its types are expanded with `type_aliases` as the ones of the crate.
+    Without condition the impl is the default one, so it is returned as is (apart from `requires`).
+    # Example:
+    for `trait Foo<T> { fn foo(&self, x: T); }`, `impl<T> Foo<T> for MyType { ... }` and `T = u8`
+    ```ignore
+    #[allow(non_camel_case_types, non_snake_case, private_interfaces, private_bounds)]
+    pub(crate) trait Foo_MyType_123 {
+        fn foo(&self, x: u8);
+    }
+    #[allow(non_camel_case_types, non_snake_case)]
+    impl Foo_MyType_123 for MyType { ... }
+    ```
+*/
+pub fn specialized_source(
+    trait_: TokenStream,
+    impl_: TokenStream,
+    condition: TokenStream,
+    type_aliases: &[TypeAliasBody],
+) -> Result<String, String> {
+    let trait_body = TraitBody::try_from(trait_).map_err(|e| e.to_string())?;
+    let impl_body = ImplBody::try_from((impl_.clone(), None)).map_err(|e| e.to_string())?;
+
+    if impl_body.trait_name != trait_body.name {
+        return Err(format!(
+            "The impl is for `{}`, not for `{}`",
+            impl_body.trait_name, trait_body.name
+        ));
+    }
+
+    let expansion = expand_when(condition, impl_, type_aliases, |_| Some(trait_body.clone()))
+        .map_err(|e| e.to_string())?;
+
+    format_source(expansion.tokens)
+}
+
+fn format_source(tokens: TokenStream) -> Result<String, String> {
+    let file = syn::parse2::<syn::File>(tokens).map_err(|e| e.to_string())?;
+    Ok(prettyplease::unparse(&file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditions::parse_condition;
+    use quote::quote;
+
+    fn get_trait() -> TokenStream {
+        quote! { trait Foo<T> { fn foo(&self, x: T); } }
+    }
+
+    fn get_impl() -> TokenStream {
+        quote! {
+            impl<T> Foo<T> for MyType {
+                fn foo(&self, x: T) {}
+            }
+        }
+    }
+
+    #[test]
+    fn source_with_condition() {
+        let condition = quote! { T = u8 };
+        let result = specialized_source(get_trait(), get_impl(), condition.clone(), &[]).unwrap();
+
+        let trait_name = ImplBody::try_from((get_impl(), parse_condition(condition).unwrap()))
+            .unwrap()
+            .specialized
+            .unwrap()
+            .trait_name;
+        assert_eq!(
+            result,
+            format!(
+                "#[allow(non_camel_case_types, non_snake_case, private_interfaces, private_bounds)]
+pub(crate) trait {trait_name} {{
+    fn foo(&self, x: u8);
+}}
+#[allow(non_camel_case_types, non_snake_case)]
+impl {trait_name} for MyType {{
+    fn foo(&self, x: u8) {{}}
+}}
+"
+            )
+        );
+    }
+
+    #[test]
+    fn source_for_each_conjunction() {
+        let result =
+            specialized_source(get_trait(), get_impl(), quote! { T = u8 | T = i32 }, &[]).unwrap();
+
+        assert_eq!(result.matches("fn foo(&self, x: u8);").count(), 1);
+        assert_eq!(result.matches("fn foo(&self, x: i32);").count(), 1);
+        assert_eq!(result.matches("impl ").count(), 2);
+    }
+
+    #[test]
+    fn source_without_condition() {
+        let result = specialized_source(get_trait(), get_impl(), quote! {}, &[]).unwrap();
+
+        assert_eq!(
+            result,
+            "impl<T> Foo<T> for MyType {\n    fn foo(&self, x: T) {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn source_for_other_trait() {
+        let trait_ = quote! { trait Bar<T> { fn foo(&self, x: T); } };
+        let result = specialized_source(trait_, get_impl(), quote! { T = u8 }, &[]);
+
+        assert_eq!(result.unwrap_err(), "The impl is for `Foo`, not for `Bar`");
+    }
+
+    #[test]
+    fn source_with_requires_and_fallback() {
+        let condition = quote! { T: Clone, requires(Vec<T>: Debug), fallback };
+        let result = specialized_source(get_trait(), get_impl(), condition, &[]).unwrap();
+
+        assert!(result.contains("Vec<T>: Debug"));
+        assert!(result.contains("impl<T> Foo<T> for MyType {"));
+        assert_eq!(
+            result.matches("impl ").count() + result.matches("impl<").count(),
+            2
+        );
+
+        let result =
+            specialized_source(get_trait(), get_impl(), quote! { requires(T: Debug) }, &[])
+                .unwrap();
+        assert!(result.contains("where\n    T: Debug,"));
+    }
+
+    #[test]
+    fn source_with_type_alias() {
+        let aliases = [TypeAliasBody::try_from(quote! { type MyVec = Vec<u8>; }).unwrap()];
+        let result =
+            specialized_source(get_trait(), get_impl(), quote! { T = MyVec }, &aliases).unwrap();
+
+        assert!(result.contains("fn foo(&self, x: Vec<u8>);"));
+    }
+}
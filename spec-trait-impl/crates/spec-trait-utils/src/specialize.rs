@@ -1,15 +1,19 @@
 use std::collections::HashSet;
 
-use crate::conditions::WhenCondition;
-use crate::conversions::{str_to_lifetime, str_to_type_name};
+use crate::conditions::{SELF, WhenCondition};
+use crate::conversions::{str_to_expr, str_to_lifetime, str_to_type_name};
 use crate::types::{
-    Aliases, replace_infers, replace_type, type_assignable, type_contains, type_contains_lifetime,
+    Aliases, replace_infers, replace_type, type_assignable, type_assignable_generic_constraints,
+    type_contains, type_contains_lifetime,
 };
 use proc_macro2::Span;
 use syn::punctuated::Punctuated;
 use syn::visit::Visit;
 use syn::visit_mut::{self, VisitMut};
-use syn::{GenericParam, Generics, Ident, LifetimeParam, Type, TypeParam};
+use syn::{
+    Expr, ExprPath, GenericArgument, GenericParam, Generics, Ident, Lifetime, LifetimeParam, Type,
+    TypeParam, TypeParamBound, TypePath,
+};
 
 // TODO: infer lifetimes as well
 
@@ -29,7 +33,9 @@ pub fn get_assignable_conditions(
     conditions
         .iter()
         .filter_map(|c| match c {
-            WhenCondition::Trait(_, _) => Some(c.clone()),
+            WhenCondition::Trait(_, _)
+            | WhenCondition::Lifetime(_, _)
+            | WhenCondition::Const(_, _) => Some(c.clone()),
             WhenCondition::Type(g, t) => {
                 let types = get_generic_types_from_conditions(g, conditions);
                 let most_specific = types.last() == Some(t);
@@ -49,6 +55,67 @@ pub fn get_assignable_conditions(
         .collect()
 }
 
+/**
+    replace the conditions on `Self` with the conditions they give to the generics of the impl,
+    by matching the type in the condition with the type the impl is for.
+    # Example:
+    for `impl<T> Foo for Wrapper<T>` and `Self = Wrapper<i32>` -> `T = i32`
+*/
+pub fn expand_self_condition(
+    condition: &WhenCondition,
+    type_name: &str,
+    impl_generics: &str,
+) -> WhenCondition {
+    match condition {
+        WhenCondition::Type(generic, type_) if generic == SELF => {
+            let Some(constrained) = type_assignable_generic_constraints(
+                type_,
+                type_name,
+                impl_generics,
+                &Aliases::default(),
+            ) else {
+                return WhenCondition::All(vec![]);
+            };
+
+            let mut types = constrained
+                .types
+                .into_iter()
+                .filter_map(|(g, t)| Some(WhenCondition::Type(g, t?)))
+                .collect::<Vec<_>>();
+            let mut lifetimes = constrained
+                .lifetimes
+                .into_iter()
+                .filter_map(|(g, lt)| Some(WhenCondition::Lifetime(g, lt?)))
+                .collect::<Vec<_>>();
+            let mut consts = constrained
+                .consts
+                .into_iter()
+                .filter_map(|(g, value)| Some(WhenCondition::Const(g, value?)))
+                .collect::<Vec<_>>();
+            types.sort_by_key(|c| c.to_string());
+            lifetimes.sort_by_key(|c| c.to_string());
+            consts.sort_by_key(|c| c.to_string());
+
+            WhenCondition::All(types.into_iter().chain(lifetimes).chain(consts).collect())
+        }
+
+        // flattened, since the nested conditions are not applied
+        WhenCondition::All(inner) => WhenCondition::All(
+            inner
+                .iter()
+                .flat_map(
+                    |c| match expand_self_condition(c, type_name, impl_generics) {
+                        WhenCondition::All(expanded) => expanded,
+                        expanded => vec![expanded],
+                    },
+                )
+                .collect(),
+        ),
+
+        _ => condition.clone(),
+    }
+}
+
 fn get_generic_types_from_conditions(generic: &str, conditions: &[WhenCondition]) -> Vec<String> {
     let mut types = conditions
         .iter()
@@ -71,6 +138,109 @@ impl VisitMut for TypeReplacer {
         replace_type(node, &self.generic, &self.type_);
         visit_mut::visit_type_mut(self, node);
     }
+
+    fn visit_expr_path_mut(&mut self, node: &mut ExprPath) {
+        // `T::default()` or `T::SIZE`, the path is replaced as the type `T::default` (e.g. `<Vec<u8>>::default`)
+        let starts_with_generic = node.qself.is_none()
+            && node.path.segments.len() > 1
+            && node.path.segments[0].ident == self.generic
+            && node.path.segments[0].arguments.is_empty();
+        if !starts_with_generic {
+            visit_mut::visit_expr_path_mut(self, node);
+            return;
+        }
+
+        let mut type_ = Type::Path(TypePath {
+            qself: None,
+            path: node.path.clone(),
+        });
+        replace_type(&mut type_, &self.generic, &self.type_);
+        if let Type::Path(type_path) = type_ {
+            node.qself = type_path.qself;
+            node.path = type_path.path;
+        }
+    }
+}
+
+pub struct LifetimeReplacer {
+    pub generic: String,
+    pub lifetime: Lifetime,
+}
+
+impl VisitMut for LifetimeReplacer {
+    fn visit_lifetime_mut(&mut self, node: &mut Lifetime) {
+        if node.to_string() == self.generic {
+            *node = self.lifetime.clone();
+        }
+    }
+}
+
+pub struct ConstReplacer {
+    pub generic: String,
+    pub value: Expr,
+}
+
+impl VisitMut for ConstReplacer {
+    fn visit_expr_mut(&mut self, node: &mut Expr) {
+        if let Expr::Path(path) = node
+            && path.qself.is_none()
+            && path.path.is_ident(&self.generic)
+        {
+            *node = self.value.clone();
+            return;
+        }
+        visit_mut::visit_expr_mut(self, node);
+    }
+
+    fn visit_generic_argument_mut(&mut self, node: &mut GenericArgument) {
+        // `Foo<N>`, the const generic is parsed as a type
+        if let GenericArgument::Type(Type::Path(path)) = node
+            && path.qself.is_none()
+            && path.path.is_ident(&self.generic)
+        {
+            *node = GenericArgument::Const(self.value.clone());
+            return;
+        }
+        visit_mut::visit_generic_argument_mut(self, node);
+    }
+}
+
+/// replace the const generic `generic` with `value` in the items, removing it from the generics
+pub fn apply_const_condition<T: Specializable>(
+    target: &mut T,
+    generics: &mut Generics,
+    other_generics: &mut Generics,
+    generic: &str,
+    value: &str,
+) {
+    remove_generic(generics, generic);
+    remove_generic(other_generics, generic);
+
+    let mut replacer = ConstReplacer {
+        generic: generic.to_owned(),
+        value: str_to_expr(value),
+    };
+
+    target.handle_items_replace(&mut replacer);
+}
+
+/// replace the lifetime generic `generic` with `lifetime` in the items, removing it from the generics
+pub fn apply_lifetime_condition<T: Specializable>(
+    target: &mut T,
+    generics: &mut Generics,
+    other_generics: &mut Generics,
+    generic: &str,
+    lifetime: &str,
+) {
+    remove_generic(generics, generic);
+    remove_generic(other_generics, generic);
+
+    let mut replacer = LifetimeReplacer {
+        generic: generic.to_owned(),
+        lifetime: str_to_lifetime(lifetime),
+    };
+
+    target.handle_items_replace(&mut replacer);
 }
 
 pub fn apply_type_condition<T: Specializable>(
@@ -125,11 +295,43 @@ pub fn remove_generic(generics: &mut Generics, generic: &str) {
         .filter(
             |param|
                 !matches!(param, GenericParam::Type(tp) if tp.ident == generic) &&
-                !matches!(param, GenericParam::Lifetime(lt) if lt.lifetime.to_string() == generic)
+                !matches!(param, GenericParam::Lifetime(lt) if lt.lifetime.to_string() == generic) &&
+                !matches!(param, GenericParam::Const(cp) if cp.ident == generic)
         )
         .collect();
 }
 
+/// rename a generic (type or lifetime) in place, keeping its bounds and default
+pub fn rename_generic(generics: &mut Generics, generic: &str, new_generic: &str) {
+    for param in generics.params.iter_mut() {
+        match param {
+            GenericParam::Type(tp) if tp.ident == generic => {
+                tp.ident = Ident::new(new_generic, Span::call_site());
+            }
+            GenericParam::Type(tp) => {
+                for bound in tp.bounds.iter_mut() {
+                    if let TypeParamBound::Lifetime(lt) = bound
+                        && lt.to_string() == generic
+                    {
+                        *lt = str_to_lifetime(new_generic);
+                    }
+                }
+            }
+            GenericParam::Lifetime(lp) => {
+                if lp.lifetime.to_string() == generic {
+                    lp.lifetime = str_to_lifetime(new_generic);
+                }
+                for bound in lp.bounds.iter_mut() {
+                    if bound.to_string() == generic {
+                        *bound = str_to_lifetime(new_generic);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 pub fn collect_generics_types<T: FromIterator<String>>(generics: &Generics) -> T {
     generics
         .params
@@ -153,23 +355,46 @@ pub fn collect_generics_lifetimes<T: FromIterator<String>>(generics: &Generics)
 }
 
 pub fn add_generic_type(generics: &mut Generics, generic: &str) {
-    generics.params.push(GenericParam::Type(TypeParam {
+    let param = GenericParam::Type(TypeParam {
         attrs: vec![],
         ident: Ident::new(generic, Span::call_site()),
         colon_token: None,
         bounds: Punctuated::new(),
         eq_token: None,
         default: None,
-    }))
+    });
+
+    // generics with a default must be trailing
+    let first_with_default = generics
+        .params
+        .iter()
+        .position(|p| matches!(p, GenericParam::Type(tp) if tp.default.is_some()));
+
+    match first_with_default {
+        Some(index) => generics.params.insert(index, param),
+        None => generics.params.push(param),
+    }
 }
 
 pub fn add_generic_lifetime(generics: &mut Generics, generic: &str) {
-    generics.params.push(GenericParam::Lifetime(LifetimeParam {
+    let param = GenericParam::Lifetime(LifetimeParam {
         attrs: vec![],
         lifetime: str_to_lifetime(generic),
         colon_token: None,
         bounds: Punctuated::new(),
-    }))
+    });
+
+    // lifetimes are printed first whatever their position, so they are kept leading
+    // to avoid a trailing comma after the last type
+    let first_not_lifetime = generics
+        .params
+        .iter()
+        .position(|p| !matches!(p, GenericParam::Lifetime(_)));
+
+    match first_not_lifetime {
+        Some(index) => generics.params.insert(index, param),
+        None => generics.params.push(param),
+    }
 }
 
 pub struct TypeVisitor {
@@ -209,6 +434,52 @@ pub fn get_used_generics<T: Specializable>(target: &T, generics: &Generics) -> H
     visitor.used_generics
 }
 
+/// add to `used_generics` the generics appearing in the bounds of the used ones, until no new generic is found
+pub fn add_generics_used_in_bounds(generics: &Generics, used_generics: &mut HashSet<String>) {
+    loop {
+        let mut visitor = TypeVisitor {
+            unused_generics: collect_generics_types::<HashSet<_>>(generics)
+                .union(&collect_generics_lifetimes::<HashSet<_>>(generics))
+                .filter(|g| !used_generics.contains(*g))
+                .cloned()
+                .collect(),
+            used_generics: HashSet::new(),
+        };
+
+        for param in &generics.params {
+            match param {
+                GenericParam::Type(tp) if used_generics.contains(&tp.ident.to_string()) => {
+                    for bound in &tp.bounds {
+                        match bound {
+                            TypeParamBound::Lifetime(lt) => {
+                                visitor.used_generics.insert(lt.to_string());
+                            }
+                            _ => visitor.visit_type_param_bound(bound),
+                        }
+                    }
+                }
+                GenericParam::Lifetime(lp) if used_generics.contains(&lp.lifetime.to_string()) => {
+                    visitor
+                        .used_generics
+                        .extend(lp.bounds.iter().map(|lt| lt.to_string()));
+                }
+                _ => {}
+            }
+        }
+
+        let new_generics = visitor
+            .used_generics
+            .into_iter()
+            .filter(|g| !used_generics.contains(g))
+            .collect::<Vec<_>>();
+
+        if new_generics.is_empty() {
+            return;
+        }
+        used_generics.extend(new_generics);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,6 +501,22 @@ mod tests {
         assert_eq!(collected, vec!["U".to_string(), "V".to_string()]);
     }
 
+    #[test]
+    fn generics_without_trailing_commas() {
+        let mut single = str_to_generics("<T>");
+        add_generic_lifetime(&mut single, "'a");
+        assert_eq!(to_string(&single).replace(" ", ""), "<'a,T>");
+
+        let mut multiple = str_to_generics("<'a, T, U>");
+        remove_generic(&mut multiple, "T");
+        add_generic_type(&mut multiple, "V");
+        add_generic_lifetime(&mut multiple, "'b");
+        assert_eq!(to_string(&multiple).replace(" ", ""), "<'a,'b,U,V>");
+
+        let trailing = str_to_generics("<T, U,>");
+        assert_eq!(to_string(&trailing).replace(" ", ""), "<T,U>");
+    }
+
     #[test]
     fn type_replacer() {
         let mut replacer = TypeReplacer {
@@ -1,17 +1,20 @@
 use crate::conditions::WhenCondition;
 use crate::conversions::{
-    str_to_generics, str_to_trait_name, str_to_type_name, strs_to_trait_items, to_string,
-    tokens_to_trait,
+    str_to_generics, str_to_lifetime, str_to_trait_name, str_to_type_name, strs_to_trait_items,
+    to_string, tokens_to_trait,
 };
-use crate::impls::ImplBody;
+use crate::impls::{ImplBody, get_trait_types, remove_trait_types};
 use crate::parsing::{
-    get_generics_lifetimes, get_generics_types, get_relevant_generics_names, parse_generics,
+    find_type_param_mut, get_generic_default, get_generics_lifetimes, get_generics_types,
+    get_relevant_generics_names, parse_generics,
 };
 use crate::specialize::{
-    Specializable, TypeReplacer, add_generic_lifetime, add_generic_type, apply_type_condition,
-    get_assignable_conditions, get_used_generics, remove_generic,
+    LifetimeReplacer, Specializable, TypeReplacer, add_generic_lifetime, add_generic_type,
+    add_generics_used_in_bounds, apply_type_condition, collect_generics_types,
+    expand_self_condition, get_assignable_conditions, get_used_generics, remove_generic,
+    rename_generic,
 };
-use crate::types::get_unique_generic_name;
+use crate::types::{get_unique_generic_name, replace_type, type_contains};
 use proc_macro2::TokenStream;
 use quote::quote;
 use serde::{Deserialize, Serialize};
@@ -19,7 +22,8 @@ use std::fmt::Debug;
 use syn::visit::Visit;
 use syn::visit_mut::VisitMut;
 use syn::{
-    Attribute, FnArg, Generics, ItemTrait, Token, TraitItem, TraitItemFn, punctuated::Punctuated,
+    Attribute, FnArg, GenericParam, Generics, ItemTrait, Token, TraitItem, TraitItemFn, Type,
+    punctuated::Punctuated,
 };
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -60,8 +64,12 @@ impl From<&TraitBody> for TokenStream {
         let generics = str_to_generics(&trait_body.generics);
         let items = strs_to_trait_items(&trait_body.items);
 
+        // the generated names (e.g. `Foo_MyType_123`, `__G_0__`) are not camel case.
+        // Visible in the crate, so that the `spec!` calls in other modules reach it with its path,
+        // also when its items use the private types of the module
         quote! {
-            trait #name #generics {
+            #[allow(non_camel_case_types, non_snake_case, private_interfaces, private_bounds)]
+            pub(crate) trait #name #generics {
                 #(#items)*
             }
         }
@@ -106,6 +114,19 @@ impl TraitBody {
         })
     }
 
+    /// the numbers of arguments of the functions in the trait with the given name
+    pub fn fn_arities(&self, fn_name: &str) -> Vec<usize> {
+        strs_to_trait_items(&self.items)
+            .iter()
+            .filter_map(|f| match f {
+                TraitItem::Fn(fn_) if fn_.sig.ident == fn_name => {
+                    Some(count_fn_args(&fn_.sig.inputs))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn specialize(&self, impl_body: &ImplBody) -> Self {
         let mut new_trait = self.clone();
         let mut specialized = new_trait.clone();
@@ -116,6 +137,15 @@ impl TraitBody {
         // replace generics with unique generic name
         specialized.replace_generics_names();
 
+        // the trait arguments that are not generics of the impl (e.g. `Vec<T>`) are replaced in the trait
+        let (mut impl_trait_generics, trait_types) = get_trait_types(&impl_body.trait_generics);
+
+        // apply the defaults of the generics elided by the impl
+        specialized.apply_elided_defaults(&to_string(&impl_trait_generics));
+
+        specialized.apply_trait_types(&impl_trait_generics, &trait_types);
+        remove_trait_types(&mut impl_trait_generics, &trait_types);
+
         // set missing generic lifetimes
         let mut generics = str_to_generics(&specialized.generics);
         let impl_generics = &impl_body.specialized.as_ref().unwrap().trait_generics;
@@ -132,27 +162,44 @@ impl TraitBody {
 
         // apply condition
         if let Some(condition) = &impl_body.condition {
-            let mut impl_generics = str_to_generics(&impl_body.trait_generics);
-            specialized.apply_condition(&mut impl_generics, condition);
+            let condition =
+                expand_self_condition(condition, &impl_body.type_name, &impl_body.impl_generics);
+            specialized.apply_condition(&mut impl_trait_generics, &condition);
         }
 
+        let impl_specialized = impl_body.specialized.as_ref().unwrap();
+        specialized.add_projection_bounds(&impl_specialized.impl_generics, &impl_trait_generics);
+
         // set missing generic types
         let mut generics = str_to_generics(&specialized.generics);
         let impl_generics = &impl_body.specialized.as_ref().unwrap().trait_generics;
         let specialized_impl_generics = str_to_generics(impl_generics);
+        let mut added_generics = vec![];
         for generic in get_generics_types::<Vec<_>>(impl_generics) {
             if specialized
                 .get_corresponding_generic(&specialized_impl_generics, &generic)
                 .is_none()
             {
                 add_generic_type(&mut generics, &generic);
+                added_generics.push(generic);
             }
         }
         specialized.generics = to_string(&generics);
 
-        // clean unused generics
-        let used_generics =
-            get_used_generics(&specialized, &str_to_generics(&specialized.generics));
+        // clean unused generics, keeping the ones added for the impl (e.g. used only in the bodies of its fns)
+        // and the ones the impl passes (e.g. used only in its associated types)
+        let generics = str_to_generics(&specialized.generics);
+        let mut used_generics = get_used_generics(&specialized, &generics);
+        used_generics.extend(added_generics);
+        used_generics.extend(
+            get_generics_lifetimes::<Vec<_>>(impl_generics)
+                .into_iter()
+                .chain(get_generics_types::<Vec<_>>(impl_generics))
+                .filter_map(|generic| {
+                    specialized.get_corresponding_generic(&specialized_impl_generics, &generic)
+                }),
+        );
+        add_generics_used_in_bounds(&generics, &mut used_generics);
 
         let mut generics = str_to_generics(&specialized.generics);
         for generic in get_generics_lifetimes::<Vec<_>>(&specialized.generics) {
@@ -187,8 +234,9 @@ impl TraitBody {
 
             WhenCondition::Type(impl_generic, type_) => {
                 let mut generics = str_to_generics(&self.generics);
+                let type_ = self.to_trait_type(impl_generics, impl_generic, type_);
 
-                apply_type_condition(self, &mut generics, impl_generics, impl_generic, type_);
+                apply_type_condition(self, &mut generics, impl_generics, impl_generic, &type_);
 
                 self.generics = to_string(&generics);
             }
@@ -197,7 +245,66 @@ impl TraitBody {
         }
     }
 
-    /// replace generics in the trait with unique names
+    /**
+       replace the other generics of the impl in the type of a condition with the corresponding ones of the trait
+       # Example:
+       for trait `TraitName<A, B>`, impl `impl<T, U> TraitName<T, U> for MyType` and `T = U::Item` -> `B::Item`
+    */
+    fn to_trait_type(&self, impl_generics: &Generics, impl_generic: &str, type_: &str) -> String {
+        let mut new_type = str_to_type_name(type_);
+
+        for generic in collect_generics_types::<Vec<_>>(impl_generics) {
+            if generic == impl_generic || !type_contains(&new_type, &generic) {
+                continue;
+            }
+            if let Some(trait_generic) = self.get_corresponding_generic(impl_generics, &generic) {
+                replace_type(&mut new_type, &generic, &str_to_type_name(&trait_generic));
+            }
+        }
+
+        to_string(&new_type)
+    }
+
+    /**
+       add to the generics of the trait used in associated type projections (e.g. `B` in `B::Item`)
+       the bounds of the corresponding generics of the impl, so that the projections are resolved
+    */
+    fn add_projection_bounds(&mut self, impl_generics: &str, impl_trait_generics: &Generics) {
+        let bounded = str_to_generics(impl_generics);
+        let mut generics = str_to_generics(&self.generics);
+        let items = self.items.join(" ");
+
+        for param in bounded.params.iter() {
+            let GenericParam::Type(impl_param) = param else {
+                continue;
+            };
+            let Some(trait_generic) =
+                self.get_corresponding_generic(impl_trait_generics, &impl_param.ident.to_string())
+            else {
+                continue;
+            };
+            if !items.contains(&format!("{} ::", trait_generic)) {
+                continue;
+            }
+
+            let Some(trait_param) = find_type_param_mut(&mut generics, &trait_generic) else {
+                continue;
+            };
+            for bound in impl_param.bounds.iter() {
+                if !trait_param
+                    .bounds
+                    .iter()
+                    .any(|b| to_string(b) == to_string(bound))
+                {
+                    trait_param.bounds.push(bound.clone());
+                }
+            }
+        }
+
+        self.generics = to_string(&generics);
+    }
+
+    /// replace generics in the trait with unique names, keeping their position, bounds and defaults
     fn replace_generics_names(&mut self) {
         let mut trait_generics = str_to_generics(&self.generics);
 
@@ -208,14 +315,15 @@ impl TraitBody {
         for generic in get_generics_types::<Vec<_>>(&self.generics) {
             let new_generic_name = get_unique_generic_name(&mut generics_types, &mut counter, None);
 
-            add_generic_type(&mut trait_generics, &new_generic_name);
-            remove_generic(&mut trait_generics, &generic);
+            rename_generic(&mut trait_generics, &generic, &new_generic_name);
 
             let type_ = str_to_type_name(&new_generic_name);
             let mut replacer = TypeReplacer {
                 generic: generic.to_owned(),
                 type_,
             };
+            // the bounds and defaults of the other generics can refer to the renamed one
+            replacer.visit_generics_mut(&mut trait_generics);
             self.handle_items_replace(&mut replacer);
         }
 
@@ -223,20 +331,79 @@ impl TraitBody {
             let new_generic_name =
                 get_unique_generic_name(&mut generics_lifetimes, &mut counter, Some("'"));
 
-            add_generic_lifetime(&mut trait_generics, &new_generic_name);
-            remove_generic(&mut trait_generics, &generic);
+            rename_generic(&mut trait_generics, &generic, &new_generic_name);
 
-            let type_ = str_to_type_name(&new_generic_name);
-            let mut replacer = TypeReplacer {
+            let mut replacer = LifetimeReplacer {
                 generic: generic.to_owned(),
-                type_,
+                lifetime: str_to_lifetime(&new_generic_name),
             };
+            // the bounds of the other generics can refer to the renamed one (e.g. `T: 'a`)
+            replacer.visit_generics_mut(&mut trait_generics);
             self.handle_items_replace(&mut replacer);
         }
 
         self.generics = to_string(&trait_generics);
     }
 
+    /**
+       replace the type generics after the ones given by the impl with their defaults,
+       so that the generics of the trait stay aligned by position with the ones of the impl
+       # Example:
+       for trait `TraitName<A, B = A>` and impl `impl<T> TraitName<T> for MyType` -> `TraitName<A>` with `B` replaced by `A`
+    */
+    fn apply_elided_defaults(&mut self, impl_generics: &str) {
+        let given = get_generics_types::<Vec<_>>(impl_generics).len();
+        let mut generics = str_to_generics(&self.generics);
+
+        for generic in get_generics_types::<Vec<_>>(&self.generics)
+            .into_iter()
+            .skip(given)
+        {
+            let Some(default) = get_generic_default(&to_string(&generics), &generic) else {
+                continue;
+            };
+
+            let mut replacer = TypeReplacer {
+                generic: generic.clone(),
+                type_: str_to_type_name(&default),
+            };
+            // the defaults of the following generics can refer to the replaced one
+            replacer.visit_generics_mut(&mut generics);
+            self.handle_items_replace(&mut replacer);
+
+            remove_generic(&mut generics, &generic);
+        }
+
+        self.generics = to_string(&generics);
+    }
+
+    /**
+        replace the trait generics that the impl passes as types (e.g. `A` with `Vec<T>` for `impl<T> Foo<Vec<T>>`),
+        with the placeholders of `get_trait_types` in `impl_generics`
+    */
+    fn apply_trait_types(&mut self, impl_generics: &Generics, trait_types: &[(String, Type)]) {
+        let trait_generics = trait_types
+            .iter()
+            .filter_map(|(placeholder, type_)| {
+                self.get_corresponding_generic(impl_generics, placeholder)
+                    .map(|generic| (generic, type_))
+            })
+            .collect::<Vec<_>>();
+
+        let mut generics = str_to_generics(&self.generics);
+        for (generic, type_) in trait_generics {
+            let mut replacer = TypeReplacer {
+                generic: generic.clone(),
+                type_: type_.clone(),
+            };
+            replacer.visit_generics_mut(&mut generics);
+            self.handle_items_replace(&mut replacer);
+
+            remove_generic(&mut generics, &generic);
+        }
+        self.generics = to_string(&generics);
+    }
+
     /**
        get the generic in the trait corresponding to the impl_generic in the impl
        # Example:
@@ -291,6 +458,388 @@ mod tests {
         .unwrap()
     }
 
+    #[test]
+    fn specialize_is_deterministic() {
+        let expand = || {
+            let trait_body = TraitBody::try_from(quote! {
+                trait Foo<'x, A, B, C> {
+                    fn foo(&self, a: &'x A, b: B, c: C);
+                }
+            })
+            .unwrap();
+            let impl_body = ImplBody::try_from((
+                quote! {
+                    impl<'a, 'b, T, U, V, W> Foo<'a, T, U, V> for Holder<'b, W> {
+                        fn foo(&self, a: &'a T, b: U, c: V) {}
+                    }
+                },
+                Some(WhenCondition::All(vec![
+                    WhenCondition::Type("T".into(), "(_, _, Vec<_>)".into()),
+                    WhenCondition::Type("U".into(), "Result<_, _>".into()),
+                    WhenCondition::Trait("W".into(), vec!["Clone".into(), "Debug".into()]),
+                ])),
+            ))
+            .unwrap();
+            let specialized_trait = trait_body.specialize(&impl_body);
+
+            (
+                TokenStream::from(&specialized_trait).to_string(),
+                TokenStream::from(&impl_body).to_string(),
+            )
+        };
+
+        // the hash sets used while specializing have a different order each time they are created
+        let first = expand();
+        for _ in 0..20 {
+            assert_eq!(expand(), first);
+        }
+    }
+
+    #[test]
+    fn find_fn_without_arguments() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Ping {
+                fn ping(&self);
+                fn ping_with(&self, times: u8);
+            }
+        })
+        .unwrap();
+
+        let ping = trait_body.find_fn("ping", 0).unwrap();
+        assert_eq!(ping.sig.ident, "ping");
+        assert!(trait_body.find_fn("ping", 1).is_none());
+        assert!(trait_body.find_fn("ping_with", 0).is_none());
+    }
+
+    #[test]
+    fn fn_arities_by_name() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Ping {
+                fn ping(&self);
+                fn ping_with(&self, times: u8, delay: u32);
+            }
+        })
+        .unwrap();
+
+        assert_eq!(trait_body.fn_arities("ping"), vec![0]);
+        assert_eq!(trait_body.fn_arities("ping_with"), vec![2]);
+        assert!(trait_body.fn_arities("pong").is_empty());
+    }
+
+    #[test]
+    fn trait_to_tokens_allows_generated_names() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<T> {
+                fn foo(&self, arg1: T);
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T> Foo<T> for MyType {
+                    fn foo(&self, arg1: T) {}
+                }
+            },
+            Some(WhenCondition::Type("T".into(), "Vec<_>".into())),
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body);
+        let tokens = TokenStream::from(&specialized).to_string();
+
+        assert!(tokens.starts_with(
+            &quote! { #[allow(non_camel_case_types, non_snake_case, private_interfaces, private_bounds)] pub(crate) trait }
+                .to_string()
+        ));
+        assert!(tokens.contains(&impl_body.specialized.unwrap().trait_name));
+    }
+
+    #[test]
+    fn specialize_impl_trait_return_type() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<T> {
+                fn iter(&self) -> impl Iterator<Item = T>;
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T> Foo<T> for MyType {
+                    fn iter(&self) -> impl Iterator<Item = T> { std::iter::empty() }
+                }
+            },
+            Some(WhenCondition::Type("T".into(), "Vec<u8>".into())),
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        assert_eq!(specialized.generics.replace(" ", ""), "");
+        assert_eq!(
+            specialized.items[0].replace(" ", ""),
+            "fn iter(&self) -> impl Iterator<Item = Vec<u8>>;".replace(" ", "")
+        );
+
+        let impl_body = impl_body.specialized.unwrap();
+        assert_eq!(
+            impl_body.items[0].replace(" ", ""),
+            "fn iter(&self) -> impl Iterator<Item = Vec<u8>> { std::iter::empty() }"
+                .replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn specialize_fn_with_lifetime_generic() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<'a, T> {
+                fn get<'b>(&'b self, other: &'a T) -> &'b T;
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<'a, T> Foo<'a, T> for MyType {
+                    fn get<'b>(&'b self, other: &'a T) -> &'b T { todo!() }
+                }
+            },
+            Some(WhenCondition::Type("T".into(), "u8".into())),
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        // the lifetime of the fn is not a generic of the trait, so it is kept as is
+        assert_eq!(specialized.generics.replace(" ", ""), "<'__G_1__>");
+        assert_eq!(
+            specialized.items[0].replace(" ", ""),
+            "fn get<'b>(&'b self, other: &'__G_1__ u8) -> &'b u8;".replace(" ", "")
+        );
+
+        let impl_body = impl_body.specialized.unwrap();
+        assert_eq!(
+            impl_body.items[0].replace(" ", ""),
+            "fn get<'b>(&'b self, other: &'a u8) -> &'b u8 { todo!() }".replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn specialize_with_generic_default() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<S: Clone, U = Vec<S>> {
+                fn foo(&self, arg1: S) -> U;
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T: Clone, V> Foo<T, V> for MyType {
+                    fn foo(&self, arg1: T) -> V { todo!() }
+                }
+            },
+            None,
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        assert_eq!(
+            specialized.generics.replace(" ", ""),
+            "<__G_0__: Clone, __G_1__ = Vec<__G_0__>>".replace(" ", "")
+        );
+        assert_eq!(
+            specialized.items[0].replace(" ", ""),
+            "fn foo(&self, arg1: __G_0__) -> __G_1__;".replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn specialize_with_generic_used_by_assoc_type() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<S> {
+                type Out;
+                fn foo(&self) -> Self::Out;
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T: Default> Foo<T> for MyType {
+                    type Out = T;
+                    fn foo(&self) -> T { T::default() }
+                }
+            },
+            Some(WhenCondition::Trait("T".into(), vec!["Default".into()])),
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        // the generic is not used by the items of the trait, but it is passed by the impl
+        assert_eq!(
+            impl_body
+                .specialized
+                .unwrap()
+                .trait_generics
+                .replace(" ", ""),
+            "<T>"
+        );
+        assert_eq!(specialized.generics.replace(" ", ""), "<__G_0__>");
+    }
+
+    #[test]
+    fn specialize_with_type_argument() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<A, B> {
+                fn foo(&self, x: A, y: B);
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T, U> Foo<Vec<T>, U> for MyType<T> {
+                    fn foo(&self, x: Vec<T>, y: U) {}
+                }
+            },
+            None,
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        // `A` is replaced by `Vec<T>`, and `T` is passed by the impl after `U`
+        assert_eq!(
+            impl_body
+                .specialized
+                .unwrap()
+                .trait_generics
+                .replace(" ", ""),
+            "<U,T>"
+        );
+        assert_eq!(specialized.generics.replace(" ", ""), "<__G_1__,T>");
+        assert_eq!(
+            specialized.items[0].replace(" ", ""),
+            "fnfoo(&self,x:Vec<T>,y:__G_1__);"
+        );
+    }
+
+    #[test]
+    fn corresponding_generic_after_trait_type() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<A, B, C> {
+                fn foo(&self, x: A, y: B, z: C);
+            }
+        })
+        .unwrap();
+        let (impl_generics, _) = get_trait_types("<(T, u8), Vec<T>, U>");
+
+        assert_eq!(
+            trait_body.get_corresponding_generic(&impl_generics, "U"),
+            Some("C".to_string())
+        );
+        // `T` is only inside the trait types, which are replaced in the trait instead
+        assert_eq!(
+            trait_body.get_corresponding_generic(&impl_generics, "T"),
+            None
+        );
+    }
+
+    #[test]
+    fn specialize_with_associated_type_condition() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Feed<A, B> {
+                fn feed(&self, item: A, items: B);
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T, U> Feed<T, U> for MyType {
+                    fn feed(&self, item: T, items: U) {}
+                }
+            },
+            Some(WhenCondition::All(vec![
+                WhenCondition::Trait("U".into(), vec!["Iterator".into()]),
+                WhenCondition::Type("T".into(), "U::Item".into()),
+            ])),
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        // `U::Item` is `B::Item` in the trait, which needs the bound of `U` to resolve it
+        assert_eq!(specialized.generics.replace(" ", ""), "<__G_1__:Iterator>");
+        assert_eq!(
+            specialized.items[0].replace(" ", ""),
+            "fnfeed(&self,item:__G_1__::Item,items:__G_1__);"
+        );
+    }
+
+    #[test]
+    fn specialize_with_elided_generic_default() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<S: Clone, U = Vec<S>> {
+                fn foo(&self, arg1: S) -> U;
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T: Clone> Foo<T> for MyType {
+                    fn foo(&self, arg1: T) -> Vec<T> { vec![] }
+                }
+            },
+            None,
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        assert_eq!(
+            specialized.generics.replace(" ", ""),
+            "<__G_0__: Clone>".replace(" ", "")
+        );
+        assert_eq!(
+            specialized.items[0].replace(" ", ""),
+            "fn foo(&self, arg1: __G_0__) -> Vec<__G_0__>;".replace(" ", "")
+        );
+    }
+
+    #[test]
+    fn specialize_with_elided_generic_default_and_added_generic() {
+        let trait_body = TraitBody::try_from(quote! {
+            trait Foo<S = u8> {
+                fn foo(&self, arg1: S);
+            }
+        })
+        .unwrap();
+        let impl_body = ImplBody::try_from((
+            quote! {
+                impl<T: Copy> Foo for T {
+                    fn foo(&self, arg1: u8) { let _: Option<T> = None; }
+                }
+            },
+            Some(WhenCondition::Trait("T".into(), vec!["Copy".into()])),
+        ))
+        .unwrap();
+
+        let specialized = trait_body.specialize(&impl_body).specialized.unwrap();
+
+        // the generic of the impl is added after the generics of the trait instead of taking the place of `S`
+        assert_eq!(
+            impl_body
+                .specialized
+                .unwrap()
+                .trait_generics
+                .replace(" ", ""),
+            "<T>"
+        );
+        assert_eq!(specialized.generics.replace(" ", ""), "<T>");
+        assert_eq!(
+            specialized.items[0].replace(" ", ""),
+            "fn foo(&self, arg1: u8);".replace(" ", "")
+        );
+    }
+
     #[test]
     fn apply_trait_condition() {
         let mut trait_body = get_trait_body();
@@ -0,0 +1,229 @@
+use crate::conditions::WhenCondition;
+use crate::conversions::{str_to_type_name, to_string};
+use crate::types::concretize;
+use proc_macro2::TokenStream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use syn::visit_mut::{self, VisitMut};
+use syn::{Error, GenericArgument, GenericParam, ItemType, PathArguments, Type};
+
+/// type alias with generics declared in the crate, e.g. `type MyResult<E> = Result<String, E>;`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TypeAliasBody {
+    pub name: String,
+    pub generics: Vec<String>,
+    pub type_: String,
+}
+
+impl TryFrom<TokenStream> for TypeAliasBody {
+    type Error = Error;
+
+    fn try_from(tokens: TokenStream) -> Result<Self, Self::Error> {
+        let item = syn::parse2::<ItemType>(tokens)?;
+
+        let generics = item
+            .generics
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Type(tp) => Some(tp.ident.to_string()),
+                GenericParam::Lifetime(lp) => Some(lp.lifetime.to_string()),
+                GenericParam::Const(_) => None,
+            })
+            .collect();
+
+        Ok(TypeAliasBody {
+            name: item.ident.to_string(),
+            generics,
+            type_: to_string(&item.ty),
+        })
+    }
+}
+
+/**
+    expand the registered type aliases in `type_`, nested ones included.
+    # Example:
+    with `type MyResult<E> = Result<String, E>;`, `Vec<MyResult<u8>>` -> `Vec<Result<String, u8>>`
+*/
+pub fn expand_type_aliases(type_: &str, aliases: &[TypeAliasBody]) -> String {
+    if aliases.is_empty() {
+        return type_.to_owned();
+    }
+
+    let mut ty = str_to_type_name(type_);
+    AliasExpander { aliases }.visit_type_mut(&mut ty);
+    to_string(&ty)
+}
+
+/// expand the registered type aliases in the types of `condition`
+pub fn expand_condition_aliases(
+    condition: WhenCondition,
+    aliases: &[TypeAliasBody],
+) -> WhenCondition {
+    match condition {
+        WhenCondition::Type(generic, type_) => {
+            WhenCondition::Type(generic, expand_type_aliases(&type_, aliases))
+        }
+        WhenCondition::Trait(..)
+        | WhenCondition::Lifetime(..)
+        | WhenCondition::Const(..)
+        | WhenCondition::Len(_)
+        | WhenCondition::Arg(_, _) => condition,
+        WhenCondition::All(inner) => WhenCondition::All(
+            inner
+                .into_iter()
+                .map(|c| expand_condition_aliases(c, aliases))
+                .collect(),
+        ),
+        WhenCondition::Any(inner) => WhenCondition::Any(
+            inner
+                .into_iter()
+                .map(|c| expand_condition_aliases(c, aliases))
+                .collect(),
+        ),
+        WhenCondition::Not(inner) => {
+            WhenCondition::Not(Box::new(expand_condition_aliases(*inner, aliases)))
+        }
+    }
+}
+
+struct AliasExpander<'a> {
+    aliases: &'a [TypeAliasBody],
+}
+
+impl AliasExpander<'_> {
+    /// the aliased type if `ty` is an alias applied to as many arguments as its generics
+    fn expand(&self, ty: &Type) -> Option<Type> {
+        let Type::Path(type_path) = ty else {
+            return None;
+        };
+        if type_path.qself.is_some() || type_path.path.segments.len() != 1 {
+            return None;
+        }
+
+        let segment = &type_path.path.segments[0];
+        let alias = self.aliases.iter().find(|a| segment.ident == a.name)?;
+
+        let args = match &segment.arguments {
+            PathArguments::None => vec![],
+            PathArguments::AngleBracketed(ab) => ab
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    GenericArgument::Type(t) => Some(to_string(t)),
+                    GenericArgument::Lifetime(l) => Some(l.to_string()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?,
+            PathArguments::Parenthesized(_) => return None,
+        };
+        if args.len() != alias.generics.len() {
+            return None;
+        }
+
+        let bindings = alias
+            .generics
+            .iter()
+            .cloned()
+            .zip(args)
+            .collect::<HashMap<_, _>>();
+
+        Some(str_to_type_name(&concretize(&alias.type_, &bindings)))
+    }
+}
+
+impl VisitMut for AliasExpander<'_> {
+    fn visit_type_mut(&mut self, node: &mut Type) {
+        // the expansion can contain other aliases, so it is visited as well
+        if let Some(expanded) = self.expand(node) {
+            *node = expanded;
+        }
+        visit_mut::visit_type_mut(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    fn get_aliases() -> Vec<TypeAliasBody> {
+        vec![
+            TypeAliasBody::try_from(quote! { type MyResult<E> = Result<String, E>; }).unwrap(),
+            TypeAliasBody::try_from(quote! { type Pair<'a, T> = (&'a T, MyResult<T>); }).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn parse_type_alias() {
+        let alias = &get_aliases()[1];
+
+        assert_eq!(alias.name, "Pair");
+        assert_eq!(alias.generics, vec!["'a", "T"]);
+        assert_eq!(alias.type_.replace(" ", ""), "(&'aT,MyResult<T>)");
+    }
+
+    #[test]
+    fn expand_simple() {
+        let aliases = get_aliases();
+
+        assert_eq!(
+            expand_type_aliases("MyResult<u8>", &aliases).replace(" ", ""),
+            "Result<String,u8>"
+        );
+        assert_eq!(expand_type_aliases("u8", &aliases), "u8");
+    }
+
+    #[test]
+    fn expand_nested() {
+        let aliases = get_aliases();
+
+        assert_eq!(
+            expand_type_aliases("Vec<MyResult<MyResult<u8>>>", &aliases).replace(" ", ""),
+            "Vec<Result<String,Result<String,u8>>>"
+        );
+        assert_eq!(
+            expand_type_aliases("Pair<'static, Vec<E>>", &aliases).replace(" ", ""),
+            "(&'staticVec<E>,Result<String,Vec<E>>)"
+        );
+    }
+
+    #[test]
+    fn expand_wrong_arguments() {
+        let aliases = get_aliases();
+
+        assert_eq!(
+            expand_type_aliases("MyResult<u8, u8>", &aliases).replace(" ", ""),
+            "MyResult<u8,u8>"
+        );
+        assert_eq!(
+            expand_type_aliases("other::MyResult<u8>", &aliases).replace(" ", ""),
+            "other::MyResult<u8>"
+        );
+    }
+
+    #[test]
+    fn expand_condition() {
+        let aliases = get_aliases();
+        let condition = WhenCondition::All(vec![
+            WhenCondition::Type("T".into(), "MyResult<_>".into()),
+            WhenCondition::Not(Box::new(WhenCondition::Type(
+                "U".into(),
+                "MyResult<u8>".into(),
+            ))),
+            WhenCondition::Trait("T".into(), vec!["Clone".into()]),
+        ]);
+
+        assert_eq!(
+            expand_condition_aliases(condition, &aliases),
+            WhenCondition::All(vec![
+                WhenCondition::Type("T".into(), "Result < String , _ >".into()),
+                WhenCondition::Not(Box::new(WhenCondition::Type(
+                    "U".into(),
+                    "Result < String , u8 >".into(),
+                ))),
+                WhenCondition::Trait("T".into(), vec!["Clone".into()]),
+            ])
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::conversions::to_string;
+use crate::parsing::parse_trait_path;
+use proc_macro2::TokenStream;
+use serde::{Deserialize, Serialize};
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, Token, Type};
+
+/// traits implemented by a type, registered once for the whole crate with `spec_type_traits!`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TypeTraitsBody {
+    pub type_name: String,
+    pub traits: Vec<String>,
+}
+
+struct TypeTraitsBodies(Vec<TypeTraitsBody>);
+
+impl Parse for TypeTraitsBody {
+    fn parse(input: ParseStream) -> Result<Self, Error> {
+        let type_name = to_string(&input.parse::<Type>()?);
+        input.parse::<Token![:]>()?; // consume the ':' token
+
+        let mut traits = vec![parse_trait_path(input)?];
+        while input.peek(Token![+]) {
+            input.parse::<Token![+]>()?; // consume the '+' token
+            traits.push(parse_trait_path(input)?);
+        }
+
+        Ok(TypeTraitsBody { type_name, traits })
+    }
+}
+
+impl Parse for TypeTraitsBodies {
+    fn parse(input: ParseStream) -> Result<Self, Error> {
+        let bodies = input.parse_terminated(TypeTraitsBody::parse, Token![;])?;
+        Ok(TypeTraitsBodies(bodies.into_iter().collect()))
+    }
+}
+
+/**
+    parse the content of `spec_type_traits!`.
+    # Example:
+    `u8: Copy + Clone; MyType: Display;` -> `vec![(u8, [Copy, Clone]), (MyType, [Display])]`
+*/
+pub fn parse_type_traits(tokens: TokenStream) -> Result<Vec<TypeTraitsBody>, Error> {
+    syn::parse2::<TypeTraitsBodies>(tokens).map(|bodies| bodies.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    #[test]
+    fn parse_single_type() {
+        let input = quote! { u8: Copy };
+        let result = parse_type_traits(input).unwrap();
+        assert_eq!(
+            result,
+            vec![TypeTraitsBody {
+                type_name: "u8".into(),
+                traits: vec!["Copy".into()],
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_multiple_types() {
+        let input = quote! { u8: Copy + Clone + Debug; Vec<MyType>: Display; };
+        let result = parse_type_traits(input).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].type_name, "u8");
+        assert_eq!(result[0].traits, vec!["Copy", "Clone", "Debug"]);
+        assert_eq!(result[1].type_name.replace(" ", ""), "Vec<MyType>");
+        assert_eq!(result[1].traits, vec!["Display"]);
+    }
+
+    #[test]
+    fn parse_missing_traits() {
+        let input = quote! { u8: ; };
+        assert!(parse_type_traits(input).is_err());
+    }
+}
@@ -2,11 +2,15 @@ use crate::{
     conversions::{str_to_generics, str_to_lifetime, str_to_type_name, to_string},
     specialize::collect_generics_lifetimes,
 };
-use proc_macro2::Span;
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
 use std::collections::{HashMap, HashSet};
+use syn::punctuated::Punctuated;
+use syn::visit_mut::{self, VisitMut};
 use syn::{
-    Expr, GenericArgument, GenericParam, Generics, Ident, PathArguments, Type, TypeArray,
-    TypeReference, TypeSlice, TypeTuple,
+    AngleBracketedGenericArguments, Expr, ExprLit, ExprUnary, GenericArgument, GenericParam,
+    Generics, Ident, Lifetime, Lit, PathArguments, PathSegment, ReturnType, Token, Type, TypeArray,
+    TypeParamBound, TypePath, TypeReference, TypeSlice, TypeTuple, UnOp,
 };
 
 pub type Aliases = HashMap<String, Vec<String>>;
@@ -18,7 +22,7 @@ pub fn get_concrete_type(type_or_alias: &str, aliases: &Aliases) -> String {
 }
 
 fn resolve_type(ty: &Type, aliases: &Aliases) -> Type {
-    match unwrap_paren(ty) {
+    match unwrap_delimiters(ty) {
         // (T, U)
         Type::Tuple(tuple) => {
             let resolved_elems = tuple
@@ -69,16 +73,40 @@ fn resolve_type(ty: &Type, aliases: &Aliases) -> Type {
             }
 
             for segment in &mut resolved_path.path.segments {
-                if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
-                    for arg in &mut args.args {
-                        if let GenericArgument::Type(inner_ty) = arg {
-                            *inner_ty = resolve_type(inner_ty, aliases);
-                        }
+                resolve_path_arguments(&mut segment.arguments, aliases);
+            }
+
+            Type::Path(resolved_path)
+        }
+
+        // dyn Trait<T> + Send + 'a
+        Type::TraitObject(object) => {
+            let mut resolved_object = object.clone();
+
+            // the arguments of the traits are resolved, the lifetimes are kept as they are
+            for bound in &mut resolved_object.bounds {
+                if let TypeParamBound::Trait(trait_) = bound {
+                    for segment in &mut trait_.path.segments {
+                        resolve_path_arguments(&mut segment.arguments, aliases);
                     }
                 }
             }
 
-            Type::Path(resolved_path)
+            Type::TraitObject(resolved_object)
+        }
+
+        // fn(T, U) -> V
+        Type::BareFn(bare_fn) => {
+            let mut resolved_fn = bare_fn.clone();
+
+            for input in &mut resolved_fn.inputs {
+                input.ty = resolve_type(&input.ty, aliases);
+            }
+            if let ReturnType::Type(_, output) = &mut resolved_fn.output {
+                **output = resolve_type(output, aliases);
+            }
+
+            Type::BareFn(resolved_fn)
         }
 
         // Default case: return the type as-is
@@ -86,12 +114,39 @@ fn resolve_type(ty: &Type, aliases: &Aliases) -> Type {
     }
 }
 
+/// resolve the types in the arguments of a path segment (e.g. `Vec<T>`, `Iterator<Item = T>`, `Fn(T) -> U`)
+fn resolve_path_arguments(arguments: &mut PathArguments, aliases: &Aliases) {
+    match arguments {
+        PathArguments::AngleBracketed(args) => {
+            for arg in &mut args.args {
+                match arg {
+                    GenericArgument::Type(inner_ty) => *inner_ty = resolve_type(inner_ty, aliases),
+                    GenericArgument::AssocType(assoc) => {
+                        assoc.ty = resolve_type(&assoc.ty, aliases)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        PathArguments::Parenthesized(args) => {
+            for input in &mut args.inputs {
+                *input = resolve_type(input, aliases);
+            }
+            if let ReturnType::Type(_, output) = &mut args.output {
+                **output = resolve_type(output, aliases);
+            }
+        }
+        PathArguments::None => {}
+    }
+}
+
 type GenericsMap = HashMap<String, Option<String>>;
 
 #[derive(Debug, Default)]
 pub struct ConstrainedGenerics {
     pub types: GenericsMap,
     pub lifetimes: GenericsMap,
+    pub consts: GenericsMap,
 }
 
 impl From<Generics> for ConstrainedGenerics {
@@ -114,10 +169,28 @@ impl From<Generics> for ConstrainedGenerics {
             })
             .collect();
 
-        ConstrainedGenerics { types, lifetimes }
+        let consts = generics
+            .params
+            .iter()
+            .filter_map(|p| match p {
+                GenericParam::Const(cp) => Some((cp.ident.to_string(), None)),
+                _ => None,
+            })
+            .collect();
+
+        ConstrainedGenerics {
+            types,
+            lifetimes,
+            consts,
+        }
     }
 }
 
+/**
+    the generics of `declared_or_concrete_type` bound by assigning `concrete_type` to it, `None` if it cannot be assigned.
+    The references are matched level by level, so a generic behind a reference is bound to the referenced type
+    (e.g. `T = i32` for `&i32` assigned to `&T`, `T = &i32` for `&&i32`).
+*/
 pub fn type_assignable_generic_constraints(
     concrete_type: &str,
     declared_or_concrete_type: &str,
@@ -154,8 +227,8 @@ fn can_assign(
     declared_or_concrete_type: &Type,
     generics: &mut ConstrainedGenerics,
 ) -> bool {
-    let t1 = unwrap_paren(concrete_type);
-    let t2 = unwrap_paren(declared_or_concrete_type);
+    let t1 = unwrap_delimiters(concrete_type);
+    let t2 = unwrap_delimiters(declared_or_concrete_type);
 
     match (t1, t2) {
         // `_`
@@ -197,55 +270,216 @@ fn can_assign(
             can_assign(&slice1.elem, &slice2.elem, generics)
         }
 
-        // `[T; N]`, `[_; N]`, `[T; _]`, `[_; _]`
+        // `[T; 3]`, `[_; 3]`, `[T; _]`, `[_; _]`, `[T; N]` with `N` const generic
         (Type::Array(array1), Type::Array(array2)) => {
             can_assign(&array1.elem, &array2.elem, generics)
                 && (matches!(array1.len, Expr::Infer(_))
                     || matches!(array2.len, Expr::Infer(_))
-                    || to_string(&array1.len) == to_string(&array2.len))
+                    || check_and_assign_const_generic(
+                        &to_string(&array1.len),
+                        &to_string(&array2.len),
+                        generics,
+                    ))
         }
 
-        // `T`, `T<U>`, `T<_>`
+        // `T`, `T<U>`, `T<_>`, `T::Assoc`
         (Type::Path(path1), Type::Path(path2))
             if path1.qself.is_none() && path2.qself.is_none() =>
         {
-            path1.path.segments.len() == path2.path.segments.len()
-                && path1
-                    .path
-                    .segments
-                    .iter()
-                    .zip(&path2.path.segments)
-                    .all(|(seg1, seg2)| {
-                        check_and_assign_type_generic(
-                            &seg1.ident.to_string(),
-                            &seg2.ident.to_string(),
-                            generics,
-                        ) && (match (&seg1.arguments, &seg2.arguments) {
-                            (
-                                PathArguments::AngleBracketed(args1),
-                                PathArguments::AngleBracketed(args2),
-                            ) => args1.args.iter().zip(&args2.args).all(|(arg1, arg2)| {
-                                match (arg1, arg2) {
-                                    (GenericArgument::Type(t1), GenericArgument::Type(t2)) => {
-                                        can_assign(t1, t2, generics)
-                                    }
-                                    _ => false,
-                                }
-                            }),
-                            _ => seg1.arguments.is_empty() && seg2.arguments.is_empty(),
-                        })
-                    })
+            can_assign_segments(
+                path1.path.segments.iter(),
+                path2.path.segments.iter(),
+                generics,
+            )
+        }
+
+        // `<u8 as Trait>::Assoc` with `<T as Trait>::Assoc`
+        (
+            Type::Path(TypePath {
+                qself: Some(qself1),
+                path: path1,
+            }),
+            Type::Path(TypePath {
+                qself: Some(qself2),
+                path: path2,
+            }),
+        ) => {
+            qself1.position == qself2.position
+                && can_assign(&qself1.ty, &qself2.ty, generics)
+                && can_assign_segments(path1.segments.iter(), path2.segments.iter(), generics)
+        }
+
+        // `<u8 as Trait>::Assoc` with `T::Assoc`, the trait of `T` is left to the compiler
+        (
+            Type::Path(TypePath {
+                qself: Some(qself1),
+                path: path1,
+            }),
+            Type::Path(TypePath {
+                qself: None,
+                path: path2,
+            }),
+        ) if path2.segments.len() > 1
+            && path2.segments[0].arguments.is_empty()
+            && generics
+                .types
+                .contains_key(&path2.segments[0].ident.to_string()) =>
+        {
+            let generic = str_to_type_name(&path2.segments[0].ident.to_string());
+
+            can_assign(&qself1.ty, &generic, generics)
+                && can_assign_segments(
+                    path1.segments.iter().skip(qself1.position),
+                    path2.segments.iter().skip(1),
+                    generics,
+                )
+        }
+
+        // `dyn Trait + Send + 'a`
+        (Type::TraitObject(object1), Type::TraitObject(object2)) => {
+            can_assign_object_bounds(&object1.bounds, &object2.bounds, generics)
         }
 
         _ => false,
     }
 }
 
-fn unwrap_paren(ty: &Type) -> &Type {
-    if let Type::Paren(paren) = ty {
-        unwrap_paren(&paren.elem)
-    } else {
-        ty
+/// check if the segments of two paths can be assigned one by one, with their generic arguments
+fn can_assign_segments<'a>(
+    segments1: impl ExactSizeIterator<Item = &'a PathSegment>,
+    segments2: impl ExactSizeIterator<Item = &'a PathSegment>,
+    generics: &mut ConstrainedGenerics,
+) -> bool {
+    segments1.len() == segments2.len()
+        && segments1.zip(segments2).all(|(seg1, seg2)| {
+            check_and_assign_type_generic(
+                &seg1.ident.to_string(),
+                &seg2.ident.to_string(),
+                generics,
+            ) && (match (&seg1.arguments, &seg2.arguments) {
+                (PathArguments::AngleBracketed(args1), PathArguments::AngleBracketed(args2)) => {
+                    can_assign_generic_args(args1, args2, generics)
+                }
+                _ => seg1.arguments.is_empty() && seg2.arguments.is_empty(),
+            })
+        })
+}
+
+/**
+    check if the bounds of two trait objects can be assigned.
+    The traits are compared regardless of their order (e.g. `dyn Trait + Send` and `dyn Send + Trait`),
+    while the lifetimes are compared by position.
+    If one of the two sides has no lifetime bounds the lifetimes are considered elided.
+*/
+fn can_assign_object_bounds(
+    bounds1: &Punctuated<TypeParamBound, Token![+]>,
+    bounds2: &Punctuated<TypeParamBound, Token![+]>,
+    generics: &mut ConstrainedGenerics,
+) -> bool {
+    let (lifetimes1, traits1) = split_object_bounds(bounds1);
+    let (lifetimes2, mut traits2) = split_object_bounds(bounds2);
+
+    let lifetimes_assignable = lifetimes1.is_empty()
+        || lifetimes2.is_empty()
+        || (lifetimes1.len() == lifetimes2.len()
+            && lifetimes1.iter().zip(&lifetimes2).all(|(lt1, lt2)| {
+                check_and_assign_lifetime_generic(&Some(lt1.clone()), &Some(lt2.clone()), generics)
+            }));
+
+    if !lifetimes_assignable || traits1.len() != traits2.len() {
+        return false;
+    }
+
+    // each trait must match a different trait on the other side
+    traits1.iter().all(|trait1| {
+        let position = traits2.iter().position(|trait2| {
+            to_string(trait1) == to_string(trait2) || can_assign(trait1, trait2, generics)
+        });
+        position.map(|p| traits2.remove(p)).is_some()
+    })
+}
+
+/// split the bounds of a trait object into the lifetimes and the traits, as types to compare their paths
+fn split_object_bounds(bounds: &Punctuated<TypeParamBound, Token![+]>) -> (Vec<String>, Vec<Type>) {
+    let mut lifetimes = vec![];
+    let mut traits = vec![];
+
+    for bound in bounds {
+        match bound {
+            TypeParamBound::Lifetime(lt) => lifetimes.push(lt.to_string()),
+            TypeParamBound::Trait(tr) => traits.push(Type::Path(TypePath {
+                qself: None,
+                path: tr.path.clone(),
+            })),
+            _ => {}
+        }
+    }
+
+    (lifetimes, traits)
+}
+
+/**
+    check if the generic arguments of a path segment can be assigned, lifetimes are compared with
+    lifetimes and types with types.
+    If one of the two sides has no lifetime arguments (e.g. `Cow<str>`) the lifetimes are considered elided.
+*/
+fn can_assign_generic_args(
+    args1: &AngleBracketedGenericArguments,
+    args2: &AngleBracketedGenericArguments,
+    generics: &mut ConstrainedGenerics,
+) -> bool {
+    let (lifetimes1, others1): (Vec<_>, Vec<_>) = args1
+        .args
+        .iter()
+        .partition(|arg| matches!(arg, GenericArgument::Lifetime(_)));
+    let (lifetimes2, others2): (Vec<_>, Vec<_>) = args2
+        .args
+        .iter()
+        .partition(|arg| matches!(arg, GenericArgument::Lifetime(_)));
+
+    let lifetimes_assignable = lifetimes1.is_empty()
+        || lifetimes2.is_empty()
+        || (lifetimes1.len() == lifetimes2.len()
+            && lifetimes1
+                .iter()
+                .zip(&lifetimes2)
+                .all(|(lt1, lt2)| match (lt1, lt2) {
+                    (GenericArgument::Lifetime(lt1), GenericArgument::Lifetime(lt2)) => {
+                        check_and_assign_lifetime_generic(
+                            &Some(lt1.to_string()),
+                            &Some(lt2.to_string()),
+                            generics,
+                        )
+                    }
+                    _ => false,
+                }));
+
+    lifetimes_assignable
+        && others1.len() == others2.len()
+        && others1
+            .iter()
+            .zip(&others2)
+            .all(|(arg1, arg2)| match (arg1, arg2) {
+                (GenericArgument::Type(t1), GenericArgument::Type(t2)) => {
+                    can_assign(t1, t2, generics)
+                }
+                // `Foo<3>` with `Foo<N>`, the const generic is parsed as a type
+                (GenericArgument::Const(c1), GenericArgument::Const(c2)) => {
+                    check_and_assign_const_generic(&to_string(c1), &to_string(c2), generics)
+                }
+                (GenericArgument::Const(c1), GenericArgument::Type(t2)) => {
+                    check_and_assign_const_generic(&to_string(c1), &to_string(t2), generics)
+                }
+                _ => false,
+            })
+}
+
+/// unwrap parentheses and the invisible groups left by macro expansion
+fn unwrap_delimiters(ty: &Type) -> &Type {
+    match ty {
+        Type::Paren(paren) => unwrap_delimiters(&paren.elem),
+        Type::Group(group) => unwrap_delimiters(&group.elem),
+        _ => ty,
     }
 }
 
@@ -277,6 +511,20 @@ fn check_and_assign_type_generic(
     concrete_type == declared_type || declared_type == "_"
 }
 
+/**
+    check if a concrete lifetime can be assigned to a declared one (`None` if elided), i.e. if:
+    - the declared lifetime is elided or `'_`, so any lifetime is accepted
+    - the declared lifetime is a generic, which is then assigned to the concrete lifetime (elided included)
+      or must be already assigned to it
+    - the two lifetimes are the same
+    - the concrete lifetime is `'static`, which outlives every lifetime
+
+    An elided concrete lifetime is unknown, so it is assigned only to elided, `'_` or generic declared lifetimes.
+    # Example:
+    - `&u8` to `&'a u8` -> false, with `'a` generic -> true
+    - `&'static u8` to `&'a u8` -> true
+    - `&'a u8` to `&'static u8` -> false
+*/
 fn check_and_assign_lifetime_generic(
     concrete_lifetime: &Option<String>,
     declared_lifetime: &Option<String>,
@@ -296,10 +544,61 @@ fn check_and_assign_lifetime_generic(
         return true;
     }
 
-    declared_lifetime.as_ref().is_none_or(|v| v == "_")
+    declared_lifetime
+        .as_ref()
+        .is_none_or(|v| v == "_" || v == "'_")
+        || concrete_lifetime == declared_lifetime
         || concrete_lifetime.as_ref().is_some_and(|c| c == "'static")
 }
 
+/// check if a concrete const value can be assigned to a declared one, assigning it if the declared one is a const generic
+fn check_and_assign_const_generic(
+    concrete_value: &str,
+    declared_value: &str,
+    generics: &mut ConstrainedGenerics,
+) -> bool {
+    let concrete_value = normalize_const_value(concrete_value);
+
+    match generics.consts.get(declared_value) {
+        Some(None) => {
+            generics
+                .consts
+                .insert(declared_value.to_string(), Some(concrete_value));
+            true
+        }
+        Some(Some(assigned)) => *assigned == concrete_value,
+        None => normalize_const_value(declared_value) == concrete_value,
+    }
+}
+
+/**
+    the canonical form of a const value, to compare the values written in different forms.
+    # Example:
+    `3usize` -> `3`, `- 1` -> `-1`, `{ 1 + 2 }` -> `{1+2}`
+*/
+pub fn normalize_const_value(value: &str) -> String {
+    match syn::parse_str::<Expr>(value) {
+        Ok(Expr::Lit(ExprLit {
+            lit: Lit::Int(int), ..
+        })) => int.base10_digits().to_string(),
+        Ok(Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        })) if matches!(
+            *expr,
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(_),
+                ..
+            })
+        ) =>
+        {
+            format!("-{}", normalize_const_value(&to_string(&expr)))
+        }
+        _ => value.replace(" ", ""),
+    }
+}
+
 pub fn type_contains(ty: &Type, generic: &str) -> bool {
     let mut type_ = ty.clone();
     let replacement = str_to_type_name("__G__");
@@ -318,6 +617,45 @@ pub fn type_contains_lifetime(ty: &Type, lifetime: &str) -> bool {
     to_string(&type_) != to_string(ty)
 }
 
+/**
+    like `type_contains`, but ignores the occurrences inside associated type projections,
+    since the compiler cannot infer a generic from the projection of one of its associated types.
+    # Example
+    `Vec<T>` contains `T`, `T::Item` and `<T as Iterator>::Item` do not
+*/
+pub fn type_contains_outside_projections(ty: &Type, generic: &str) -> bool {
+    let mut type_ = ty.clone();
+    erase_projections(&mut type_, generic);
+
+    type_contains(&type_, generic)
+}
+
+/// replace the associated type projections of the generic with `_` (e.g. `Vec<_>` for `Vec<T::Item>`)
+pub fn erase_projections(ty: &mut Type, generic: &str) {
+    ProjectionEraser { generic }.visit_type_mut(ty);
+}
+
+struct ProjectionEraser<'a> {
+    generic: &'a str,
+}
+
+impl VisitMut for ProjectionEraser<'_> {
+    fn visit_type_mut(&mut self, node: &mut Type) {
+        // <T as Trait>::Assoc, T::Assoc
+        if let Type::Path(type_path) = node
+            && (type_path.qself.is_some()
+                || (type_path.path.segments.len() > 1
+                    && type_path.path.segments[0].ident == self.generic
+                    && type_path.path.segments[0].arguments.is_empty()))
+        {
+            *node = str_to_type_name("_");
+            return;
+        }
+
+        visit_mut::visit_type_mut(self, node);
+    }
+}
+
 /// Replaces all occurrences of `prev` in the given type with `new`.
 pub fn replace_type(ty: &mut Type, prev: &str, new: &Type) {
     if to_string(ty) == to_string(&str_to_type_name(prev)) {
@@ -344,6 +682,7 @@ pub fn replace_type(ty: &mut Type, prev: &str, new: &Type) {
 
         // (T)
         Type::Paren(s) => replace_type(&mut s.elem, prev, new),
+        Type::Group(g) => replace_type(&mut g.elem, prev, new),
 
         // _
         Type::Infer(_) if prev == "_" => {
@@ -362,10 +701,22 @@ pub fn replace_type(ty: &mut Type, prev: &str, new: &Type) {
                 return;
             }
 
+            // <T as Trait>::Assoc
+            if let Some(qself) = &mut type_path.qself {
+                replace_type(&mut qself.ty, prev, new);
+            }
+
+            // T::Assoc, with `T` replaced by a type that is not an identifier (e.g. `<Vec<u8>>::Assoc`)
+            let qualify = type_path.qself.is_none()
+                && type_path.path.segments.len() > 1
+                && type_path.path.segments[0].ident == prev
+                && type_path.path.segments[0].arguments.is_empty()
+                && !matches!(new, Type::Path(p) if p.qself.is_none() && p.path.get_ident().is_some());
+
             // T<U>
-            for seg in &mut type_path.path.segments {
-                // T
-                if seg.ident == prev {
+            for (i, seg) in type_path.path.segments.iter_mut().enumerate() {
+                // T, the generic can only be the first segment (e.g. not in `Other::T`)
+                if i == 0 && seg.ident == prev && !qualify {
                     seg.ident = Ident::new(&to_string(&new.clone()), Span::call_site());
                 }
 
@@ -378,6 +729,18 @@ pub fn replace_type(ty: &mut Type, prev: &str, new: &Type) {
                     }
                 }
             }
+
+            if qualify {
+                let assoc = type_path
+                    .path
+                    .segments
+                    .iter()
+                    .skip(1)
+                    .map(to_string)
+                    .collect::<Vec<_>>()
+                    .join("::");
+                *ty = str_to_type_name(&format!("<{}>::{}", to_string(new), assoc));
+            }
         }
         _ => {}
     }
@@ -400,12 +763,19 @@ pub fn replace_lifetime(ty: &mut Type, prev: &str, new: &str) {
         Type::Array(a) => replace_lifetime(&mut a.elem, prev, new),
         Type::Slice(s) => replace_lifetime(&mut s.elem, prev, new),
         Type::Paren(p) => replace_lifetime(&mut p.elem, prev, new),
+        Type::Group(g) => replace_lifetime(&mut g.elem, prev, new),
         Type::Path(type_path) => {
             for seg in &mut type_path.path.segments {
                 if let PathArguments::AngleBracketed(ref mut ab) = seg.arguments {
                     for arg in ab.args.iter_mut() {
-                        if let GenericArgument::Type(inner_ty) = arg {
-                            replace_lifetime(inner_ty, prev, new);
+                        match arg {
+                            GenericArgument::Type(inner_ty) => {
+                                replace_lifetime(inner_ty, prev, new)
+                            }
+                            GenericArgument::Lifetime(lt) if lt.to_string() == prev => {
+                                *lt = str_to_lifetime(new);
+                            }
+                            _ => {}
                         }
                     }
                 }
@@ -415,6 +785,145 @@ pub fn replace_lifetime(ty: &mut Type, prev: &str, new: &str) {
     }
 }
 
+/**
+    Substitutes all the `bindings` (generic -> type) in `pattern` at once, so a binding is never applied to the result of another one.
+    Lifetimes can be bound too (e.g. `'a` -> `'static`).
+    # Example
+    `Vec<T>` with `{T: u8}` -> `Vec<u8>`
+*/
+pub fn concretize(pattern: &str, bindings: &HashMap<String, String>) -> String {
+    let mut ty = str_to_type_name(pattern);
+
+    let (lifetimes, types): (HashMap<_, _>, HashMap<_, _>) = bindings
+        .iter()
+        .partition(|(generic, _)| generic.starts_with('\''));
+
+    let mut replacer = BindingsReplacer {
+        types: types
+            .into_iter()
+            .map(|(generic, type_)| (generic.to_owned(), str_to_type_name(type_)))
+            .collect(),
+        lifetimes: lifetimes
+            .into_iter()
+            .map(|(generic, lifetime)| (generic.to_owned(), str_to_lifetime(lifetime)))
+            .collect(),
+    };
+    replacer.visit_type_mut(&mut ty);
+
+    to_string(&ty)
+}
+
+struct BindingsReplacer {
+    types: HashMap<String, Type>,
+    lifetimes: HashMap<String, Lifetime>,
+}
+
+impl VisitMut for BindingsReplacer {
+    fn visit_type_mut(&mut self, node: &mut Type) {
+        // T
+        if let Type::Path(type_path) = node
+            && type_path.qself.is_none()
+            && let Some(ident) = type_path.path.get_ident()
+            && let Some(new) = self.types.get(&ident.to_string())
+        {
+            *node = new.clone();
+            return;
+        }
+
+        visit_mut::visit_type_mut(self, node);
+    }
+
+    fn visit_lifetime_mut(&mut self, node: &mut Lifetime) {
+        if let Some(new) = self.lifetimes.get(&node.to_string()) {
+            *node = new.clone();
+        }
+    }
+}
+
+/**
+    the type reached by one level of deref of a reference or of a smart pointer (`Box`, `Rc`, `Arc`).
+    # Example:
+    `Box<MyType>` -> `MyType`, `&MyType` -> `MyType`, `MyType` -> none
+*/
+pub fn deref_type(type_: &str) -> Option<String> {
+    match unwrap_delimiters(&str_to_type_name(type_)) {
+        Type::Reference(r) => Some(to_string(&r.elem)),
+        Type::Path(p) if p.qself.is_none() => {
+            let last = p.path.segments.last()?;
+            if !["Box", "Rc", "Arc"].contains(&last.ident.to_string().as_str()) {
+                return None;
+            }
+            match &last.arguments {
+                PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                    match args.args.first()? {
+                        GenericArgument::Type(inner) => Some(to_string(inner)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// counts the concrete parts of a type (names, references, pointers, tuples, arrays, ...),
+/// so that each argument of a multi-argument generic weighs the same regardless of its name length
+pub fn specificity(ty: &Type) -> usize {
+    fn count(tokens: TokenStream) -> usize {
+        tokens
+            .into_iter()
+            .map(|token| match token {
+                TokenTree::Ident(_) | TokenTree::Literal(_) => 1,
+                TokenTree::Punct(punct) => matches!(punct.as_char(), '&' | '*') as usize,
+                TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+                    count(group.stream())
+                }
+                TokenTree::Group(group) => 1 + count(group.stream()),
+            })
+            .sum()
+    }
+    count(ty.to_token_stream())
+}
+
+/**
+    the maximum nesting of a type, where the wildcards (and the generics once replaced with `_`) are leaves of depth 0.
+    # Example:
+    `Vec<Vec<u8>>` -> 3, `Vec<Vec<_>>` -> 2, `(&u8, _)` -> 3
+*/
+pub fn type_depth(ty: &Type) -> usize {
+    let max_depth =
+        |types: &mut dyn Iterator<Item = &Type>| types.map(type_depth).max().unwrap_or(0);
+
+    match ty {
+        Type::Infer(_) => 0,
+        Type::Verbatim(tokens) if tokens.is_empty() => 0,
+        Type::Paren(p) => type_depth(&p.elem),
+        Type::Group(g) => type_depth(&g.elem),
+        Type::Reference(r) => 1 + type_depth(&r.elem),
+        Type::Ptr(p) => 1 + type_depth(&p.elem),
+        Type::Slice(s) => 1 + type_depth(&s.elem),
+        Type::Array(a) => 1 + type_depth(&a.elem),
+        Type::Tuple(t) => 1 + max_depth(&mut t.elems.iter()),
+        Type::Path(p) => {
+            1 + max_depth(&mut p.path.segments.iter().flat_map(|seg| {
+                match &seg.arguments {
+                    PathArguments::AngleBracketed(ab) => ab
+                        .args
+                        .iter()
+                        .filter_map(|arg| match arg {
+                            GenericArgument::Type(inner) => Some(inner),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>(),
+                    _ => vec![],
+                }
+            }))
+        }
+        _ => 1,
+    }
+}
+
 /// removes all lifetimes present in generics
 pub fn strip_lifetimes(ty: &mut Type, generics: &Generics) {
     match ty {
@@ -447,6 +956,7 @@ pub fn strip_lifetimes(ty: &mut Type, generics: &Generics) {
 
         // (T)
         Type::Paren(s) => strip_lifetimes(&mut s.elem, generics),
+        Type::Group(g) => strip_lifetimes(&mut g.elem, generics),
 
         // T, T<U>
         Type::Path(type_path) => {
@@ -505,6 +1015,10 @@ pub fn assign_lifetimes(t1: &mut Type, t2: &Type, generics: &mut ConstrainedGene
             assign_lifetimes(&mut paren1.elem, &paren2.elem, generics);
         }
 
+        // invisible groups left by macro expansion
+        (Type::Group(group1), t2) => assign_lifetimes(&mut group1.elem, t2, generics),
+        (t1, Type::Group(group2)) => assign_lifetimes(t1, &group2.elem, generics),
+
         // `[T; N]`, `[_; N]`, `[T; _]`, `[_; _]`
         (Type::Array(array1), Type::Array(array2)) => {
             assign_lifetimes(&mut array1.elem, &array2.elem, generics);
@@ -540,6 +1054,37 @@ pub fn assign_lifetimes(t1: &mut Type, t2: &Type, generics: &mut ConstrainedGene
     }
 }
 
+/**
+    check if every type matching `specific` also matches `general`, with `_` matching any type.
+    # Example:
+    `Vec<u8>` implies `Vec<_>`, but `(_, u8)` does not imply `(i32, _)`
+*/
+pub fn type_implies(specific: &str, general: &str) -> bool {
+    let mut generics = HashSet::new();
+    let mut counter = 0;
+
+    // the wildcards of `specific` stand for unknown concrete types
+    let mut specific = str_to_type_name(specific);
+    replace_infers(&mut specific, &mut generics, &mut counter, &mut vec![]);
+
+    // the wildcards of `general` can be assigned to anything
+    let mut general = str_to_type_name(general);
+    let mut general_generics = vec![];
+    replace_infers(
+        &mut general,
+        &mut generics,
+        &mut counter,
+        &mut general_generics,
+    );
+
+    type_assignable(
+        &to_string(&specific),
+        &to_string(&general),
+        &format!("<{}>", general_generics.join(", ")),
+        &Aliases::default(),
+    )
+}
+
 // TODO: use replace_type to simplify this function
 /// Replaces all occurrences of `_` (inferred types) in the given type with fresh generic type parameters.
 pub fn replace_infers(
@@ -567,6 +1112,7 @@ pub fn replace_infers(
 
         // (_)
         Type::Paren(p) => replace_infers(&mut p.elem, generics, counter, new_generics),
+        Type::Group(g) => replace_infers(&mut g.elem, generics, counter, new_generics),
 
         // T<_>
         Type::Path(type_path) => {
@@ -612,7 +1158,7 @@ pub fn get_unique_generic_name(
 mod tests {
     use super::*;
     use quote::quote;
-    use syn::parse2;
+    use syn::{TypeGroup, parse2};
 
     fn get_aliases() -> Aliases {
         let mut aliases = Aliases::new();
@@ -641,6 +1187,24 @@ mod tests {
         assert_eq!(to_string(&resolved).replace(" ", ""), "&u8");
     }
 
+    #[test]
+    fn resolve_type_groups() {
+        let ty = Type::Group(TypeGroup {
+            group_token: Default::default(),
+            elem: Box::new(str_to_type_name("&MyType")),
+        });
+
+        let resolved = resolve_type(&ty, &get_aliases());
+        assert_eq!(to_string(&resolved).replace(" ", ""), "&u8");
+
+        let concrete = get_concrete_type(&to_string(&ty), &get_aliases());
+        assert_eq!(concrete.replace(" ", ""), "&u8");
+
+        let mut generics = ConstrainedGenerics::from(str_to_generics("<T>"));
+        assert!(can_assign(&ty, &str_to_type_name("&T"), &mut generics));
+        assert!(can_assign(&str_to_type_name("&MyType"), &ty, &mut generics));
+    }
+
     #[test]
     fn resolve_type_arrays() {
         let ty = str_to_type_name("[MyType; 3]");
@@ -679,6 +1243,125 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_type_trait_objects() {
+        let ty = str_to_type_name("Box<dyn MyTrait<MyType, Item = MyType> + Send + 'static>");
+        let resolved = resolve_type(&ty, &get_aliases());
+        assert_eq!(
+            to_string(&resolved).replace(" ", ""),
+            "Box<dynMyTrait<u8,Item=u8>+Send+'static>"
+        );
+    }
+
+    #[test]
+    fn resolve_type_nested_in_trait_objects() {
+        let ty = str_to_type_name("Box<dyn Fn(MyType, &MyType) -> Vec<MyType> + Send>");
+        let resolved = resolve_type(&ty, &get_aliases());
+        assert_eq!(
+            to_string(&resolved).replace(" ", ""),
+            "Box<dynFn(u8,&u8)->Vec<u8>+Send>"
+        );
+
+        let ty = str_to_type_name("&dyn Iterator<Item = Option<MyType>>");
+        let resolved = resolve_type(&ty, &get_aliases());
+        assert_eq!(
+            to_string(&resolved).replace(" ", ""),
+            "&dynIterator<Item=Option<u8>>"
+        );
+
+        // an alias of a trait object
+        let mut aliases = get_aliases();
+        aliases.insert("dyn Error".to_string(), vec!["E".to_string()]);
+        let ty = str_to_type_name("Result<MyType, Box<E>>");
+        let resolved = resolve_type(&ty, &aliases);
+        assert_eq!(
+            to_string(&resolved).replace(" ", ""),
+            "Result<u8,Box<dynError>>"
+        );
+    }
+
+    #[test]
+    fn resolve_type_fn_pointers() {
+        let ty = str_to_type_name("fn(MyType, &[MyType]) -> Option<MyType>");
+        let resolved = resolve_type(&ty, &get_aliases());
+        assert_eq!(
+            to_string(&resolved).replace(" ", ""),
+            "fn(u8,&[u8])->Option<u8>"
+        );
+
+        let ty = str_to_type_name("Vec<fn(MyType)>");
+        let resolved = resolve_type(&ty, &get_aliases());
+        assert_eq!(to_string(&resolved).replace(" ", ""), "Vec<fn(u8)>");
+    }
+
+    #[test]
+    fn compare_trait_objects() {
+        let aliases = Aliases::default();
+
+        assert!(type_assignable("&dyn Trait", "&dyn Trait", "", &aliases));
+        assert!(type_assignable("&dyn Trait", "&_", "", &aliases));
+        assert!(type_assignable(
+            "Box<dyn Trait + Send>",
+            "Box<dyn Send + Trait>",
+            "",
+            &aliases
+        ));
+        assert!(!type_assignable(
+            "Box<dyn Trait + Send>",
+            "Box<dyn Trait>",
+            "",
+            &aliases
+        ));
+        assert!(!type_assignable(
+            "Box<dyn Trait>",
+            "Box<dyn Trait + Send>",
+            "",
+            &aliases
+        ));
+        assert!(!type_assignable(
+            "Box<dyn Trait + Send>",
+            "Box<dyn Trait + Sync>",
+            "",
+            &aliases
+        ));
+        assert!(type_assignable(
+            "Box<dyn Trait<u8> + Send>",
+            "Box<dyn Send + Trait<T>>",
+            "<T>",
+            &aliases
+        ));
+        assert!(type_assignable(
+            "Box<dyn Fn(u8) -> u8>",
+            "Box<dyn Fn(u8) -> u8>",
+            "",
+            &aliases
+        ));
+    }
+
+    #[test]
+    fn compare_trait_objects_lifetimes() {
+        let aliases = Aliases::default();
+
+        assert!(type_assignable(
+            "Box<dyn Trait + Send + 'static>",
+            "Box<dyn Trait + Send + 'a>",
+            "<'a>",
+            &aliases
+        ));
+        assert!(type_assignable(
+            "Box<dyn Trait + Send + 'a>",
+            "Box<dyn Trait + Send>",
+            "",
+            &aliases
+        ));
+        assert!(!type_assignable(
+            "Box<dyn Trait + 'a>",
+            "Box<dyn Trait + 'static>",
+            "<'a>",
+            &aliases
+        ));
+    }
+
     #[test]
     fn compare_types_simple() {
         let mut g = ConstrainedGenerics::default();
@@ -766,6 +1449,12 @@ mod tests {
         let t1 = str_to_type_name("&u8");
         let t2 = str_to_type_name("&i8");
         assert!(!can_assign(&t1, &t2, &mut g));
+
+        // owned and borrowed types are distinct
+        let t1 = str_to_type_name("&u8");
+        let t2 = str_to_type_name("u8");
+        assert!(!can_assign(&t1, &t2, &mut g));
+        assert!(!can_assign(&t2, &t1, &mut g));
     }
 
     #[test]
@@ -816,6 +1505,41 @@ mod tests {
         assert!(!can_assign(&t1, &t2, &mut g));
     }
 
+    #[test]
+    fn compare_types_references_lifetimes_both_directions() {
+        let assignable = |t1: &str, t2: &str, generics: &str| {
+            let mut g = ConstrainedGenerics::from(str_to_generics(generics));
+            can_assign(&str_to_type_name(t1), &str_to_type_name(t2), &mut g)
+        };
+        let types = ["&u8", "&'a u8", "&'static u8"];
+
+        // (concrete, declared) -> assignable, with `'a` not a generic
+        let expected = [
+            [true, false, false],
+            [true, true, false],
+            [true, true, true],
+        ];
+        for (i, t1) in types.iter().enumerate() {
+            for (j, t2) in types.iter().enumerate() {
+                assert_eq!(assignable(t1, t2, ""), expected[i][j], "{} to {}", t1, t2);
+            }
+        }
+
+        // with `'a` generic, it takes any concrete lifetime
+        let expected = [[true, true, false], [true, true, false], [true, true, true]];
+        for (i, t1) in types.iter().enumerate() {
+            for (j, t2) in types.iter().enumerate() {
+                assert_eq!(
+                    assignable(t1, t2, "<'a>"),
+                    expected[i][j],
+                    "{} to {} with 'a generic",
+                    t1,
+                    t2
+                );
+            }
+        }
+    }
+
     #[test]
     fn compare_types_slices() {
         let mut g = ConstrainedGenerics::default();
@@ -940,6 +1664,138 @@ mod tests {
         assert!(!can_assign(&t1, &t2, &mut g));
     }
 
+    #[test]
+    fn compare_types_paths_with_lifetimes() {
+        let mut g = ConstrainedGenerics::default();
+
+        let t1 = str_to_type_name("Cow<'static, str>");
+        let t2 = str_to_type_name("Cow<'static, str>");
+        assert!(can_assign(&t1, &t2, &mut g));
+
+        let t1 = str_to_type_name("Cow<'static, str>");
+        let t2 = str_to_type_name("Cow<'_, _>");
+        assert!(can_assign(&t1, &t2, &mut g));
+
+        let t1 = str_to_type_name("Cow<'a, str>");
+        let t2 = str_to_type_name("Cow<_>");
+        assert!(can_assign(&t1, &t2, &mut g));
+
+        let t1 = str_to_type_name("Cow<str>");
+        let t2 = str_to_type_name("Cow<'static, _>");
+        assert!(can_assign(&t1, &t2, &mut g));
+
+        g.lifetimes.insert("'a".to_string(), None);
+        g.types.insert("T".to_string(), None);
+        let t1 = str_to_type_name("Cow<'a, str>");
+        let t2 = str_to_type_name("Cow<'a, T>");
+        assert!(can_assign(&t1, &t2, &mut g));
+
+        let t1 = str_to_type_name("Cow<'a, str>");
+        let t2 = str_to_type_name("Cow<'a, [u8]>");
+        assert!(!can_assign(&t1, &t2, &mut g));
+
+        let t1 = str_to_type_name("Cow<'a, str>");
+        let t2 = str_to_type_name("Cow<'static, str>");
+        assert!(!can_assign(&t1, &t2, &mut g));
+    }
+
+    #[test]
+    fn compare_types_paths_mixed_wildcards() {
+        let aliases = Aliases::default();
+
+        assert!(type_assignable(
+            "HashMap<u8, String>",
+            "HashMap<_, String>",
+            "",
+            &aliases
+        ));
+        assert!(!type_assignable(
+            "HashMap<u8, String>",
+            "HashMap<_, u8>",
+            "",
+            &aliases
+        ));
+        assert!(!type_assignable(
+            "HashMap<u8, String>",
+            "HashMap<_>",
+            "",
+            &aliases
+        ));
+
+        let g = type_assignable_generic_constraints(
+            "HashMap<u8, String>",
+            "HashMap<_, V>",
+            "<V>",
+            &aliases,
+        )
+        .unwrap();
+        assert_eq!(g.types.get("V"), Some(&Some("String".to_string())));
+
+        let g = type_assignable_generic_constraints(
+            "HashMap<u8, String>",
+            "HashMap<K, _>",
+            "<K>",
+            &aliases,
+        )
+        .unwrap();
+        assert_eq!(g.types.get("K"), Some(&Some("u8".to_string())));
+    }
+
+    #[test]
+    fn compare_types_const_generics() {
+        let aliases = Aliases::default();
+
+        let g =
+            type_assignable_generic_constraints("[u8; 3]", "[u8; N]", "<const N: usize>", &aliases)
+                .unwrap();
+        assert_eq!(g.consts.get("N"), Some(&Some("3".to_string())));
+
+        let g = type_assignable_generic_constraints(
+            "([u8; 3usize], [i32; 3])",
+            "([u8; N], [i32; N])",
+            "<const N: usize>",
+            &aliases,
+        )
+        .unwrap();
+        assert_eq!(g.consts.get("N"), Some(&Some("3".to_string())));
+
+        assert!(!type_assignable(
+            "([u8; 3], [i32; 4])",
+            "([u8; N], [i32; N])",
+            "<const N: usize>",
+            &aliases
+        ));
+        assert!(type_assignable("[u8; 3usize]", "[u8; 3]", "", &aliases));
+        assert!(!type_assignable("[u8; 3]", "[u8; 4]", "", &aliases));
+    }
+
+    #[test]
+    fn wildcard_type_implication() {
+        assert!(type_implies("u8", "_"));
+        assert!(type_implies("Vec<u8>", "Vec<_>"));
+        assert!(type_implies("(i32, u8)", "(_, u8)"));
+        assert!(type_implies("(_, u8)", "(_, _)"));
+        assert!(type_implies("&Vec<_>", "&_"));
+
+        assert!(!type_implies("_", "u8"));
+        assert!(!type_implies("(_, u8)", "(i32, _)"));
+        assert!(!type_implies("(_, _)", "(_, u8)"));
+        assert!(!type_implies("Vec<u8>", "Vec<V>"));
+    }
+
+    #[test]
+    fn deref_types() {
+        let deref = |type_: &str| deref_type(type_).map(|t| t.replace(" ", ""));
+
+        assert_eq!(deref("Box<MyType>"), Some("MyType".to_string()));
+        assert_eq!(deref("std::rc::Rc<Vec<u8>>"), Some("Vec<u8>".to_string()));
+        assert_eq!(deref("Arc<dyn Foo>"), Some("dynFoo".to_string()));
+        assert_eq!(deref("&'a mut MyType"), Some("MyType".to_string()));
+        assert_eq!(deref("&Box<MyType>"), Some("Box<MyType>".to_string()));
+        assert_eq!(deref("MyType"), None);
+        assert_eq!(deref("Vec<MyType>"), None);
+    }
+
     #[test]
     fn compare_types_nested() {
         let mut g = ConstrainedGenerics::default();
@@ -973,6 +1829,10 @@ mod tests {
             "(T)",
             "Other<T>",
             "T<Other>",
+            "&'a Other<T>",
+            "&mut Other<&T>",
+            "T::Assoc",
+            "<T as Trait>::Assoc",
         ];
         for ty in types {
             let type_ = str_to_type_name(ty);
@@ -998,6 +1858,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn contains_lifetime_in_path_arguments() {
+        let type_ = str_to_type_name("Cow<'a, str>");
+        assert!(type_contains_lifetime(&type_, "'a"));
+        assert!(!type_contains_lifetime(&type_, "'b"));
+    }
+
+    #[test]
+    fn contains_type_outside_projections() {
+        let contains = |ty: &str| type_contains_outside_projections(&str_to_type_name(ty), "T");
+
+        assert!(contains("Vec<T>"));
+        assert!(contains("(T, T::Assoc)"));
+        assert!(!contains("T::Assoc"));
+        assert!(!contains("Option<T::Assoc>"));
+        assert!(!contains("<T as Trait>::Assoc"));
+        assert!(!contains("<Vec<T> as Trait>::Assoc"));
+        assert!(!contains("Self::Assoc"));
+    }
+
     #[test]
     fn replace_type_simple() {
         let mut ty: Type = parse2(quote! { T }).unwrap();
@@ -1042,6 +1922,11 @@ mod tests {
         replace_type(&mut ty, "&T", &new_ty);
 
         assert_eq!(to_string(&ty).replace(" ", ""), "String".to_string());
+
+        let mut ty: Type = parse2(quote! { &'a Wrapper<T> }).unwrap();
+        replace_type(&mut ty, "T", &new_ty);
+
+        assert_eq!(to_string(&ty).replace(" ", ""), "&'aWrapper<String>");
     }
 
     #[test]
@@ -1136,6 +2021,159 @@ mod tests {
         assert_eq!(to_string(&ty).replace(" ", ""), "String".to_string());
     }
 
+    #[test]
+    fn type_depth_nesting() {
+        let depth = |ty: &str| type_depth(&str_to_type_name(ty));
+
+        assert_eq!(depth("u8"), 1);
+        assert_eq!(depth("_"), 0);
+        assert_eq!(depth("Vec<u8>"), 2);
+        assert_eq!(depth("Vec<Vec<u8>>"), 3);
+        assert_eq!(depth("Vec<Vec<_>>"), 2);
+        assert_eq!(depth("Vec<Vec<_>>"), depth("Vec<u8>"));
+        assert_eq!(depth("HashMap<u8, Vec<u8>>"), 3);
+        assert_eq!(depth("(&u8, _)"), 3);
+        assert_eq!(depth("&[(u8)]"), 3);
+        assert_eq!(depth("[Vec<_>; 3]"), 2);
+        assert_eq!(depth("Cow<'a, str>"), 2);
+
+        // the generics count as wildcards once replaced
+        let mut ty = str_to_type_name("Vec<Vec<T>>");
+        replace_type(&mut ty, "T", &str_to_type_name("_"));
+        assert_eq!(type_depth(&ty), 2);
+    }
+
+    #[test]
+    fn replace_type_assoc_path() {
+        let replace = |ty: &str, new: &str| {
+            let mut ty = str_to_type_name(ty);
+            replace_type(&mut ty, "T", &str_to_type_name(new));
+            to_string(&ty).replace(" ", "")
+        };
+
+        assert_eq!(replace("T::Assoc", "u8"), "u8::Assoc");
+        assert_eq!(replace("T::Assoc", "Vec<u8>"), "<Vec<u8>>::Assoc");
+        assert_eq!(replace("Option<T::Item>", "&u8"), "Option<<&u8>::Item>");
+        assert_eq!(
+            replace("<T as Trait>::Assoc", "Vec<u8>"),
+            "<Vec<u8>asTrait>::Assoc"
+        );
+        // `T` is only the generic in the first segment
+        assert_eq!(replace("Other::T", "Vec<u8>"), "Other::T");
+    }
+
+    #[test]
+    fn compare_types_reference_generic() {
+        let aliases = Aliases::default();
+
+        let g = type_assignable_generic_constraints("&i32", "&T", "<T>", &aliases).unwrap();
+        assert_eq!(g.types.get("T"), Some(&Some("i32".to_string())));
+
+        let g = type_assignable_generic_constraints("&&i32", "&T", "<T>", &aliases).unwrap();
+        assert_eq!(
+            g.types
+                .get("T")
+                .cloned()
+                .flatten()
+                .map(|t| t.replace(" ", "")),
+            Some("&i32".to_string())
+        );
+
+        assert!(!type_assignable("i32", "&T", "<T>", &aliases));
+    }
+
+    #[test]
+    fn compare_types_assoc_paths() {
+        let aliases = Aliases::default();
+
+        let g = type_assignable_generic_constraints(
+            "<u8 as Trait>::Assoc",
+            "T::Assoc",
+            "<T: Trait>",
+            &aliases,
+        )
+        .unwrap();
+        assert_eq!(g.types.get("T"), Some(&Some("u8".to_string())));
+
+        let g = type_assignable_generic_constraints(
+            "<Vec<u8> as Trait>::Assoc",
+            "<T as Trait>::Assoc",
+            "<T>",
+            &aliases,
+        )
+        .unwrap();
+        assert_eq!(
+            g.types
+                .get("T")
+                .cloned()
+                .flatten()
+                .map(|t| t.replace(" ", "")),
+            Some("Vec<u8>".to_string())
+        );
+
+        assert!(type_assignable("u8::Assoc", "T::Assoc", "<T>", &aliases));
+        assert!(!type_assignable(
+            "<u8 as Trait>::Other",
+            "T::Assoc",
+            "<T>",
+            &aliases
+        ));
+        assert!(!type_assignable(
+            "<u8 as Trait>::Assoc",
+            "<T as Other>::Assoc",
+            "<T>",
+            &aliases
+        ));
+        assert!(!type_assignable("<u8 as Trait>::Assoc", "u8", "", &aliases));
+    }
+
+    fn get_bindings(bindings: &[(&str, &str)]) -> HashMap<String, String> {
+        bindings
+            .iter()
+            .map(|(generic, type_)| (generic.to_string(), type_.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn concretize_simple() {
+        let bindings = get_bindings(&[("T", "u8")]);
+
+        assert_eq!(concretize("Vec<T>", &bindings).replace(" ", ""), "Vec<u8>");
+        assert_eq!(concretize("T", &bindings), "u8");
+        assert_eq!(concretize("U", &bindings), "U");
+    }
+
+    #[test]
+    fn concretize_nested() {
+        let bindings = get_bindings(&[("T", "u8"), ("U", "String")]);
+
+        assert_eq!(
+            concretize("Option<(T, &[U], Vec<Vec<T>>, [U; 2])>", &bindings).replace(" ", ""),
+            "Option<(u8,&[String],Vec<Vec<u8>>,[String;2])>"
+        );
+        assert_eq!(
+            concretize("Box<dyn Fn(T) -> U>", &bindings).replace(" ", ""),
+            "Box<dynFn(u8)->String>"
+        );
+    }
+
+    #[test]
+    fn concretize_compound() {
+        let bindings = get_bindings(&[("T", "Vec<(u8, &'a str)>"), ("'a", "'static")]);
+
+        assert_eq!(
+            concretize("HashMap<T, &'a T>", &bindings).replace(" ", ""),
+            "HashMap<Vec<(u8,&'astr)>,&'staticVec<(u8,&'astr)>>"
+        );
+    }
+
+    #[test]
+    fn concretize_simultaneous() {
+        let bindings = get_bindings(&[("T", "U"), ("U", "u8")]);
+
+        assert_eq!(concretize("(T, U)", &bindings).replace(" ", ""), "(U,u8)");
+    }
+
     #[test]
     fn replace_infers_simple() {
         let mut ty: Type = parse2(quote! { _ }).unwrap();
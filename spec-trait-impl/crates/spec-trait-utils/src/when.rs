@@ -0,0 +1,162 @@
+use crate::conditions::{
+    get_conflicting_conditions, get_conjunctions, parse_condition, split_fallback, split_for,
+    split_priority, split_requires,
+};
+use crate::conversions::{strs_to_where_predicates, tokens_to_impl};
+use crate::impls::{ImplBody, break_attr, fallback_impl, impls_for_types};
+use crate::traits::TraitBody;
+use crate::type_aliases::{TypeAliasBody, expand_condition_aliases};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::Error;
+
+/// what `when` generates for an impl, before the impls are registered in the cache
+#[derive(Debug, Clone)]
+pub struct WhenExpansion {
+    /// the condition as written (e.g. `T: Foo` for `#[when(T: Foo, priority = 1)]`), `None` for the default impl
+    pub condition: Option<TokenStream>,
+    /// the impls for `spec!`, one for each conjunction of the condition, and the one from `fallback`
+    pub impls: Vec<ImplBody>,
+    /// the specialized traits and impls that replace the item
+    pub tokens: TokenStream,
+}
+
+/**
+    expand the condition `attr` of `when` on the impl `item`, with the type aliases of the crate and
+    `get_trait` to find the trait of the impl by name.
+    With `for = {...}` the impl is for `_` and it is expanded once for each of the types.
+    The errors point at the part of the condition that cannot be parsed.
+*/
+pub fn expand_when(
+    attr: TokenStream,
+    item: TokenStream,
+    type_aliases: &[TypeAliasBody],
+    get_trait: impl Fn(&str) -> Option<TraitBody>,
+) -> Result<WhenExpansion, Error> {
+    let (attr, types) = split_for(attr)?;
+    if types.is_empty() {
+        return expand_impl(attr, item, type_aliases, &get_trait);
+    }
+
+    // the specialized trait names contain the type, so they stay unique across the types
+    let mut expansion = WhenExpansion {
+        condition: None,
+        impls: vec![],
+        tokens: TokenStream::new(),
+    };
+    for impl_ in impls_for_types(&tokens_to_impl(item)?, &types)? {
+        let type_expansion =
+            expand_impl(attr.clone(), quote! { #impl_ }, type_aliases, &get_trait)?;
+        expansion.condition = type_expansion.condition;
+        expansion.impls.extend(type_expansion.impls);
+        expansion.tokens.extend(type_expansion.tokens);
+    }
+    Ok(expansion)
+}
+
+/// expand `when` on an impl for a single type
+fn expand_impl(
+    attr: TokenStream,
+    item: TokenStream,
+    type_aliases: &[TypeAliasBody],
+    get_trait: &dyn Fn(&str) -> Option<TraitBody>,
+) -> Result<WhenExpansion, Error> {
+    let (attr, requires) = split_requires(attr)?;
+    let (attr, fallback) = split_fallback(attr)?;
+    let (attr, priority) = split_priority(attr)?;
+    let condition = parse_condition(attr.clone())?;
+
+    // without condition the impl is the default one, so it is left as is (apart from `requires`)
+    let Some(condition) = condition else {
+        if fallback.is_some() {
+            return Err(Error::new(
+                Span::call_site(),
+                "`fallback` needs a condition, the impl without condition is the default one",
+            ));
+        }
+        let impl_body = ImplBody {
+            priority,
+            ..ImplBody::try_from((item.clone(), None))?
+        };
+        return Ok(WhenExpansion {
+            condition: None,
+            impls: vec![impl_body],
+            tokens: add_requires(item, &requires)?,
+        });
+    };
+
+    // expanded as in the cache, so that the generated trait names match
+    let condition = expand_condition_aliases(condition, type_aliases);
+    let conjunctions = get_conjunctions(condition.clone());
+
+    // a conjunction assigning two unrelated types to a generic would generate an impl that never matches
+    let impl_generics = ImplBody::try_from((item.clone(), None))?.impl_generics;
+    for c in &conjunctions {
+        if let Some((a, b)) = get_conflicting_conditions(c, &impl_generics) {
+            let message = format!(
+                "Contradictory condition: `{}` and `{}` cannot both hold, the impl would never be used",
+                a, b
+            );
+            return Err(Error::new(Span::call_site(), message));
+        }
+    }
+
+    let mut impls = vec![];
+    let mut parts = vec![];
+    for c in conjunctions {
+        let impl_body = ImplBody {
+            source_condition: Some(condition.clone()),
+            priority,
+            requires: requires.clone(),
+            fallback: fallback.is_some(),
+            ..ImplBody::try_from((item.clone(), Some(c)))?
+        };
+
+        let Some(trait_body) = get_trait(&impl_body.trait_name) else {
+            let message = format!(
+                "Trait `{}` not found, a trait of another crate must be registered with `spec_trait! {{ #[foreign] trait ... }}`",
+                impl_body.trait_name
+            );
+            return Err(Error::new(Span::call_site(), message));
+        };
+
+        let specialized_trait = trait_body.specialize(&impl_body);
+
+        let trait_token_stream = TokenStream::from(&specialized_trait);
+        let impl_token_stream = TokenStream::from(&impl_body);
+
+        parts.push(quote! {
+            #trait_token_stream
+            #impl_token_stream
+        });
+        impls.push(impl_body);
+    }
+
+    if let Some(fallback) = &fallback {
+        let (impl_no_attrs, _) = break_attr(&tokens_to_impl(item)?);
+        let fallback_impl = fallback_impl(&impl_no_attrs, fallback);
+        impls.push(ImplBody::try_from((quote! { #fallback_impl }, None))?);
+        parts.push(quote! { #fallback_impl });
+    }
+
+    Ok(WhenExpansion {
+        condition: Some(attr),
+        impls,
+        tokens: quote! { #(#parts)* },
+    })
+}
+
+/// add the where predicates of `requires` to the default impl
+fn add_requires(item: TokenStream, requires: &[String]) -> Result<TokenStream, Error> {
+    if requires.is_empty() {
+        return Ok(item);
+    }
+
+    let mut impl_ = tokens_to_impl(item)?;
+    impl_
+        .generics
+        .make_where_clause()
+        .predicates
+        .extend(strs_to_where_predicates(requires));
+    Ok(quote! { #impl_ })
+}
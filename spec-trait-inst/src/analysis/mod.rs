@@ -4,6 +4,7 @@ pub mod utils;
 use crate::CliArgs;
 use rustc_middle::mir;
 use rustc_middle::ty;
+use std::time::Duration;
 use sti_analysis::STIAnalysis;
 
 pub struct Analyzer<'tcx> {
@@ -42,11 +43,31 @@ impl<'tcx> Analyzer<'tcx> {
         log::debug!("Finished analysis: {}", name);
     }
 
+    fn report_elapsed(&self, name: &str, elapsed: Option<Duration>) {
+        let Some(elapsed) = elapsed else {
+            log::warn!("No timing available for {}", name);
+            return;
+        };
+
+        if self.cli_args.timings {
+            println!("{} took {:?}", name, elapsed);
+        } else {
+            log::info!("{} took {:?}", name, elapsed);
+        }
+    }
+
     pub fn run(&self) {
         self.pre_process_cli_args();
-        println!("CIAO");
         self.run_analysis("STIAnalysis", |analyzer| {
-            STIAnalysis::new(analyzer).run();
+            let sti_analysis = STIAnalysis::new(analyzer);
+            sti_analysis.run();
+            // The tests install the plugin with `test-mode`, so they check the measurement and not only its output
+            #[cfg(feature = "test-mode")]
+            assert!(
+                sti_analysis.elapsed().is_some(),
+                "STIAnalysis::run must measure its elapsed time"
+            );
+            analyzer.report_elapsed("STIAnalysis", sti_analysis.elapsed());
         });
         self.post_process_cli_args();
     }
@@ -0,0 +1,98 @@
+use serde::Serialize;
+use std::fmt;
+
+/// A method call to a trait method, i.e. a candidate for specialization.
+#[derive(Serialize, Debug, Clone)]
+pub struct CallSite {
+    /// The location of the call in the source code
+    pub span: String,
+    /// The name of the called method
+    pub method: String,
+    /// The type of the receiver of the call
+    pub receiver_type: String,
+    /// The path of the trait defining the method
+    pub trait_name: String,
+    /// The impls of the trait that could be selected
+    pub candidates: Vec<String>,
+    /// The impl selected by the compiler, if it can be resolved
+    pub selected: Option<String>,
+}
+
+/// How a value is passed to a call, written as the matching receiver form.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiverKind {
+    /// The value is moved (or copied), i.e. `self`
+    Owned,
+    /// The value is borrowed, i.e. `&self`
+    Shared,
+    /// The value is mutably borrowed, i.e. `&mut self`
+    Mutable,
+}
+
+impl fmt::Display for ReceiverKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiverKind::Owned => write!(f, "self"),
+            ReceiverKind::Shared => write!(f, "&self"),
+            ReceiverKind::Mutable => write!(f, "&mut self"),
+        }
+    }
+}
+
+/// A call to a trait method with the argument types inferred by the compiler.
+#[derive(Serialize, Debug, Clone)]
+pub struct InferredCall {
+    /// The location of the call in the source code
+    pub span: String,
+    /// The call as written in the source code, e.g. `x.foo(1)`
+    pub call: String,
+    /// The type of the receiver
+    pub receiver_type: String,
+    /// The types of the arguments, receiver excluded
+    pub arg_types: Vec<String>,
+    /// How the receiver is passed
+    pub receiver_kind: ReceiverKind,
+    /// How the arguments are passed, receiver excluded
+    pub arg_kinds: Vec<ReceiverKind>,
+    /// Whether the receiver is borrowed by the call, e.g. `x.foo()` with `fn foo(&self)`
+    pub receiver_borrowed: bool,
+    /// Whether the method is found through a deref of the receiver, e.g. `Box<T>` to `T`
+    pub receiver_deref: bool,
+}
+
+impl InferredCall {
+    /// Returns the annotation to write by hand in `spec!`.
+    ///
+    /// This is the whole macro invocation, e.g. `spec! { x.foo(1u8); ZST; [u8] }`,
+    /// with `deref` when the method is found through a deref of the receiver
+    /// and `no_borrow` when the receiver is passed as is, e.g. `self` in a `&self` method.
+    /// The way each value is passed follows as a comment, e.g. `// &self, self`.
+    pub fn to_spec_annotation(&self) -> String {
+        let mut parts = vec![
+            self.call.clone(),
+            self.receiver_type.clone(),
+            format!("[{}]", self.arg_types.join(", ")),
+        ];
+        if self.receiver_deref {
+            parts.push("deref".to_string());
+        }
+        if !self.receiver_borrowed {
+            parts.push("no_borrow".to_string());
+        }
+
+        let kinds = std::iter::once(&self.receiver_kind)
+            .chain(self.arg_kinds.iter())
+            .map(|kind| kind.to_string())
+            .collect::<Vec<_>>();
+        format!("spec! {{ {} }} // {}", parts.join("; "), kinds.join(", "))
+    }
+}
+
+/// The output of `--emit-spec-json` for a single crate.
+#[derive(Serialize, Debug, Clone)]
+pub struct SpecReport {
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub call_sites: Vec<CallSite>,
+    pub inferred_calls: Vec<InferredCall>,
+}
@@ -1,8 +1,10 @@
+pub mod call_site;
 mod sti_visitor;
 
 use super::Analyzer;
+use call_site::SpecReport;
 use rustc_hir::def_id::LOCAL_CRATE;
-use std::{cell::Cell, time::Duration};
+use std::{cell::Cell, fs, time::Duration};
 use sti_visitor::STIVisitor;
 
 pub struct STIAnalysis<'tcx, 'a> {
@@ -21,10 +23,15 @@ impl<'tcx, 'a> STIAnalysis<'tcx, 'a> {
         }
     }
 
-    fn visitor(&self) {
+    /// Returns the time spent by the visitor, `None` before `run` is called.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed.get()
+    }
+
+    fn visitor(&self) -> SpecReport {
         log::info!("Starting the STI visitor for crate {}", self.krate_name);
 
-        let visitor: &mut STIVisitor<'tcx, 'a> = &mut STIVisitor::new(self.analyzer);
+        let mut visitor: STIVisitor<'tcx, 'a> = STIVisitor::new(self.analyzer);
 
         /*
             Useless:
@@ -46,12 +53,42 @@ impl<'tcx, 'a> STIAnalysis<'tcx, 'a> {
             let item = self.analyzer.tcx.hir_item(*item_id);
             visitor.visit_with_hir_id_and_item(hir_id, item);
         }
+
+        visitor.into_report(self.krate_name.clone())
+    }
+
+    /// Prints the `spec!` annotations inferred for each call when `--suggest-spec` is given.
+    fn suggest_spec(&self, report: &SpecReport) {
+        if !self.analyzer.cli_args.suggest_spec {
+            return;
+        }
+
+        for inferred_call in &report.inferred_calls {
+            println!(
+                "{}: {}",
+                inferred_call.span,
+                inferred_call.to_spec_annotation()
+            );
+        }
+    }
+
+    /// Writes the calls found by the visitor to the path given with `--emit-spec-json`.
+    fn emit_spec_json(&self, report: &SpecReport) {
+        let Some(path) = &self.analyzer.cli_args.emit_spec_json else {
+            return;
+        };
+
+        let json = serde_json::to_string_pretty(report).expect("Failed to serialize the report");
+        log::debug!("Writing the spec JSON to {}", path);
+        fs::write(path, json).expect("Failed to write the spec JSON");
     }
 
     pub fn run(&self) {
         let start_time = std::time::Instant::now();
-        self.visitor();
+        let report = self.visitor();
         let elapsed = start_time.elapsed();
         self.elapsed.set(Some(elapsed));
+        self.suggest_spec(&report);
+        self.emit_spec_json(&report);
     }
 }
@@ -1,20 +1,152 @@
+use super::call_site::{CallSite, InferredCall, ReceiverKind, SpecReport};
 use super::Analyzer;
+use rustc_ast::Mutability;
 use rustc_hir::{
-    intravisit::{FnKind, Visitor, VisitorExt},
-    HirId, Impl, Item, ItemKind, TraitImplHeader,
+    def::DefKind,
+    def_id::DefId,
+    intravisit::{self, FnKind, Visitor, VisitorExt},
+    BodyId, Expr, ExprKind, HirId, Impl, Item, ItemKind, TraitImplHeader,
 };
+use rustc_middle::ty::{self, adjustment::Adjust, Ty, TypeckResults};
+use rustc_span::Ident;
+use std::collections::HashSet;
 
-// TODO(bruzzone): remove when `analyzer` is used.
-#[allow(dead_code)]
 pub struct STIVisitor<'tcx, 'a> {
     // The analyzer contains the `TyCtxt`
     analyzer: &'a Analyzer<'tcx>,
+    // The typeck results of the body currently visited
+    typeck_results: Option<&'tcx TypeckResults<'tcx>>,
+    // The trait method calls found so far
+    call_sites: Vec<CallSite>,
+    // The calls to trait methods found so far, with their inferred argument types
+    inferred_calls: Vec<InferredCall>,
+    // The calls already recorded, the same body can be reached more than once
+    recorded: HashSet<HirId>,
 }
 
 // Guardare le tre diverse tipologie di linear: copy move e borrow
 impl<'tcx, 'a> STIVisitor<'tcx, 'a> {
     pub fn new(analyzer: &'a Analyzer<'tcx>) -> Self {
-        Self { analyzer }
+        Self {
+            analyzer,
+            typeck_results: None,
+            call_sites: Vec::new(),
+            inferred_calls: Vec::new(),
+            recorded: HashSet::new(),
+        }
+    }
+
+    /// Consumes the visitor and returns the calls found in the crate.
+    pub fn into_report(self, krate: String) -> SpecReport {
+        SpecReport {
+            krate,
+            call_sites: self.call_sites,
+            inferred_calls: self.inferred_calls,
+        }
+    }
+
+    /// Returns how a value of type `ty` is passed to a call.
+    ///
+    /// The type must be the adjusted one, so that auto-borrowed receivers are seen as borrows,
+    /// i.e. the type of the operand given to the call in the MIR.
+    fn receiver_kind(ty: Ty<'tcx>) -> ReceiverKind {
+        match ty.kind() {
+            ty::Ref(_, _, Mutability::Mut) => ReceiverKind::Mutable,
+            ty::Ref(_, _, Mutability::Not) => ReceiverKind::Shared,
+            _ => ReceiverKind::Owned,
+        }
+    }
+
+    /// Returns the method called by `expr` and its trait, only for the calls to trait methods,
+    /// the only ones that can be specialized.
+    fn trait_method(
+        &self,
+        typeck_results: &'tcx TypeckResults<'tcx>,
+        expr: &'tcx Expr<'tcx>,
+    ) -> Option<(DefId, DefId)> {
+        let tcx = self.analyzer.tcx;
+        let method_def_id = typeck_results.type_dependent_def_id(expr.hir_id)?;
+        let trait_def_id = tcx.parent(method_def_id);
+        (tcx.def_kind(trait_def_id) == DefKind::Trait).then_some((method_def_id, trait_def_id))
+    }
+
+    fn record_inferred_call(
+        &mut self,
+        typeck_results: &'tcx TypeckResults<'tcx>,
+        expr: &'tcx Expr<'tcx>,
+        receiver: &'tcx Expr<'tcx>,
+        args: &'tcx [Expr<'tcx>],
+    ) {
+        // `spec!` only dispatches calls to trait methods
+        if self.trait_method(typeck_results, expr).is_none() {
+            return;
+        }
+
+        let source_map = self.analyzer.tcx.sess.source_map();
+        let Ok(call) = source_map.span_to_snippet(expr.span) else {
+            return;
+        };
+
+        let inferred_call = InferredCall {
+            span: source_map.span_to_diagnostic_string(expr.span),
+            call,
+            receiver_type: typeck_results.expr_ty(receiver).peel_refs().to_string(),
+            arg_types: args
+                .iter()
+                .map(|arg| typeck_results.expr_ty(arg).to_string())
+                .collect(),
+            receiver_kind: Self::receiver_kind(typeck_results.expr_ty_adjusted(receiver)),
+            arg_kinds: args
+                .iter()
+                .map(|arg| Self::receiver_kind(typeck_results.expr_ty_adjusted(arg)))
+                .collect(),
+            receiver_borrowed: typeck_results
+                .expr_adjustments(receiver)
+                .iter()
+                .any(|adjustment| matches!(adjustment.kind, Adjust::Borrow(_))),
+            receiver_deref: typeck_results.expr_ty_adjusted(receiver).peel_refs()
+                != typeck_results.expr_ty(receiver).peel_refs(),
+        };
+        log::debug!("Found inferred call: {:?}", inferred_call);
+        self.inferred_calls.push(inferred_call);
+    }
+
+    fn record_call_site(
+        &mut self,
+        typeck_results: &'tcx TypeckResults<'tcx>,
+        expr: &'tcx Expr<'tcx>,
+        method: Ident,
+        receiver: &'tcx Expr<'tcx>,
+    ) {
+        let tcx = self.analyzer.tcx;
+        // Only trait methods can be specialized
+        let Some((method_def_id, trait_def_id)) = self.trait_method(typeck_results, expr) else {
+            return;
+        };
+
+        let args = typeck_results.node_args(expr.hir_id);
+        let typing_env = ty::TypingEnv::post_analysis(tcx, expr.hir_id.owner.def_id);
+        let selected = ty::Instance::try_resolve(tcx, typing_env, method_def_id, args)
+            .ok()
+            .flatten()
+            .map(|instance| tcx.parent(instance.def_id()))
+            .filter(|impl_def_id| matches!(tcx.def_kind(*impl_def_id), DefKind::Impl { .. }))
+            .map(|impl_def_id| tcx.def_path_str(impl_def_id));
+        let candidates = tcx
+            .all_impls(trait_def_id)
+            .map(|impl_def_id| tcx.def_path_str(impl_def_id))
+            .collect();
+
+        let call_site = CallSite {
+            span: tcx.sess.source_map().span_to_diagnostic_string(expr.span),
+            method: method.to_string(),
+            receiver_type: typeck_results.expr_ty(receiver).to_string(),
+            trait_name: tcx.def_path_str(trait_def_id),
+            candidates,
+            selected,
+        };
+        log::debug!("Found call site: {:?}", call_site);
+        self.call_sites.push(call_site);
     }
 
     /// The entry point of the visitor.
@@ -164,4 +296,24 @@ impl<'tcx> Visitor<'tcx> for STIVisitor<'tcx, '_> {
         // Continue walking the item.
         rustc_hir::intravisit::walk_item(self, item);
     }
+
+    fn visit_nested_body(&mut self, body_id: BodyId) {
+        let tcx = self.analyzer.tcx;
+        let old_typeck_results = self.typeck_results.replace(tcx.typeck_body(body_id));
+        self.visit_body(tcx.hir_body(body_id));
+        self.typeck_results = old_typeck_results;
+    }
+
+    fn visit_expr(&mut self, expr: &'tcx Expr<'tcx>) {
+        match (self.typeck_results, expr.kind) {
+            (Some(typeck_results), ExprKind::MethodCall(segment, receiver, args, _))
+                if self.recorded.insert(expr.hir_id) =>
+            {
+                self.record_inferred_call(typeck_results, expr, receiver, args);
+                self.record_call_site(typeck_results, expr, segment.ident, receiver);
+            }
+            _ => {}
+        }
+        intravisit::walk_expr(self, expr);
+    }
 }
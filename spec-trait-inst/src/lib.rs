@@ -43,6 +43,18 @@ pub struct CliArgs {
     #[clap(long)]
     filter_with_file: Option<String>,
 
+    /// Print the time spent by each analysis
+    #[clap(long)]
+    timings: bool,
+
+    /// Print the `spec!` annotations inferred for each method call
+    #[clap(long)]
+    suggest_spec: bool,
+
+    /// Write the trait method calls, with their candidate and selected impls, as JSON to this path
+    #[clap(long)]
+    emit_spec_json: Option<String>,
+
     #[clap(last = true)]
     // mytool --allcaps -- some extra args here
     //                     ^^^^^^^^^^^^^^^^^^^^ these are cargo args
@@ -151,7 +163,7 @@ impl rustc_driver::Callbacks for PluginCallbacks {
     ) -> rustc_driver::Compilation {
         // Abort if errors occurred during expansion.
         compiler.sess.dcx().abort_if_errors();
-        println!("OOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOO");
+        log::debug!("Running the analyzer after expansion");
         Analyzer::<'tcx>::new(tcx, self.args.clone()).run();
         compiler.sess.dcx().abort_if_errors();
 
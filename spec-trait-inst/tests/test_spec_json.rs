@@ -0,0 +1,53 @@
+mod utils;
+
+mod test_spec_json {
+    use crate::utils::run_with_cargo_bin;
+    use serde_json::Value;
+    use std::{env, fs};
+
+    const FOLDER: &str = "tests/workspaces/spec_json";
+
+    #[test]
+    fn test_emit_spec_json() -> Result<(), String> {
+        let json_path = env::temp_dir().join("spec_trait_inst_spec_json.json");
+        let _ = fs::remove_file(&json_path);
+
+        run_with_cargo_bin(
+            FOLDER,
+            None,
+            &["--emit-spec-json", json_path.to_str().unwrap()],
+        )?;
+
+        let json = fs::read_to_string(&json_path).map_err(|e| e.to_string())?;
+        let report: Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+        assert_eq!(report["crate"], "spec_json");
+        let call_sites = report["call_sites"].as_array().unwrap();
+        let greet_calls = call_sites
+            .iter()
+            .filter(|call_site| call_site["method"] == "greet")
+            .collect::<Vec<_>>();
+        assert_eq!(greet_calls.len(), 2);
+
+        for call_site in &greet_calls {
+            assert!(call_site["span"].is_string());
+            assert!(call_site["trait_name"].as_str().unwrap().ends_with("Greet"));
+            assert_eq!(call_site["candidates"].as_array().unwrap().len(), 2);
+        }
+
+        let receivers = greet_calls
+            .iter()
+            .map(|call_site| call_site["receiver_type"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(receivers, vec!["Foo", "Bar"]);
+
+        let selected = greet_calls
+            .iter()
+            .map(|call_site| call_site["selected"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert!(selected[0].contains("Foo"));
+        assert!(selected[1].contains("Bar"));
+
+        Ok(())
+    }
+}
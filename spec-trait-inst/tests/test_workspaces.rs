@@ -16,7 +16,12 @@ mod test_workspaces {
     #[test]
     fn test_help_output() -> Result<(), String> {
         let (output, _) = run_with_cargo_bin(FOLDER, None, &["--help"])?;
-        for options in &["--print-crate"] {
+        for options in &[
+            "--print-crate",
+            "--timings",
+            "--suggest-spec",
+            "--emit-spec-json",
+        ] {
             assert!(output.contains(options));
         }
         Ok(())
@@ -30,6 +35,54 @@ mod test_workspaces {
         Ok(())
     }
 
+    #[test]
+    fn test_first_clean_stdout() -> Result<(), String> {
+        let (output, _) = run_with_cargo_bin(FOLDER, None, &[])?;
+        assert_eq!(output, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_timings() -> Result<(), String> {
+        // the plugin built with `test-mode` fails if `elapsed()` is `None` after `run()`
+        let (output, _) = run_with_cargo_bin(FOLDER, None, &["--timings"])?;
+        let elapsed = output
+            .lines()
+            .find_map(|line| line.strip_prefix("STIAnalysis took "))
+            .expect("the elapsed time is printed");
+        // the `Debug` format of a `Duration`, e.g. `1.5ms`
+        assert!(elapsed.starts_with(|c: char| c.is_ascii_digit()));
+        assert!(elapsed.ends_with('s'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_suggest_spec_inferred_types() -> Result<(), String> {
+        let (output, _) = run_with_cargo_bin(FOLDER, None, &["--suggest-spec"])?;
+        assert!(output.contains("spec! { t1.clone(); T; [] }"));
+        assert!(output.contains("spec! { t2.set(1); T; [i32] }"));
+        // only the calls to trait methods can be dispatched by `spec!`
+        assert!(!output.contains("test_own("));
+        assert!(!output.contains("test_bor("));
+        assert!(!output.contains("test_mut_bor("));
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_suggest_spec_receiver_kinds() -> Result<(), String> {
+        let (output, _) = run_with_cargo_bin(FOLDER, None, &["--suggest-spec"])?;
+        // `&self` and `&mut self` borrow the receiver, `self` takes it as is
+        assert!(output.contains("spec! { t1.get(); T; [] } // &self"));
+        assert!(output.contains("spec! { t2.set(1); T; [i32] } // &mut self, self"));
+        assert!(output.contains("spec! { t2.into_value(); T; []; no_borrow } // self"));
+        // `clone` takes `&self`, so `t1` is auto-borrowed
+        assert!(output.contains("spec! { t1.clone(); T; [] } // &self"));
+        // the method of `T` is found through the deref of the `Box`
+        assert!(output.contains("spec! { boxed.get(); "));
+        assert!(output.contains("Box<T>; []; deref } // &self"));
+        Ok(())
+    }
+
     #[test]
     fn test_first_contains_plug_version() -> Result<(), String> {
         let (output, _) = run_with_cargo_bin(FOLDER, None, &["-V"])?;
@@ -3,6 +3,26 @@ struct T{
     value: i32,
 }
 
+trait Value {
+    fn get(&self) -> i32;
+    fn set(&mut self, value: i32);
+    fn into_value(self) -> i32;
+}
+
+impl Value for T {
+    fn get(&self) -> i32 {
+        self.value
+    }
+
+    fn set(&mut self, value: i32) {
+        self.value = value;
+    }
+
+    fn into_value(self) -> i32 {
+        self.value
+    }
+}
+
 fn test_own(t: T) {
     // Set the value to 0
     let mut t = t;
@@ -24,5 +44,10 @@ fn main() {
     test_own(t1.clone()); // This is a `move` where the value is not in the `local_var_decl`
     test_bor(&t1);
     test_mut_bor(&mut t2);
+    let _ = t1.get();
+    t2.set(1);
+    let boxed = Box::new(T { value: 10 });
+    let _ = boxed.get(); // The method of `T` is found through the deref of the `Box`
+    let _ = t2.into_value();
     test_own(t1);
 }
@@ -0,0 +1,25 @@
+trait Greet {
+    fn greet(&self) -> String;
+}
+
+struct Foo;
+struct Bar;
+
+impl Greet for Foo {
+    fn greet(&self) -> String {
+        "Foo".to_string()
+    }
+}
+
+impl Greet for Bar {
+    fn greet(&self) -> String {
+        "Bar".to_string()
+    }
+}
+
+fn main() {
+    let foo = Foo;
+    let bar = Bar;
+    println!("{}", foo.greet());
+    println!("{}", bar.greet());
+}